@@ -0,0 +1,51 @@
+use crate::args;
+
+/// UI scales F6 steps through.
+pub const UI_SCALES: [f32; 4] = [1.0, 1.5, 2.0, 3.0];
+
+/// Heat haze displacement and ripple speed left with reduced motion.
+pub const REDUCED_MOTION_HAZE: f32 = 0.25;
+
+/// Options for long sessions and for viewers sensitive to motion or low contrast.
+///
+/// Reduced motion drops the effects that move or flicker on their own: motion blur is off,
+/// autofocus jumps to its target instead of breathing towards it, heat haze shimmers less
+/// and slower, and the per-frame noise of the stochastic effects (soft shadows, sky
+/// occlusion, rough reflections) holds still while the camera does. High contrast draws the
+/// editor gizmo in brighter colors with a black outline, and the UI scale widens it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Accessibility {
+    pub reduced_motion: bool,
+    pub high_contrast: bool,
+    pub ui_scale: f32,
+}
+
+impl Accessibility {
+    pub fn new() -> Self {
+        Self { reduced_motion: false, high_contrast: false, ui_scale: 1.0 }
+    }
+
+    /// Applies `--reduced-motion`, `--high-contrast` and `--ui-scale <factor>`, which take
+    /// precedence over the restored session.
+    pub fn apply_args(&mut self) {
+        if args::flag("--reduced-motion") {
+            self.reduced_motion = true;
+        }
+        if args::flag("--high-contrast") {
+            self.high_contrast = true;
+        }
+        if let Some(scale) = args::number::<f32>("--ui-scale") {
+            self.set_ui_scale(scale);
+        }
+    }
+
+    /// Sets the UI scale, clamped to the range of `UI_SCALES`.
+    pub fn set_ui_scale(&mut self, scale: f32) {
+        self.ui_scale = scale.clamp(UI_SCALES[0], UI_SCALES[UI_SCALES.len() - 1]);
+    }
+
+    /// Next entry of `UI_SCALES` after the current scale, wrapping around.
+    pub fn next_ui_scale(&mut self) {
+        self.ui_scale = UI_SCALES.iter().copied().find(|&scale| scale > self.ui_scale).unwrap_or(UI_SCALES[0]);
+    }
+}
@@ -0,0 +1,313 @@
+use glam::{Mat4, Quat, Vec3};
+use bytemuck::{Pod, Zeroable};
+
+/// Local joint transform (glTF style translation / rotation / scale).
+#[derive(Clone, Copy)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self { translation, rotation: Quat::IDENTITY, scale: Vec3::ONE }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+/// Easing applied to the interpolation parameter between two keyframes.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,    // Starts at rest, accelerates
+    EaseOut,   // Decelerates to rest
+    Hold,      // Keeps the first key's value until the next key
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::Hold => 0.0,
+        }
+    }
+}
+
+/// Object transform at `time`; `easing` shapes the interpolation towards the next keyframe.
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub time: f32,
+    pub transform: Transform,
+    pub easing: Easing,
+}
+
+/// Keyframed rigid transform (translation, rotation, scale) of a scene object.
+/// Keyframes are sorted by time; looping tracks wrap around after the last one.
+pub struct TransformTrack {
+    pub keyframes: Vec<Keyframe>,
+    pub looping: bool,
+}
+
+impl TransformTrack {
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |key| key.time)
+    }
+
+    pub fn sample(&self, time: f32) -> Mat4 {
+        let duration = self.duration();
+        let time = if self.looping && duration > 0.0 { time.rem_euclid(duration) } else { time };
+
+        let next = self.keyframes.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return self.keyframes.first().map_or(Mat4::IDENTITY, |key| key.transform.matrix());
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].transform.matrix();
+        }
+
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = a.easing.apply((time - a.time) / (b.time - a.time));
+        Transform {
+            translation: a.transform.translation.lerp(b.transform.translation, t),
+            rotation: a.transform.rotation.slerp(b.transform.rotation, t),
+            scale: a.transform.scale.lerp(b.transform.scale, t),
+        }.matrix()
+    }
+}
+
+pub struct Joint {
+    pub parent: Option<usize>, // Parents always come before their children
+    pub rest: Transform,
+    pub inverse_bind: Mat4,
+}
+
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    pub root: Mat4, // Transform of the nodes above the root joints (e.g. a glTF armature)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+}
+
+#[derive(Clone, Copy)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+/// Keyframes for one property of one joint. Values are xyz (translation, scale) or a quaternion (rotation).
+pub struct Channel {
+    pub joint: usize,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub values: Vec<[f32; 4]>,
+}
+
+pub struct AnimationClip {
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+/// Per-vertex skinning influences, uploaded alongside the mesh for the skinning compute pass.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct SkinVertex {
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
+}
+
+/// A skinned mesh: its rest-pose vertices live in `Scene::meshes[mesh_index]`, and
+/// each frame they are deformed by the skeleton posed with the looping clip.
+pub struct Skin {
+    pub mesh_index: usize,
+    pub vertices: Vec<SkinVertex>,
+    pub skeleton: Skeleton,
+    pub clip: AnimationClip,
+}
+
+impl Skin {
+    /// Skinning matrices (joint global transform * inverse bind matrix) at `time`, looping the clip.
+    pub fn joint_matrices(&self, time: f32) -> Vec<Mat4> {
+        let time = if self.clip.duration > 0.0 { time.rem_euclid(self.clip.duration) } else { 0.0 };
+
+        let mut locals: Vec<Transform> = self.skeleton.joints.iter().map(|joint| joint.rest).collect();
+        for channel in &self.clip.channels {
+            let value = channel.sample(time);
+            let local = &mut locals[channel.joint];
+            match channel.property {
+                Property::Translation => local.translation = Vec3::new(value[0], value[1], value[2]),
+                Property::Rotation => local.rotation = Quat::from_array(value).normalize(),
+                Property::Scale => local.scale = Vec3::new(value[0], value[1], value[2]),
+            }
+        }
+
+        let mut globals: Vec<Mat4> = Vec::with_capacity(locals.len());
+        for (joint, local) in self.skeleton.joints.iter().zip(&locals) {
+            let global = match joint.parent {
+                Some(parent) => globals[parent] * local.matrix(),
+                None => self.skeleton.root * local.matrix(),
+            };
+            globals.push(global);
+        }
+
+        globals.iter().zip(&self.skeleton.joints).map(|(global, joint)| *global * joint.inverse_bind).collect()
+    }
+}
+
+impl Channel {
+    fn sample(&self, time: f32) -> [f32; 4] {
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 {
+            return self.values[0];
+        }
+        if next == self.times.len() {
+            return self.values[next - 1];
+        }
+
+        let (a, b) = (self.values[next - 1], self.values[next]);
+        if self.interpolation == Interpolation::Step {
+            return a;
+        }
+        let t = (time - self.times[next - 1]) / (self.times[next] - self.times[next - 1]);
+        match self.property {
+            Property::Rotation => Quat::from_array(a).slerp(Quat::from_array(b), t).to_array(),
+            _ => [
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+                0.0,
+            ],
+        }
+    }
+}
+
+// Joints of the procedural walker
+const HIPS: usize = 0;
+const SPINE: usize = 1;
+const HEAD: usize = 2;
+const LEFT_THIGH: usize = 3;
+const LEFT_SHIN: usize = 4;
+const RIGHT_THIGH: usize = 5;
+const RIGHT_SHIN: usize = 6;
+const LEFT_UPPER_ARM: usize = 7;
+const LEFT_FOREARM: usize = 8;
+const RIGHT_UPPER_ARM: usize = 9;
+const RIGHT_FOREARM: usize = 10;
+
+/// Skeleton of the demo person, in meters with the feet at y = 0.
+/// Joint positions are given in model space and converted to parent-relative rest transforms.
+pub fn walker_skeleton() -> Skeleton {
+    let joints: [(Option<usize>, Vec3); 11] = [
+        (None, Vec3::new(0.0, 0.95, 0.0)),                    // Hips
+        (Some(HIPS), Vec3::new(0.0, 1.05, 0.0)),              // Spine
+        (Some(SPINE), Vec3::new(0.0, 1.5, 0.0)),              // Head (neck)
+        (Some(HIPS), Vec3::new(0.1, 0.9, 0.0)),               // Left thigh
+        (Some(LEFT_THIGH), Vec3::new(0.1, 0.5, 0.0)),         // Left shin (knee)
+        (Some(HIPS), Vec3::new(-0.1, 0.9, 0.0)),              // Right thigh
+        (Some(RIGHT_THIGH), Vec3::new(-0.1, 0.5, 0.0)),       // Right shin (knee)
+        (Some(SPINE), Vec3::new(0.22, 1.42, 0.0)),            // Left upper arm (shoulder)
+        (Some(LEFT_UPPER_ARM), Vec3::new(0.24, 1.15, 0.0)),   // Left forearm (elbow)
+        (Some(SPINE), Vec3::new(-0.22, 1.42, 0.0)),           // Right upper arm
+        (Some(RIGHT_UPPER_ARM), Vec3::new(-0.24, 1.15, 0.0)), // Right forearm
+    ];
+
+    Skeleton {
+        joints: joints.iter().map(|&(parent, position)| {
+            let parent_position = parent.map_or(Vec3::ZERO, |p| joints[p].1);
+            Joint {
+                parent,
+                rest: Transform::from_translation(position - parent_position),
+                inverse_bind: Mat4::from_translation(-position),
+            }
+        }).collect(),
+        root: Mat4::IDENTITY,
+    }
+}
+
+/// Looping walk cycle for `walker_skeleton`: swinging legs and arms with bending knees and elbows.
+pub fn walk_cycle() -> AnimationClip {
+    const DURATION: f32 = 1.2;
+    const KEYS: usize = 9;
+
+    let times: Vec<f32> = (0..KEYS).map(|i| i as f32 / (KEYS - 1) as f32 * DURATION).collect();
+    let swing = |joint: usize, amplitude: f32, phase: f32, bias: f32| Channel {
+        joint,
+        property: Property::Rotation,
+        interpolation: Interpolation::Linear,
+        times: times.clone(),
+        values: times.iter().map(|t| {
+            let angle = bias + amplitude * (t / DURATION * std::f32::consts::TAU + phase).sin();
+            Quat::from_rotation_x(angle).to_array()
+        }).collect(),
+    };
+    // Knees and elbows only bend one way
+    let bend = |joint: usize, amplitude: f32, phase: f32| Channel {
+        joint,
+        property: Property::Rotation,
+        interpolation: Interpolation::Linear,
+        times: times.clone(),
+        values: times.iter().map(|t| {
+            let angle = amplitude * (0.5 + 0.5 * (t / DURATION * std::f32::consts::TAU + phase).sin());
+            Quat::from_rotation_x(angle).to_array()
+        }).collect(),
+    };
+    let bob = Channel {
+        joint: HIPS,
+        property: Property::Translation,
+        interpolation: Interpolation::Linear,
+        times: times.clone(),
+        values: times.iter().map(|t| {
+            let y = 0.95 + 0.02 * (t / DURATION * std::f32::consts::TAU * 2.0).cos();
+            [0.0, y, 0.0, 0.0]
+        }).collect(),
+    };
+
+    use std::f32::consts::PI;
+    AnimationClip {
+        duration: DURATION,
+        channels: vec![
+            bob,
+            swing(LEFT_THIGH, 0.5, 0.0, 0.0),
+            swing(RIGHT_THIGH, 0.5, PI, 0.0),
+            bend(LEFT_SHIN, 0.8, PI * 0.5),
+            bend(RIGHT_SHIN, 0.8, PI * 1.5),
+            swing(LEFT_UPPER_ARM, 0.4, PI, 0.0),
+            swing(RIGHT_UPPER_ARM, 0.4, 0.0, 0.0),
+            bend(LEFT_FOREARM, -0.5, PI * 0.5),
+            bend(RIGHT_FOREARM, -0.5, PI * 1.5),
+            swing(SPINE, 0.05, PI * 0.5, 0.05),
+        ],
+    }
+}
+
+/// Body segments of the walker as (joint, parent joint to blend into, start, end, half thickness).
+pub fn walker_segments() -> Vec<(usize, usize, Vec3, Vec3, f32)> {
+    vec![
+        (HIPS, HIPS, Vec3::new(0.0, 0.85, 0.0), Vec3::new(0.0, 1.05, 0.0), 0.16),
+        (SPINE, HIPS, Vec3::new(0.0, 1.05, 0.0), Vec3::new(0.0, 1.45, 0.0), 0.17),
+        (LEFT_THIGH, HIPS, Vec3::new(0.1, 0.9, 0.0), Vec3::new(0.1, 0.5, 0.0), 0.07),
+        (LEFT_SHIN, LEFT_THIGH, Vec3::new(0.1, 0.5, 0.0), Vec3::new(0.1, 0.05, 0.0), 0.06),
+        (RIGHT_THIGH, HIPS, Vec3::new(-0.1, 0.9, 0.0), Vec3::new(-0.1, 0.5, 0.0), 0.07),
+        (RIGHT_SHIN, RIGHT_THIGH, Vec3::new(-0.1, 0.5, 0.0), Vec3::new(-0.1, 0.05, 0.0), 0.06),
+        (LEFT_UPPER_ARM, SPINE, Vec3::new(0.22, 1.42, 0.0), Vec3::new(0.24, 1.15, 0.0), 0.05),
+        (LEFT_FOREARM, LEFT_UPPER_ARM, Vec3::new(0.24, 1.15, 0.0), Vec3::new(0.25, 0.9, 0.0), 0.045),
+        (RIGHT_UPPER_ARM, SPINE, Vec3::new(-0.22, 1.42, 0.0), Vec3::new(-0.24, 1.15, 0.0), 0.05),
+        (RIGHT_FOREARM, RIGHT_UPPER_ARM, Vec3::new(-0.24, 1.15, 0.0), Vec3::new(-0.25, 0.9, 0.0), 0.045),
+    ]
+}
+
+/// Joint the walker's head geometry is bound to, and the head center in model space.
+pub fn walker_head() -> (usize, Vec3) {
+    (HEAD, Vec3::new(0.0, 1.62, 0.0))
+}
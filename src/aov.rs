@@ -0,0 +1,199 @@
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use exr::block::{BlockIndex, UncompressedBlock};
+use exr::block::writer::ChunksWriter;
+use exr::meta::BlockDescription;
+use exr::meta::header::Header;
+use exr::prelude::{
+    AnyChannel, AnyChannels, ChannelDescription, Compression, Encoding, FlatSamples, Image, ImageAttributes, IntegerBounds, Layer,
+    LayerAttributes, LineOrder, SampleType, Text, Vec2, WritableImage,
+};
+
+/// AOVs read back from the GPU, as RGBA floats.
+pub struct Aovs {
+    pub depth: Vec<f32>, // Hit distance along the camera ray, in all three color channels
+    pub normal: Vec<f32>, // World space
+    pub albedo: Vec<f32>,
+    pub motion: Vec<f32>, // Pixels, in red and green
+    pub instance: Vec<f32>, // TLAS instance in red, -1 on the sky
+}
+
+impl Aovs {
+    /// All AOVs of a `pixels` large image, zeroed.
+    pub fn zeroed(pixels: usize) -> Self {
+        Self {
+            depth: vec![0.0; pixels * 4],
+            normal: vec![0.0; pixels * 4],
+            albedo: vec![0.0; pixels * 4],
+            motion: vec![0.0; pixels * 4],
+            instance: vec![0.0; pixels * 4],
+        }
+    }
+
+    pub fn images_mut(&mut self) -> [&mut Vec<f32>; 5] {
+        [&mut self.depth, &mut self.normal, &mut self.albedo, &mut self.motion, &mut self.instance]
+    }
+
+    pub fn images(&self) -> [&Vec<f32>; 5] {
+        [&self.depth, &self.normal, &self.albedo, &self.motion, &self.instance]
+    }
+}
+
+/// Writes a linear HDR image as an OpenEXR file, with the AOVs (if any) as further layers:
+/// `beauty` (R, G, B, A), `depth` (Z), `normal` and `albedo` (R, G, B), `motion` (R, G, in
+/// pixels) and `instance` (a 32-bit unsigned `id`, 0xFFFFFFFF on the sky), so compositing
+/// tools find everything in one file. All channels are 32-bit floats except the instance ID,
+/// compressed losslessly.
+pub fn write_layered_exr(path: impl AsRef<std::path::Path>, width: u32, height: u32, beauty: &[f32], aovs: Option<&Aovs>) -> Result<(), Box<dyn std::error::Error>> {
+    let size = (width as usize, height as usize);
+    let channel = |rgba: &[f32], index: usize| FlatSamples::F32(rgba.chunks_exact(4).map(|p| p[index]).collect());
+    let layer = |name: &str, channels: Vec<AnyChannel<FlatSamples>>| {
+        Layer::new(size, LayerAttributes::named(name), Encoding::FAST_LOSSLESS, AnyChannels::sort(channels.into()))
+    };
+
+    let mut layers = vec![layer("beauty", vec![
+        AnyChannel::new("R", channel(beauty, 0)),
+        AnyChannel::new("G", channel(beauty, 1)),
+        AnyChannel::new("B", channel(beauty, 2)),
+        AnyChannel::new("A", channel(beauty, 3)),
+    ])];
+    if let Some(aovs) = aovs {
+        let rgb = |rgba: &[f32]| vec![
+            AnyChannel::new("R", channel(rgba, 0)),
+            AnyChannel::new("G", channel(rgba, 1)),
+            AnyChannel::new("B", channel(rgba, 2)),
+        ];
+        let instance_ids = aovs.instance.chunks_exact(4).map(|p| if p[0] < 0.0 { u32::MAX } else { p[0] as u32 }).collect();
+        layers.push(layer("depth", vec![AnyChannel::new("Z", channel(&aovs.depth, 0))]));
+        layers.push(layer("normal", rgb(&aovs.normal)));
+        layers.push(layer("albedo", rgb(&aovs.albedo)));
+        layers.push(layer("motion", vec![
+            AnyChannel::new("R", channel(&aovs.motion, 0)),
+            AnyChannel::new("G", channel(&aovs.motion, 1)),
+        ]));
+        layers.push(layer("instance", vec![AnyChannel::new("id", FlatSamples::U32(instance_ids))]));
+    }
+
+    Image::from_layers(ImageAttributes::new(IntegerBounds::from_dimensions(size)), layers)
+        .write()
+        .to_file(path)?;
+    Ok(())
+}
+
+/// Channels of an EXR layer in the file's (alphabetical) order, with the RGBA component each is
+/// taken from.
+type LayerChannels = &'static [(&'static str, usize)];
+
+/// Layers of a streamed EXR, as `write_layered_exr` lays them out.
+const STREAMED_LAYERS: [(&str, SampleType, LayerChannels); 6] = [
+    ("beauty", SampleType::F32, &[("A", 3), ("B", 2), ("G", 1), ("R", 0)]),
+    ("depth", SampleType::F32, &[("Z", 0)]),
+    ("normal", SampleType::F32, &[("B", 2), ("G", 1), ("R", 0)]),
+    ("albedo", SampleType::F32, &[("B", 2), ("G", 1), ("R", 0)]),
+    ("motion", SampleType::F32, &[("G", 1), ("R", 0)]),
+    ("instance", SampleType::U32, &[("id", 0)]),
+];
+
+/// The layered EXR of `write_layered_exr`, AOVs included, written a band of rows at a time for
+/// images too large to hold in memory. Every row is a scanline block of its own, RLE compressed
+/// and written by the `exr` crate on a thread that takes the blocks as the bands come in.
+pub struct LayeredExrWriter {
+    width: usize,
+    height: usize,
+    next_row: usize,
+    blocks: Option<mpsc::Sender<Vec<UncompressedBlock>>>, // None once all rows are in
+    writer: Option<JoinHandle<exr::error::UnitResult>>,
+}
+
+impl LayeredExrWriter {
+    pub fn create(path: impl AsRef<std::path::Path>, width: u32, height: u32) -> std::io::Result<Self> {
+        let (width, height) = (width as usize, height as usize);
+        let headers = STREAMED_LAYERS.iter().map(|&(name, sample_type, channels)| {
+            let channels = channels.iter().map(|&(channel, _)| ChannelDescription::named(channel, sample_type)).collect();
+            Header::new(Text::from(name), (width, height), channels)
+                .with_encoding(Compression::RLE, BlockDescription::ScanLines, LineOrder::Increasing)
+        }).collect();
+
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let (sender, receiver) = mpsc::channel::<Vec<UncompressedBlock>>();
+        let writer = std::thread::spawn(move || {
+            exr::block::write(file, headers, true, |meta, chunks| {
+                for block in receiver.into_iter().flatten() {
+                    let y = block.index.pixel_position.y();
+                    chunks.write_chunk(y, block.compress_to_chunk(&meta.headers)?)?;
+                }
+                Ok(())
+            })
+        });
+        Ok(Self { width, height, next_row: 0, blocks: Some(sender), writer: Some(writer) })
+    }
+
+    /// Appends whole rows of linear RGBA floats, top to bottom, with the AOVs of the same rows.
+    pub fn write_rows(&mut self, beauty: &[f32], aovs: &Aovs) -> Result<(), Box<dyn std::error::Error>> {
+        let rows = beauty.len() / (self.width * 4);
+        if self.next_row + rows > self.height {
+            return Err("more rows than the image height".into());
+        }
+
+        let images = std::iter::once(beauty).chain(aovs.images().into_iter().map(Vec::as_slice));
+        let mut blocks = Vec::with_capacity(rows * STREAMED_LAYERS.len());
+        for (layer, ((_, sample_type, channels), image)) in STREAMED_LAYERS.iter().zip(images).enumerate() {
+            for (row, pixels) in image.chunks_exact(self.width * 4).take(rows).enumerate() {
+                let mut data = Vec::with_capacity(self.width * channels.len() * 4);
+                for &(_, component) in channels.iter() {
+                    for sample in pixels.chunks_exact(4).map(|pixel| pixel[component]) {
+                        match sample_type {
+                            SampleType::U32 => data.extend_from_slice(&(if sample < 0.0 { u32::MAX } else { sample as u32 }).to_ne_bytes()),
+                            _ => data.extend_from_slice(&sample.to_ne_bytes()),
+                        }
+                    }
+                }
+                blocks.push(UncompressedBlock {
+                    index: BlockIndex {
+                        layer,
+                        pixel_position: Vec2(0, self.next_row + row),
+                        pixel_size: Vec2(self.width, 1),
+                        level: Vec2(0, 0),
+                    },
+                    data,
+                });
+            }
+        }
+        self.next_row += rows;
+
+        let sent = self.blocks.as_ref().is_some_and(|sender| sender.send(blocks).is_ok());
+        if !sent {
+            // The writer gave up; its error says why
+            self.blocks = None;
+            self.join()?;
+            return Err("the EXR writer has stopped".into());
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.next_row < self.height {
+            return Err(format!("{} rows missing", self.height - self.next_row).into());
+        }
+        self.blocks = None;
+        self.join()
+    }
+
+    /// Waits for the writer thread, which completes the file once the sender is dropped.
+    fn join(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.writer.take().map(JoinHandle::join) {
+            Some(Ok(result)) => Ok(result?),
+            Some(Err(_)) => Err("the EXR writer panicked".into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for LayeredExrWriter {
+    /// Closes the file before it may be deleted, e.g. when a photo is cancelled: the writer
+    /// stops at the missing rows.
+    fn drop(&mut self) {
+        self.blocks = None;
+        let _ = self.join();
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// What a flag takes after it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Switch, // Nothing
+    Text, // Any value
+    Number, // A decimal number
+    Integer, // A whole number of 0 or more
+}
+
+/// Every flag of the command line, with what it takes. The modules using a flag document it.
+const FLAGS: &[(&str, Kind)] = &[
+    ("--auto-exposure", Kind::Switch),
+    ("--background-fps", Kind::Number),
+    ("--chromatic-aberration", Kind::Number),
+    ("--f-number", Kind::Number),
+    ("--film-grain", Kind::Number),
+    ("--focal-length", Kind::Number),
+    ("--gpu", Kind::Text),
+    ("--gpu-checkpoints", Kind::Switch),
+    ("--gpu-robustness", Kind::Switch),
+    ("--grass", Kind::Integer),
+    ("--hdr", Kind::Text),
+    ("--hdr-paper-white", Kind::Number),
+    ("--high-contrast", Kind::Switch),
+    ("--hybrid", Kind::Switch),
+    ("--import-crease-angle", Kind::Number),
+    ("--import-units", Kind::Text),
+    ("--import-up", Kind::Text),
+    ("--lang", Kind::Text),
+    ("--list-gpus", Kind::Switch),
+    ("--lod-bias", Kind::Number),
+    ("--max-fps", Kind::Number),
+    ("--metrics", Kind::Text),
+    ("--noclip", Kind::Switch),
+    ("--ortho-height", Kind::Number),
+    ("--panorama", Kind::Switch),
+    ("--panorama-stereo", Kind::Switch),
+    ("--particles", Kind::Integer),
+    ("--projection", Kind::Text),
+    ("--record-every", Kind::Integer),
+    ("--record-ffmpeg", Kind::Switch),
+    ("--record-fps", Kind::Integer),
+    ("--reduced-motion", Kind::Switch),
+    ("--render", Kind::Text),
+    ("--render-scale", Kind::Text), // A fraction or a percentage
+    ("--samples", Kind::Integer),
+    ("--seed", Kind::Integer),
+    ("--sensor-height", Kind::Number),
+    ("--shader-printf", Kind::Switch),
+    ("--sharpen", Kind::Number),
+    ("--split-screen", Kind::Switch),
+    ("--stream-distance", Kind::Number),
+    ("--streaming", Kind::Switch),
+    ("--suite", Kind::Switch),
+    ("--target-fps", Kind::Number),
+    ("--terrain-seed", Kind::Integer),
+    ("--terrain-size", Kind::Number),
+    ("--transition-time", Kind::Number),
+    ("--ui-scale", Kind::Number),
+    ("--upscaler", Kind::Text),
+    ("--vignette", Kind::Number),
+    ("--volume", Kind::Text),
+    ("--volume-density", Kind::Number),
+    ("--volume-scale", Kind::Number),
+    ("--walk", Kind::Switch),
+];
+
+/// The flags given, with their values. A flag whose value was missing or not a number is
+/// there without one, so it falls back to its default.
+static ARGS: OnceLock<HashMap<&'static str, Option<String>>> = OnceLock::new();
+
+/// Parses the command line, once: warns about unknown flags and arguments, flags missing their
+/// value, and values that aren't numbers where a number is expected. The first of repeated
+/// flags counts.
+pub fn parse() {
+    parsed();
+}
+
+fn parsed() -> &'static HashMap<&'static str, Option<String>> {
+    ARGS.get_or_init(|| {
+        let mut flags = HashMap::new();
+        let mut args = std::env::args().skip(1).peekable();
+        while let Some(arg) = args.next() {
+            let Some(&(name, kind)) = FLAGS.iter().find(|(name, _)| *name == arg) else {
+                log::warn!("Ignoring unknown argument {}", arg);
+                continue;
+            };
+            let value = match kind {
+                Kind::Switch => None,
+                _ => match args.next_if(|value| !value.starts_with("--")) {
+                    None => {
+                        log::warn!("Ignoring {}, it expects a value", name);
+                        None
+                    }
+                    Some(value) if kind == Kind::Number && value.parse::<f64>().is_err() => {
+                        log::warn!("Ignoring {} {}, expected a number", name, value);
+                        None
+                    }
+                    Some(value) if kind == Kind::Integer && value.parse::<u64>().is_err() => {
+                        log::warn!("Ignoring {} {}, expected a whole number", name, value);
+                        None
+                    }
+                    value => value,
+                },
+            };
+            flags.entry(name).or_insert(value);
+        }
+        flags
+    })
+}
+
+/// Whether `name` was given.
+pub fn flag(name: &str) -> bool {
+    parsed().contains_key(name)
+}
+
+/// Value given with `name`, if any.
+pub fn value(name: &str) -> Option<&'static str> {
+    parsed().get(name)?.as_deref()
+}
+
+/// Value given with `name` as a number. Only flags `FLAGS` lists as numbers were checked.
+pub fn number<T: FromStr>(name: &str) -> Option<T> {
+    value(name)?.parse().ok()
+}
@@ -0,0 +1,194 @@
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use glam::{Mat4, Quat, Vec3};
+use bytemuck::Pod;
+use crate::animation::{AnimationClip, Channel, Interpolation, Joint, Property, Skeleton, Transform};
+use crate::gltf_loader;
+use crate::scene::{Mesh, SkinnedMesh};
+use crate::scene_file;
+
+/// Directory of the cached imports, one file per source file and content hash.
+const CACHE_DIR: &str = "asset_cache";
+
+const MAGIC: &[u8; 4] = b"RTAC";
+/// Bumped whenever the layout below or what the importer produces changes, so stale caches
+/// are imported again instead of misread.
+const VERSION: u32 = 3;
+
+/// `gltf_loader::load_skinned`, through a binary cache of its result keyed on the file's
+/// contents and the crease angle: repeated runs with the same file skip parsing and flattening
+/// it. A missing, stale or unreadable cache falls back to importing the file and rewrites the
+/// cache.
+pub fn load_skinned(path: impl AsRef<Path>, crease_angle: f32) -> Result<SkinnedMesh, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    let mut key = std::fs::read(path)?;
+    key.extend_from_slice(&crease_angle.to_le_bytes()); // Changes the normals generated for meshes without them
+    let hash = fnv1a(&key);
+    let cache_path = cache_path(path, hash);
+
+    if let Ok(bytes) = std::fs::read(&cache_path) {
+        match decode(&bytes, hash) {
+            Ok(model) => {
+                log::info!("Loaded {} from {}", path.display(), cache_path.display());
+                return Ok(model);
+            }
+            Err(e) => log::warn!("Ignoring asset cache {}: {}", cache_path.display(), e),
+        }
+    }
+
+    let model = gltf_loader::load_skinned(path, crease_angle)?;
+    let written = std::fs::create_dir_all(CACHE_DIR)
+        .and_then(|()| scene_file::write_atomically(&cache_path, &encode(&model, hash)));
+    if let Err(e) = written {
+        log::warn!("Failed to write asset cache {}: {}", cache_path.display(), e);
+    }
+    Ok(model)
+}
+
+fn cache_path(path: &Path, hash: u64) -> PathBuf {
+    let stem = path.file_stem().map_or_else(|| "asset".into(), |stem| stem.to_string_lossy());
+    Path::new(CACHE_DIR).join(format!("{}-{:016x}.bin", stem, hash))
+}
+
+/// 64-bit FNV-1a, stable across builds unlike `std`'s hashers.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+// Layout, all little endian: magic, version, source hash, then the vertices, indices and skin
+// vertices as raw arrays, the skeleton and the clip. Arrays are prefixed with their length.
+
+fn encode(model: &SkinnedMesh, hash: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    put_u32(&mut out, VERSION);
+    out.extend_from_slice(&hash.to_le_bytes());
+
+    put_array(&mut out, &model.mesh.vertices);
+    put_array(&mut out, &model.mesh.indices);
+    put_array(&mut out, &model.skin);
+
+    put_array(&mut out, &model.skeleton.root.to_cols_array());
+    put_u32(&mut out, model.skeleton.joints.len() as u32);
+    for joint in &model.skeleton.joints {
+        put_u32(&mut out, joint.parent.map_or(u32::MAX, |parent| parent as u32));
+        put_array(&mut out, &joint.rest.translation.to_array());
+        put_array(&mut out, &joint.rest.rotation.to_array());
+        put_array(&mut out, &joint.rest.scale.to_array());
+        put_array(&mut out, &joint.inverse_bind.to_cols_array());
+    }
+
+    out.extend_from_slice(&model.clip.duration.to_le_bytes());
+    put_u32(&mut out, model.clip.channels.len() as u32);
+    for channel in &model.clip.channels {
+        put_u32(&mut out, channel.joint as u32);
+        out.push(channel.property as u8);
+        out.push(match channel.interpolation {
+            Interpolation::Step => 0,
+            Interpolation::Linear => 1,
+        });
+        put_array(&mut out, &channel.times);
+        put_array(&mut out, &channel.values);
+    }
+    out
+}
+
+fn decode(bytes: &[u8], hash: u64) -> Result<SkinnedMesh, Box<dyn std::error::Error>> {
+    let mut reader = Reader { bytes };
+    if reader.take(4)? != MAGIC || reader.u32()? != VERSION {
+        return Err("written by another version".into());
+    }
+    if reader.u64()? != hash {
+        return Err("made from another file".into());
+    }
+
+    let mesh = Mesh { vertices: reader.array()?, indices: reader.array()? };
+    let skin = reader.array()?;
+
+    let root = Mat4::from_cols_array(&reader.floats()?);
+    let joint_count = reader.u32()? as usize;
+    let mut joints = Vec::with_capacity(joint_count.min(bytes.len()));
+    for _ in 0..joint_count {
+        let parent = reader.u32()?;
+        let rest = Transform {
+            translation: Vec3::from_array(reader.floats()?),
+            rotation: Quat::from_array(reader.floats()?),
+            scale: Vec3::from_array(reader.floats()?),
+        };
+        let inverse_bind = Mat4::from_cols_array(&reader.floats()?);
+        joints.push(Joint { parent: (parent != u32::MAX).then_some(parent as usize), rest, inverse_bind });
+    }
+
+    let duration = f32::from_le_bytes(reader.take(4)?.try_into()?);
+    let channel_count = reader.u32()? as usize;
+    let mut channels = Vec::with_capacity(channel_count.min(bytes.len()));
+    for _ in 0..channel_count {
+        let joint = reader.u32()? as usize;
+        let property = match reader.take(1)?[0] {
+            0 => Property::Translation,
+            1 => Property::Rotation,
+            2 => Property::Scale,
+            _ => return Err("unknown animation property".into()),
+        };
+        let interpolation = match reader.take(1)?[0] {
+            0 => Interpolation::Step,
+            1 => Interpolation::Linear,
+            _ => return Err("unknown interpolation".into()),
+        };
+        channels.push(Channel { joint, property, interpolation, times: reader.array()?, values: reader.array()? });
+    }
+
+    // The importer guarantees these, a cache that breaks them would crash the skinning or
+    // sampling the clip, which starts from a channel's first key
+    if skin.len() != mesh.vertices.len()
+        || mesh.indices.iter().any(|&i| i as usize >= mesh.vertices.len())
+        || joints.iter().enumerate().any(|(i, joint)| joint.parent.is_some_and(|parent| parent >= i))
+        || channels.iter().any(|channel| channel.joint >= joints.len() || channel.times.is_empty() || channel.times.len() != channel.values.len())
+    {
+        return Err("inconsistent contents".into());
+    }
+    Ok(SkinnedMesh { mesh, skin, skeleton: Skeleton { joints, root }, clip: AnimationClip { duration, channels } })
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_array<T: Pod>(out: &mut Vec<u8>, values: &[T]) {
+    put_u32(out, values.len() as u32);
+    out.extend_from_slice(bytemuck::cast_slice(values));
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        if len > self.bytes.len() {
+            return Err("truncated".into());
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn array<T: Pod>(&mut self) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len.checked_mul(size_of::<T>()).ok_or("truncated")?)?;
+        Ok(bytes.chunks_exact(size_of::<T>()).map(bytemuck::pod_read_unaligned).collect())
+    }
+
+    /// An array written from a fixed size one, e.g. a vector or matrix.
+    fn floats<const N: usize>(&mut self) -> Result<[f32; N], Box<dyn std::error::Error>> {
+        self.array::<f32>()?.try_into().map_err(|_| "wrong vector or matrix size".into())
+    }
+}
@@ -0,0 +1,260 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+use glam::{Quat, Vec3, Vec4};
+use crate::accessibility::Accessibility;
+use crate::camera::{self, CameraPose, PhysicalLens, Projection};
+use crate::bookmarks::BOOKMARK_SLOTS;
+use crate::scene::Scene;
+use crate::scene_file;
+use crate::weather::WeatherKind;
+use crate::light::Light;
+
+/// Scene edits not yet saved with F5, in the scene file format.
+pub const AUTOSAVE_FILE: &str = "scene.autosave.txt";
+/// Camera and render settings of the last session.
+pub const SESSION_FILE: &str = "session.txt";
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Camera and render settings restored at startup.
+#[derive(Clone)]
+pub struct Session {
+    pub camera_position: Vec3,
+    pub camera_orientation: Quat,
+    pub lens_profile: usize,
+    pub projection: Projection,
+    pub physical_lens: PhysicalLens,
+    pub ortho_height: f32,
+    pub mouse_sensitivity: f32,
+    pub invert_y: bool,
+    pub speed: f32,
+    pub settings: Vec4,
+    pub caustics: bool,
+    pub sky_occlusion: bool,
+    pub heat_haze: bool,
+    pub motion_blur: bool,
+    pub bloom: bool,
+    pub samples_per_pixel: u32,
+    pub accessibility: Accessibility,
+    pub depth_of_field: bool,
+    pub weather: WeatherKind,
+    pub light: Light,
+    pub hybrid: bool,
+    pub anaglyph: bool,
+    pub light_path_channel: usize,
+    pub bookmarks: [Option<CameraPose>; BOOKMARK_SLOTS],
+}
+
+/// Periodic, crash-safe saving of the editor's work and the session. The session is
+/// written every interval; the scene only when it has edits the autosave doesn't hold yet,
+/// and right away (`edited`) before the edit goes through a risky GPU operation such as an
+/// acceleration structure rebuild, so a driver reset can't take it along. Files are
+/// replaced atomically, so a crash mid-save leaves the previous autosave intact.
+pub struct Autosave {
+    pub enabled: bool,
+    dirty: bool, // Scene edits newer than the autosave file
+    last_save: Instant,
+}
+
+impl Autosave {
+    pub fn new() -> Self {
+        Self { enabled: true, dirty: false, last_save: Instant::now() }
+    }
+
+    /// Records a scene edit and saves it before the caller acts on it.
+    pub fn edited(&mut self, scene: &Scene, session: &Session) {
+        self.dirty = true;
+        self.save(scene, session);
+    }
+
+    /// Saves right away, whatever the interval; for exiting.
+    pub fn save_now(&mut self, scene: &Scene, session: &Session) {
+        self.save(scene, session);
+    }
+
+    /// Saves if the interval has passed; call once per frame.
+    pub fn tick(&mut self, scene: &Scene, session: &Session) {
+        if self.last_save.elapsed() >= AUTOSAVE_INTERVAL {
+            self.save(scene, session);
+        }
+    }
+
+    fn save(&mut self, scene: &Scene, session: &Session) {
+        self.last_save = Instant::now();
+        if !self.enabled {
+            return;
+        }
+        if let Err(e) = save_session(session, SESSION_FILE) {
+            log::warn!("Autosave: failed to write {}: {}", SESSION_FILE, e);
+        }
+        if self.dirty {
+            match scene_file::save(scene, AUTOSAVE_FILE) {
+                Ok(()) => self.dirty = false,
+                Err(e) => log::warn!("Autosave: failed to write {}: {}", AUTOSAVE_FILE, e),
+            }
+        }
+    }
+}
+
+/// Whether the autosave holds edits made after the scene file was last saved.
+pub fn has_unsaved_edits() -> bool {
+    let modified = |path: &str| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    match (modified(AUTOSAVE_FILE), modified(scene_file::SCENE_FILE)) {
+        (Some(autosave), Some(saved)) => autosave > saved,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+/// Writes the session as `key values...` lines.
+fn save_session(session: &Session, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let flag = |enabled: bool| if enabled { 1 } else { 0 };
+    let p = session.camera_position;
+    let s = session.settings;
+    let l = session.light;
+    let q = session.camera_orientation;
+    let mut text = format!(
+        "# rust-raytracing session\n\
+         camera {} {} {} {} {} {} {} {}\n\
+         projection {} {} {} {} {}\n\
+         controls {} {} {}\n\
+         settings {} {} {} {}\n\
+         caustics {}\n\
+         sky_occlusion {}\n\
+         heat_haze {}\n\
+         motion_blur {}\n\
+         bloom {}\n\
+         samples_per_pixel {}\n\
+         accessibility {} {} {}\n\
+         depth_of_field {}\n\
+         weather {:?}\n\
+         light {} {} {} {} {} {} {} {}\n\
+         render_mode {} {} {}\n",
+        p.x, p.y, p.z, q.x, q.y, q.z, q.w, session.lens_profile,
+        session.projection.name(), session.physical_lens.focal_length, session.physical_lens.sensor_height, session.physical_lens.f_number, session.ortho_height,
+        session.mouse_sensitivity, flag(session.invert_y), session.speed,
+        s.x, s.y, s.z, s.w,
+        flag(session.caustics),
+        flag(session.sky_occlusion),
+        flag(session.heat_haze),
+        flag(session.motion_blur),
+        flag(session.bloom),
+        session.samples_per_pixel,
+        flag(session.accessibility.reduced_motion), flag(session.accessibility.high_contrast), session.accessibility.ui_scale,
+        flag(session.depth_of_field),
+        session.weather,
+        l.position.x, l.position.y, l.position.z, l.color.x, l.color.y, l.color.z, l.intensity, l.radius,
+        flag(session.hybrid), flag(session.anaglyph), session.light_path_channel,
+    );
+    for (slot, pose) in session.bookmarks.iter().enumerate() {
+        if let Some(pose) = pose {
+            let (p, q) = (pose.position, pose.orientation);
+            text += &format!("bookmark {} {} {} {} {} {} {} {}\n", slot + 1, p.x, p.y, p.z, q.x, q.y, q.z, q.w);
+        }
+    }
+    scene_file::write_atomically(path, text.as_bytes())
+}
+
+/// Reads a session written by `save_session` over `session`. Unknown keys are skipped so
+/// older and newer session files still load.
+pub fn load_session(session: &mut Session, path: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)?;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let floats = |count: usize| -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+            if tokens.len() != count + 1 {
+                return Err(format!("`{}` expects {} values", tokens[0], count).into());
+            }
+            Ok(tokens[1..].iter().map(|token| token.parse()).collect::<Result<_, _>>()?)
+        };
+        let flag = || -> Result<bool, Box<dyn std::error::Error>> { Ok(floats(1)?[0] != 0.0) };
+        // Position, orientation and what follows; the orientation is a quaternion, or the yaw
+        // and pitch of sessions from before the camera could roll
+        let posed = |before: usize, after: usize| -> Result<(Vec<f32>, Quat), Box<dyn std::error::Error>> {
+            let v = if tokens.len() == before + after + 6 { floats(before + after + 5)? } else { floats(before + after + 7)? };
+            let angles = &v[before + 3..v.len() - after];
+            let orientation = match *angles {
+                [yaw, pitch] => camera::orientation_from_yaw_pitch(yaw, pitch),
+                [x, y, z, w] if Quat::from_xyzw(x, y, z, w).length() > 0.5 => Quat::from_xyzw(x, y, z, w).normalize(),
+                _ => return Err(format!("`{}` has an invalid orientation", tokens[0]).into()),
+            };
+            Ok((v, orientation))
+        };
+        match tokens[0] {
+            "camera" => {
+                let (v, orientation) = posed(0, 1)?;
+                session.camera_position = Vec3::new(v[0], v[1], v[2]);
+                session.camera_orientation = orientation;
+                session.lens_profile = v[v.len() - 1] as usize;
+            }
+            "projection" => {
+                if tokens.len() != 6 {
+                    return Err("`projection` expects a name and 4 values".into());
+                }
+                session.projection = Projection::parse(tokens[1]).ok_or("unknown projection")?;
+                let v: Vec<f32> = tokens[2..].iter().map(|token| token.parse()).collect::<Result<_, _>>()?;
+                if v.iter().any(|&v| v <= 0.0) {
+                    return Err("`projection` values must be positive".into());
+                }
+                session.physical_lens = PhysicalLens { focal_length: v[0], sensor_height: v[1], f_number: v[2] };
+                session.ortho_height = v[3];
+            }
+            "controls" => {
+                let v = floats(3)?;
+                session.mouse_sensitivity = v[0];
+                session.invert_y = v[1] != 0.0;
+                session.speed = v[2];
+            }
+            "settings" => session.settings = Vec4::from_slice(&floats(4)?),
+            "caustics" => session.caustics = flag()?,
+            "sky_occlusion" => session.sky_occlusion = flag()?,
+            "heat_haze" => session.heat_haze = flag()?,
+            "motion_blur" => session.motion_blur = flag()?,
+            "bloom" => session.bloom = flag()?,
+            "samples_per_pixel" => session.samples_per_pixel = floats(1)?[0] as u32,
+            "accessibility" => {
+                let v = floats(3)?;
+                session.accessibility.reduced_motion = v[0] != 0.0;
+                session.accessibility.high_contrast = v[1] != 0.0;
+                session.accessibility.ui_scale = v[2];
+            }
+            "depth_of_field" => session.depth_of_field = flag()?,
+            "light" => {
+                let v = floats(8)?;
+                session.light = Light {
+                    position: Vec3::new(v[0], v[1], v[2]),
+                    color: Vec3::new(v[3], v[4], v[5]),
+                    intensity: v[6],
+                    radius: v[7],
+                };
+            }
+            "render_mode" => {
+                let v = floats(3)?;
+                session.hybrid = v[0] != 0.0;
+                session.anaglyph = v[1] != 0.0;
+                session.light_path_channel = v[2] as usize;
+            }
+            "bookmark" => {
+                let (v, orientation) = posed(1, 0)?;
+                let slot = v[0] as usize;
+                if !(1..=BOOKMARK_SLOTS).contains(&slot) {
+                    return Err(format!("bookmark slots are 1 to {}", BOOKMARK_SLOTS).into());
+                }
+                session.bookmarks[slot - 1] = Some(CameraPose { position: Vec3::new(v[1], v[2], v[3]), orientation });
+            }
+            "weather" => {
+                session.weather = match tokens.get(1).copied() {
+                    Some("Clear") => WeatherKind::Clear,
+                    Some("Rain") => WeatherKind::Rain,
+                    Some("Snow") => WeatherKind::Snow,
+                    _ => return Err("unknown weather".into()),
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use std::fmt::Write as _;
+use std::time::Instant;
+use glam::{Vec3, Vec4};
+use crate::accessibility::Accessibility;
+use crate::camera::{self, CameraPose};
+use crate::renderer::Renderer;
+use crate::weather::WeatherKind;
+
+const WARMUP_SECONDS: f32 = 3.0; // Not measured: lets the sky cache and the GPU clocks settle
+const MEASURE_SECONDS: f32 = 20.0;
+pub const REPORT_FILE: &str = "benchmark_report.txt";
+
+/// Point on a scenario's camera flight; the camera moves in a straight line between keys,
+/// turning along the shortest arc (see `CameraPose::interpolate`).
+struct CameraKey {
+    time: f32, // Seconds into the measurement
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+}
+
+/// A scripted benchmark run: renderer settings plus a camera flight.
+struct Scenario {
+    name: &'static str,
+    exercises: &'static str, // Subsystems the scenario stresses, for the report
+    settings: Vec4, // Soft shadows, reflections, refraction, SSS
+    caustics: bool,
+    sky_occlusion: bool,
+    heat_haze: bool,
+    motion_blur: bool,
+    weather: WeatherKind,
+    animated: bool, // Paused scenarios freeze the scene at time zero
+    camera: &'static [CameraKey],
+}
+
+const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "static-interior",
+        exercises: "refraction, caustics, projector lights, sky occlusion; no acceleration structure updates",
+        settings: Vec4::new(1.0, 1.0, 1.0, 1.0),
+        caustics: true,
+        sky_occlusion: true,
+        heat_haze: false,
+        motion_blur: false,
+        weather: WeatherKind::Clear,
+        animated: false,
+        camera: &[
+            CameraKey { time: 0.0, position: Vec3::new(-5.0, 1.5, 2.0), yaw: -90.0, pitch: 0.0 },
+            CameraKey { time: MEASURE_SECONDS, position: Vec3::new(-3.5, 1.7, 1.2), yaw: -110.0, pitch: -5.0 },
+        ],
+    },
+    Scenario {
+        name: "forest",
+        exercises: "thousands of TLAS instances, soft shadows through foliage, sky occlusion",
+        settings: Vec4::new(1.0, 1.0, 0.0, 0.0),
+        caustics: false,
+        sky_occlusion: true,
+        heat_haze: false,
+        motion_blur: false,
+        weather: WeatherKind::Clear,
+        animated: true,
+        camera: &[
+            CameraKey { time: 0.0, position: Vec3::new(0.0, 2.0, -11.0), yaw: -90.0, pitch: 0.0 },
+            CameraKey { time: MEASURE_SECONDS * 0.5, position: Vec3::new(-8.0, 2.5, -13.0), yaw: -135.0, pitch: 5.0 },
+            CameraKey { time: MEASURE_SECONDS, position: Vec3::new(-12.0, 3.0, -4.0), yaw: -200.0, pitch: 0.0 },
+        ],
+    },
+    Scenario {
+        name: "dynamic-city",
+        exercises: "keyframed and skinned animation, per-frame TLAS rebuilds for rain, heat haze, motion blur, all ray types",
+        settings: Vec4::new(1.0, 1.0, 1.0, 1.0),
+        caustics: true,
+        sky_occlusion: true,
+        heat_haze: true,
+        motion_blur: true,
+        weather: WeatherKind::Rain,
+        animated: true,
+        camera: &[
+            CameraKey { time: 0.0, position: Vec3::new(6.0, 1.8, 12.0), yaw: -110.0, pitch: -5.0 },
+            CameraKey { time: MEASURE_SECONDS, position: Vec3::new(6.0, 2.5, -6.0), yaw: -160.0, pitch: -10.0 },
+        ],
+    },
+];
+
+struct ScenarioResult {
+    name: &'static str,
+    exercises: &'static str,
+    frame_times: Vec<f32>, // Milliseconds, measured frames only
+}
+
+impl ScenarioResult {
+    fn average_fps(&self) -> f32 {
+        1000.0 * self.frame_times.len() as f32 / self.frame_times.iter().sum::<f32>().max(f32::EPSILON)
+    }
+
+    /// Frame time the slowest 1% of frames reach or exceed.
+    fn percentile_99_ms(&self) -> f32 {
+        let mut sorted = self.frame_times.clone();
+        sorted.sort_by(f32::total_cmp);
+        sorted.get(sorted.len() * 99 / 100).copied().unwrap_or(0.0)
+    }
+}
+
+/// Runs every scenario back to back (`--suite`), then writes a consolidated report for
+/// comparing hardware. Drive it by calling `advance` before each frame.
+pub struct BenchmarkSuite {
+    current: usize,
+    scenario_time: f32, // Seconds since the current scenario started, warm-up included
+    last_frame: Option<Instant>,
+    frame_times: Vec<f32>,
+    results: Vec<ScenarioResult>,
+}
+
+impl BenchmarkSuite {
+    pub fn new() -> Self {
+        Self { current: 0, scenario_time: 0.0, last_frame: None, frame_times: Vec::new(), results: Vec::new() }
+    }
+
+    /// Records the time of the previous frame, moves on to the next scenario when this one is
+    /// done and sets up the renderer for the next frame. Returns `false` once the report has
+    /// been written and the application should exit.
+    pub fn advance(&mut self, renderer: &mut Renderer) -> Result<bool, Box<dyn std::error::Error>> {
+        let now = Instant::now();
+        let Some(last_frame) = self.last_frame.replace(now) else {
+            Self::start(&SCENARIOS[0], renderer);
+            return Ok(true);
+        };
+        let dt = now.duration_since(last_frame).as_secs_f32();
+        if self.scenario_time >= WARMUP_SECONDS {
+            self.frame_times.push(dt * 1000.0);
+        }
+        self.scenario_time += dt;
+
+        if self.scenario_time >= WARMUP_SECONDS + MEASURE_SECONDS {
+            let scenario = &SCENARIOS[self.current];
+            let result = ScenarioResult { name: scenario.name, exercises: scenario.exercises, frame_times: std::mem::take(&mut self.frame_times) };
+            log::info!("Benchmark {}: {:.1} FPS average", result.name, result.average_fps());
+            self.results.push(result);
+
+            self.current += 1;
+            self.scenario_time = 0.0;
+            if self.current == SCENARIOS.len() {
+                self.report(renderer)?;
+                return Ok(false);
+            }
+            Self::start(&SCENARIOS[self.current], renderer);
+        }
+
+        let scenario = &SCENARIOS[self.current];
+        renderer.camera.set_pose(camera_at(scenario.camera, (self.scenario_time - WARMUP_SECONDS).max(0.0)));
+        Ok(true)
+    }
+
+    fn start(scenario: &Scenario, renderer: &mut Renderer) {
+        log::info!("Benchmark {}: {} ({}s warm-up, {}s measured)", scenario.name, scenario.exercises, WARMUP_SECONDS, MEASURE_SECONDS);
+        renderer.settings = scenario.settings;
+        renderer.caustics = scenario.caustics;
+        renderer.set_sky_occlusion(scenario.sky_occlusion);
+        renderer.heat_haze = scenario.heat_haze;
+        renderer.motion_blur = scenario.motion_blur;
+        renderer.set_weather(scenario.weather);
+        renderer.animation_paused = !scenario.animated;
+        renderer.set_scene_time(0.0);
+        renderer.camera.lens_profile = 0;
+        renderer.depth_of_field = false;
+        renderer.anaglyph = false;
+        renderer.light_path_channel = 0;
+        renderer.editor.active = false;
+        renderer.accessibility = Accessibility::new(); // Reduced motion would skip work the scenarios measure
+    }
+
+    fn report(&self, renderer: &Renderer) -> Result<(), Box<dyn std::error::Error>> {
+        let (width, height) = renderer.render_resolution();
+        let mut report = String::new();
+        writeln!(report, "Rust Vulkan Raytracing benchmark suite")?;
+        writeln!(report, "GPU: {}", renderer.gpu_name())?;
+        writeln!(report, "Platform: {}", std::env::consts::OS)?;
+        writeln!(report, "Resolution: {}x{}", width, height)?;
+        writeln!(report, "Warm-up / measured: {}s / {}s per scenario", WARMUP_SECONDS, MEASURE_SECONDS)?;
+        writeln!(report)?;
+        writeln!(report, "{:<16} {:>7} {:>8} {:>8} {:>10} {:>9}", "Scenario", "Frames", "Avg FPS", "Avg ms", "1% low FPS", "Worst ms")?;
+        for result in &self.results {
+            let worst = result.frame_times.iter().copied().fold(0.0, f32::max);
+            writeln!(report, "{:<16} {:>7} {:>8.1} {:>8.2} {:>10.1} {:>9.2}",
+                result.name,
+                result.frame_times.len(),
+                result.average_fps(),
+                1000.0 / result.average_fps(),
+                1000.0 / result.percentile_99_ms().max(f32::EPSILON),
+                worst)?;
+        }
+        // Geometric mean, so no single scenario dominates the score
+        let score = (self.results.iter().map(|result| result.average_fps().ln()).sum::<f32>() / self.results.len() as f32).exp();
+        writeln!(report, "{:<16} {:>7} {:>8.1}", "Overall", "", score)?;
+        writeln!(report)?;
+        for result in &self.results {
+            writeln!(report, "{}: {}", result.name, result.exercises)?;
+        }
+
+        for line in report.lines() {
+            log::info!("{}", line);
+        }
+        std::fs::write(REPORT_FILE, &report)?;
+        log::info!("Benchmark report written to {}", REPORT_FILE);
+        Ok(())
+    }
+}
+
+/// Camera pose `time` seconds into a flight.
+fn camera_at(keys: &[CameraKey], time: f32) -> CameraPose {
+    let next = keys.iter().position(|key| key.time > time).unwrap_or(keys.len() - 1).max(1);
+    let (a, b) = (&keys[next - 1], &keys[next]);
+    let t = ((time - a.time) / (b.time - a.time)).clamp(0.0, 1.0);
+    let pose = |key: &CameraKey| CameraPose { position: key.position, orientation: camera::orientation_from_yaw_pitch(key.yaw, key.pitch) };
+    pose(a).interpolate(&pose(b), t)
+}
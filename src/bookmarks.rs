@@ -0,0 +1,112 @@
+use winit::keyboard::KeyCode;
+use crate::camera::CameraPose;
+use crate::args;
+
+/// Number of bookmark slots, on numpad 1-9.
+pub const BOOKMARK_SLOTS: usize = 9;
+/// Seconds a transition takes, unless `--transition-time` asks for another.
+const DEFAULT_TRANSITION_TIME: f32 = 1.5;
+
+/// Transition time from `--transition-time <seconds>` (0 jumps straight to the pose).
+pub fn transition_time_from_args() -> f32 {
+    args::number::<f32>("--transition-time")
+        .filter(|&seconds| seconds >= 0.0)
+        .unwrap_or(DEFAULT_TRANSITION_TIME)
+}
+
+/// Bookmark slot of a numpad digit key: 1-9 are slots 0-8.
+pub fn bookmark_slot(key: KeyCode) -> Option<usize> {
+    let slot = match key {
+        KeyCode::Numpad1 => 0,
+        KeyCode::Numpad2 => 1,
+        KeyCode::Numpad3 => 2,
+        KeyCode::Numpad4 => 3,
+        KeyCode::Numpad5 => 4,
+        KeyCode::Numpad6 => 5,
+        KeyCode::Numpad7 => 6,
+        KeyCode::Numpad8 => 7,
+        KeyCode::Numpad9 => 8,
+        _ => return None,
+    };
+    Some(slot)
+}
+
+/// A glide from one pose to another, easing in and out.
+struct Transition {
+    from: CameraPose,
+    to: CameraPose,
+    elapsed: f32,
+}
+
+/// Saved camera poses (Ctrl + numpad 1-9 stores, numpad 1-9 recalls) and the tour that visits
+/// them in order (numpad 0), e.g. as a camera path for a recording. The camera glides between
+/// poses over the transition time instead of jumping, unless that is 0.
+pub struct Bookmarks {
+    pub slots: [Option<CameraPose>; BOOKMARK_SLOTS],
+    pub transition_time: f32,
+    transition: Option<Transition>,
+    tour: Option<usize>, // Slot the tour goes to after the current transition
+}
+
+impl Bookmarks {
+    pub fn new(transition_time: f32) -> Self {
+        Bookmarks { slots: [None; BOOKMARK_SLOTS], transition_time, transition: None, tour: None }
+    }
+
+    pub fn store(&mut self, slot: usize, pose: CameraPose) {
+        self.slots[slot] = Some(pose);
+        log::info!("Bookmark {} stored", slot + 1);
+    }
+
+    /// Sets off from `from` towards bookmark `slot`. Returns false if the slot is empty.
+    pub fn recall(&mut self, slot: usize, from: CameraPose) -> bool {
+        let Some(to) = self.slots[slot] else {
+            log::info!("Bookmark {} is empty (Ctrl + numpad {} stores it)", slot + 1, slot + 1);
+            return false;
+        };
+        self.tour = None;
+        self.transition = Some(Transition { from, to, elapsed: 0.0 });
+        log::info!("Bookmark {}", slot + 1);
+        true
+    }
+
+    /// Visits the stored bookmarks in order, starting from `from`.
+    pub fn start_tour(&mut self, from: CameraPose) {
+        let Some(first) = self.next_stored(0) else {
+            log::info!("No bookmarks to tour (Ctrl + numpad 1-9 stores them)");
+            return;
+        };
+        self.recall(first, from);
+        self.tour = self.next_stored(first + 1);
+        log::info!("Touring the bookmarks");
+    }
+
+    /// Stops a transition or tour where the camera is, e.g. when the user takes over.
+    pub fn cancel(&mut self) {
+        self.transition = None;
+        self.tour = None;
+    }
+
+    /// Advances the transition by `dt` seconds and returns the camera's pose, if moving.
+    pub fn update(&mut self, dt: f32) -> Option<CameraPose> {
+        let transition = self.transition.as_mut()?;
+        transition.elapsed += dt;
+        let t = if self.transition_time <= 0.0 { 1.0 } else { (transition.elapsed / self.transition_time).min(1.0) };
+        if t < 1.0 {
+            // Smoothstep: the camera starts and stops gently, as a camera operator would
+            let eased = t * t * (3.0 - 2.0 * t);
+            return Some(transition.from.interpolate(&transition.to, eased));
+        }
+        let arrived = transition.to;
+        self.transition = None;
+        if let Some(slot) = self.tour {
+            self.recall(slot, arrived);
+            self.tour = self.next_stored(slot + 1);
+        }
+        Some(arrived)
+    }
+
+    fn next_stored(&self, from_slot: usize) -> Option<usize> {
+        (from_slot..BOOKMARK_SLOTS).find(|&slot| self.slots[slot].is_some())
+    }
+}
@@ -1,15 +1,160 @@
-use glam::{Mat4, Vec3};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
 use winit::keyboard::KeyCode;
+use crate::args;
 
+/// Field of view and Brown-Conrady distortion of a real camera, so renders can be
+/// matched against footage shot with it. Coefficients follow the OpenCV convention
+/// (normalized image coordinates, y pointing down).
+pub struct LensProfile {
+    pub name: &'static str,
+    pub fov_y: f32, // Vertical field of view in degrees, for a 16:9 frame
+    pub k1: f32,
+    pub k2: f32,
+    pub p1: f32,
+    pub p2: f32,
+}
+
+pub const LENS_PROFILES: [LensProfile; 4] = [
+    LensProfile { name: "Ideal pinhole", fov_y: 45.0, k1: 0.0, k2: 0.0, p1: 0.0, p2: 0.0 },
+    LensProfile { name: "GoPro (wide)", fov_y: 69.5, k1: -0.26, k2: 0.07, p1: 0.0005, p2: -0.0003 },
+    LensProfile { name: "35mm film, 24mm lens", fov_y: 45.7, k1: -0.06, k2: 0.02, p1: 0.0, p2: 0.0 },
+    LensProfile { name: "35mm film, 50mm lens", fov_y: 22.9, k1: -0.015, k2: 0.002, p1: 0.0, p2: 0.0 },
+];
+
+impl LensProfile {
+    /// Undistorted normalized image point that the lens maps to `distorted`, by the same
+    /// fixed-point iteration as raygen.
+    pub fn undistort(&self, distorted: Vec2) -> Vec2 {
+        let mut p = distorted;
+        for _ in 0..8 {
+            let r2 = p.length_squared();
+            let radial = 1.0 + self.k1 * r2 + self.k2 * r2 * r2;
+            let tangential = Vec2::new(
+                2.0 * self.p1 * p.x * p.y + self.p2 * (r2 + 2.0 * p.x * p.x),
+                self.p1 * (r2 + 2.0 * p.y * p.y) + 2.0 * self.p2 * p.x * p.y,
+            );
+            p = (distorted - tangential) / radial;
+        }
+        p
+    }
+
+    fn has_distortion(&self) -> bool {
+        self.k1 != 0.0 || self.k2 != 0.0 || self.p1 != 0.0 || self.p2 != 0.0
+    }
+}
+
+// Mouse look and movement defaults, and the factor per adjusting key press
+const DEFAULT_MOUSE_SENSITIVITY: f32 = 0.1; // Degrees per mouse count
+const DEFAULT_SPEED: f32 = 0.1; // Meters per key press
+const CONTROL_STEP: f32 = 1.25;
+const MOUSE_SENSITIVITY_RANGE: (f32, f32) = (0.01, 1.0);
+const SPEED_RANGE: (f32, f32) = (0.01, 5.0);
+const ROLL_STEP: f32 = 5.0; // Degrees per key press
+
+/// Field of view of the fisheye projection across the frame's height, degrees.
+pub const FISHEYE_FOV: f32 = 180.0;
+
+/// How camera rays leave the camera.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Projection {
+    Perspective, // Pinhole through the lens profile, depth of field with F
+    Orthographic, // Parallel rays, `ortho_height` meters tall
+    Fisheye, // Equidistant, FISHEYE_FOV tall, black outside the image circle
+    ThinLens, // Physical lens and sensor, always with its depth of field
+}
+
+impl Projection {
+    pub fn next(self) -> Self {
+        match self {
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::Fisheye,
+            Projection::Fisheye => Projection::ThinLens,
+            Projection::ThinLens => Projection::Perspective,
+        }
+    }
+
+    /// Parses the `--projection` and session file names.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "perspective" => Some(Projection::Perspective),
+            "orthographic" => Some(Projection::Orthographic),
+            "fisheye" => Some(Projection::Fisheye),
+            "thin-lens" => Some(Projection::ThinLens),
+            _ => None,
+        }
+    }
+
+    /// Name in `--projection` and the session file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Projection::Perspective => "perspective",
+            Projection::Orthographic => "orthographic",
+            Projection::Fisheye => "fisheye",
+            Projection::ThinLens => "thin-lens",
+        }
+    }
+}
+
+/// Lens and sensor of the thin lens projection: the field of view follows from the focal length
+/// and sensor height, the aperture from the f-number.
+#[derive(Clone, Copy, Debug)]
+pub struct PhysicalLens {
+    pub focal_length: f32, // mm
+    pub sensor_height: f32, // mm
+    pub f_number: f32,
+}
+
+impl PhysicalLens {
+    /// Vertical field of view in degrees.
+    pub fn fov_y(&self) -> f32 {
+        (2.0 * (self.sensor_height / (2.0 * self.focal_length)).atan()).to_degrees()
+    }
+
+    /// Aperture radius in meters.
+    pub fn aperture_radius(&self) -> f32 {
+        self.focal_length / (2.0 * self.f_number) * 0.001
+    }
+}
+
+/// Camera orientation without roll that looks `yaw` degrees around from +X towards +Z and
+/// `pitch` degrees up, as the camera used to be described (and benchmark keys still are).
+pub fn orientation_from_yaw_pitch(yaw: f32, pitch: f32) -> Quat {
+    Quat::from_rotation_y(-(yaw + 90.0).to_radians()) * Quat::from_rotation_x(pitch.to_radians())
+}
+
+/// Where the camera is and which way it looks, for bookmarks and camera paths.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CameraPose {
+    pub position: Vec3,
+    pub orientation: Quat, // See `Camera::orientation`
+}
+
+impl CameraPose {
+    /// The pose `t` (0 to 1) of the way to `other`: the position moves in a straight line and
+    /// the orientation turns along the shortest arc (slerp), so the camera never swings the
+    /// long way round.
+    pub fn interpolate(&self, other: &CameraPose, t: f32) -> CameraPose {
+        CameraPose {
+            position: self.position.lerp(other.position, t),
+            orientation: self.orientation.slerp(other.orientation, t).normalize(),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Camera {
     pub position: Vec3,
     pub forward: Vec3,
     pub up: Vec3,
     pub right: Vec3,
-    pub yaw: f32,
-    pub pitch: f32,
+    pub orientation: Quat, // Turns camera space (x right, y up, looking down -z) into world space
     pub speed: f32,
     pub mouse_sensitivity: f32,
+    pub invert_y: bool, // Moving the mouse up looks down
+    pub lens_profile: usize, // Index into LENS_PROFILES
+    pub projection: Projection,
+    pub physical_lens: PhysicalLens,
+    pub ortho_height: f32, // Height of the orthographic view, meters
 }
 
 impl Camera {
@@ -19,22 +164,85 @@ impl Camera {
             forward: Vec3::new(0.0, 0.0, -1.0),
             up: Vec3::Y,
             right: Vec3::X,
-            yaw: -90.0,
-            pitch: 0.0,
-            speed: 0.1,
-            mouse_sensitivity: 0.1,
+            orientation: Quat::IDENTITY,
+            speed: DEFAULT_SPEED,
+            mouse_sensitivity: DEFAULT_MOUSE_SENSITIVITY,
+            invert_y: false,
+            lens_profile: 0,
+            projection: Projection::Perspective,
+            // A 50mm lens at f/2.8 on a full frame (36x24mm) sensor
+            physical_lens: PhysicalLens { focal_length: 50.0, sensor_height: 24.0, f_number: 2.8 },
+            ortho_height: 10.0,
+        }
+    }
+
+    /// Applies `--projection <perspective|orthographic|fisheye|thin-lens>`, `--focal-length <mm>`,
+    /// `--sensor-height <mm>`, `--f-number <n>` and `--ortho-height <m>`, which take precedence
+    /// over the restored session.
+    pub fn apply_projection_args(&mut self) {
+        let positive = |flag: &str| args::number::<f32>(flag).filter(|&v| v > 0.0);
+        if let Some(name) = args::value("--projection") {
+            match Projection::parse(name) {
+                Some(projection) => self.projection = projection,
+                None => log::warn!("Unknown projection `{}`, expected perspective, orthographic, fisheye or thin-lens", name),
+            }
+        }
+        if let Some(focal_length) = positive("--focal-length") {
+            self.physical_lens.focal_length = focal_length;
         }
+        if let Some(sensor_height) = positive("--sensor-height") {
+            self.physical_lens.sensor_height = sensor_height;
+        }
+        if let Some(f_number) = positive("--f-number") {
+            self.physical_lens.f_number = f_number;
+        }
+        if let Some(height) = positive("--ortho-height") {
+            self.ortho_height = height;
+        }
+    }
+
+    /// Vertical field of view in degrees of the perspective projections.
+    pub fn fov_y(&self) -> f32 {
+        match self.projection {
+            Projection::ThinLens => self.physical_lens.fov_y(),
+            _ => self.lens().fov_y,
+        }
+    }
+
+    /// `distortion_params` of the camera UBO: the lens profile's coefficients, which only the
+    /// perspective projection uses.
+    pub fn distortion_params(&self) -> Vec4 {
+        let lens = self.lens();
+        match self.projection {
+            Projection::Perspective => Vec4::new(lens.k1, lens.k2, lens.p1, lens.p2),
+            _ => Vec4::ZERO,
+        }
+    }
+
+    /// `projection_params` of the camera UBO: x: projection (0: perspective and thin lens,
+    /// 3: orthographic, 4: fisheye), y: orthographic half height or fisheye field of view in radians.
+    pub fn projection_params(&self) -> Vec4 {
+        match self.projection {
+            Projection::Perspective | Projection::ThinLens => Vec4::ZERO,
+            Projection::Orthographic => Vec4::new(3.0, self.ortho_height * 0.5, 0.0, 0.0),
+            Projection::Fisheye => Vec4::new(4.0, FISHEYE_FOV.to_radians(), 0.0, 0.0),
+        }
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose { position: self.position, orientation: self.orientation }
+    }
+
+    pub fn set_pose(&mut self, pose: CameraPose) {
+        self.position = pose.position;
+        self.orientation = pose.orientation.normalize();
+        self.update_vectors();
     }
 
     pub fn update_vectors(&mut self) {
-        let front = Vec3::new(
-            self.yaw.to_radians().cos() * self.pitch.to_radians().cos(),
-            self.pitch.to_radians().sin(),
-            self.yaw.to_radians().sin() * self.pitch.to_radians().cos(),
-        ).normalize();
-        self.forward = front;
-        self.right = self.forward.cross(Vec3::Y).normalize();
-        self.up = self.right.cross(self.forward).normalize();
+        self.forward = self.orientation * Vec3::NEG_Z;
+        self.right = self.orientation * Vec3::X;
+        self.up = self.orientation * Vec3::Y;
     }
 
     pub fn handle_input(&mut self, key: KeyCode) {
@@ -45,31 +253,105 @@ impl Camera {
             KeyCode::KeyD => self.position += self.right * self.speed,
             KeyCode::KeyQ => self.position += Vec3::Y * self.speed,
             KeyCode::KeyE => self.position -= Vec3::Y * self.speed,
+            KeyCode::KeyZ => self.roll(ROLL_STEP),
+            KeyCode::KeyX => self.roll(-ROLL_STEP),
             _ => {}
         }
     }
 
+    /// Adjusts the controls: ,/. the mouse sensitivity, ;/' the movement speed, / toggles
+    /// invert-Y. Returns false for keys it doesn't use.
+    pub fn adjust_controls(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Period => self.set_mouse_sensitivity(self.mouse_sensitivity * CONTROL_STEP),
+            KeyCode::Comma => self.set_mouse_sensitivity(self.mouse_sensitivity / CONTROL_STEP),
+            KeyCode::Quote => self.set_speed(self.speed * CONTROL_STEP),
+            KeyCode::Semicolon => self.set_speed(self.speed / CONTROL_STEP),
+            KeyCode::Slash => self.invert_y = !self.invert_y,
+            _ => return false,
+        }
+        log::info!("Controls: mouse sensitivity {:.3}, invert Y {}, speed {:.3} m", self.mouse_sensitivity, if self.invert_y { "on" } else { "off" }, self.speed);
+        true
+    }
+
+    /// Sets the mouse sensitivity, clamped to its range.
+    pub fn set_mouse_sensitivity(&mut self, sensitivity: f32) {
+        self.mouse_sensitivity = sensitivity.clamp(MOUSE_SENSITIVITY_RANGE.0, MOUSE_SENSITIVITY_RANGE.1);
+    }
+
+    /// Sets the movement speed, clamped to its range.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(SPEED_RANGE.0, SPEED_RANGE.1);
+    }
+
+    /// Mouse look: turns about the world's vertical, which keeps the horizon's tilt, and pitches
+    /// about the camera's own right axis, all the way over the top if need be.
     pub fn handle_mouse_input(&mut self, dx: f64, dy: f64) {
-        self.yaw += dx as f32 * self.mouse_sensitivity;
-        self.pitch -= dy as f32 * self.mouse_sensitivity; // Invert Y
+        let dy = if self.invert_y { -dy } else { dy };
+        // Upside down, turning the other way round keeps the view following the mouse
+        let vertical = if self.up.y < 0.0 { Vec3::NEG_Y } else { Vec3::Y };
+        let turn = Quat::from_axis_angle(vertical, -(dx as f32 * self.mouse_sensitivity).to_radians());
+        let pitch = Quat::from_rotation_x(-(dy as f32 * self.mouse_sensitivity).to_radians()); // Mouse y points down
+        self.orientation = (turn * self.orientation * pitch).normalize();
+        self.update_vectors();
+    }
 
-        if self.pitch > 89.0 {
-            self.pitch = 89.0;
-        }
-        if self.pitch < -89.0 {
-            self.pitch = -89.0;
-        }
+    /// Rolls the camera about its view direction, counterclockwise (to the left) for positive
+    /// degrees.
+    fn roll(&mut self, degrees: f32) {
+        self.orientation = (self.orientation * Quat::from_rotation_z(degrees.to_radians())).normalize();
+        self.update_vectors();
     }
 
     pub fn view_matrix(&self) -> Mat4 {
         Mat4::look_at_rh(self.position, self.position + self.forward, self.up)
     }
 
+    pub fn lens(&self) -> &LensProfile {
+        &LENS_PROFILES[self.lens_profile]
+    }
+
+    /// The fisheye has no projection matrix; rasterization and motion vectors make do with the
+    /// perspective one.
     pub fn proj_matrix(&self, aspect: f32) -> Mat4 {
         // Vulkan has inverted Y-axis compared to OpenGL
-        let mut proj = Mat4::perspective_rh(45.0f32.to_radians(), aspect, 0.1, 1000.0);
+        let mut proj = match self.projection {
+            Projection::Orthographic => {
+                let half_height = self.ortho_height * 0.5;
+                Mat4::orthographic_rh(-half_height * aspect, half_height * aspect, -half_height, half_height, 0.1, 1000.0)
+            }
+            _ => Mat4::perspective_rh(self.fov_y().to_radians(), aspect, 0.1, 1000.0),
+        };
         // Flip Y-axis for Vulkan's coordinate system
         proj.y_axis.y *= -1.0;
         proj
     }
+
+    /// World space origin and direction of the pinhole camera ray through `ndc`
+    /// (Vulkan NDC, y down), the same ray raygen traces for that point.
+    pub fn primary_ray(&self, ndc: Vec2, aspect: f32) -> (Vec3, Vec3) {
+        match self.projection {
+            Projection::Orthographic => {
+                let half_height = self.ortho_height * 0.5;
+                let origin = self.position + (self.right * ndc.x * aspect - self.up * ndc.y) * half_height;
+                return (origin, self.forward);
+            }
+            Projection::Fisheye => {
+                let p = Vec2::new(ndc.x * aspect, -ndc.y);
+                let theta = p.length() * FISHEYE_FOV.to_radians() * 0.5;
+                let sideways = p.normalize_or_zero() * theta.sin();
+                return (self.position, self.right * sideways.x + self.up * sideways.y + self.forward * theta.cos());
+            }
+            Projection::Perspective | Projection::ThinLens => {}
+        }
+        let target = self.proj_matrix(aspect).inverse() * Vec4::new(ndc.x, ndc.y, 1.0, 1.0);
+        let mut direction = target.truncate().normalize();
+        let lens = self.lens();
+        if self.projection == Projection::Perspective && lens.has_distortion() {
+            let image = Vec2::new(direction.x, -direction.y) / -direction.z;
+            let undistorted = lens.undistort(image);
+            direction = Vec3::new(undistorted.x, -undistorted.y, -1.0).normalize();
+        }
+        (self.position, self.view_matrix().inverse().transform_vector3(direction))
+    }
 }
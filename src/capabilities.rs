@@ -0,0 +1,152 @@
+use ash::{vk, Instance};
+use std::ffi::CStr;
+use crate::vulkan::RAY_TRACING_EXTENSIONS;
+
+/// VK_KHR_portability_subset. It is a provisional extension, so it is named here rather than
+/// through ash's provisional bindings.
+pub const PORTABILITY_SUBSET_NAME: &CStr = c"VK_KHR_portability_subset";
+
+/// Ray recursion depth the ray tracing pipeline is created with where the device allows it.
+const PREFERRED_RAY_RECURSION_DEPTH: u32 = 10;
+/// Recursion levels besides the reflection and refraction bounces: the camera ray, and the
+/// shadow, subsurface and hit info rays traced from the deepest bounce. A device with less
+/// can't run the ray tracing shaders at all.
+const NON_BOUNCE_RECURSION_DEPTH: u32 = 5;
+
+/// What the selected GPU can do, queried once at startup. Logged as a report, and the settings
+/// that depend on it (ray tracing itself, the recursion depth, dynamic resolution) are turned
+/// down to what the device supports up front instead of failing later in Vulkan calls.
+#[derive(Clone, Debug, Default)]
+pub struct Capabilities {
+    pub device_name: String,
+    pub api_version: u32,
+    pub driver_version: u32,
+    pub ray_tracing_pipeline: bool, // All of RAY_TRACING_EXTENSIONS
+    pub ray_query: bool, // VK_KHR_ray_query, reported only: the renderer traces with pipelines
+    pub portability_subset: bool, // VK_KHR_portability_subset: Vulkan over another API, like MoltenVK over Metal
+    pub max_ray_recursion_depth: u32,
+    pub max_ray_dispatch_invocations: u32,
+    pub max_instance_count: u64, // Per TLAS
+    pub max_geometry_count: u64, // Per BLAS
+    pub max_primitive_count: u64, // Triangles per BLAS
+    pub max_image_dimension_2d: u32,
+    pub max_push_constants_size: u32,
+    pub max_storage_buffer_range: u32,
+    pub max_sampled_images_per_stage: u32,
+    pub device_local_memory: u64, // Bytes over all device local heaps
+    pub timestamps: bool, // Timestamp queries on the graphics and compute queues
+    pub timestamp_period: f32, // Nanoseconds per timestamp tick
+    pub subgroup_ray_tracing: bool, // Subgroup arithmetic in raygen and closest hit shaders
+    pub texture_compression_bc: bool,
+}
+
+impl Capabilities {
+    /// Queries `pdevice`, whose extensions are `extensions`. The ray tracing limits are only
+    /// read (and otherwise 0) when the device has the ray tracing extensions.
+    pub fn query(instance: &Instance, pdevice: vk::PhysicalDevice, extensions: &[vk::ExtensionProperties]) -> Self {
+        let has_extension = |name: &CStr| extensions.iter()
+            .any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == name);
+        let ray_tracing_pipeline = RAY_TRACING_EXTENSIONS.into_iter().all(&has_extension);
+
+        let mut rt_props = vk::PhysicalDeviceRayTracingPipelinePropertiesKHR::default();
+        let mut as_props = vk::PhysicalDeviceAccelerationStructurePropertiesKHR::default();
+        let mut subgroup_props = vk::PhysicalDeviceSubgroupProperties::default();
+        let mut props = vk::PhysicalDeviceProperties2 { p_next: &mut subgroup_props as *mut _ as *mut _, ..Default::default() };
+        // Structures of extensions the device lacks mustn't be chained
+        if ray_tracing_pipeline {
+            as_props.p_next = &mut rt_props as *mut _ as *mut _;
+            subgroup_props.p_next = &mut as_props as *mut _ as *mut _;
+        }
+        unsafe { instance.get_physical_device_properties2(pdevice, &mut props) };
+        let properties = props.properties;
+        let limits = properties.limits;
+
+        let mem_props = unsafe { instance.get_physical_device_memory_properties(pdevice) };
+        let device_local_memory = mem_props.memory_heaps[..mem_props.memory_heap_count as usize].iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum();
+        let features = unsafe { instance.get_physical_device_features(pdevice) };
+        let ray_tracing_stages = vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR;
+        let subgroup_ray_tracing = subgroup_props.supported_stages.contains(ray_tracing_stages)
+            && subgroup_props.supported_operations.contains(vk::SubgroupFeatureFlags::BASIC | vk::SubgroupFeatureFlags::ARITHMETIC);
+
+        Self {
+            device_name: unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }.to_string_lossy().into_owned(),
+            api_version: properties.api_version,
+            driver_version: properties.driver_version,
+            ray_tracing_pipeline,
+            ray_query: has_extension(vk::KHR_RAY_QUERY_NAME),
+            portability_subset: has_extension(PORTABILITY_SUBSET_NAME),
+            max_ray_recursion_depth: rt_props.max_ray_recursion_depth,
+            max_ray_dispatch_invocations: rt_props.max_ray_dispatch_invocation_count,
+            max_instance_count: as_props.max_instance_count,
+            max_geometry_count: as_props.max_geometry_count,
+            max_primitive_count: as_props.max_primitive_count,
+            max_image_dimension_2d: limits.max_image_dimension2_d,
+            max_push_constants_size: limits.max_push_constants_size,
+            max_storage_buffer_range: limits.max_storage_buffer_range,
+            max_sampled_images_per_stage: limits.max_per_stage_descriptor_sampled_images,
+            device_local_memory,
+            timestamps: limits.timestamp_compute_and_graphics == vk::TRUE,
+            timestamp_period: limits.timestamp_period,
+            subgroup_ray_tracing,
+            texture_compression_bc: features.texture_compression_bc == vk::TRUE,
+        }
+    }
+
+    /// Why the ray tracing pipeline can't run on this device, if it can't.
+    pub fn ray_tracing_unsupported(&self) -> Option<String> {
+        if !self.ray_tracing_pipeline {
+            return Some("it lacks the ray tracing pipeline extensions (VK_KHR_ray_tracing_pipeline, VK_KHR_acceleration_structure)".into());
+        }
+        if self.max_ray_recursion_depth < NON_BOUNCE_RECURSION_DEPTH {
+            return Some(format!("its ray recursion depth of {} is below the {} the shaders need",
+                self.max_ray_recursion_depth, NON_BOUNCE_RECURSION_DEPTH));
+        }
+        None
+    }
+
+    /// Recursion depth to create the ray tracing pipeline with.
+    pub fn ray_recursion_depth(&self) -> u32 {
+        PREFERRED_RAY_RECURSION_DEPTH.min(self.max_ray_recursion_depth)
+    }
+
+    /// Reflection and refraction bounces the shaders trace (`MAX_BOUNCE_DEPTH`), which is what
+    /// a device with less recursion than preferred gives up.
+    pub fn bounce_depth(&self) -> u32 {
+        self.ray_recursion_depth().saturating_sub(NON_BOUNCE_RECURSION_DEPTH)
+    }
+
+    /// Why a scene of `instances` TLAS instances, whose largest mesh has `max_triangles`
+    /// triangles, can't be put into acceleration structures on this device, if it can't.
+    pub fn scene_exceeds_limits(&self, instances: usize, max_triangles: usize) -> Option<String> {
+        if instances as u64 > self.max_instance_count {
+            return Some(format!("{} instances, the device takes {} per TLAS", instances, self.max_instance_count));
+        }
+        if max_triangles as u64 > self.max_primitive_count {
+            return Some(format!("a mesh of {} triangles, the device takes {} per BLAS", max_triangles, self.max_primitive_count));
+        }
+        None
+    }
+
+    /// Logs the report, one line per area.
+    pub fn log(&self) {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        log::info!("Device capabilities of {}:", self.device_name);
+        log::info!("  Vulkan {}.{}.{}{}, driver version {:#x}",
+            vk::api_version_major(self.api_version), vk::api_version_minor(self.api_version), vk::api_version_patch(self.api_version),
+            if self.portability_subset { " (portability subset)" } else { "" }, self.driver_version);
+        log::info!("  Ray tracing pipeline: {}, ray query: {}", yes_no(self.ray_tracing_pipeline), yes_no(self.ray_query));
+        if self.ray_tracing_pipeline {
+            log::info!("  Ray recursion depth: {} (using {}, {} bounces), dispatch: {} rays",
+                self.max_ray_recursion_depth, self.ray_recursion_depth(), self.bounce_depth(), self.max_ray_dispatch_invocations);
+            log::info!("  Acceleration structures: {} instances per TLAS, {} geometries and {} triangles per BLAS",
+                self.max_instance_count, self.max_geometry_count, self.max_primitive_count);
+        }
+        log::info!("  Images up to {} px, push constants {} B, storage buffers up to {} MB, {} sampled images per stage",
+            self.max_image_dimension_2d, self.max_push_constants_size, self.max_storage_buffer_range / (1024 * 1024), self.max_sampled_images_per_stage);
+        log::info!("  Device local memory: {} MB, GPU timestamps: {}, BC textures: {}, subgroup arithmetic in ray tracing shaders: {}",
+            self.device_local_memory / (1024 * 1024), yes_no(self.timestamps), yes_no(self.texture_compression_bc), yes_no(self.subgroup_ray_tracing));
+    }
+}
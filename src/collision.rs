@@ -0,0 +1,272 @@
+use glam::{Mat4, Vec3};
+use crate::preview;
+use crate::scene::{RayMask, Scene};
+use crate::args;
+
+/// Closest the camera gets to a surface.
+const RADIUS: f32 = 0.3;
+/// Height of the camera above the ground while walking.
+const EYE_HEIGHT: f32 = 1.7;
+/// Ledges up to this high are stepped onto rather than blocking the way.
+const STEP_HEIGHT: f32 = 0.4;
+const GRAVITY: f32 = 9.81;
+const JUMP_SPEED: f32 = 4.0;
+/// Walking off the world stops falling here.
+const FLOOR_LIMIT: f32 = -50.0;
+/// Shapes per BVH leaf.
+const LEAF_SIZE: usize = 4;
+
+/// How the camera moves through the scene.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NavigationMode {
+    Fly, // Free flight, stopped by surfaces
+    Walk, // On the ground with gravity, Q jumps
+    Noclip, // Free flight through everything
+}
+
+impl NavigationMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            NavigationMode::Fly => "fly",
+            NavigationMode::Walk => "walk",
+            NavigationMode::Noclip => "noclip",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            NavigationMode::Fly => NavigationMode::Walk,
+            NavigationMode::Walk => NavigationMode::Noclip,
+            NavigationMode::Noclip => NavigationMode::Fly,
+        }
+    }
+}
+
+/// Starting navigation mode: `--walk`, `--noclip`, or flight that collides with the scene.
+pub fn navigation_from_args() -> NavigationMode {
+    if args::flag("--walk") {
+        NavigationMode::Walk
+    } else if args::flag("--noclip") {
+        NavigationMode::Noclip
+    } else {
+        NavigationMode::Fly
+    }
+}
+
+/// A static TLAS instance the camera collides with.
+struct Shape {
+    mesh_index: usize,
+    transform: Mat4,
+    min: Vec3,
+    max: Vec3,
+}
+
+struct Node {
+    min: Vec3,
+    max: Vec3,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Leaf { start: usize, end: usize }, // Range of `Collider::shapes`
+    Inner { left: usize, right: usize },
+}
+
+/// CPU ray casts against the scene's static instances, through a BVH over their world bounds
+/// and the preview's triangle tests within each instance.
+struct Collider {
+    shapes: Vec<Shape>,
+    nodes: Vec<Node>,
+}
+
+impl Collider {
+    /// Instances rays from the camera can hit, leaving out the moving ones (animated,
+    /// simulated, precipitation and particles), whose bounds would change every frame.
+    fn new(scene: &Scene) -> Self {
+        let mut moving = vec![false; scene.objects.len()];
+        let moving_objects = scene.animations.iter().map(|animation| animation.object_index)
+            .chain(scene.rigid_bodies.iter().map(|body| body.object_index))
+            .chain(scene.precipitation.clone())
+            .chain(scene.particles.clone());
+        for object_index in moving_objects {
+            moving[object_index] = true;
+        }
+
+        let shape = |mesh_index: usize, transform: Mat4| {
+            let (min, max) = scene.meshes[mesh_index].transformed_bounds(transform);
+            Shape { mesh_index, transform, min, max }
+        };
+        let objects = scene.objects.iter().enumerate()
+            .filter(|&(i, obj)| !moving[i] && obj.visible && obj.rays.contains(RayMask::CAMERA))
+            .map(|(_, obj)| shape(obj.mesh_index, obj.transform));
+        // Grass is walked through, and sways anyway
+        let batches = scene.instance_batches.iter().enumerate()
+            .filter(|&(i, _)| scene.grass != Some(i))
+            .flat_map(|(_, batch)| batch.instances.iter().map(move |inst| (batch.mesh_index, inst.transform)))
+            .map(|(mesh_index, transform)| shape(mesh_index, transform));
+        let mut shapes: Vec<Shape> = objects.chain(batches).collect();
+
+        let mut nodes = Vec::new();
+        if !shapes.is_empty() {
+            let count = shapes.len();
+            build(&mut shapes, 0, count, &mut nodes);
+        }
+        Collider { shapes, nodes }
+    }
+
+    /// Distance along the unit `direction` to the closest surface within `t_max`.
+    fn cast(&self, scene: &Scene, origin: Vec3, direction: Vec3, t_max: f32) -> Option<f32> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_dir = direction.recip();
+        let mut closest: Option<f32> = None;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !slab_hit(node.min, node.max, origin, inv_dir, closest.unwrap_or(t_max)) {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Inner { left, right } => stack.extend([left, right]),
+                NodeKind::Leaf { start, end } => {
+                    for shape in &self.shapes[start..end] {
+                        let t_max = closest.unwrap_or(t_max);
+                        if let Some((_, t)) = preview::intersect_mesh(&scene.meshes[shape.mesh_index], shape.transform, origin, direction, t_max) {
+                            closest = Some(t);
+                        }
+                    }
+                }
+            }
+        }
+        closest
+    }
+}
+
+/// Builds the BVH node over `shapes[start..end]`, splitting at the median of the longest axis
+/// of their centers, and returns its index.
+fn build(shapes: &mut [Shape], start: usize, end: usize, nodes: &mut Vec<Node>) -> usize {
+    let (min, max) = shapes[start..end].iter()
+        .fold((Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)), |(min, max), shape| (min.min(shape.min), max.max(shape.max)));
+    let index = nodes.len();
+    nodes.push(Node { min, max, kind: NodeKind::Leaf { start, end } });
+    if end - start <= LEAF_SIZE {
+        return index;
+    }
+
+    let center = |shape: &Shape| (shape.min + shape.max) * 0.5;
+    let (center_min, center_max) = shapes[start..end].iter()
+        .fold((Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)), |(min, max), shape| (min.min(center(shape)), max.max(center(shape))));
+    let extent = center_max - center_min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z { 0 } else if extent.y >= extent.z { 1 } else { 2 };
+    let mid = (start + end) / 2;
+    shapes[start..end].select_nth_unstable_by(mid - start, |a, b| center(a)[axis].total_cmp(&center(b)[axis]));
+
+    let left = build(shapes, start, mid, nodes);
+    let right = build(shapes, mid, end, nodes);
+    nodes[index].kind = NodeKind::Inner { left, right };
+    index
+}
+
+fn slab_hit(min: Vec3, max: Vec3, origin: Vec3, inv_dir: Vec3, t_max: f32) -> bool {
+    let (t0, t1) = ((min - origin) * inv_dir, (max - origin) * inv_dir);
+    t0.min(t1).max_element().max(0.0) <= t0.max(t1).min_element().min(t_max)
+}
+
+/// Keeps the camera out of the scene's geometry: key presses move it only as far as the
+/// surfaces in the way allow, sliding along them, and in walk mode it stays on the ground.
+pub struct Navigation {
+    pub mode: NavigationMode,
+    collider: Collider,
+    vertical_speed: f32, // Walk mode, up is positive
+}
+
+impl Navigation {
+    pub fn new(scene: &Scene, mode: NavigationMode) -> Self {
+        let collider = Collider::new(scene);
+        log::info!("Navigation: {} ({} collision shapes)", mode.label(), collider.shapes.len());
+        Navigation { mode, collider, vertical_speed: 0.0 }
+    }
+
+    /// Picks up editor changes to the static instances.
+    pub fn rebuild(&mut self, scene: &Scene) {
+        self.collider = Collider::new(scene);
+    }
+
+    pub fn cycle_mode(&mut self) {
+        self.mode = self.mode.next();
+        self.vertical_speed = 0.0;
+        log::info!("Navigation: {}", self.mode.label());
+    }
+
+    /// Where a camera at `position` moved by `delta` (a key press) ends up. Walking keeps the
+    /// move horizontal and turns moving up into a jump.
+    pub fn move_camera(&mut self, scene: &Scene, position: Vec3, delta: Vec3) -> Vec3 {
+        match self.mode {
+            NavigationMode::Noclip => position + delta,
+            NavigationMode::Fly => self.slide(scene, position, delta, &[0.0]),
+            NavigationMode::Walk => {
+                if delta.y > 0.0 && self.ground_distance(scene, position, EYE_HEIGHT + 0.05).is_some() {
+                    self.vertical_speed = JUMP_SPEED;
+                }
+                let horizontal = Vec3::new(delta.x, 0.0, delta.z).normalize_or_zero() * delta.length();
+                // Blocked at eye and knee height, steps below the knee are climbed
+                let moved = self.slide(scene, position, horizontal, &[0.0, EYE_HEIGHT - STEP_HEIGHT]);
+                match self.ground_distance(scene, moved, EYE_HEIGHT) {
+                    Some(distance) => moved + Vec3::Y * (EYE_HEIGHT - distance),
+                    None => moved,
+                }
+            }
+        }
+    }
+
+    /// Applies gravity to a walking camera at `position` over `dt` seconds.
+    pub fn update(&mut self, scene: &Scene, position: Vec3, dt: f32) -> Vec3 {
+        if self.mode != NavigationMode::Walk {
+            return position;
+        }
+        let dt = dt.min(0.1);
+        self.vertical_speed -= GRAVITY * dt;
+        let dy = self.vertical_speed * dt;
+        if dy > 0.0 {
+            if self.collider.cast(scene, position, Vec3::Y, dy + RADIUS).is_some() {
+                self.vertical_speed = 0.0;
+                return position;
+            }
+            return position + Vec3::Y * dy;
+        }
+        match self.ground_distance(scene, position, EYE_HEIGHT - dy) {
+            Some(distance) => {
+                self.vertical_speed = 0.0;
+                position + Vec3::Y * (EYE_HEIGHT - distance)
+            }
+            None if position.y + dy < FLOOR_LIMIT => {
+                self.vertical_speed = 0.0;
+                position
+            }
+            None => position + Vec3::Y * dy,
+        }
+    }
+
+    fn ground_distance(&self, scene: &Scene, position: Vec3, max_distance: f32) -> Option<f32> {
+        self.collider.cast(scene, position, -Vec3::Y, max_distance)
+    }
+
+    /// `position + delta`, or where sliding along the blocking surfaces gets: the move is
+    /// retried one axis at a time. Casts from each of `heights` below the camera.
+    fn slide(&self, scene: &Scene, position: Vec3, delta: Vec3, heights: &[f32]) -> Vec3 {
+        let blocked = |from: Vec3, step: Vec3| {
+            let length = step.length();
+            length > 0.0 && heights.iter().any(|&height| {
+                self.collider.cast(scene, from - Vec3::Y * height, step / length, length + RADIUS).is_some()
+            })
+        };
+        if !blocked(position, delta) {
+            return position + delta;
+        }
+        [Vec3::X, Vec3::Z, Vec3::Y].into_iter().fold(position, |position, axis| {
+            let step = axis * delta.dot(axis);
+            if blocked(position, step) { position } else { position + step }
+        })
+    }
+}
@@ -0,0 +1,93 @@
+// Color conventions, end to end:
+// - Authored colors (material colors in code and `materials.txt`, instance color overrides)
+//   are sRGB encoded, as a color picker shows them. They are decoded to linear when they are
+//   uploaded for the shaders.
+// - Everything the shaders light with is linear: vertex colors (glTF's are linear), light
+//   colors and intensities, the sky, textures (all of them data: normal maps, gobos and alpha
+//   masks, in UNORM formats). The traced radiance, the accumulation image, the HDR image and
+//   the luminance image are linear, and so are the EXR files and AOVs.
+// - The display images (storage, post and upscale, all B8G8R8A8_UNORM) hold sRGB encoded
+//   color, the way the SRGB_NONLINEAR swapchain takes it: raygen and the raster preview
+//   encode when they write them, and the passes over them (down to the resolve pass onto the
+//   swapchain) decode, work in linear and encode again (`include/display.glsl`). PNGs are sRGB
+//   encoded like them.
+
+/// sRGB decoding of one channel in 0..1.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+/// sRGB encoding of one linear channel, clamped to 0..1.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Decodes an authored RGBA color for the shaders. Alpha is linear already.
+pub fn srgb_to_linear_rgba(color: [f32; 4]) -> [f32; 4] {
+    [srgb_to_linear(color[0]), srgb_to_linear(color[1]), srgb_to_linear(color[2]), color[3]]
+}
+
+/// Encodes a linear RGBA color for the display images (e.g. as a clear value). Alpha stays linear.
+pub fn linear_to_srgb_rgba(color: [f32; 4]) -> [f32; 4] {
+    [linear_to_srgb(color[0]), linear_to_srgb(color[1]), linear_to_srgb(color[2]), color[3]]
+}
+
+/// Encodes linear RGBA floats as 8-bit sRGB, for PNGs and the display images. Alpha stays linear.
+pub fn linear_to_srgb8(pixels: &[f32]) -> Vec<u8> {
+    pixels.chunks_exact(4)
+        .flat_map(|p| [linear_to_srgb(p[0]), linear_to_srgb(p[1]), linear_to_srgb(p[2]), p[3].clamp(0.0, 1.0)])
+        .map(|v| (v * 255.0).round() as u8)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trip() {
+        for i in 0..=255 {
+            let c = i as f32 / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(c)) - c).abs() < 1e-5, "{}", c);
+        }
+    }
+
+    #[test]
+    fn breakpoints() {
+        // Both pieces meet at the breakpoints, so neither curve jumps there
+        assert!((srgb_to_linear(0.04045) - 0.04045 / 12.92).abs() < 1e-7);
+        assert!((((0.04045f32 + 0.055) / 1.055).powf(2.4) - srgb_to_linear(0.04045)).abs() < 1e-5);
+        assert!((linear_to_srgb(0.0031308) - 0.0031308 * 12.92).abs() < 1e-7);
+        assert!((1.055 * 0.0031308f32.powf(1.0 / 2.4) - 0.055 - linear_to_srgb(0.0031308)).abs() < 1e-5);
+        assert!((srgb_to_linear(0.04045) - 0.0031308).abs() < 1e-6);
+    }
+
+    #[test]
+    fn encoding_clamps() {
+        assert_eq!(linear_to_srgb(-1.0), 0.0);
+        assert!((linear_to_srgb(4.0) - 1.0).abs() < 1e-6);
+        assert_eq!(linear_to_srgb8(&[-1.0, 2.0, 100.0, 1.5]), [0, 255, 255, 255]);
+    }
+
+    #[test]
+    fn srgb8_reference_values() {
+        // 18% grey, linear middle grey and the darkest encoded step
+        assert_eq!(linear_to_srgb8(&[0.0, 0.18, 0.5, 0.5]), [0, 118, 188, 128]);
+        assert_eq!(linear_to_srgb8(&[1.0, 0.0003, 0.0, 0.0]), [255, 1, 0, 0]);
+    }
+
+    #[test]
+    fn srgb8_round_trip() {
+        for i in 0..=255u8 {
+            let c = srgb_to_linear(i as f32 / 255.0);
+            assert_eq!(linear_to_srgb8(&[c, c, c, 1.0])[..3], [i; 3]);
+        }
+    }
+
+    #[test]
+    fn alpha_stays_linear() {
+        assert_eq!(srgb_to_linear_rgba([1.0, 0.5, 0.0, 0.5])[3], 0.5);
+        assert_eq!(linear_to_srgb_rgba([1.0, 0.5, 0.0, 0.5])[3], 0.5);
+    }
+}
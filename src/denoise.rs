@@ -0,0 +1,21 @@
+/// Denoises a linear HDR image with Intel Open Image Denoise, guided by albedo and normal AOVs.
+/// All images are RGBA floats, row by row; the alpha of `color` is passed through. OIDN runs
+/// on the best device it finds: a supported GPU (SYCL, CUDA or HIP) if there is one, else the CPU.
+pub fn denoise(width: u32, height: u32, color: &[f32], albedo: &[f32], normal: &[f32]) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+    let rgb = |rgba: &[f32]| -> Vec<f32> { rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect() };
+    let (color_rgb, albedo_rgb, normal_rgb) = (rgb(color), rgb(albedo), rgb(normal));
+    let mut output = vec![0.0f32; color_rgb.len()];
+
+    let device = oidn::Device::new();
+    oidn::RayTracing::new(&device)
+        .hdr(true)
+        .image_dimensions(width as usize, height as usize)
+        .albedo_normal(&albedo_rgb, &normal_rgb)
+        .filter(&color_rgb, &mut output)
+        .map_err(|e| format!("OIDN filter failed: {:?}", e))?;
+    if let Err((_, message)) = device.get_error() {
+        return Err(format!("OIDN error: {}", message).into());
+    }
+
+    Ok(output.chunks_exact(3).zip(color.chunks_exact(4)).flat_map(|(c, p)| [c[0], c[1], c[2], p[3]]).collect())
+}
@@ -0,0 +1,165 @@
+use ash::vk;
+use crate::args;
+
+/// Brightness SDR white is shown at on an HDR display unless `--hdr-paper-white` gives
+/// another, nits. Around what HDR desktops show their windows at.
+const DEFAULT_PAPER_WHITE: f32 = 200.0;
+
+/// What the swapchain presents, and so the transfer function the resolve pass encodes with.
+///
+/// SDR presents the display image sRGB encoded, on a UNORM swapchain in the sRGB color space.
+/// With `--hdr scrgb` or `--hdr hdr10`, on a surface
+/// that offers that color space, the resolve pass draws the unclamped HDR image instead, with
+/// the display image's white at the paper white (`--hdr-paper-white <nits>`) and highlights
+/// above it up to what the monitor can show:
+/// - scRGB: FP16, linear with sRGB primaries, 1.0 at 80 nits. Windows' HDR mode composes in it.
+/// - HDR10: 10 bits, PQ encoded absolute luminance with Rec. 2020 primaries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayOutput {
+    Sdr,
+    Scrgb,
+    Hdr10,
+}
+
+impl DisplayOutput {
+    pub fn name(self) -> &'static str {
+        match self {
+            DisplayOutput::Sdr => "SDR",
+            DisplayOutput::Scrgb => "scRGB",
+            DisplayOutput::Hdr10 => "HDR10",
+        }
+    }
+
+    pub fn is_hdr(self) -> bool {
+        self != DisplayOutput::Sdr
+    }
+
+    /// The transfer function the resolve and HUD shaders encode with, `OUTPUT_*` in
+    /// `include/display.glsl`.
+    pub fn transfer(self) -> u32 {
+        self as u32
+    }
+
+    fn color_space(self) -> vk::ColorSpaceKHR {
+        match self {
+            DisplayOutput::Sdr => vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            DisplayOutput::Scrgb => vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+            DisplayOutput::Hdr10 => vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+        }
+    }
+
+    /// Swapchain formats that can hold the output, in order of preference.
+    fn formats(self) -> &'static [vk::Format] {
+        match self {
+            DisplayOutput::Sdr => &[vk::Format::B8G8R8A8_UNORM],
+            DisplayOutput::Scrgb => &[vk::Format::R16G16B16A16_SFLOAT],
+            DisplayOutput::Hdr10 => &[vk::Format::A2B10G10R10_UNORM_PACK32, vk::Format::A2R10G10B10_UNORM_PACK32],
+        }
+    }
+
+    /// The surface format among `available` that presents this output, if any.
+    fn surface_format(self, available: &[vk::SurfaceFormatKHR]) -> Option<vk::SurfaceFormatKHR> {
+        self.formats().iter().find_map(|&format| available.iter()
+            .find(|surface_format| surface_format.format == format && surface_format.color_space == self.color_space())
+            .copied())
+    }
+}
+
+/// Picks the swapchain's output and format from the formats the surface offers: the HDR output
+/// `--hdr` asks for if the surface has it, else SDR. `colorspace_extension` is whether
+/// VK_EXT_swapchain_colorspace is enabled, without which surfaces offer no HDR color spaces.
+pub fn choose_output(available: &[vk::SurfaceFormatKHR], colorspace_extension: bool) -> (DisplayOutput, vk::SurfaceFormatKHR) {
+    let sdr = vk::SurfaceFormatKHR { format: vk::Format::B8G8R8A8_UNORM, color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR };
+    let offered: Vec<&str> = [DisplayOutput::Scrgb, DisplayOutput::Hdr10].into_iter()
+        .filter(|output| output.surface_format(available).is_some())
+        .map(DisplayOutput::name)
+        .collect();
+    if offered.is_empty() {
+        log::info!("The surface offers no HDR output");
+    } else {
+        log::info!("HDR outputs the surface offers: {} (--hdr selects one)", offered.join(", "));
+    }
+
+    let Some(requested) = requested_output() else {
+        return (DisplayOutput::Sdr, sdr);
+    };
+    match requested.surface_format(available) {
+        Some(surface_format) => (requested, surface_format),
+        None => {
+            if colorspace_extension {
+                log::warn!("--hdr {}: the surface doesn't offer it, presenting SDR", requested.name());
+            } else {
+                log::warn!("--hdr {}: the Vulkan driver lacks VK_EXT_swapchain_colorspace, presenting SDR", requested.name());
+            }
+            (DisplayOutput::Sdr, sdr)
+        }
+    }
+}
+
+/// The HDR output `--hdr <scrgb|hdr10>` asks for, if any. Unknown ones are logged and ignored.
+fn requested_output() -> Option<DisplayOutput> {
+    let value = args::value("--hdr")?;
+    match value {
+        "scrgb" => Some(DisplayOutput::Scrgb),
+        "hdr10" => Some(DisplayOutput::Hdr10),
+        _ => {
+            log::warn!("Unknown --hdr {}, expected scrgb or hdr10", value);
+            None
+        }
+    }
+}
+
+/// Strength of the resolve pass's sharpening from `--sharpen <0..1>`, off without it. The
+/// sharpening is contrast adaptive: it is strongest on soft detail and backs off at edges that
+/// are already crisp, and never overshoots the neighboring pixels.
+pub fn sharpness_from_args() -> f32 {
+    let Some(value) = args::value("--sharpen") else {
+        return 0.0;
+    };
+    match value.parse::<f32>() {
+        Ok(sharpness) if (0.0..=1.0).contains(&sharpness) => sharpness,
+        _ => {
+            log::warn!("Ignoring --sharpen {}, expected 0 to 1", value);
+            0.0
+        }
+    }
+}
+
+/// The largest rect with `source`'s aspect ratio centered in `target`, where the resolve pass
+/// draws the picture, leaving black bars on two sides. Bars narrower than a source pixel would
+/// only come from rounding the render size to whole pixels, so the picture fills those.
+pub fn letterbox(source: vk::Extent2D, target: vk::Extent2D) -> vk::Rect2D {
+    let (source_width, source_height) = (source.width as f32, source.height as f32);
+    let scale = (target.width as f32 / source_width).min(target.height as f32 / source_height);
+    let mut extent = vk::Extent2D {
+        width: ((source_width * scale).round() as u32).clamp(1, target.width),
+        height: ((source_height * scale).round() as u32).clamp(1, target.height),
+    };
+    if ((target.width - extent.width) as f32) < scale {
+        extent.width = target.width;
+    }
+    if ((target.height - extent.height) as f32) < scale {
+        extent.height = target.height;
+    }
+    vk::Rect2D {
+        offset: vk::Offset2D {
+            x: ((target.width - extent.width) / 2) as i32,
+            y: ((target.height - extent.height) / 2) as i32,
+        },
+        extent,
+    }
+}
+
+/// Luminance SDR white is shown at in HDR output, nits.
+pub fn paper_white_from_args() -> f32 {
+    let Some(value) = args::value("--hdr-paper-white") else {
+        return DEFAULT_PAPER_WHITE;
+    };
+    match value.parse::<f32>() {
+        Ok(nits) if (80.0..=10000.0).contains(&nits) => nits,
+        _ => {
+            log::warn!("Ignoring --hdr-paper-white {}, expected 80 to 10000 nits", value);
+            DEFAULT_PAPER_WHITE
+        }
+    }
+}
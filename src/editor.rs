@@ -0,0 +1,117 @@
+use glam::{Mat4, Vec3};
+use winit::keyboard::KeyCode;
+use crate::scene::Scene;
+use crate::scene_file;
+
+// Keyboard nudge per key press
+const TRANSLATE_STEP: f32 = 0.1; // Meters
+const ROTATE_STEP: f32 = 5.0; // Degrees
+const SCALE_STEP: f32 = 1.05; // Factor
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+/// In-app scene editor. Edits the selected object (see `Renderer::selection`) along one
+/// axis at a time: translation and rotation use world axes around the object's origin,
+/// scaling uses the object's own axes.
+pub struct Editor {
+    pub active: bool,
+    pub mode: GizmoMode,
+    pub axis: usize, // 0: x, 1: y, 2: z
+    moved: bool, // Transforms changed since the TLAS was last rebuilt
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            mode: GizmoMode::Translate,
+            axis: 0,
+            moved: false,
+        }
+    }
+
+    /// Handles a key while the editor is active. Returns false for keys it doesn't use,
+    /// which then go on to the regular controls.
+    pub fn handle_input(&mut self, key: KeyCode, scene: &mut Scene, selection: Option<usize>) -> bool {
+        match key {
+            KeyCode::KeyM => {
+                self.mode = match self.mode {
+                    GizmoMode::Translate => GizmoMode::Rotate,
+                    GizmoMode::Rotate => GizmoMode::Scale,
+                    GizmoMode::Scale => GizmoMode::Translate,
+                };
+                log::info!("Editor: {:?}", self.mode);
+            }
+            KeyCode::KeyX | KeyCode::KeyY | KeyCode::KeyZ => {
+                self.axis = match key {
+                    KeyCode::KeyX => 0,
+                    KeyCode::KeyY => 1,
+                    _ => 2,
+                };
+                log::info!("Editor: {} axis", ["X", "Y", "Z"][self.axis]);
+            }
+            KeyCode::ArrowUp | KeyCode::ArrowRight | KeyCode::ArrowDown | KeyCode::ArrowLeft => {
+                let Some(object_index) = selection else {
+                    log::info!("Editor: click an object to select it first");
+                    return true;
+                };
+                let sign = if matches!(key, KeyCode::ArrowUp | KeyCode::ArrowRight) { 1.0 } else { -1.0 };
+                self.nudge(scene, object_index, sign);
+            }
+            KeyCode::BracketLeft | KeyCode::BracketRight => {
+                let Some(object_index) = selection else {
+                    log::info!("Editor: click an object to select it first");
+                    return true;
+                };
+                let count = scene.materials.len();
+                let object = &mut scene.objects[object_index];
+                object.material_index = if key == KeyCode::BracketRight {
+                    (object.material_index + 1) % count
+                } else {
+                    (object.material_index + count - 1) % count
+                };
+                self.moved = true;
+                log::info!("Editor: object {} material {}", object_index, object.material_index);
+            }
+            KeyCode::F5 => match scene_file::save(scene, scene_file::SCENE_FILE) {
+                Ok(()) => log::info!("Editor: saved scene to {}", scene_file::SCENE_FILE),
+                Err(e) => log::error!("Editor: failed to save {}: {}", scene_file::SCENE_FILE, e),
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    fn nudge(&mut self, scene: &mut Scene, object_index: usize, sign: f32) {
+        let axis = [Vec3::X, Vec3::Y, Vec3::Z][self.axis];
+        match self.mode {
+            GizmoMode::Translate => {
+                let offset = Mat4::from_translation(axis * TRANSLATE_STEP * sign);
+                scene.edit_transform(object_index, |m| offset * m);
+            }
+            GizmoMode::Rotate => {
+                let rotation = Mat4::from_axis_angle(axis, (ROTATE_STEP * sign).to_radians());
+                scene.edit_transform(object_index, |m| {
+                    let origin = m.w_axis.truncate();
+                    Mat4::from_translation(origin) * rotation * Mat4::from_translation(-origin) * m
+                });
+            }
+            GizmoMode::Scale => {
+                let factor = if sign > 0.0 { SCALE_STEP } else { 1.0 / SCALE_STEP };
+                let scale = Mat4::from_scale(Vec3::ONE + axis * (factor - 1.0));
+                scene.edit_transform(object_index, |m| m * scale);
+            }
+        }
+        self.moved = true;
+    }
+
+    /// Whether objects changed since the last call, i.e. the TLAS needs a rebuild.
+    pub fn take_moved(&mut self) -> bool {
+        std::mem::take(&mut self.moved)
+    }
+}
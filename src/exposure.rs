@@ -0,0 +1,115 @@
+use winit::keyboard::KeyCode;
+use crate::args;
+
+/// Bins of the luminance histogram, over log2 luminance from `MIN_LOG_LUMINANCE` to
+/// `MAX_LOG_LUMINANCE`. Pixels outside the range land in the end bins.
+pub const HISTOGRAM_BINS: usize = 64;
+pub const MIN_LOG_LUMINANCE: f32 = -12.0;
+pub const MAX_LOG_LUMINANCE: f32 = 4.0;
+
+/// Fractions of the darkest and brightest pixels left out of the average, so a bright sky or a
+/// black corner doesn't decide the exposure of the rest of the view.
+const DARK_PERCENTILE: f32 = 0.1;
+const BRIGHT_PERCENTILE: f32 = 0.95;
+/// Average luminance auto exposure brings the view to: middle gray.
+const TARGET_LUMINANCE: f32 = 0.18;
+/// Exposures auto exposure stays within, so a view of nothing but sky or a dark corner isn't
+/// blown out or crushed to black.
+const MIN_AUTO_EXPOSURE: f32 = 0.25;
+const MAX_AUTO_EXPOSURE: f32 = 16.0;
+/// Rate the eye adapts at, per second: faster to brighter views, as the eye does.
+const ADAPT_SPEED_BRIGHTER: f32 = 3.0;
+const ADAPT_SPEED_DARKER: f32 = 1.0;
+/// EV offset + and - step by, stops.
+const EV_STEP: f32 = 0.5;
+const MAX_EV_OFFSET: f32 = 8.0;
+
+/// Exposure of the traced image, applied by raygen before it writes the displayed color.
+///
+/// Manual exposure scales by 2^EV offset, so an offset of 0 shows the scene as traced. Auto
+/// exposure (eye adaptation, from `--auto-exposure`, toggled with Backspace) reads the
+/// luminance histogram the exposure pass builds of each frame and eases the exposure towards
+/// one that brings the view's average to middle gray, so walking from the bright street into
+/// a dark interior stays viewable; the EV offset then biases it. Accumulated images hold the
+/// exposure they started with.
+pub struct Exposure {
+    pub auto: bool,
+    pub ev_offset: f32, // Stops
+    adapted: f32, // Auto exposure's current multiplier, before the EV offset
+}
+
+impl Exposure {
+    pub fn new() -> Self {
+        Self { auto: auto_exposure_from_args(), ev_offset: 0.0, adapted: 1.0 }
+    }
+
+    /// Handles a key. Returns false for keys it doesn't use.
+    pub fn handle_input(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Equal | KeyCode::NumpadAdd => self.ev_offset = (self.ev_offset + EV_STEP).min(MAX_EV_OFFSET),
+            KeyCode::Minus | KeyCode::NumpadSubtract => self.ev_offset = (self.ev_offset - EV_STEP).max(-MAX_EV_OFFSET),
+            KeyCode::Backspace => {
+                self.auto = !self.auto;
+                log::info!("Auto exposure: {}", if self.auto { "on" } else { "off" });
+                return true;
+            }
+            _ => return false,
+        }
+        log::info!("Exposure: {:+.1} EV", self.ev_offset);
+        true
+    }
+
+    /// Multiplier of the traced color.
+    pub fn multiplier(&self) -> f32 {
+        let manual = self.ev_offset.exp2();
+        if self.auto { self.adapted * manual } else { manual }
+    }
+
+    /// Eases auto exposure towards the view's luminance `histogram` over `dt` seconds, or
+    /// jumps there with `instant` (reduced motion). An empty histogram (no frame measured in
+    /// this slot yet) leaves it as it is.
+    pub fn adapt(&mut self, histogram: &[u32; HISTOGRAM_BINS], dt: f32, instant: bool) {
+        if !self.auto {
+            return;
+        }
+        let Some(average) = average_luminance(histogram) else {
+            return;
+        };
+        let target = (TARGET_LUMINANCE / average).clamp(MIN_AUTO_EXPOSURE, MAX_AUTO_EXPOSURE);
+        if instant {
+            self.adapted = target;
+            return;
+        }
+        // Ease in stops, so brightening and darkening by the same factor take the same time
+        let speed = if target < self.adapted { ADAPT_SPEED_BRIGHTER } else { ADAPT_SPEED_DARKER };
+        let (current, target) = (self.adapted.log2(), target.log2());
+        self.adapted = (current + (target - current) * (1.0 - (-dt * speed).exp())).exp2();
+    }
+}
+
+fn auto_exposure_from_args() -> bool {
+    args::flag("--auto-exposure")
+}
+
+/// Geometric mean luminance of the pixels between the dark and bright percentiles of
+/// `histogram`, or None for an empty one.
+fn average_luminance(histogram: &[u32; HISTOGRAM_BINS]) -> Option<f32> {
+    let total: u64 = histogram.iter().map(|&count| count as u64).sum();
+    if total == 0 {
+        return None;
+    }
+    let (low, high) = (total as f32 * DARK_PERCENTILE, total as f32 * BRIGHT_PERCENTILE);
+    let bin_size = (MAX_LOG_LUMINANCE - MIN_LOG_LUMINANCE) / HISTOGRAM_BINS as f32;
+    let (mut seen, mut weight, mut log_sum) = (0.0f32, 0.0f32, 0.0f32);
+    for (bin, &count) in histogram.iter().enumerate() {
+        // The part of this bin's pixels between the percentiles
+        let start = seen;
+        seen += count as f32;
+        let counted = seen.min(high) - start.max(low);
+        if counted > 0.0 {
+            weight += counted;
+            log_sum += counted * (MIN_LOG_LUMINANCE + (bin as f32 + 0.5) * bin_size);
+        }
+    }
+    (weight > 0.0).then(|| (log_sum / weight).exp2())
+}
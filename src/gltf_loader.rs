@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::path::Path;
+use glam::{Mat4, Quat, Vec3};
+use crate::animation::{AnimationClip, Channel, Interpolation, Joint, Property, Skeleton, SkinVertex, Transform};
+use crate::scene::{Mesh, SkinnedMesh, VertexAttributes};
+
+/// Loads the first skinned mesh node of a glTF / GLB file. All primitives of the mesh are
+/// merged into one mesh, and the first animation in the file becomes the clip.
+/// Joints are reordered so parents come before their children. Normals, UVs, vertex colors
+/// (COLOR_0) and tangents are optional, see `Mesh::from_attributes`; missing normals are
+/// smoothed up to `crease_angle` degrees.
+pub fn load_skinned(path: impl AsRef<Path>, crease_angle: f32) -> Result<SkinnedMesh, Box<dyn std::error::Error>> {
+    let (document, buffers, _images) = gltf::import(path)?;
+    let buffer_data = |buffer: gltf::Buffer| Some(&buffers[buffer.index()][..]);
+
+    let node = document.nodes()
+        .find(|node| node.mesh().is_some() && node.skin().is_some())
+        .ok_or("no skinned mesh node")?;
+    let (mesh, skin) = (node.mesh().unwrap(), node.skin().unwrap());
+
+    // Parent of every node, to rebuild the joint hierarchy
+    let mut node_parents = HashMap::new();
+    for parent in document.nodes() {
+        for child in parent.children() {
+            node_parents.insert(child.index(), parent.index());
+        }
+    }
+    let node_transforms: HashMap<usize, Transform> = document.nodes().map(|node| {
+        let (translation, rotation, scale) = node.transform().decomposed();
+        (node.index(), Transform {
+            translation: Vec3::from(translation),
+            rotation: Quat::from_array(rotation),
+            scale: Vec3::from(scale),
+        })
+    }).collect();
+
+    let joint_nodes: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+    let inverse_binds: Vec<Mat4> = match skin.reader(buffer_data).read_inverse_bind_matrices() {
+        Some(matrices) => matrices.map(|m| Mat4::from_cols_array_2d(&m)).collect(),
+        None => vec![Mat4::IDENTITY; joint_nodes.len()],
+    };
+
+    // Nearest ancestor that is also a joint; other ancestors only contribute to the root transform
+    let joint_parent = |node: usize| {
+        let mut current = node_parents.get(&node).copied();
+        while let Some(ancestor) = current {
+            if let Some(joint) = joint_nodes.iter().position(|&n| n == ancestor) {
+                return Some(joint);
+            }
+            current = node_parents.get(&ancestor).copied();
+        }
+        None
+    };
+
+    // Depth-first order so parents precede children; `remap` maps skin joint index -> skeleton index
+    let mut order = Vec::with_capacity(joint_nodes.len());
+    let mut pending: Vec<usize> = (0..joint_nodes.len()).filter(|&j| joint_parent(joint_nodes[j]).is_none()).collect();
+    while let Some(joint) = pending.pop() {
+        order.push(joint);
+        pending.extend((0..joint_nodes.len()).filter(|&j| joint_parent(joint_nodes[j]) == Some(joint)));
+    }
+    let mut remap = vec![0; joint_nodes.len()];
+    for (new_index, &old_index) in order.iter().enumerate() {
+        remap[old_index] = new_index;
+    }
+
+    let root_joint = *order.first().ok_or("skin has no joints")?;
+    let mut root = Mat4::IDENTITY;
+    let mut current = node_parents.get(&joint_nodes[root_joint]).copied();
+    while let Some(ancestor) = current {
+        root = node_transforms[&ancestor].matrix() * root;
+        current = node_parents.get(&ancestor).copied();
+    }
+
+    let skeleton = Skeleton {
+        joints: order.iter().map(|&j| Joint {
+            parent: joint_parent(joint_nodes[j]).map(|parent| remap[parent]),
+            rest: node_transforms[&joint_nodes[j]],
+            inverse_bind: inverse_binds[j],
+        }).collect(),
+        root,
+    };
+
+    let mut out = Mesh { vertices: Vec::new(), indices: Vec::new() };
+    let mut skin_vertices = Vec::new();
+    for primitive in mesh.primitives() {
+        let reader = primitive.reader(buffer_data);
+        let base = out.vertices.len() as u32;
+
+        let positions: Vec<[f32; 3]> = reader.read_positions().ok_or("primitive without positions")?.collect();
+        let attributes = VertexAttributes {
+            normals: reader.read_normals().map(|normals| normals.collect()),
+            uvs: reader.read_tex_coords(0).map(|uvs| uvs.into_f32().collect()),
+            colors: reader.read_colors(0).map(|colors| colors.into_rgb_f32().collect()),
+            tangents: reader.read_tangents().map(|tangents| tangents.collect()),
+            positions,
+        };
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..attributes.positions.len() as u32).collect(),
+        };
+        let vertex_count = attributes.positions.len();
+        let (part, sources) = Mesh::from_attributes(attributes, indices, crease_angle)?;
+
+        let joints: Vec<[u16; 4]> = reader.read_joints(0).ok_or("primitive without joints")?.into_u16().collect();
+        let weights: Vec<[f32; 4]> = reader.read_weights(0).ok_or("primitive without weights")?.into_f32().collect();
+        if joints.len() != vertex_count || weights.len() != vertex_count {
+            return Err("primitive attributes have mismatched lengths".into());
+        }
+        if joints.iter().flatten().any(|&j| j as usize >= remap.len()) {
+            return Err("vertex references a joint outside the skin".into());
+        }
+
+        for &source in &sources {
+            let (joints, weights) = (joints[source as usize], weights[source as usize]);
+            let total: f32 = weights.iter().sum();
+            let scale = if total > 0.0 { 1.0 / total } else { 0.0 };
+            skin_vertices.push(SkinVertex {
+                joints: joints.map(|j| remap[j as usize] as u32),
+                weights: weights.map(|w| w * scale),
+            });
+        }
+        out.vertices.extend(part.vertices);
+        out.indices.extend(part.indices.iter().map(|i| base + i));
+    }
+
+    let mut clip = AnimationClip { duration: 0.0, channels: Vec::new() };
+    if let Some(animation) = document.animations().next() {
+        for channel in animation.channels() {
+            let Some(joint) = joint_nodes.iter().position(|&n| n == channel.target().node().index()) else {
+                continue;
+            };
+            let reader = channel.reader(buffer_data);
+            let (Some(inputs), Some(outputs)) = (reader.read_inputs(), reader.read_outputs()) else {
+                continue;
+            };
+            let times: Vec<f32> = inputs.collect();
+            let (property, mut values): (Property, Vec<[f32; 4]>) = match outputs {
+                gltf::animation::util::ReadOutputs::Translations(t) => (Property::Translation, t.map(|v| [v[0], v[1], v[2], 0.0]).collect()),
+                gltf::animation::util::ReadOutputs::Rotations(r) => (Property::Rotation, r.into_f32().collect()),
+                gltf::animation::util::ReadOutputs::Scales(s) => (Property::Scale, s.map(|v| [v[0], v[1], v[2], 0.0]).collect()),
+                gltf::animation::util::ReadOutputs::MorphTargetWeights(_) => continue,
+            };
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                // Keys are stored as (in tangent, value, out tangent); keep the values and interpolate linearly
+                gltf::animation::Interpolation::CubicSpline => {
+                    values = values.chunks_exact(3).map(|key| key[1]).collect();
+                    Interpolation::Linear
+                }
+            };
+            if times.is_empty() || times.len() != values.len() {
+                continue;
+            }
+
+            clip.duration = clip.duration.max(*times.last().unwrap());
+            clip.channels.push(Channel { joint: remap[joint], property, interpolation, times, values });
+        }
+    }
+
+    Ok(SkinnedMesh { mesh: out, skin: skin_vertices, skeleton, clip })
+}
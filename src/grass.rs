@@ -0,0 +1,112 @@
+use glam::{Mat4, Quat, Vec3, Vec4};
+use rayon::prelude::*;
+use crate::scene::{Instance, Mesh, Scene, Vertex};
+use crate::terrain::Terrain;
+use crate::texture::hash2;
+use crate::args;
+
+/// Grass tufts on the terrain unless `--grass` gives another count.
+const DEFAULT_COUNT: usize = 20000;
+/// Half extent of the grass-free square around the street, meters.
+const CLEARING: f32 = 10.5;
+/// Grass grows up to this many meters above the flat middle, the hilltops are rock.
+const TREE_LINE: f32 = 5.0;
+/// Horizontal direction the wind blows towards.
+const WIND_DIRECTION: Vec3 = Vec3::new(0.8, 0.0, 0.6);
+const WIND_SPEED: f32 = 4.0; // m/s the gusts roll across the field at
+const GUST_SIZE: f32 = 12.0; // Meters between gust fronts
+const SWAY: f32 = 0.25; // Tip displacement per meter of height in a gust, at most
+const FLUTTER: f32 = 0.06; // Same, for the fast per-tuft motion
+
+/// Grass tuft count from `--grass <n>`, to stress instancing and any-hit shading with more instances.
+pub fn count_from_args() -> usize {
+    args::number("--grass").unwrap_or(DEFAULT_COUNT)
+}
+
+/// Grass card: two crossed unit quads standing on the origin, textured with the blades'
+/// alpha mask (v = 0 at the tips).
+pub fn card_mesh() -> Mesh {
+    let mut vertices = Vec::with_capacity(8);
+    for (axis, normal) in [(Vec3::X, Vec3::Z), (Vec3::Z, Vec3::X)] {
+        for (side, height) in [(-0.5, 0.0), (0.5, 0.0), (0.5, 1.0), (-0.5, 1.0)] {
+            let position = axis * side + Vec3::Y * height;
+            vertices.push(Vertex::new(position.into(), normal.into(), [side + 0.5, 1.0 - height]));
+        }
+    }
+    let indices = vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7];
+    Mesh { vertices, indices }
+}
+
+/// `count` grass tufts of `material_index` scattered over the terrain's gentle slopes, outside
+/// the street and below the rocky hilltops. Candidate spots are drawn from a hash sequence and
+/// the unsuitable ones skipped, so there may be fewer.
+pub fn tufts(terrain: &Terrain, material_index: usize, count: usize) -> Vec<Instance> {
+    let half = terrain.size * 0.5 - 1.0;
+    (0..count as i32)
+        .filter_map(|i| {
+            let x = (hash2(i, 17) * 2.0 - 1.0) * half;
+            let z = (hash2(i, 31) * 2.0 - 1.0) * half;
+            if x.abs() < CLEARING && z.abs() < CLEARING {
+                return None;
+            }
+            let ground = terrain.height(x, z);
+            let slope = (terrain.height(x + 0.5, z) - terrain.height(x - 0.5, z)).abs()
+                .max((terrain.height(x, z + 0.5) - terrain.height(x, z - 0.5)).abs());
+            if ground > TREE_LINE || slope > 0.6 {
+                return None;
+            }
+            let height = 0.35 + 0.35 * hash2(i, 47);
+            let dryness = hash2(i, 59);
+            Some(Instance {
+                // Sunk a little so the card's base is hidden on slopes, the terrain object sits 0.15 below its heightmap
+                transform: Mat4::from_scale_rotation_translation(
+                    Vec3::new(0.6 + 0.4 * hash2(i, 61), height, 0.6 + 0.4 * hash2(i, 67)),
+                    Quat::from_rotation_y(hash2(i, 73) * std::f32::consts::TAU),
+                    Vec3::new(x, ground - 0.2, z),
+                ),
+                material_index,
+                // Fresh green with some tufts drying to straw
+                color: [0.12 + 0.35 * dryness * dryness, 0.4 + 0.1 * dryness, 0.06 + 0.06 * dryness, 1.0],
+            })
+        })
+        .collect()
+}
+
+/// Wind over the scene's grass: every frame, each tuft's card is sheared in the wind direction
+/// from its rest pose, by gusts rolling across the field plus a faster flutter of its own. Only
+/// the instance transforms change, so the TLAS is refit rather than rebuilt.
+pub struct Wind {
+    rest: Vec<Mat4>, // Transforms the grass was placed with
+}
+
+impl Wind {
+    pub fn new(scene: &Scene) -> Self {
+        let rest = scene.grass
+            .map(|batch| scene.instance_batches[batch].instances.iter().map(|inst| inst.transform).collect())
+            .unwrap_or_default();
+        Wind { rest }
+    }
+
+    /// Whether there is grass to sway, i.e. `update` needs to run and the TLAS be refit.
+    pub fn active(&self) -> bool {
+        !self.rest.is_empty()
+    }
+
+    /// Poses the grass at scene `time` (seconds).
+    pub fn update(&self, time: f32, scene: &mut Scene) {
+        let Some(batch) = scene.grass else { return };
+        let direction = WIND_DIRECTION.normalize();
+        scene.instance_batches[batch].instances.par_iter_mut().zip(&self.rest).enumerate().for_each(|(i, (inst, rest))| {
+            let position = rest.w_axis.truncate();
+            let front = (position.dot(direction) - time * WIND_SPEED) / GUST_SIZE * std::f32::consts::TAU;
+            let gust = 0.5 + 0.5 * front.sin();
+            let phase = hash2(i as i32, 89) * std::f32::consts::TAU;
+            let flutter = (time * 7.0 + phase).sin();
+            // Shear in world space, so the tips lean downwind whichever way the card faces
+            let lean = direction * (SWAY * gust * gust) + Vec3::new(phase.cos(), 0.0, phase.sin()) * (FLUTTER * flutter);
+            let shear = Mat4::from_cols(Vec4::X, Vec4::new(lean.x, 1.0 - 0.5 * lean.length_squared(), lean.z, 0.0), Vec4::Z, Vec4::W);
+            // About the tuft's base, which stays on the ground
+            inst.transform = Mat4::from_translation(position) * shear * Mat4::from_translation(-position) * *rest;
+        });
+    }
+}
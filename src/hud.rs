@@ -0,0 +1,353 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use glam::Vec2;
+
+/// Size of a glyph cell in pixels, before the UI scale.
+pub const GLYPH_WIDTH: u32 = 8;
+pub const GLYPH_HEIGHT: u32 = 16;
+/// Glyph instances each frame slot has room for.
+pub const MAX_GLYPHS: usize = 8192;
+
+/// How long a status message stays up, and how many are shown at once.
+const MESSAGE_DURATION: Duration = Duration::from_secs(4);
+const MAX_MESSAGES: usize = 6;
+/// Margin around the text blocks, in glyph cells.
+const MARGIN: f32 = 0.5;
+
+/// The controls help is split into at most this many side by side columns to fit the window.
+const MAX_HELP_COLUMNS: usize = 3;
+
+const TEXT_COLOR: u32 = 0xffffffff;
+const HINT_COLOR: u32 = 0xffb0b0b0; // Gray
+const WARN_COLOR: u32 = 0xff40d0ff; // Yellow
+const ERROR_COLOR: u32 = 0xff4040ff; // Red
+
+/// Characters after printable ASCII in `FONT`; others are drawn as '?'.
+const EXTRA_CHARS: &str = "°áéíóúüñÁÉÍÓÚÜÑ¿¡×–";
+
+/// 8x16 bitmap font, one byte per row with the leftmost pixel in the high bit: printable ASCII,
+/// then `EXTRA_CHARS`. Rasterized from DejaVu Sans Mono (Bitstream Vera license).
+const FONT: [[u8; 16]; 114] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00], // '!'
+    [0x00, 0x00, 0x00, 0x24, 0x24, 0x24, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x00, 0x00, 0x00, 0x12, 0x16, 0x7f, 0x34, 0x24, 0xfe, 0x6c, 0x68, 0x48, 0x00, 0x00, 0x00, 0x00], // '#'
+    [0x00, 0x00, 0x00, 0x08, 0x3e, 0x68, 0x68, 0x38, 0x0e, 0x0a, 0x0e, 0x3c, 0x08, 0x00, 0x00, 0x00], // '$'
+    [0x00, 0x00, 0x00, 0x70, 0x90, 0x90, 0x76, 0x18, 0x4e, 0x09, 0x09, 0x0e, 0x00, 0x00, 0x00, 0x00], // '%'
+    [0x00, 0x00, 0x18, 0x3c, 0x20, 0x20, 0x30, 0x59, 0x49, 0xc6, 0x46, 0x3e, 0x00, 0x00, 0x00, 0x00], // '&'
+    [0x00, 0x00, 0x00, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x00, 0x00, 0x00, 0x08, 0x08, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x08, 0x08, 0x00, 0x00, 0x00], // '('
+    [0x00, 0x00, 0x00, 0x10, 0x10, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x10, 0x10, 0x00, 0x00, 0x00], // ')'
+    [0x00, 0x00, 0x00, 0x00, 0x3c, 0x18, 0x66, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '*'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x7e, 0x7e, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x10, 0x10, 0x00, 0x00], // ','
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // '.'
+    [0x00, 0x00, 0x00, 0x06, 0x04, 0x0c, 0x08, 0x18, 0x10, 0x30, 0x20, 0x20, 0x40, 0x00, 0x00, 0x00], // '/'
+    [0x00, 0x00, 0x00, 0x3c, 0x66, 0x42, 0x42, 0x5a, 0x42, 0x66, 0x24, 0x3c, 0x00, 0x00, 0x00, 0x00], // '0'
+    [0x00, 0x00, 0x00, 0x38, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x3e, 0x00, 0x00, 0x00, 0x00], // '1'
+    [0x00, 0x00, 0x10, 0x7c, 0x06, 0x06, 0x04, 0x0c, 0x18, 0x30, 0x60, 0x7e, 0x00, 0x00, 0x00, 0x00], // '2'
+    [0x00, 0x00, 0x10, 0x7c, 0x06, 0x06, 0x1c, 0x1c, 0x06, 0x02, 0x06, 0x7c, 0x00, 0x00, 0x00, 0x00], // '3'
+    [0x00, 0x00, 0x00, 0x0c, 0x1c, 0x14, 0x24, 0x64, 0x4c, 0x7e, 0x04, 0x04, 0x00, 0x00, 0x00, 0x00], // '4'
+    [0x00, 0x00, 0x00, 0x7c, 0x60, 0x60, 0x7c, 0x06, 0x06, 0x06, 0x06, 0x7c, 0x00, 0x00, 0x00, 0x00], // '5'
+    [0x00, 0x00, 0x08, 0x3c, 0x60, 0x40, 0x7c, 0x66, 0x42, 0x42, 0x66, 0x3c, 0x00, 0x00, 0x00, 0x00], // '6'
+    [0x00, 0x00, 0x00, 0x7e, 0x04, 0x04, 0x0c, 0x08, 0x08, 0x18, 0x10, 0x30, 0x00, 0x00, 0x00, 0x00], // '7'
+    [0x00, 0x00, 0x18, 0x3c, 0x66, 0x66, 0x3c, 0x3c, 0x42, 0x42, 0x66, 0x3c, 0x00, 0x00, 0x00, 0x00], // '8'
+    [0x00, 0x00, 0x10, 0x3c, 0x46, 0x42, 0x46, 0x66, 0x3a, 0x06, 0x04, 0x38, 0x00, 0x00, 0x00, 0x00], // '9'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // ':'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x18, 0x18, 0x10, 0x10, 0x00, 0x00], // ';'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x1c, 0x60, 0x70, 0x1c, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00], // '<'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x00, 0x00, 0x7e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '='
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x60, 0x38, 0x06, 0x0e, 0x38, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00], // '>'
+    [0x00, 0x00, 0x18, 0x3c, 0x06, 0x04, 0x0c, 0x18, 0x10, 0x00, 0x10, 0x18, 0x00, 0x00, 0x00, 0x00], // '?'
+    [0x00, 0x00, 0x00, 0x1c, 0x22, 0x42, 0x4f, 0x93, 0x91, 0x93, 0x5f, 0x40, 0x20, 0x1e, 0x00, 0x00], // '@'
+    [0x00, 0x00, 0x00, 0x18, 0x18, 0x3c, 0x24, 0x24, 0x7e, 0x7e, 0x42, 0xc3, 0x00, 0x00, 0x00, 0x00], // 'A'
+    [0x00, 0x00, 0x00, 0x7c, 0x46, 0x42, 0x7c, 0x7c, 0x42, 0x42, 0x66, 0x7c, 0x00, 0x00, 0x00, 0x00], // 'B'
+    [0x00, 0x00, 0x0c, 0x3e, 0x20, 0x60, 0x40, 0x40, 0x40, 0x60, 0x20, 0x1e, 0x00, 0x00, 0x00, 0x00], // 'C'
+    [0x00, 0x00, 0x00, 0x7c, 0x46, 0x46, 0x42, 0x42, 0x42, 0x46, 0x44, 0x78, 0x00, 0x00, 0x00, 0x00], // 'D'
+    [0x00, 0x00, 0x00, 0x7e, 0x60, 0x60, 0x7c, 0x7c, 0x60, 0x60, 0x60, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'E'
+    [0x00, 0x00, 0x00, 0x3e, 0x20, 0x20, 0x3c, 0x3c, 0x20, 0x20, 0x20, 0x20, 0x00, 0x00, 0x00, 0x00], // 'F'
+    [0x00, 0x00, 0x08, 0x3e, 0x60, 0x40, 0x40, 0x46, 0x42, 0x42, 0x22, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'G'
+    [0x00, 0x00, 0x00, 0x42, 0x42, 0x42, 0x7e, 0x7e, 0x42, 0x42, 0x42, 0x42, 0x00, 0x00, 0x00, 0x00], // 'H'
+    [0x00, 0x00, 0x00, 0x3c, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'I'
+    [0x00, 0x00, 0x00, 0x1c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0c, 0x78, 0x00, 0x00, 0x00, 0x00], // 'J'
+    [0x00, 0x00, 0x00, 0x46, 0x4c, 0x58, 0x70, 0x78, 0x4c, 0x44, 0x46, 0x43, 0x00, 0x00, 0x00, 0x00], // 'K'
+    [0x00, 0x00, 0x00, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'L'
+    [0x00, 0x00, 0x00, 0x66, 0x66, 0x66, 0x5a, 0x5a, 0x42, 0x42, 0x42, 0x42, 0x00, 0x00, 0x00, 0x00], // 'M'
+    [0x00, 0x00, 0x00, 0x62, 0x62, 0x72, 0x52, 0x5a, 0x4a, 0x4e, 0x46, 0x46, 0x00, 0x00, 0x00, 0x00], // 'N'
+    [0x00, 0x00, 0x18, 0x3c, 0x66, 0x42, 0x42, 0x42, 0x42, 0x42, 0x66, 0x3c, 0x00, 0x00, 0x00, 0x00], // 'O'
+    [0x00, 0x00, 0x00, 0x7e, 0x62, 0x62, 0x62, 0x7c, 0x60, 0x60, 0x60, 0x60, 0x00, 0x00, 0x00, 0x00], // 'P'
+    [0x00, 0x00, 0x18, 0x3c, 0x66, 0x42, 0x42, 0x42, 0x42, 0x42, 0x66, 0x3c, 0x0c, 0x00, 0x00, 0x00], // 'Q'
+    [0x00, 0x00, 0x00, 0x7c, 0x46, 0x46, 0x46, 0x7c, 0x44, 0x46, 0x42, 0x43, 0x00, 0x00, 0x00, 0x00], // 'R'
+    [0x00, 0x00, 0x18, 0x3e, 0x40, 0x40, 0x70, 0x1c, 0x06, 0x02, 0x46, 0x7c, 0x00, 0x00, 0x00, 0x00], // 'S'
+    [0x00, 0x00, 0x00, 0x7e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // 'T'
+    [0x00, 0x00, 0x00, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x66, 0x3c, 0x00, 0x00, 0x00, 0x00], // 'U'
+    [0x00, 0x00, 0x00, 0x42, 0x42, 0x66, 0x24, 0x24, 0x24, 0x3c, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // 'V'
+    [0x00, 0x00, 0x00, 0xc3, 0xc3, 0x5a, 0x5a, 0x5a, 0x5a, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // 'W'
+    [0x00, 0x00, 0x00, 0x62, 0x24, 0x3c, 0x18, 0x18, 0x3c, 0x24, 0x66, 0x43, 0x00, 0x00, 0x00, 0x00], // 'X'
+    [0x00, 0x00, 0x00, 0x42, 0x66, 0x24, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // 'Y'
+    [0x00, 0x00, 0x00, 0x7e, 0x06, 0x04, 0x08, 0x18, 0x10, 0x20, 0x60, 0x7f, 0x00, 0x00, 0x00, 0x00], // 'Z'
+    [0x00, 0x00, 0x1c, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1c, 0x00, 0x00], // '['
+    [0x00, 0x00, 0x00, 0x40, 0x20, 0x20, 0x10, 0x10, 0x18, 0x08, 0x0c, 0x04, 0x06, 0x00, 0x00, 0x00], // '\\'
+    [0x00, 0x00, 0x38, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x38, 0x00, 0x00], // ']'
+    [0x00, 0x00, 0x00, 0x18, 0x24, 0x42, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x00], // '_'
+    [0x00, 0x00, 0x10, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x06, 0x06, 0x7e, 0x46, 0x46, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'a'
+    [0x00, 0x00, 0x40, 0x60, 0x60, 0x7c, 0x66, 0x62, 0x62, 0x62, 0x66, 0x7c, 0x00, 0x00, 0x00, 0x00], // 'b'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x1e, 0x20, 0x60, 0x60, 0x60, 0x20, 0x1e, 0x00, 0x00, 0x00, 0x00], // 'c'
+    [0x00, 0x00, 0x02, 0x06, 0x06, 0x3e, 0x66, 0x46, 0x46, 0x46, 0x66, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'd'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x66, 0x42, 0x7e, 0x40, 0x60, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'e'
+    [0x00, 0x00, 0x0e, 0x18, 0x10, 0x7e, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00, 0x00, 0x00], // 'f'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x66, 0x46, 0x46, 0x46, 0x66, 0x3e, 0x06, 0x04, 0x38, 0x00], // 'g'
+    [0x00, 0x00, 0x40, 0x60, 0x60, 0x7c, 0x66, 0x66, 0x62, 0x62, 0x62, 0x62, 0x00, 0x00, 0x00, 0x00], // 'h'
+    [0x00, 0x00, 0x08, 0x08, 0x00, 0x38, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'i'
+    [0x00, 0x00, 0x08, 0x08, 0x00, 0x38, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x18, 0x70, 0x00], // 'j'
+    [0x00, 0x00, 0x20, 0x20, 0x20, 0x26, 0x2c, 0x38, 0x38, 0x2c, 0x26, 0x22, 0x00, 0x00, 0x00, 0x00], // 'k'
+    [0x00, 0x00, 0x70, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x18, 0x0e, 0x00, 0x00, 0x00, 0x00], // 'l'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x00, 0x00, 0x00, 0x00], // 'm'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0x66, 0x66, 0x62, 0x62, 0x62, 0x62, 0x00, 0x00, 0x00, 0x00], // 'n'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x66, 0x42, 0x42, 0x42, 0x66, 0x3c, 0x00, 0x00, 0x00, 0x00], // 'o'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0x66, 0x62, 0x62, 0x62, 0x66, 0x7c, 0x60, 0x60, 0x40, 0x00], // 'p'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x66, 0x46, 0x42, 0x46, 0x66, 0x3e, 0x02, 0x02, 0x02, 0x00], // 'q'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00], // 'r'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x20, 0x20, 0x3c, 0x04, 0x06, 0x3c, 0x00, 0x00, 0x00, 0x00], // 's'
+    [0x00, 0x00, 0x00, 0x10, 0x10, 0x7e, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1e, 0x00, 0x00, 0x00, 0x00], // 't'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x62, 0x62, 0x62, 0x62, 0x66, 0x66, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'u'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x66, 0x24, 0x24, 0x3c, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // 'v'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x81, 0xc3, 0x5a, 0x5a, 0x5a, 0x66, 0x24, 0x00, 0x00, 0x00, 0x00], // 'w'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x24, 0x18, 0x18, 0x3c, 0x24, 0x42, 0x00, 0x00, 0x00, 0x00], // 'x'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x62, 0x24, 0x24, 0x1c, 0x18, 0x18, 0x18, 0x30, 0x60, 0x00], // 'y'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x04, 0x0c, 0x18, 0x30, 0x20, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'z'
+    [0x00, 0x00, 0x0c, 0x08, 0x18, 0x18, 0x18, 0x10, 0x30, 0x18, 0x18, 0x18, 0x18, 0x0c, 0x00, 0x00], // '{'
+    [0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00], // '|'
+    [0x00, 0x00, 0x30, 0x10, 0x18, 0x18, 0x18, 0x08, 0x0c, 0x18, 0x18, 0x18, 0x10, 0x30, 0x00, 0x00], // '}'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x70, 0x0e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+    [0x00, 0x00, 0x18, 0x3c, 0x24, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '°'
+    [0x00, 0x00, 0x08, 0x18, 0x00, 0x3c, 0x06, 0x06, 0x7e, 0x46, 0x46, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'á'
+    [0x00, 0x00, 0x0c, 0x08, 0x00, 0x3c, 0x66, 0x42, 0x7e, 0x40, 0x60, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'é'
+    [0x00, 0x00, 0x08, 0x18, 0x00, 0x38, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'í'
+    [0x00, 0x00, 0x08, 0x18, 0x00, 0x3c, 0x66, 0x42, 0x42, 0x42, 0x66, 0x3c, 0x00, 0x00, 0x00, 0x00], // 'ó'
+    [0x00, 0x00, 0x08, 0x18, 0x00, 0x62, 0x62, 0x62, 0x62, 0x66, 0x66, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'ú'
+    [0x00, 0x00, 0x24, 0x00, 0x00, 0x62, 0x62, 0x62, 0x62, 0x66, 0x66, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'ü'
+    [0x00, 0x00, 0x34, 0x28, 0x00, 0x7c, 0x66, 0x66, 0x62, 0x62, 0x62, 0x62, 0x00, 0x00, 0x00, 0x00], // 'ñ'
+    [0x08, 0x00, 0x00, 0x18, 0x18, 0x3c, 0x24, 0x24, 0x7e, 0x7e, 0x42, 0xc3, 0x00, 0x00, 0x00, 0x00], // 'Á'
+    [0x08, 0x00, 0x00, 0x7e, 0x60, 0x60, 0x7c, 0x7c, 0x60, 0x60, 0x60, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'É'
+    [0x08, 0x00, 0x00, 0x3c, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'Í'
+    [0x08, 0x00, 0x18, 0x3c, 0x66, 0x42, 0x42, 0x42, 0x42, 0x42, 0x66, 0x3c, 0x00, 0x00, 0x00, 0x00], // 'Ó'
+    [0x08, 0x00, 0x00, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x66, 0x3c, 0x00, 0x00, 0x00, 0x00], // 'Ú'
+    [0x24, 0x00, 0x00, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x66, 0x3c, 0x00, 0x00, 0x00, 0x00], // 'Ü'
+    [0x34, 0x00, 0x00, 0x62, 0x62, 0x72, 0x52, 0x5a, 0x4a, 0x4e, 0x46, 0x46, 0x00, 0x00, 0x00, 0x00], // 'Ñ'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x18, 0x18, 0x30, 0x20, 0x60, 0x3c, 0x10, 0x00], // '¿'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00], // '¡'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x24, 0x18, 0x18, 0x24, 0x42, 0x00, 0x00, 0x00, 0x00, 0x00], // '×'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '–'
+];
+
+/// A glyph quad, as the HUD vertex shader reads it.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GlyphInstance {
+    pub position: Vec2, // Top left corner, window pixels
+    pub glyph: u32, // Index into the font
+    pub color: u32, // RGBA8, red in the low byte
+}
+
+/// The font for the HUD shaders: each glyph's 16 rows packed four to a word, top row in the
+/// low byte.
+pub fn font_words() -> Vec<u32> {
+    FONT.iter()
+        .flat_map(|glyph| glyph.chunks_exact(4).map(|rows| u32::from_le_bytes([rows[0], rows[1], rows[2], rows[3]])))
+        .collect()
+}
+
+fn glyph_index(ch: char) -> u32 {
+    match ch {
+        ' '..='~' => ch as u32 - ' ' as u32,
+        _ => match EXTRA_CHARS.chars().position(|extra| extra == ch) {
+            Some(i) => 95 + i as u32,
+            None => '?' as u32 - ' ' as u32,
+        },
+    }
+}
+
+struct Message {
+    text: String,
+    color: u32,
+    shown: Instant,
+}
+
+/// Status messages for the HUD, fed by the logger.
+static MESSAGES: Mutex<VecDeque<Message>> = Mutex::new(VecDeque::new());
+
+/// env_logger's output, with this program's info, warning and error messages also shown as HUD
+/// status messages. Messages with another target (the controls help) only go to the log.
+struct HudLogger {
+    inner: env_logger::Logger,
+}
+
+impl log::Log for HudLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.inner.log(record);
+        if !self.inner.matches(record) || record.level() > log::Level::Info || !record.target().starts_with(env!("CARGO_CRATE_NAME")) {
+            return;
+        }
+        let text = record.args().to_string();
+        if text.trim().is_empty() {
+            return;
+        }
+        let color = match record.level() {
+            log::Level::Error => ERROR_COLOR,
+            log::Level::Warn => WARN_COLOR,
+            _ => TEXT_COLOR,
+        };
+        // Multi-line messages show their first line
+        let text = text.lines().next().unwrap_or_default().to_string();
+        if let Ok(mut messages) = MESSAGES.lock() {
+            messages.push_back(Message { text, color, shown: Instant::now() });
+            while messages.len() > MAX_MESSAGES {
+                messages.pop_front();
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Logs through env_logger (info and up unless `RUST_LOG` says otherwise) and the HUD.
+pub fn init_logging() {
+    let inner = env_logger::Builder::from_default_env()
+        .filter_level(log::LevelFilter::Info)
+        .build();
+    log::set_max_level(inner.filter());
+    if log::set_boxed_logger(Box::new(HudLogger { inner })).is_err() {
+        eprintln!("A logger was already set");
+    }
+}
+
+/// Splits `text` at spaces into lines of at most `width` characters (words longer than that
+/// are cut), indenting the continuation lines.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    const INDENT: &str = "  ";
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    let mut fresh = true; // Nothing but the indent on the line yet
+    for word in text.split(' ') {
+        let mut word: Vec<char> = word.chars().collect();
+        loop {
+            let space = usize::from(!fresh);
+            if line_width + space + word.len() <= width {
+                if !fresh {
+                    line.push(' ');
+                }
+                line.extend(&word);
+                line_width += space + word.len();
+                fresh = false;
+                break;
+            }
+            if fresh {
+                let fits = width.saturating_sub(line_width).max(1);
+                line.extend(word.drain(..fits));
+            }
+            lines.push(std::mem::replace(&mut line, INDENT.to_string()));
+            line_width = INDENT.len();
+            fresh = true;
+        }
+    }
+    if !fresh || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Text drawn over the rendered image: frame statistics in the top left corner, the controls
+/// help (toggled with F1) below them, and recent status messages in the bottom left corner.
+/// It's composited onto the swapchain image, so it stays out of photos, long exposures and
+/// recordings.
+pub struct Hud {
+    stats: Vec<String>,
+    help: Vec<String>, // Controls list, in the current language
+    help_hint: String, // Shown in place of the list while it's hidden
+    pub show_help: bool,
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Hud { stats: Vec::new(), help: Vec::new(), help_hint: String::new(), show_help: false }
+    }
+
+    /// Replaces the statistics lines (frame rate, progress, memory).
+    pub fn set_stats(&mut self, stats: Vec<String>) {
+        self.stats = stats;
+    }
+
+    /// Replaces the controls help and the hint telling how to show it.
+    pub fn set_help(&mut self, help: Vec<String>, hint: &str) {
+        self.help = help;
+        self.help_hint = hint.to_string();
+    }
+
+    /// Glyph quads for a `width` x `height` window, with glyph cells `scale` times their size.
+    /// Drops the status messages that have been up long enough.
+    pub fn layout(&self, width: u32, height: u32, scale: f32) -> Vec<GlyphInstance> {
+        let cell = Vec2::new(GLYPH_WIDTH as f32, GLYPH_HEIGHT as f32) * scale;
+        let columns = ((width as f32 / cell.x - 2.0 * MARGIN).floor().max(0.0)) as usize;
+        let rows = height as f32 / cell.y;
+        let mut glyphs = Vec::new();
+        let mut add_line = |text: &str, column: f32, row: f32, width: usize, color: u32| {
+            // Spaces are drawn too, for a continuous background behind the line
+            for (i, ch) in text.chars().take(width).enumerate() {
+                glyphs.push(GlyphInstance {
+                    position: Vec2::new(column + i as f32, row) * cell,
+                    glyph: glyph_index(ch),
+                    color,
+                });
+            }
+        };
+
+        for (row, line) in self.stats.iter().enumerate() {
+            add_line(line, MARGIN, MARGIN + row as f32, columns, TEXT_COLOR);
+        }
+
+        let top = MARGIN + self.stats.len() as f32;
+        if !self.show_help {
+            add_line(&self.help_hint, MARGIN, top, columns, HINT_COLOR);
+        } else if !self.help.is_empty() {
+            // Below the stats, wrapped to the window width and split into columns when too long
+            let top = if self.stats.is_empty() { top } else { top + 1.0 };
+            let available_rows = ((rows - top - MARGIN).floor() as usize).max(1);
+            for panels in 1..=MAX_HELP_COLUMNS {
+                let width = ((columns + 2) / panels).saturating_sub(2).max(8);
+                let lines: Vec<String> = self.help.iter().flat_map(|line| wrap(line, width)).collect();
+                let panel_rows = lines.len().div_ceil(panels);
+                if panel_rows > available_rows && panels < MAX_HELP_COLUMNS {
+                    continue;
+                }
+                // Whatever doesn't fit even then is cut off at the bottom
+                let panel_rows = panel_rows.min(available_rows);
+                for (i, line) in lines.iter().enumerate().take(panel_rows * panels) {
+                    let column = MARGIN + ((i / panel_rows) * (width + 2)) as f32;
+                    add_line(line, column, top + (i % panel_rows) as f32, width, TEXT_COLOR);
+                }
+                break;
+            }
+        }
+
+        if let Ok(mut messages) = MESSAGES.lock() {
+            messages.retain(|message| message.shown.elapsed() < MESSAGE_DURATION);
+            for (i, message) in messages.iter().rev().enumerate() {
+                add_line(&message.text, MARGIN, rows - MARGIN - 1.0 - i as f32, columns, message.color);
+            }
+        }
+        glyphs.truncate(MAX_GLYPHS);
+        glyphs
+    }
+}
@@ -0,0 +1,46 @@
+use crate::scene::{RayMask, Scene};
+use crate::args;
+
+/// Hybrid rendering, from `--hybrid` (toggled with J): a raster G-buffer pass finds the
+/// triangle each pixel sees and raygen shades it directly, so only the shadow, sky visibility,
+/// reflection and refraction rays are traced. It applies to single-sample pinhole frames, the
+/// other ones are fully traced.
+pub fn hybrid_from_args() -> bool {
+    args::flag("--hybrid")
+}
+
+/// A non-indexed G-buffer draw: `instance_count` consecutive TLAS instances from
+/// `first_instance`, which share a mesh of `index_count` indices. The vertex shader pulls the
+/// vertices through the mesh's index buffer.
+#[derive(Clone, Copy, Debug)]
+pub struct RasterDraw {
+    pub mesh_index: usize,
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_instance: u32,
+}
+
+/// The draws covering every TLAS instance camera rays see, numbered like the TLAS: the scene objects,
+/// then the instance batches. `meshes` is the mesh each instance uses (see `LodSelection`);
+/// a batch is split into runs of instances at the same level of detail.
+pub fn raster_draws(scene: &Scene, meshes: &[usize]) -> Vec<RasterDraw> {
+    let draw = |mesh_index: usize, first_instance: usize, instance_count: usize| RasterDraw {
+        mesh_index,
+        index_count: scene.meshes[mesh_index].indices.len() as u32,
+        instance_count: instance_count as u32,
+        first_instance: first_instance as u32,
+    };
+    let mut draws: Vec<RasterDraw> = scene.objects.iter().enumerate()
+        .filter(|(_, obj)| obj.visible && obj.rays.contains(RayMask::CAMERA))
+        .map(|(i, _)| draw(meshes[i], i, 1))
+        .collect();
+    let mut first_instance = scene.objects.len();
+    for batch in &scene.instance_batches {
+        let batch_meshes = &meshes[first_instance..first_instance + batch.instances.len()];
+        for run in batch_meshes.chunk_by(|a, b| a == b) {
+            draws.push(draw(run[0], first_instance, run.len()));
+            first_instance += run.len();
+        }
+    }
+    draws
+}
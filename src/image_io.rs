@@ -0,0 +1,112 @@
+use std::io::Write;
+use std::path::Path;
+
+/// Writes 8-bit RGBA pixels as a PNG. The image data is stored uncompressed
+/// (deflate "stored" blocks), which keeps the encoder dependency free.
+pub fn write_png(path: impl AsRef<Path>, width: u32, height: u32, rgba: &[u8]) -> std::io::Result<()> {
+    let mut png = PngWriter::create(path, width, height)?;
+    png.write_rows(rgba)?;
+    png.finish()
+}
+
+/// PNG written a band of rows at a time, for images too large to hold in memory. Each band
+/// becomes one IDAT chunk continuing a single zlib stream of stored deflate blocks.
+pub struct PngWriter {
+    file: std::io::BufWriter<std::fs::File>,
+    width: u32,
+    rows_left: u32,
+    adler: (u32, u32),
+}
+
+impl PngWriter {
+    pub fn create(path: impl AsRef<Path>, width: u32, height: u32) -> std::io::Result<Self> {
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]); // 8 bit, RGBA, deflate, adaptive filtering, no interlace
+
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        file.write_all(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A])?;
+        write_png_chunk(&mut file, b"IHDR", &ihdr)?;
+        write_png_chunk(&mut file, b"IDAT", &[0x78, 0x01])?; // zlib header
+        Ok(Self { file, width, rows_left: height, adler: (1, 0) })
+    }
+
+    /// Appends whole rows of 8-bit RGBA pixels, top to bottom.
+    pub fn write_rows(&mut self, rgba: &[u8]) -> std::io::Result<()> {
+        let rows = (rgba.len() / (self.width as usize * 4)) as u32;
+        if rows > self.rows_left {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "more rows than the image height"));
+        }
+        self.rows_left -= rows;
+
+        let mut raw = Vec::with_capacity((self.width as usize * 4 + 1) * rows as usize);
+        for row in rgba.chunks_exact(self.width as usize * 4) {
+            raw.push(0); // Filter type: none
+            raw.extend_from_slice(row);
+        }
+        self.adler = adler32_update(self.adler, &raw);
+        let last = self.rows_left == 0;
+        let mut data = deflate_stored(&raw, last);
+        if last {
+            data.extend_from_slice(&((self.adler.1 << 16) | self.adler.0).to_be_bytes());
+        }
+        write_png_chunk(&mut self.file, b"IDAT", &data)
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        if self.rows_left > 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} rows missing", self.rows_left)));
+        }
+        write_png_chunk(&mut self.file, b"IEND", &[])?;
+        self.file.flush()
+    }
+}
+
+fn write_png_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    out.write_all(&crc.to_be_bytes())
+}
+
+/// Splits data into uncompressed deflate blocks, marking the last one final if `last`.
+fn deflate_stored(data: &[u8], last: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 0xFFFF * 5 + 5);
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() && last {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(if last && blocks.peek().is_none() { 1 } else { 0 });
+        out.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out
+}
+
+fn crc32<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Continues an Adler-32 checksum, kept as its (a, b) sums and starting from (1, 0).
+fn adler32_update((mut a, mut b): (u32, u32), data: &[u8]) -> (u32, u32) {
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (a, b)
+}
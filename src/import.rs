@@ -0,0 +1,99 @@
+use glam::{Mat3, Mat4, Vec3, Vec4};
+use crate::scene::{Mesh, SkinnedMesh};
+use crate::args;
+
+/// Units an imported file's positions are in, each with its size in meters.
+const UNITS: [(&str, f32); 5] = [("m", 1.0), ("cm", 0.01), ("mm", 0.001), ("in", 0.0254), ("ft", 0.3048)];
+
+/// Smoothing of generated normals unless `--import-crease-angle` gives another one, degrees.
+const DEFAULT_CREASE_ANGLE: f32 = 60.0;
+
+/// How imported assets are converted into the world's conventions (meters, Y up) while the
+/// scene loads. glTF is specified in meters with Y up, but DCC tools export centimeters or
+/// Z up often enough that files need converting: `--import-units <m|cm|mm|in|ft>` names the
+/// unit a file's positions are in and `--import-up z` marks it as Z up. Meshes without
+/// normals get generated ones, smooth across edges up to `--import-crease-angle <degrees>`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImportOptions {
+    pub meters_per_unit: f32,
+    pub z_up: bool,
+    pub crease_angle: f32, // Degrees, 0: faceted, 180: smooth everywhere
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self { meters_per_unit: 1.0, z_up: false, crease_angle: DEFAULT_CREASE_ANGLE }
+    }
+}
+
+impl ImportOptions {
+    /// The options the command line asks for. Unknown units or axes are logged and ignored.
+    pub fn from_args() -> Self {
+        let mut options = Self::default();
+        if let Some(units) = args::value("--import-units") {
+            match UNITS.iter().find(|(name, _)| *name == units) {
+                Some(&(_, meters)) => options.meters_per_unit = meters,
+                None => log::warn!("Unknown --import-units {}, expected one of m, cm, mm, in, ft", units),
+            }
+        }
+        match args::value("--import-up") {
+            Some("z") => options.z_up = true,
+            Some("y") | None => {}
+            Some(axis) => log::warn!("Unknown --import-up {}, expected y or z", axis),
+        }
+        if let Some(angle) = args::value("--import-crease-angle") {
+            match angle.parse::<f32>() {
+                Ok(angle) if (0.0..=180.0).contains(&angle) => options.crease_angle = angle,
+                _ => log::warn!("Ignoring --import-crease-angle {}, expected 0 to 180 degrees", angle),
+            }
+        }
+        options
+    }
+
+    /// Turns Z up into Y up: a quarter turn about X, so the file's -Y (its front) faces +Z.
+    fn rotation(&self) -> Mat3 {
+        if self.z_up { Mat3::from_rotation_x(-std::f32::consts::FRAC_PI_2) } else { Mat3::IDENTITY }
+    }
+
+    /// Maps the file's space into the world's: the axis conversion, then scaled to meters.
+    pub fn conversion(&self) -> Mat4 {
+        Mat4::from_scale(Vec3::splat(self.meters_per_unit)) * Mat4::from_mat3(self.rotation())
+    }
+
+    /// Whether the conversion leaves the file as it is.
+    pub fn is_identity(&self) -> bool {
+        self.meters_per_unit == 1.0 && !self.z_up
+    }
+
+    /// Converts a static mesh's positions, normals and tangents.
+    pub fn apply_to_mesh(&self, mesh: &mut Mesh) {
+        if self.is_identity() {
+            return;
+        }
+        let (conversion, rotation) = (self.conversion(), self.rotation());
+        // Directions only turn, the scale is uniform
+        for vertex in &mut mesh.vertices {
+            vertex.pos = conversion.transform_point3(Vec3::from(vertex.pos)).to_array();
+            vertex.nrm = (rotation * Vec3::from(vertex.nrm)).to_array();
+            let tangent = rotation * Vec4::from(vertex.tangent).truncate();
+            vertex.tangent = [tangent.x, tangent.y, tangent.z, vertex.tangent[3]];
+        }
+    }
+
+    /// Converts a skinned mesh: its rest pose like a static mesh, and its skeleton so that
+    /// skinning the converted vertices gives the converted pose. Joints keep animating in the
+    /// file's space, between the inverse of the conversion (undone from the vertices by the
+    /// inverse bind matrices) and the conversion (applied again by the root).
+    pub fn apply_to_skinned(&self, model: &mut SkinnedMesh) {
+        if self.is_identity() {
+            return;
+        }
+        self.apply_to_mesh(&mut model.mesh);
+        let conversion = self.conversion();
+        let inverse = conversion.inverse();
+        model.skeleton.root = conversion * model.skeleton.root;
+        for joint in &mut model.skeleton.joints {
+            joint.inverse_bind *= inverse;
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use glam::{Vec3, Vec4};
+use winit::keyboard::KeyCode;
+
+// Adjustment per key press
+const MOVE_STEP: f32 = 0.5; // Meters
+const INTENSITY_STEP: f32 = 1.25; // Factor
+const RADIUS_STEP: f32 = 0.1; // Meters
+const MAX_RADIUS: f32 = 3.0;
+
+/// Colors K steps through: white, warm (tungsten), sunset, cool (overcast daylight).
+pub const LIGHT_COLORS: [Vec3; 4] = [
+    Vec3::new(1.0, 1.0, 1.0),
+    Vec3::new(1.0, 0.8, 0.6),
+    Vec3::new(1.0, 0.55, 0.3),
+    Vec3::new(0.75, 0.85, 1.0),
+];
+
+/// The point light lighting the scene, with the shadow rays and the caustic photons starting
+/// from it. The radius spreads the soft shadows.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Light {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub radius: f32,
+}
+
+impl Light {
+    pub fn new() -> Self {
+        Self {
+            position: Vec3::new(10.0, 10.0, 10.0),
+            color: LIGHT_COLORS[0],
+            intensity: 1.0,
+            radius: 0.5,
+        }
+    }
+
+    /// `light_pos` of the camera UBO: position and radius.
+    pub fn position_param(&self) -> Vec4 {
+        self.position.extend(self.radius)
+    }
+
+    /// `light_color` of the camera UBO: color times intensity.
+    pub fn color_param(&self) -> Vec4 {
+        (self.color * self.intensity).extend(0.0)
+    }
+
+    /// Handles a key while the light controls are on (G): arrows move the light horizontally,
+    /// Page Up/Down vertically, +/- change the intensity, [/] the radius and K cycles the color.
+    /// Returns false for keys it doesn't use, which then go on to the regular controls.
+    pub fn handle_input(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::ArrowLeft => self.position.x -= MOVE_STEP,
+            KeyCode::ArrowRight => self.position.x += MOVE_STEP,
+            KeyCode::ArrowUp => self.position.z -= MOVE_STEP,
+            KeyCode::ArrowDown => self.position.z += MOVE_STEP,
+            KeyCode::PageUp => self.position.y += MOVE_STEP,
+            KeyCode::PageDown => self.position.y -= MOVE_STEP,
+            KeyCode::Equal | KeyCode::NumpadAdd => self.intensity *= INTENSITY_STEP,
+            KeyCode::Minus | KeyCode::NumpadSubtract => self.intensity /= INTENSITY_STEP,
+            KeyCode::BracketRight => self.radius = (self.radius + RADIUS_STEP).min(MAX_RADIUS),
+            KeyCode::BracketLeft => self.radius = (self.radius - RADIUS_STEP).max(0.0),
+            KeyCode::KeyK => {
+                let next = LIGHT_COLORS.iter().position(|&color| color == self.color).map_or(0, |i| (i + 1) % LIGHT_COLORS.len());
+                self.color = LIGHT_COLORS[next];
+            }
+            _ => return false,
+        }
+        log::info!(
+            "Light: position ({:.1}, {:.1}, {:.1}), intensity {:.2}, radius {:.1}, color ({:.2}, {:.2}, {:.2})",
+            self.position.x, self.position.y, self.position.z, self.intensity, self.radius, self.color.x, self.color.y, self.color.z,
+        );
+        true
+    }
+}
@@ -0,0 +1,203 @@
+use crate::args;
+
+/// Languages of the window title, the HUD and the controls help.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Language {
+    English,
+    Spanish,
+}
+
+/// Translated text for one language.
+pub struct Bundle {
+    pub title: &'static str,
+    pub fps: &'static str, // Unit after the frame rate on the HUD
+    pub rays_per_second: &'static str, // Unit after the ray rate on the HUD, millions per second
+    pub controls_header: &'static str,
+    pub controls: &'static [&'static str],
+    pub controls_physics: &'static str, // Only listed with the `physics` feature
+    pub controls_denoise: &'static str, // Only listed with the `denoise` feature
+    pub help_hint: &'static str, // On the HUD while the controls help is hidden
+    pub language_changed: &'static str,
+    // Progress of long exposures and photos on the HUD
+    pub long_exposure: &'static str,
+    pub photo: &'static str,
+    pub tile: &'static str,
+    pub samples: &'static str,
+    pub noise: &'static str,
+    pub converged: &'static str,
+    pub gpu_memory: &'static str, // Label of the memory breakdown on the HUD
+}
+
+const ENGLISH: Bundle = Bundle {
+    title: "Rust Vulkan Raytracing Demo",
+    fps: "FPS",
+    rays_per_second: "Mrays/s",
+    controls_header: "=== CONTROLS ===",
+    controls: &[
+        "Mouse: Look around",
+        "Left click: Pick the object under the cursor (focuses on it with depth of field on)",
+        "W/A/S/D: Move horizontally",
+        "Q/E: Move up/down",
+        "Z/X: Roll left/right",
+        ",/.: Mouse sensitivity down/up, ;/': Movement speed down/up, /: Toggle invert Y",
+        "`: Release/capture the cursor",
+        "1: Toggle Soft Shadows",
+        "2: Toggle Reflections",
+        "3: Toggle Refractions",
+        "4: Toggle Subsurface Scattering",
+        "5: Toggle Caustics (photon mapping)",
+        "6: Toggle Sky Occlusion (cached environment lighting)",
+        "7: Toggle split screen (two views, each with its own camera and toggles)",
+        "8: Switch control to the other split screen view",
+        "T: Capture long exposure (EXR + PNG)",
+        "L: Cycle light path channel (beauty, direct/indirect diffuse, specular, transmission, caustics)",
+        "Ctrl+L: Toggle the traversal heatmap (acceleration structure cost per camera ray)",
+        "V: Toggle red/cyan anaglyph stereo",
+        "J: Toggle hybrid rendering (rasterized primary visibility)",
+        "U: Show GPU memory usage per category on the HUD",
+        "F: Toggle depth of field (autofocus on the screen center)",
+        "H: Toggle heat haze",
+        "B: Toggle motion blur",
+        "Delete: Toggle bloom",
+        "Ctrl+1/2/3: Toggle vignette, film grain, chromatic aberration",
+        "+/-: Exposure up/down half a stop, Backspace: toggle auto exposure (eye adaptation)",
+        "F8: Start/stop recording frames (PNG sequence or ffmpeg video)",
+        "F9: Take a photo of the current view (press again to cancel), F10: cycle photo size",
+        "9: Cycle photo projection (perspective, 360° panorama, 360° stereo panorama)",
+        "I: Toggle teaching mode (freezes the scene, the mouse moves the probe, O: next stage)",
+        "Space: Pause/resume the animation, \\: step one frame while paused",
+        "P: Cycle weather (clear, rain, snow)",
+        "End: Toggle the sparks from the porch lamp",
+        "C: Cycle camera profile (pinhole, GoPro, 35mm lenses)",
+        "0: Cycle camera projection (perspective, orthographic, fisheye, thin lens)",
+        "Tab: Toggle editor (click to select, M: translate/rotate/scale, X/Y/Z: axis, arrows: nudge, [/]: material, F5: save)",
+        "Insert: Material preview (the selected object's material on a ball in a studio, [/]: material, Enter: apply to the selection)",
+        "G: Toggle light controls (arrows, Page Up/Down: move, +/-: intensity, [/]: radius, K: color)",
+        "F1: Show/hide this list",
+        "F2: Switch language",
+        "F7: Cycle camera rays per pixel (1, 2, 4, 8)",
+        "F3: Toggle reduced motion",
+        "F4: Toggle high contrast",
+        "F6: Cycle UI scale",
+        "F11: Toggle Fullscreen",
+        "F12: Cycle navigation (fly with collision, walk with gravity where Q jumps, noclip)",
+        "Numpad 1-9: Go to a camera bookmark (Ctrl + numpad 1-9 stores it), numpad 0: tour the bookmarks",
+        "Home: Reset the camera and settings to defaults",
+        "ESC: Exit",
+    ],
+    controls_physics: "R: Drop the physics spheres again",
+    controls_denoise: "N: Toggle denoising of long exposures and photos",
+    help_hint: "F1: Controls",
+    language_changed: "Language: English",
+    long_exposure: "Long exposure",
+    photo: "Photo",
+    tile: "tile",
+    samples: "samples",
+    noise: "noise",
+    converged: "converged",
+    gpu_memory: "GPU memory",
+};
+
+const SPANISH: Bundle = Bundle {
+    title: "Demo de trazado de rayos con Rust y Vulkan",
+    fps: "FPS",
+    rays_per_second: "Mrayos/s",
+    controls_header: "=== CONTROLES ===",
+    controls: &[
+        "Ratón: Mirar alrededor",
+        "Clic izquierdo: Seleccionar el objeto bajo el cursor (enfoca sobre él con la profundidad de campo activada)",
+        "W/A/S/D: Moverse en horizontal",
+        "Q/E: Subir/bajar",
+        "Z/X: Girar la cámara sobre su eje a la izquierda/derecha",
+        ",/.: Bajar/subir la sensibilidad del ratón, ;/': Bajar/subir la velocidad, /: Invertir el eje Y",
+        "`: Liberar/capturar el cursor",
+        "1: Activar/desactivar sombras suaves",
+        "2: Activar/desactivar reflejos",
+        "3: Activar/desactivar refracciones",
+        "4: Activar/desactivar dispersión subsuperficial",
+        "5: Activar/desactivar cáusticas (mapa de fotones)",
+        "6: Activar/desactivar oclusión del cielo (iluminación ambiental en caché)",
+        "7: Activar/desactivar la pantalla dividida (dos vistas, cada una con su cámara y sus opciones)",
+        "8: Pasar el control a la otra vista de la pantalla dividida",
+        "T: Capturar una exposición larga (EXR + PNG)",
+        "L: Cambiar el canal de trayectorias de luz (beauty, difusa directa/indirecta, especular, transmisión, cáusticas)",
+        "Ctrl+L: Activar/desactivar el mapa de calor del recorrido (coste de las estructuras de aceleración por rayo de cámara)",
+        "V: Activar/desactivar estéreo anaglifo rojo/cian",
+        "J: Activar/desactivar el renderizado híbrido (visibilidad primaria rasterizada)",
+        "U: Mostrar el uso de memoria de la GPU por categoría en el HUD",
+        "F: Activar/desactivar la profundidad de campo (enfoque automático en el centro de la pantalla)",
+        "H: Activar/desactivar la reverberación por calor",
+        "B: Activar/desactivar el desenfoque de movimiento",
+        "Supr: Activar/desactivar el resplandor (bloom)",
+        "Ctrl+1/2/3: Activar/desactivar viñeteado, grano de película, aberración cromática",
+        "+/-: Subir/bajar la exposición medio paso, Retroceso: activar/desactivar la exposición automática (adaptación del ojo)",
+        "F8: Iniciar/detener la grabación de fotogramas (secuencia PNG o vídeo con ffmpeg)",
+        "F9: Tomar una foto de la vista actual (pulsar de nuevo para cancelar), F10: cambiar el tamaño de la foto",
+        "9: Cambiar la proyección de la foto (perspectiva, panorama de 360°, panorama estéreo de 360°)",
+        "I: Activar/desactivar el modo didáctico (congela la escena, el ratón mueve la sonda, O: siguiente etapa)",
+        "Espacio: Pausar/reanudar la animación, \\: avanzar un fotograma en pausa",
+        "P: Cambiar la meteorología (despejado, lluvia, nieve)",
+        "Fin: Activar/desactivar las chispas de la lámpara del porche",
+        "C: Cambiar el perfil de cámara (estenopeica, GoPro, objetivos de 35 mm)",
+        "0: Cambiar la proyección de la cámara (perspectiva, ortográfica, ojo de pez, lente delgada)",
+        "Tab: Activar/desactivar el editor (clic para seleccionar, M: mover/rotar/escalar, X/Y/Z: eje, flechas: desplazar, [/]: material, F5: guardar)",
+        "Insert: Vista previa de material (el material del objeto seleccionado en una esfera en un estudio, [/]: material, Intro: aplicar a la selección)",
+        "G: Activar/desactivar los controles de la luz (flechas, Re Pág/Av Pág: mover, +/-: intensidad, [/]: radio, K: color)",
+        "F1: Mostrar/ocultar esta lista",
+        "F2: Cambiar de idioma",
+        "F7: Cambiar los rayos de cámara por píxel (1, 2, 4, 8)",
+        "F3: Activar/desactivar el movimiento reducido",
+        "F4: Activar/desactivar el alto contraste",
+        "F6: Cambiar la escala de la interfaz",
+        "F11: Pantalla completa",
+        "F12: Cambiar la navegación (vuelo con colisiones, caminar con gravedad donde Q salta, sin colisiones)",
+        "Teclado numérico 1-9: Ir a un marcador de cámara (Ctrl + teclado numérico 1-9 lo guarda), teclado numérico 0: recorrer los marcadores",
+        "Inicio: Restablecer la cámara y las opciones a sus valores predeterminados",
+        "ESC: Salir",
+    ],
+    controls_physics: "R: Volver a soltar las esferas de la simulación física",
+    controls_denoise: "N: Activar/desactivar la eliminación de ruido de las exposiciones largas y las fotos",
+    help_hint: "F1: Controles",
+    language_changed: "Idioma: español",
+    long_exposure: "Exposición larga",
+    photo: "Foto",
+    tile: "tesela",
+    samples: "muestras",
+    noise: "ruido",
+    converged: "convergida",
+    gpu_memory: "Memoria GPU",
+};
+
+impl Language {
+    /// Language from `--lang <code>`, else from the `LANG` environment variable (e.g. `es_ES.UTF-8`),
+    /// else English.
+    pub fn detect() -> Self {
+        args::value("--lang")
+            .and_then(Self::from_code)
+            .or_else(|| std::env::var("LANG").ok().and_then(|code| Self::from_code(&code)))
+            .unwrap_or(Language::English)
+    }
+
+    /// Parses a language code, ignoring any region and encoding suffix.
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code.get(..2)?.to_ascii_lowercase().as_str() {
+            "en" => Some(Language::English),
+            "es" => Some(Language::Spanish),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Language::English => Language::Spanish,
+            Language::Spanish => Language::English,
+        }
+    }
+
+    pub fn bundle(self) -> &'static Bundle {
+        match self {
+            Language::English => &ENGLISH,
+            Language::Spanish => &SPANISH,
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use glam::Vec3;
+use crate::scene::Scene;
+use crate::args;
+
+/// A level only gives way to a finer one this much closer than where it took over, so an
+/// instance at a switching distance doesn't flip every frame.
+const HYSTERESIS: f32 = 1.1;
+
+/// Level-of-detail distance scale from `--lod-bias <factor>`: 2 switches to the coarser meshes
+/// twice as far away, 0 always uses the full meshes.
+pub fn lod_bias_from_args() -> f32 {
+    args::number::<f32>("--lod-bias")
+        .filter(|&bias| bias >= 0.0)
+        .unwrap_or(1.0)
+}
+
+/// The mesh every TLAS instance uses, in TLAS order: its own or one of its coarser levels, by
+/// its distance from the camera relative to its size.
+pub struct LodSelection {
+    bias: f32,
+    meshes: Vec<usize>,
+}
+
+impl LodSelection {
+    /// Starts out with every instance at its full mesh.
+    pub fn new(scene: &Scene, bias: f32) -> Self {
+        if bias > 0.0 && !scene.lods.is_empty() {
+            log::info!("Level of detail: {} meshes with coarser levels, bias {}", scene.lods.len(), bias);
+        }
+        LodSelection { bias, meshes: base_meshes(scene).map(|(mesh_index, _, _)| mesh_index).collect() }
+    }
+
+    pub fn meshes(&self) -> &[usize] {
+        &self.meshes
+    }
+
+    /// Picks the levels for a camera at `camera`. Returns whether any instance changed mesh,
+    /// which needs the TLAS instances rebuilt.
+    pub fn update(&mut self, scene: &Scene, camera: Vec3) -> bool {
+        let mut changed = false;
+        self.meshes.resize(scene.instance_count(), 0);
+        for (selected, (mesh_index, position, scale)) in self.meshes.iter_mut().zip(base_meshes(scene)) {
+            let level = match scene.lods.iter().find(|lod| lod.mesh_index == mesh_index) {
+                Some(lod) if self.bias > 0.0 => {
+                    // Distance in multiples of the instance's size, so large instances keep their detail farther out
+                    let distance = position.distance(camera) / (scale * self.bias);
+                    let current = lod.levels.iter().position(|level| level.mesh_index == *selected).map_or(0, |i| i + 1);
+                    let coarser = lod.levels.iter().take_while(|level| level.distance <= distance).count();
+                    let finer = lod.levels.iter().take_while(|level| level.distance / HYSTERESIS <= distance).count();
+                    let level = if coarser > current { coarser } else { current.min(finer) };
+                    if level == 0 { mesh_index } else { lod.levels[level - 1].mesh_index }
+                }
+                _ => mesh_index,
+            };
+            changed |= *selected != level;
+            *selected = level;
+        }
+        changed
+    }
+}
+
+/// Full mesh, position and largest scale of every TLAS instance, in TLAS order.
+fn base_meshes(scene: &Scene) -> impl Iterator<Item = (usize, Vec3, f32)> + '_ {
+    let placement = |transform: &glam::Mat4| {
+        let (scale, _, position) = transform.to_scale_rotation_translation();
+        (position, scale.max_element().max(f32::EPSILON))
+    };
+    let objects = scene.objects.iter().map(move |obj| {
+        let (position, scale) = placement(&obj.transform);
+        (obj.mesh_index, position, scale)
+    });
+    let batches = scene.instance_batches.iter().flat_map(move |batch| {
+        batch.instances.iter().map(move |inst| {
+            let (position, scale) = placement(&inst.transform);
+            (batch.mesh_index, position, scale)
+        })
+    });
+    objects.chain(batches)
+}
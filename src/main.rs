@@ -2,33 +2,103 @@ mod vulkan;
 mod renderer;
 mod camera;
 mod scene;
+mod texture;
+mod animation;
+mod gltf_loader;
+mod editor;
+mod weather;
+mod scene_file;
+mod locale;
+mod autosave;
+mod benchmark;
+mod accessibility;
+mod teaching;
+mod photo;
+mod recording;
+mod rng;
+mod light;
+mod upscale;
+mod progress;
+mod image_io;
+mod aov;
+mod hybrid;
+mod preview;
+mod memory;
+mod asset_cache;
+mod streaming;
+mod lod;
+mod collision;
+mod split_screen;
+mod pacing;
+mod bookmarks;
+mod hud;
+mod terrain;
+mod particles;
+mod grass;
+mod volume;
+mod material_preview;
+mod material_file;
+mod stats;
+mod metrics;
+mod import;
+mod exposure;
+mod post;
+mod display;
+mod color;
+mod capabilities;
+mod args;
+#[cfg(feature = "physics")]
+mod physics;
+#[cfg(feature = "denoise")]
+mod denoise;
 
 use winit::{
     event::{Event, WindowEvent, KeyEvent, DeviceEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{CursorGrabMode, Window, WindowBuilder},
     keyboard::{PhysicalKey},
 };
 use renderer::Renderer;
+use benchmark::BenchmarkSuite;
+use photo::OfflineRender;
+use locale::Language;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::Builder::from_default_env()
-        .filter_level(log::LevelFilter::Info)
-        .init();
+    // Log messages also show up on the HUD
+    hud::init_logging();
 
     log::info!("Starting Rust Vulkan Raytracing Demo");
     log::info!("Platform: {}", std::env::consts::OS);
+    args::parse();
+
+    // --list-gpus shows the devices --gpu can pick from and exits
+    if args::flag("--list-gpus") {
+        return vulkan::list_gpus();
+    }
+
+    // --suite runs the benchmark scenarios instead of the interactive demo
+    let mut benchmark = args::flag("--suite").then(BenchmarkSuite::new);
+    // --render <width>x<height> renders one photo of the restored view and exits
+    let offline = OfflineRender::from_args()?;
+
+    let mut language = Language::detect();
 
     let event_loop = EventLoop::new()?;
     let window = WindowBuilder::new()
-        .with_title("Rust Vulkan Raytracing Demo")
+        .with_title(language.bundle().title)
         .with_inner_size(winit::dpi::LogicalSize::new(1280.0, 720.0))
         .build(&event_loop)?;
 
-    window.set_cursor_visible(false);
-    if let Err(_) = window.set_cursor_grab(winit::window::CursorGrabMode::Locked) {
-         let _ = window.set_cursor_grab(winit::window::CursorGrabMode::Confined);
-    }
+    // The cursor is captured for mouse look until ` releases it, and given back while another
+    // window has focus
+    let mut cursor_captured = true;
+    let mut focused = true;
+    set_cursor_captured(&window, cursor_captured);
+    // Minimized or fully covered: nothing is rendered, so the GPU idles in the background
+    let mut hidden = false;
+    let mut paused = false;
+    // --max-fps caps the frame rate, and the window renders at a lower one while in the background
+    let mut pacer = pacing::frame_pacer_from_args();
 
     log::info!("Initializing Vulkan renderer...");
     let mut renderer = match Renderer::new(&window) {
@@ -42,9 +112,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             // Special handling for common errors
             if e.to_string().contains("INCOMPATIBLE_DRIVER") {
                 log::error!("\nThis error typically means:");
-                log::error!("  - On macOS: Native Vulkan is not supported. You need MoltenVK.");
+                log::error!("  - On macOS: no Vulkan driver was found. Install MoltenVK (e.g. with the Vulkan SDK) and point VK_ICD_FILENAMES at its MoltenVK_icd.json.");
                 log::error!("  - On Linux/Windows: GPU drivers are outdated or incompatible.");
-                log::error!("  - Ray tracing extensions may not be supported by your GPU.");
             } else if e.to_string().contains("OUT_OF_DEVICE_MEMORY") ||
                       e.to_string().contains("OUT_OF_HOST_MEMORY") {
                 log::error!("\nMemory allocation failed. Possible causes:");
@@ -58,20 +127,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    // Print controls
-    log::info!("");
-    log::info!("=== CONTROLS ===");
-    log::info!("  Mouse: Look around");
-    log::info!("  W/A/S/D: Move horizontally");
-    log::info!("  Q/E: Move up/down");
-    log::info!("  1: Toggle Soft Shadows");
-    log::info!("  2: Toggle Reflections");
-    log::info!("  3: Toggle Refractions");
-    log::info!("  4: Toggle Subsurface Scattering");
-    log::info!("  F11: Toggle Fullscreen");
-    log::info!("  ESC: Exit");
-    log::info!("================");
-    log::info!("");
+    print_controls(language);
+    renderer.hud.set_help(controls_lines(language), language.bundle().help_hint);
+
+    if benchmark.is_some() {
+        log::info!("Running the benchmark suite, input is ignored until it finishes");
+        // Scenario settings must not end up in the user's session
+        renderer.autosave.enabled = false;
+    }
+    if let Some(render) = &offline {
+        log::info!("Offline render, input is ignored until it finishes");
+        renderer.autosave.enabled = false;
+        renderer.start_photo(render.width, render.height, render.projection, render.samples)?;
+    }
+    let interactive = benchmark.is_none() && offline.is_none();
 
     // FPS tracking
     let mut frame_count = 0u32;
@@ -84,9 +153,32 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => elwt.exit(),
                 WindowEvent::Resized(size) => {
-                    renderer.resize(size.width, size.height);
+                    // Some platforms minimize by resizing to nothing
+                    hidden = size.width == 0 || size.height == 0;
+                    if !hidden {
+                        renderer.resize(size.width, size.height);
+                    }
                 }
-                WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key), state, .. }, .. } => {
+                WindowEvent::Occluded(occluded) => hidden = occluded,
+                WindowEvent::Focused(focus) => {
+                    focused = focus;
+                    set_cursor_captured(&window, cursor_captured && focused);
+                }
+                WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key), state, .. }, .. } if interactive => {
+                    if key == winit::keyboard::KeyCode::F2 && state == winit::event::ElementState::Pressed {
+                        language = language.next();
+                        log::info!("{}", language.bundle().language_changed);
+                        print_controls(language);
+                        renderer.hud.set_help(controls_lines(language), language.bundle().help_hint);
+                        window.set_title(language.bundle().title);
+                    }
+                    if key == winit::keyboard::KeyCode::F1 && state == winit::event::ElementState::Pressed {
+                        renderer.hud.show_help = !renderer.hud.show_help;
+                    }
+                    if key == winit::keyboard::KeyCode::Backquote && state == winit::event::ElementState::Pressed {
+                        cursor_captured = !cursor_captured;
+                        set_cursor_captured(&window, cursor_captured && focused);
+                    }
                     // Handle F11 for fullscreen toggle
                     if key == winit::keyboard::KeyCode::F11 && state == winit::event::ElementState::Pressed {
                         if window.fullscreen().is_some() {
@@ -98,31 +190,86 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     renderer.handle_input(key, state);
                 }
                 WindowEvent::RedrawRequested => {
+                    if let Some(suite) = &mut benchmark {
+                        match suite.advance(&mut renderer) {
+                            Ok(true) => {}
+                            Ok(false) => {
+                                elwt.exit();
+                                return;
+                            }
+                            Err(e) => {
+                                log::error!("Benchmark error: {}", e);
+                                elwt.exit();
+                                return;
+                            }
+                        }
+                    }
                     if let Err(e) = renderer.render(&window) {
                         log::error!("Render error: {}", e);
+                        if e.downcast_ref::<ash::vk::Result>() == Some(&ash::vk::Result::ERROR_DEVICE_LOST) {
+                            renderer.log_checkpoints();
+                        }
+                        elwt.exit();
+                    }
+                    if offline.is_some() && !renderer.photo_in_progress() {
                         elwt.exit();
+                        return;
                     }
 
-                    // Update FPS counter
+                    // Update the FPS counter and the rest of the HUD's stats
                     frame_count += 1;
                     let now = std::time::Instant::now();
                     let elapsed = now.duration_since(last_fps_update).as_secs_f32();
                     if elapsed >= 0.5 {
                         let fps = frame_count as f32 / elapsed;
-                        window.set_title(&format!("Rust Vulkan Raytracing Demo - {:.1} FPS", fps));
+                        let text = language.bundle();
+                        let mut stats = vec![format!("{:.1} {}", fps, text.fps)];
+                        if let Some(rays) = renderer.take_ray_count() {
+                            stats.push(format!("{:.1} {}", rays as f64 / elapsed as f64 / 1e6, text.rays_per_second));
+                        }
+                        if let Some(progress) = renderer.progress() {
+                            stats.push(progress.describe(text));
+                        }
+                        if let Some(memory) = renderer.memory_summary() {
+                            stats.push(format!("{} {}", text.gpu_memory, memory));
+                        }
+                        renderer.hud.set_stats(stats);
                         frame_count = 0;
                         last_fps_update = now;
                     }
                 }
-                _ => {
+                _ if interactive => {
                     renderer.handle_window_event(&event);
                 }
+                _ => (),
             },
             Event::AboutToWait => {
+                // Offline renders and the benchmark carry on in the background
+                if hidden && interactive {
+                    paused = true;
+                    elwt.set_control_flow(ControlFlow::Wait);
+                    return;
+                }
+                // Offline renders and the benchmark aren't slowed down in the background either
+                if let Some(next_frame) = pacer.wait(focused || !interactive) {
+                    elwt.set_control_flow(ControlFlow::WaitUntil(next_frame));
+                    return;
+                }
+                if paused {
+                    paused = false;
+                    renderer.resume();
+                    frame_count = 0;
+                    last_fps_update = std::time::Instant::now();
+                }
                 window.request_redraw();
             }
-            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
-                renderer.camera.handle_mouse_input(delta.0, delta.1);
+            Event::LoopExiting => {
+                renderer.stop_recording();
+                renderer.flush_metrics();
+                renderer.save_session();
+            }
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } if interactive && cursor_captured && focused => {
+                renderer.handle_mouse_motion(delta.0, delta.1);
             }
             _ => (),
         }
@@ -130,3 +277,40 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Hides the cursor and locks it to the window for mouse look (confines it where locking isn't
+/// supported), or gives it back.
+fn set_cursor_captured(window: &Window, captured: bool) {
+    window.set_cursor_visible(!captured);
+    if !captured {
+        let _ = window.set_cursor_grab(CursorGrabMode::None);
+    } else if window.set_cursor_grab(CursorGrabMode::Locked).is_err() {
+        let _ = window.set_cursor_grab(CursorGrabMode::Confined);
+    }
+}
+
+/// The controls list in `language`, with the keys of the enabled optional features.
+fn controls_lines(language: Language) -> Vec<String> {
+    let text = language.bundle();
+    let mut lines: Vec<String> = text.controls.iter().map(|line| line.to_string()).collect();
+    if cfg!(feature = "physics") {
+        lines.push(text.controls_physics.to_string());
+    }
+    if cfg!(feature = "denoise") {
+        lines.push(text.controls_denoise.to_string());
+    }
+    lines
+}
+
+/// Logs the controls help in `language`, to the console only: the HUD shows it on F1 instead of
+/// as status messages.
+fn print_controls(language: Language) {
+    let text = language.bundle();
+    log::info!(target: "controls", "");
+    log::info!(target: "controls", "{}", text.controls_header);
+    for line in controls_lines(language) {
+        log::info!(target: "controls", "  {}", line);
+    }
+    log::info!(target: "controls", "{}", "=".repeat(text.controls_header.chars().count()));
+    log::info!(target: "controls", "");
+}
@@ -0,0 +1,131 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use crate::scene::{Material, Scene};
+
+/// Material overrides, applied over the built-in materials at startup and again whenever the
+/// file changes while the app runs.
+pub const MATERIAL_FILE: &str = "materials.txt";
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Fields a line can set, each four floats as in `Material`.
+const FIELDS: [&str; 8] = ["color", "params", "textures", "alpha", "sss", "film", "dispersion", "lobes"];
+
+fn field_mut<'a>(material: &'a mut Material, field: &str) -> Option<&'a mut [f32; 4]> {
+    Some(match field {
+        "color" => &mut material.color,
+        "params" => &mut material.params,
+        "textures" => &mut material.textures,
+        "alpha" => &mut material.alpha,
+        "sss" => &mut material.sss,
+        "film" => &mut material.film,
+        "dispersion" => &mut material.dispersion,
+        "lobes" => &mut material.lobes,
+        _ => return None,
+    })
+}
+
+/// Parses the overrides in `text`, one `material <index> <field> <4 floats>` line each (e.g.
+/// `material 4 color 0.8 0.1 0.1 1`), and returns `base` with them applied. Materials and
+/// fields without a line keep their built-in values, so deleting a line reverts it.
+pub fn parse(text: &str, base: &[Material]) -> Result<Vec<Material>, Box<dyn std::error::Error>> {
+    let mut materials = base.to_vec();
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: String| format!("line {}: {}", line_number + 1, message);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() != 7 || tokens[0] != "material" {
+            return Err(error("expected `material <index> <field> <4 floats>`".to_string()).into());
+        }
+        let index: usize = tokens[1].parse().map_err(|_| error(format!("bad material index `{}`", tokens[1])))?;
+        let material = materials.get_mut(index).ok_or_else(|| error(format!("material {} out of range", index)))?;
+        let field = field_mut(material, tokens[2]).ok_or_else(|| error(format!("unknown field `{}`, expected one of {}", tokens[2], FIELDS.join(", "))))?;
+        for (value, token) in field.iter_mut().zip(&tokens[3..]) {
+            *value = token.parse().map_err(|_| error(format!("bad number `{}`", token)))?;
+        }
+    }
+    Ok(materials)
+}
+
+/// Watches the material file for artists tweaking colors and roughness while the app runs:
+/// checks its modification time a few times a second and reapplies it to the scene's
+/// materials when it changed. A file that doesn't parse is logged and the materials are left
+/// as they are until it's fixed.
+pub struct MaterialWatcher {
+    path: PathBuf,
+    base: Vec<Material>, // Built-in materials the overrides apply to
+    modified: Option<SystemTime>, // Of the file last applied
+    last_poll: Instant,
+    instances_changed: bool, // A material switched alpha testing on or off since the last call
+}
+
+impl MaterialWatcher {
+    /// Starts watching, applying the file to `scene` right away if there is one.
+    pub fn new(scene: &mut Scene) -> Self {
+        let mut watcher = Self {
+            path: PathBuf::from(MATERIAL_FILE),
+            base: scene.materials.clone(),
+            modified: None,
+            last_poll: Instant::now(),
+            instances_changed: false,
+        };
+        if watcher.reload(scene) {
+            log::info!("Loaded material overrides from {}", MATERIAL_FILE);
+        }
+        watcher
+    }
+
+    /// Reapplies the file if it changed since it was last applied. Returns whether
+    /// `scene.materials` changed, i.e. the material buffer needs uploading; call once per frame.
+    pub fn poll(&mut self, scene: &mut Scene) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let reloaded = self.reload(scene);
+        if reloaded {
+            log::info!("Reloaded materials from {}", MATERIAL_FILE);
+        }
+        reloaded
+    }
+
+    fn reload(&mut self, scene: &mut Scene) -> bool {
+        let modified = std::fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok();
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        // A deleted file reverts to the built-in materials
+        let text = match modified {
+            Some(_) => match std::fs::read_to_string(&self.path) {
+                Ok(text) => text,
+                Err(e) => {
+                    log::warn!("Failed to read {}: {}", MATERIAL_FILE, e);
+                    return false;
+                }
+            },
+            None => String::new(),
+        };
+        match parse(&text, &self.base) {
+            Ok(materials) => {
+                // Alpha testing is set on the TLAS instances, the other fields are read by the shaders
+                let alpha_tested = |material: &Material| material.alpha[0] >= 0.0;
+                self.instances_changed |= scene.materials.iter().zip(&materials).any(|(old, new)| alpha_tested(old) != alpha_tested(new));
+                scene.materials = materials;
+                true
+            }
+            Err(e) => {
+                log::warn!("Ignoring {}: {}", MATERIAL_FILE, e);
+                false
+            }
+        }
+    }
+
+    /// Whether a reload switched alpha testing on or off for a material since the last call,
+    /// i.e. the TLAS needs a rebuild for its instances' opaque flags.
+    pub fn take_instances_changed(&mut self) -> bool {
+        std::mem::take(&mut self.instances_changed)
+    }
+}
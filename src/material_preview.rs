@@ -0,0 +1,119 @@
+use glam::{Mat4, Quat, Vec3};
+use winit::keyboard::KeyCode;
+use crate::camera::CameraPose;
+use crate::light::Light;
+use crate::scene::Scene;
+
+/// Where the studio stands: high above the street, so its floor hides the scene from the ball
+/// and the sky around it is the studio's.
+pub const STUDIO_CENTER: Vec3 = Vec3::new(0.0, 300.0, 0.0);
+const BALL_DIAMETER: f32 = 1.0;
+/// Camera relative to the ball, looking at it slightly from above
+const CAMERA_OFFSET: Vec3 = Vec3::new(0.0, 0.45, 3.0);
+/// Key light relative to the ball: front left, above
+const KEY_LIGHT_OFFSET: Vec3 = Vec3::new(-3.0, 3.0, 3.0);
+
+/// Material preview: a ball with one material on a studio floor under a studio environment
+/// (softboxes over a dark backdrop, drawn by the miss shader), to inspect a material on its
+/// own. The ball and floor are scene objects (`Scene::studio`) kept hidden while it is off.
+/// [ and ] step through the materials and Enter gives the selected object the previewed one.
+pub struct MaterialPreview {
+    pub active: bool,
+    pub material_index: usize,
+    scene_pose: CameraPose, // Where the camera was in the scene, restored when leaving
+    changed: bool, // Studio objects changed since the TLAS was last rebuilt
+}
+
+impl MaterialPreview {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            material_index: 4, // Car paint
+            scene_pose: CameraPose { position: Vec3::ZERO, orientation: Quat::IDENTITY },
+            changed: false,
+        }
+    }
+
+    /// Enters the studio showing `material_index`, or leaves it. Returns the camera pose to
+    /// switch to: looking at the ball, or back where the camera was in the scene.
+    pub fn toggle(&mut self, scene: &mut Scene, material_index: usize, pose: CameraPose) -> CameraPose {
+        self.active = !self.active;
+        for object_index in scene.studio.clone() {
+            scene.objects[object_index].visible = self.active;
+        }
+        self.changed = true;
+        if !self.active {
+            return self.scene_pose;
+        }
+        self.scene_pose = pose;
+        self.show(scene, material_index);
+        let ball = STUDIO_CENTER + Vec3::Y * BALL_DIAMETER * 0.5;
+        let pitch = -(CAMERA_OFFSET.y / CAMERA_OFFSET.z).atan();
+        CameraPose { position: ball + CAMERA_OFFSET, orientation: Quat::from_rotation_x(pitch) }
+    }
+
+    /// Handles a key while the preview is on. Returns false for keys it doesn't use, which then
+    /// go on to the regular controls.
+    pub fn handle_input(&mut self, key: KeyCode, scene: &mut Scene, selection: Option<usize>) -> bool {
+        match key {
+            KeyCode::BracketLeft | KeyCode::BracketRight => {
+                let count = scene.materials.len();
+                let next = if key == KeyCode::BracketRight {
+                    (self.material_index + 1) % count
+                } else {
+                    (self.material_index + count - 1) % count
+                };
+                self.show(scene, next);
+            }
+            KeyCode::Enter => {
+                let Some(object_index) = selection else {
+                    log::info!("Material preview: select an object in the scene to apply the material to it");
+                    return true;
+                };
+                scene.objects[object_index].material_index = self.material_index;
+                self.changed = true;
+                log::info!("Material preview: object {} now uses material {}", object_index, self.material_index);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn show(&mut self, scene: &mut Scene, material_index: usize) {
+        self.material_index = material_index;
+        scene.objects[scene.studio.start].material_index = material_index;
+        self.changed = true;
+        log::info!("Material preview: material {}", material_index);
+    }
+
+    /// The point light while the preview is on: the studio's key light instead of the scene's.
+    pub fn light(&self, scene_light: &Light) -> Light {
+        if !self.active {
+            return *scene_light;
+        }
+        Light {
+            position: STUDIO_CENTER + KEY_LIGHT_OFFSET,
+            color: Vec3::ONE,
+            intensity: 1.0,
+            radius: 0.5,
+        }
+    }
+
+    /// Where the camera is in the scene: the pose it left for the studio while the preview is on.
+    pub fn scene_pose(&self, camera_pose: CameraPose) -> CameraPose {
+        if self.active { self.scene_pose } else { camera_pose }
+    }
+
+    /// Whether the studio objects changed since the last call, i.e. the TLAS needs a rebuild.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// Transforms of the studio objects, the ball and then the floor.
+pub fn studio_transforms() -> [Mat4; 2] {
+    [
+        Mat4::from_scale_rotation_translation(Vec3::splat(BALL_DIAMETER), Quat::IDENTITY, STUDIO_CENTER + Vec3::Y * BALL_DIAMETER * 0.5),
+        Mat4::from_scale_rotation_translation(Vec3::new(24.0, 0.1, 24.0), Quat::IDENTITY, STUDIO_CENTER - Vec3::Y * 0.05),
+    ]
+}
@@ -0,0 +1,85 @@
+use ash::vk;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// What a device memory allocation holds, for the memory report.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MemoryCategory {
+    AccelerationStructures, // BLAS and TLAS storage and their scratch buffers
+    Geometry, // Vertices, indices, skinning data, instance data and transforms
+    Images, // Render targets and textures
+    ShaderBindingTable,
+    Buffers, // Everything else: uniforms, photon grid, sky cache, staging and readback
+}
+
+impl MemoryCategory {
+    pub const ALL: [MemoryCategory; 5] = [
+        MemoryCategory::AccelerationStructures,
+        MemoryCategory::Geometry,
+        MemoryCategory::Images,
+        MemoryCategory::ShaderBindingTable,
+        MemoryCategory::Buffers,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MemoryCategory::AccelerationStructures => "AS",
+            MemoryCategory::Geometry => "geometry",
+            MemoryCategory::Images => "images",
+            MemoryCategory::ShaderBindingTable => "SBT",
+            MemoryCategory::Buffers => "buffers",
+        }
+    }
+}
+
+/// Living device memory allocations of the renderer, by category. Allocations are recorded by
+/// the renderer's allocation helpers and forgotten when freed through `forget`.
+#[derive(Default)]
+pub struct MemoryTracker {
+    allocations: RefCell<HashMap<vk::DeviceMemory, (MemoryCategory, u64)>>,
+}
+
+impl MemoryTracker {
+    pub fn record(&self, memory: vk::DeviceMemory, category: MemoryCategory, bytes: u64) {
+        self.allocations.borrow_mut().insert(memory, (category, bytes));
+    }
+
+    pub fn forget(&self, memory: vk::DeviceMemory) {
+        self.allocations.borrow_mut().remove(&memory);
+    }
+
+    /// Size of a living allocation, 0 for one that isn't recorded (e.g. a null handle).
+    pub fn size(&self, memory: vk::DeviceMemory) -> u64 {
+        self.allocations.borrow().get(&memory).map_or(0, |&(_, bytes)| bytes)
+    }
+
+    /// Bytes allocated in `category`.
+    pub fn category_total(&self, category: MemoryCategory) -> u64 {
+        self.allocations.borrow().values().filter(|&&(c, _)| c == category).map(|&(_, bytes)| bytes).sum()
+    }
+
+    /// Bytes allocated in all categories.
+    pub fn total(&self) -> u64 {
+        self.totals().iter().sum()
+    }
+
+    /// Bytes allocated per category, in `MemoryCategory::ALL` order.
+    fn totals(&self) -> [u64; 5] {
+        let mut totals = [0; 5];
+        for &(category, bytes) in self.allocations.borrow().values() {
+            let index = MemoryCategory::ALL.iter().position(|&c| c == category).unwrap();
+            totals[index] += bytes;
+        }
+        totals
+    }
+
+    /// One-line breakdown, e.g. "412 MB (AS 96 MB, geometry 48 MB, images 250 MB, SBT 0 MB, buffers 18 MB)".
+    pub fn describe(&self) -> String {
+        const MB: u64 = 1024 * 1024;
+        let totals = self.totals();
+        let parts: Vec<String> = MemoryCategory::ALL.iter().zip(totals)
+            .map(|(category, bytes)| format!("{} {} MB", category.label(), bytes.div_ceil(MB)))
+            .collect();
+        format!("{} MB ({})", totals.iter().sum::<u64>().div_ceil(MB), parts.join(", "))
+    }
+}
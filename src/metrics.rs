@@ -0,0 +1,123 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+use crate::args;
+
+/// GPU time of a frame's passes, from the timestamps written between them, milliseconds.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuTimes {
+    pub scene_ms: f32, // Skinning, acceleration structure updates and the trace (or raster preview)
+    pub post_ms: f32, // Exposure, bloom, post and upscale passes
+    pub resolve_ms: f32, // Resolve pass and HUD
+}
+
+impl GpuTimes {
+    pub fn total_ms(&self) -> f32 {
+        self.scene_ms + self.post_ms + self.resolve_ms
+    }
+}
+
+/// One frame's row of the metrics log. The CPU side is known once the frame is submitted, the
+/// GPU side once its timestamps are read back, when its frame slot comes around again.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameMetrics {
+    pub frame: u32,
+    pub time: f32, // Seconds since the log was opened, at submit
+    pub cpu_ms: f32, // Recording and submitting the frame, without waiting for the GPU
+    pub frame_ms: f32, // Since the previous frame started
+    pub gpu: Option<GpuTimes>, // Without timestamp support, none
+    pub render_width: u32,
+    pub render_height: u32,
+    pub samples_per_pixel: u32,
+    pub rays: u64, // Rays the shaders traced: camera, shadow, bounce, subsurface and photon rays; 0 in the raster preview
+    pub vram_bytes: u64, // Device local memory in use
+}
+
+impl FrameMetrics {
+    /// Rays per second of GPU scene time, if the GPU time is known.
+    pub fn rays_per_second(&self) -> Option<f64> {
+        let scene_ms = self.gpu?.scene_ms;
+        (scene_ms > 0.0).then(|| self.rays as f64 / (scene_ms as f64 / 1000.0))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MetricsFormat {
+    Csv,
+    JsonLines,
+}
+
+const CSV_HEADER: &str = "frame,time_s,cpu_ms,frame_ms,gpu_ms,gpu_scene_ms,gpu_post_ms,gpu_resolve_ms,render_width,render_height,samples_per_pixel,rays,rays_per_second,vram_mb";
+
+/// Per-frame metrics written to the file `--metrics <file>` names, for comparing performance
+/// offline: CSV if it ends in `.csv`, else JSON lines (one object per frame).
+pub struct MetricsLog {
+    writer: BufWriter<File>,
+    format: MetricsFormat,
+    path: String,
+    opened: Instant,
+}
+
+impl MetricsLog {
+    /// Opens the log `--metrics` asks for, if any. Failing to create the file is logged and
+    /// leaves the metrics off.
+    pub fn from_args() -> Option<Self> {
+        let path = args::value("--metrics")?.to_string();
+        let format = if path.to_lowercase().ends_with(".csv") { MetricsFormat::Csv } else { MetricsFormat::JsonLines };
+        let mut writer = match File::create(&path) {
+            Ok(file) => BufWriter::new(file),
+            Err(e) => {
+                log::warn!("--metrics {}: {}, not writing metrics", path, e);
+                return None;
+            }
+        };
+        if format == MetricsFormat::Csv {
+            if let Err(e) = writeln!(writer, "{}", CSV_HEADER) {
+                log::warn!("--metrics {}: {}, not writing metrics", path, e);
+                return None;
+            }
+        }
+        log::info!("Writing per-frame metrics to {}", path);
+        Some(Self { writer, format, path, opened: Instant::now() })
+    }
+
+    /// Seconds since the log was opened.
+    pub fn elapsed(&self) -> f32 {
+        self.opened.elapsed().as_secs_f32()
+    }
+
+    /// Appends a frame's row. Rows are buffered and reach the file in batches.
+    pub fn write(&mut self, frame: &FrameMetrics) {
+        let gpu = frame.gpu;
+        let gpu_ms = [gpu.map(|t| t.total_ms()), gpu.map(|t| t.scene_ms), gpu.map(|t| t.post_ms), gpu.map(|t| t.resolve_ms)];
+        let vram_mb = frame.vram_bytes as f64 / (1024.0 * 1024.0);
+        let result = match self.format {
+            MetricsFormat::Csv => {
+                let optional = |value: Option<f64>| value.map_or(String::new(), |v| format!("{:.3}", v));
+                writeln!(self.writer, "{},{:.3},{:.3},{:.3},{},{},{},{},{},{},{},{},{},{:.1}",
+                    frame.frame, frame.time, frame.cpu_ms, frame.frame_ms,
+                    optional(gpu_ms[0].map(f64::from)), optional(gpu_ms[1].map(f64::from)), optional(gpu_ms[2].map(f64::from)), optional(gpu_ms[3].map(f64::from)),
+                    frame.render_width, frame.render_height, frame.samples_per_pixel, frame.rays,
+                    frame.rays_per_second().map_or(String::new(), |rays| format!("{:.0}", rays)), vram_mb)
+            }
+            MetricsFormat::JsonLines => {
+                let optional = |value: Option<f64>| value.map_or("null".to_string(), |v| format!("{:.3}", v));
+                writeln!(self.writer, "{{\"frame\":{},\"time_s\":{:.3},\"cpu_ms\":{:.3},\"frame_ms\":{:.3},\"gpu_ms\":{},\"gpu_scene_ms\":{},\"gpu_post_ms\":{},\"gpu_resolve_ms\":{},\"render_width\":{},\"render_height\":{},\"samples_per_pixel\":{},\"rays\":{},\"rays_per_second\":{},\"vram_mb\":{:.1}}}",
+                    frame.frame, frame.time, frame.cpu_ms, frame.frame_ms,
+                    optional(gpu_ms[0].map(f64::from)), optional(gpu_ms[1].map(f64::from)), optional(gpu_ms[2].map(f64::from)), optional(gpu_ms[3].map(f64::from)),
+                    frame.render_width, frame.render_height, frame.samples_per_pixel, frame.rays,
+                    frame.rays_per_second().map_or("null".to_string(), |rays| format!("{:.0}", rays)), vram_mb)
+            }
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to write metrics to {}: {}", self.path, e);
+        }
+    }
+
+    /// Writes out the buffered rows, at exit.
+    pub fn flush(&mut self) {
+        if let Err(e) = self.writer.flush() {
+            log::warn!("Failed to write metrics to {}: {}", self.path, e);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+use crate::args;
+
+/// Frame rate while the window doesn't have focus, unless `--background-fps` asks for another.
+const DEFAULT_BACKGROUND_FPS: f32 = 10.0;
+/// Lowest frame rate limit, a frame every 10 seconds. Lower ones are raised to it: their frame
+/// interval wouldn't fit a `Duration`.
+const MIN_FPS: f32 = 0.1;
+
+/// Frame pacing from `--max-fps <n>` (uncapped without it) and `--background-fps <n>` (0 keeps
+/// the full rate in the background).
+pub fn frame_pacer_from_args() -> FramePacer {
+    let fps = |flag: &str| args::number::<f32>(flag).filter(|&fps| fps >= 0.0);
+    let max_fps = fps("--max-fps").filter(|&fps| fps > 0.0);
+    let background_fps = fps("--background-fps").unwrap_or(DEFAULT_BACKGROUND_FPS);
+    if let Some(max_fps) = max_fps {
+        log::info!("Frame rate capped at {} FPS", max_fps);
+    }
+    FramePacer::new(max_fps, (background_fps > 0.0).then_some(background_fps))
+}
+
+/// Spaces frames out to a frame rate cap, and to a lower one while the window is in the
+/// background, so the demo doesn't run the GPU flat out when nobody is looking at it. The
+/// event loop sleeps until the next frame is due instead of spinning.
+pub struct FramePacer {
+    max_fps: Option<f32>,
+    background_fps: Option<f32>,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new(max_fps: Option<f32>, background_fps: Option<f32>) -> Self {
+        FramePacer { max_fps, background_fps, next_frame: Instant::now() }
+    }
+
+    /// Frame rate limit for a window that has focus (`focused`) or not.
+    fn limit(&self, focused: bool) -> Option<f32> {
+        match (focused, self.background_fps) {
+            (false, Some(background)) => Some(self.max_fps.map_or(background, |max| max.min(background))),
+            _ => self.max_fps,
+        }
+    }
+
+    /// When the next frame may start, if that's later than now. Otherwise the frame starts now
+    /// and the one after it is scheduled.
+    pub fn wait(&mut self, focused: bool) -> Option<Instant> {
+        let now = Instant::now();
+        let Some(fps) = self.limit(focused) else {
+            self.next_frame = now;
+            return None;
+        };
+        if now < self.next_frame {
+            return Some(self.next_frame);
+        }
+        // Frames on schedule are spaced evenly; after a late one the schedule starts over
+        // rather than hurrying to catch up
+        let interval = Duration::from_secs_f32(1.0 / fps.max(MIN_FPS));
+        self.next_frame += interval;
+        if self.next_frame < now {
+            self.next_frame = now + interval;
+        }
+        None
+    }
+}
@@ -0,0 +1,138 @@
+use glam::{Mat4, Quat, Vec3};
+use crate::scene::Scene;
+use crate::args;
+
+// Scene content the sparks use
+const SPARK_MATERIAL: usize = 18;
+const EMBER_MATERIAL: usize = 19;
+
+/// Spark particles in the scene unless `--particles` gives another count.
+const DEFAULT_POOL_SIZE: usize = 2000;
+/// Where the sparks shower from: the porch lamp's shorting wiring.
+const SOURCE: Vec3 = Vec3::new(-3.6, 2.0, -2.85);
+const SPEED: f32 = 3.0; // m/s, at most
+const LIFETIME: f32 = 2.5; // Seconds, at most
+const COOLING_AGE: f32 = 0.4; // Fraction of its lifetime after which a spark glows as an ember
+const GRAVITY: f32 = 9.81;
+const DRAG: f32 = 0.8; // 1/s
+const GROUND: f32 = -0.05; // Top of the asphalt
+const RESTITUTION: f32 = 0.3;
+const BURST_INTERVAL: f32 = 1.5; // Seconds between bursts, on average
+const BURST_SHARE: f32 = 0.15; // Fraction of the pool a burst spawns, at most
+
+/// Spark particle pool size from `--particles <n>`, to stress TLAS rebuilds with more instances.
+pub fn pool_size_from_args() -> usize {
+    args::number("--particles").unwrap_or(DEFAULT_POOL_SIZE)
+}
+
+#[derive(Clone, Copy, Default)]
+struct Particle {
+    position: Vec3,
+    velocity: Vec3,
+    age: f32, // Seconds
+    lifetime: f32, // Seconds, 0 while the particle is dead
+}
+
+/// Sparks simulated on the CPU and drawn as the scene's pool of particle objects, one small
+/// TLAS instance each: bursts shower from the porch lamp, bounce off the asphalt and cool
+/// into embers. Dead particles are hidden through their instance mask, so the TLAS keeps its
+/// instance count while every frame rebuilds it with the live ones moved.
+pub struct Particles {
+    pub enabled: bool, // Sparks are spawned, the live ones burn out either way
+    particles: Vec<Particle>,
+    next_burst: f32, // Seconds
+    rng: u32,
+}
+
+impl Particles {
+    /// Sparks for `scene`'s particle pool, spawned at random by `seed`.
+    pub fn new(scene: &Scene, seed: u32) -> Self {
+        Particles {
+            enabled: false,
+            particles: vec![Particle::default(); scene.particles.len()],
+            next_burst: 0.0,
+            rng: crate::rng::tea(seed, 0x7f4a_7c15).max(1), // Xorshift state must not be 0
+        }
+    }
+
+    /// Whether any spark is alive or about to be spawned, i.e. `update` needs to run and the TLAS be rebuilt.
+    pub fn active(&self) -> bool {
+        self.enabled || self.particles.iter().any(|particle| particle.lifetime > 0.0)
+    }
+
+    /// Advances the sparks by `dt` seconds and poses the scene's particle objects.
+    pub fn update(&mut self, dt: f32, scene: &mut Scene) {
+        if self.enabled {
+            self.next_burst -= dt;
+            if self.next_burst <= 0.0 {
+                self.burst();
+                self.next_burst = BURST_INTERVAL * (0.3 + 1.4 * self.random());
+            }
+        }
+
+        for (particle, object) in self.particles.iter_mut().zip(&mut scene.objects[scene.particles.clone()]) {
+            if particle.lifetime > 0.0 {
+                particle.age += dt;
+                if particle.age >= particle.lifetime {
+                    particle.lifetime = 0.0;
+                }
+            }
+            object.visible = particle.lifetime > 0.0;
+            if !object.visible {
+                continue;
+            }
+
+            particle.velocity.y -= GRAVITY * dt;
+            particle.velocity *= (-DRAG * dt).exp();
+            particle.position += particle.velocity * dt;
+            if particle.position.y < GROUND {
+                particle.position.y = GROUND;
+                particle.velocity.y = -particle.velocity.y * RESTITUTION;
+                particle.velocity.x *= 0.6;
+                particle.velocity.z *= 0.6;
+            }
+
+            // Streak stretched along the motion, as the eye sees a glowing spark
+            let speed = particle.velocity.length();
+            let direction = if speed > 1e-3 { particle.velocity / speed } else { Vec3::Y };
+            object.transform = Mat4::from_scale_rotation_translation(
+                Vec3::new(0.01, (speed * 0.02).clamp(0.01, 0.12), 0.01),
+                Quat::from_rotation_arc(Vec3::Y, direction),
+                particle.position,
+            );
+            object.material_index = if particle.age > particle.lifetime * COOLING_AGE { EMBER_MATERIAL } else { SPARK_MATERIAL };
+        }
+    }
+
+    /// Brings up to `BURST_SHARE` of the pool's dead particles to life at the source,
+    /// flying out and down in a cone.
+    fn burst(&mut self) {
+        let mut count = ((self.particles.len() as f32 * BURST_SHARE * (0.3 + 0.7 * self.random())) as usize).max(1);
+        for i in 0..self.particles.len() {
+            if count == 0 {
+                break;
+            }
+            if self.particles[i].lifetime > 0.0 {
+                continue;
+            }
+            count -= 1;
+            let angle = self.random() * std::f32::consts::TAU;
+            let spread = self.random();
+            let direction = Vec3::new(angle.cos() * spread, -0.2 - self.random() * 0.5, angle.sin() * spread + 0.5).normalize();
+            self.particles[i] = Particle {
+                position: SOURCE,
+                velocity: direction * SPEED * (0.3 + 0.7 * self.random()),
+                age: 0.0,
+                lifetime: LIFETIME * (0.4 + 0.6 * self.random()),
+            };
+        }
+    }
+
+    // Xorshift, uniform in [0, 1)
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
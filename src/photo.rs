@@ -0,0 +1,334 @@
+use std::time::Instant;
+use glam::Vec4;
+use crate::color;
+use crate::aov::{self, Aovs, LayeredExrWriter};
+use crate::image_io::PngWriter;
+use crate::args;
+
+/// Samples accumulated per pixel of a photo, unless the offline render asks for another count.
+pub const PHOTO_SAMPLES: u32 = 1024;
+
+/// Output sizes F10 cycles through, in multiples of the window size.
+pub const PHOTO_SCALES: [u32; 3] = [1, 2, 4];
+
+/// Largest width or height of an offline render. The tile count, not the GPU, limits the size;
+/// this keeps the EXR's 32-bit row sizes in range.
+pub const OFFLINE_MAX_SIZE: u32 = 65536;
+
+/// Distance between the eyes of stereo panoramas, meters.
+const PANORAMA_EYE_SEPARATION: f32 = 0.064;
+
+/// How a photo maps its pixels to camera rays.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PhotoProjection {
+    Perspective, // The camera's view
+    Panorama, // 360° equirectangular around the camera, 2:1
+    StereoPanorama, // Omni-directional stereo (ODS) for VR video: left eye on top, right eye below, 1:1
+}
+
+impl PhotoProjection {
+    /// Projection from `--panorama` or `--panorama-stereo`, perspective otherwise.
+    pub fn from_args() -> Self {
+        if args::flag("--panorama-stereo") {
+            PhotoProjection::StereoPanorama
+        } else if args::flag("--panorama") {
+            PhotoProjection::Panorama
+        } else {
+            PhotoProjection::Perspective
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PhotoProjection::Perspective => "perspective",
+            PhotoProjection::Panorama => "360° panorama",
+            PhotoProjection::StereoPanorama => "360° stereo panorama",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            PhotoProjection::Perspective => PhotoProjection::Panorama,
+            PhotoProjection::Panorama => PhotoProjection::StereoPanorama,
+            PhotoProjection::StereoPanorama => PhotoProjection::Perspective,
+        }
+    }
+
+    /// Size of a photo `width` pixels wide: panoramas cover 360° by 180° per eye, whatever the
+    /// window's shape.
+    pub fn size(self, width: u32, height: u32) -> (u32, u32) {
+        match self {
+            PhotoProjection::Perspective => (width, height),
+            PhotoProjection::Panorama => (width, (width / 2).max(1)),
+            PhotoProjection::StereoPanorama => (width, width),
+        }
+    }
+
+    /// `projection_params` of the camera UBO: x: projection (0: perspective, 1: equirectangular,
+    /// 2: ODS top/bottom), y: eye separation.
+    pub fn params(self) -> Vec4 {
+        match self {
+            PhotoProjection::Perspective => Vec4::ZERO,
+            PhotoProjection::Panorama => Vec4::new(1.0, 0.0, 0.0, 0.0),
+            PhotoProjection::StereoPanorama => Vec4::new(2.0, PANORAMA_EYE_SEPARATION, 0.0, 0.0),
+        }
+    }
+}
+
+/// Largest photo (in pixels) kept whole in memory with its AOVs, 3840x2160, so the `denoise`
+/// feature can denoise it. Larger ones are streamed to disk, AOVs included, but not denoised.
+const WHOLE_MAX_PIXELS: usize = 3840 * 2160;
+
+/// A photo being rendered: the scene and camera hold still while `samples` jittered samples
+/// accumulate in one tile at a time. A tile is a render-resolution window into the photo's
+/// frame, so photos can be far larger than the swapchain. Finished rows of tiles are cropped
+/// to the photo size and streamed to a PNG. Photos up to `WHOLE_MAX_PIXELS` are also kept
+/// whole with their AOVs for a layered EXR; larger ones stream the layered EXR (linear) with
+/// the AOVs too, so only one row of tiles is held in memory.
+pub struct PhotoCapture {
+    pub width: u32,
+    pub height: u32,
+    pub projection: PhotoProjection,
+    pub samples: u32,
+    pub samples_done: u32, // In the current tile
+    tile_width: u32,
+    tile_height: u32,
+    tiles_x: u32,
+    tiles_y: u32,
+    tile: u32, // Tile being accumulated, row by row from the top left
+    band: Vec<f32>, // Stitched RGBA of the current row of tiles
+    prefix: String,
+    png: PngWriter,
+    hdr: HdrOutput,
+    started: Instant,
+    shutter_open: f32, // Scene time the shutter opens at
+    shutter: f32, // Seconds the shutter stays open, 0 for a still scene
+}
+
+/// Where a photo's linear color and AOVs go.
+enum HdrOutput {
+    Streamed { exr: LayeredExrWriter, aovs: Aovs }, // AOVs of the current row of tiles
+    Whole(WholePhoto),
+}
+
+/// Color and AOVs of a whole photo, stitched from the tiles.
+pub struct WholePhoto {
+    pub color: Vec<f32>,
+    pub aovs: Aovs,
+}
+
+/// A photo written to `<prefix>.exr` and `<prefix>.png`.
+pub struct SavedPhoto {
+    pub prefix: String,
+    pub width: u32,
+    pub height: u32,
+    pub render_time: f32, // Seconds
+    #[cfg(feature = "denoise")]
+    pub whole: Option<WholePhoto>, // For the denoiser
+}
+
+/// Part of the photo a tile covers.
+#[derive(Clone, Copy)]
+struct TilePlacement {
+    x: usize, // Top left pixel in the photo
+    y: usize,
+    width: usize, // Cropped to the photo
+    height: usize,
+    tile_width: usize, // Uncropped, as traced
+    photo_width: usize,
+}
+
+impl TilePlacement {
+    /// Copies the covered part of a tile-sized RGBA image into `dst`, whose rows are as wide
+    /// as the photo and start at photo row `dst_row`.
+    fn stitch(self, dst: &mut [f32], dst_row: usize, src: &[f32]) {
+        for (row, src) in src.chunks_exact(self.tile_width * 4).take(self.height).enumerate() {
+            let start = ((self.y - dst_row + row) * self.photo_width + self.x) * 4;
+            dst[start..start + self.width * 4].copy_from_slice(&src[..self.width * 4]);
+        }
+    }
+}
+
+/// Offline render requested on the command line: `--render <width>x<height>` renders one photo
+/// of the restored session's view, optionally with `--samples <count>` per pixel and as a
+/// panorama (`--panorama`, `--panorama-stereo`), and exits.
+pub struct OfflineRender {
+    pub width: u32,
+    pub height: u32,
+    pub samples: u32,
+    pub projection: PhotoProjection,
+}
+
+impl OfflineRender {
+    pub fn from_args() -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        if !args::flag("--render") {
+            return Ok(None);
+        }
+        let size = args::value("--render").ok_or("--render expects <width>x<height>, e.g. 15360x8640")?;
+        let (width, height) = size.split_once('x').ok_or("--render expects <width>x<height>, e.g. 15360x8640")?;
+        let (width, height): (u32, u32) = (width.parse()?, height.parse()?);
+        if width == 0 || height == 0 || width > OFFLINE_MAX_SIZE || height > OFFLINE_MAX_SIZE {
+            return Err(format!("--render size must be between 1 and {} on each axis", OFFLINE_MAX_SIZE).into());
+        }
+        let samples = args::number::<u32>("--samples").map_or(PHOTO_SAMPLES, |samples| samples.max(1));
+        Ok(Some(Self { width, height, samples, projection: PhotoProjection::from_args() }))
+    }
+}
+
+impl PhotoCapture {
+    /// Starts a `width` x `height` photo saved as `<prefix>.exr` and `<prefix>.png`, traced in
+    /// tiles of `tile_width` x `tile_height`.
+    pub fn start(prefix: String, width: u32, height: u32, projection: PhotoProjection, tile_width: u32, tile_height: u32, samples: u32) -> std::io::Result<Self> {
+        let pixels = width as usize * height as usize;
+        let hdr = if pixels <= WHOLE_MAX_PIXELS {
+            HdrOutput::Whole(WholePhoto { color: Vec::with_capacity(pixels * 4), aovs: Aovs::zeroed(pixels) })
+        } else {
+            log::warn!("Photo mode: {}x{} is too large to keep in memory, saving it without denoising", width, height);
+            HdrOutput::Streamed { exr: LayeredExrWriter::create(format!("{}.exr", prefix), width, height)?, aovs: Aovs::zeroed(0) }
+        };
+        let png = PngWriter::create(format!("{}.png", prefix), width, height)?;
+        Ok(Self {
+            width,
+            height,
+            projection,
+            samples,
+            samples_done: 0,
+            tile_width,
+            tile_height,
+            tiles_x: width.div_ceil(tile_width),
+            tiles_y: height.div_ceil(tile_height),
+            tile: 0,
+            band: Vec::new(),
+            prefix,
+            png,
+            hdr,
+            started: Instant::now(),
+            shutter_open: 0.0,
+            shutter: 0.0,
+        })
+    }
+
+    /// Keeps the shutter open for `shutter` seconds of scene time from `open`, for motion blur.
+    pub fn set_shutter(&mut self, open: f32, shutter: f32) {
+        self.shutter_open = open;
+        self.shutter = shutter;
+    }
+
+    /// Scene time to pose the scene at for the next sample. Samples spread over the shutter
+    /// interval in van der Corput order, so every prefix of them covers it evenly.
+    pub fn sample_time(&self) -> f32 {
+        let fraction = self.samples_done.reverse_bits() as f32 / (u32::MAX as f32 + 1.0);
+        self.shutter_open + self.shutter * fraction
+    }
+
+    pub fn tile_count(&self) -> u32 {
+        self.tiles_x * self.tiles_y
+    }
+
+    /// Tile being accumulated, counting from 0.
+    pub fn current_tile(&self) -> u32 {
+        self.tile
+    }
+
+    /// Seconds since the photo was started.
+    pub fn elapsed(&self) -> f32 {
+        self.started.elapsed().as_secs_f32()
+    }
+
+    fn placement(&self) -> TilePlacement {
+        let (x, y) = ((self.tile % self.tiles_x) * self.tile_width, (self.tile / self.tiles_x) * self.tile_height);
+        TilePlacement {
+            x: x as usize,
+            y: y as usize,
+            width: (self.width - x).min(self.tile_width) as usize,
+            height: (self.height - y).min(self.tile_height) as usize,
+            tile_width: self.tile_width as usize,
+            photo_width: self.width as usize,
+        }
+    }
+
+    /// `tile_params` of the camera UBO: photo size and current tile origin, in pixels.
+    pub fn tile_params(&self) -> Vec4 {
+        let placement = self.placement();
+        Vec4::new(self.width as f32, self.height as f32, placement.x as f32, placement.y as f32)
+    }
+
+    /// Adds the AOVs of the current tile, before `add_tile` moves on.
+    pub fn add_tile_aovs(&mut self, aovs: &Aovs) {
+        let placement = self.placement();
+        let (dst_aovs, dst_row) = match &mut self.hdr {
+            HdrOutput::Streamed { aovs: band, .. } => {
+                if placement.x == 0 {
+                    *band = Aovs::zeroed(placement.photo_width * placement.height);
+                }
+                (band, placement.y)
+            }
+            HdrOutput::Whole(whole) => (&mut whole.aovs, 0),
+        };
+        for (dst, src) in dst_aovs.images_mut().into_iter().zip(aovs.images()) {
+            placement.stitch(dst, dst_row, src);
+        }
+    }
+
+    /// Adds the finished tile (averaged RGBA at the tile size) and moves on to the next one,
+    /// writing out the row of tiles once it is complete. Returns whether that was the last tile.
+    pub fn add_tile(&mut self, color: &[f32]) -> Result<bool, Box<dyn std::error::Error>> {
+        let placement = self.placement();
+        if placement.x == 0 {
+            self.band = vec![0.0; placement.photo_width * placement.height * 4];
+        }
+        placement.stitch(&mut self.band, placement.y, color);
+
+        self.tile += 1;
+        self.samples_done = 0;
+        if self.tile.is_multiple_of(self.tiles_x) {
+            let rgba8 = color::linear_to_srgb8(&self.band);
+            self.png.write_rows(&rgba8)?;
+            match &mut self.hdr {
+                HdrOutput::Streamed { exr, aovs } => exr.write_rows(&self.band, aovs)?,
+                HdrOutput::Whole(whole) => whole.color.extend_from_slice(&self.band),
+            }
+        }
+        if self.tile < self.tile_count() {
+            log::info!("Photo mode: tile {}/{} done", self.tile, self.tile_count());
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Completes the files once all tiles are in. A photo kept whole is written now, as a
+    /// layered EXR with its AOVs.
+    pub fn finish(self) -> Result<SavedPhoto, Box<dyn std::error::Error>> {
+        self.png.finish()?;
+        let whole = match self.hdr {
+            HdrOutput::Streamed { exr, .. } => {
+                exr.finish()?;
+                None
+            }
+            HdrOutput::Whole(whole) => {
+                aov::write_layered_exr(format!("{}.exr", self.prefix), self.width, self.height, &whole.color, Some(&whole.aovs))?;
+                Some(whole)
+            }
+        };
+        #[cfg(not(feature = "denoise"))]
+        drop(whole);
+        Ok(SavedPhoto {
+            prefix: self.prefix,
+            width: self.width,
+            height: self.height,
+            render_time: self.started.elapsed().as_secs_f32(),
+            #[cfg(feature = "denoise")]
+            whole,
+        })
+    }
+
+    /// Stops the render and deletes the partly written files.
+    pub fn cancel(self) {
+        let prefix = self.prefix;
+        drop((self.png, self.hdr));
+        for extension in ["exr", "png"] {
+            let _ = std::fs::remove_file(format!("{}.{}", prefix, extension));
+        }
+        log::info!("Photo mode: cancelled");
+    }
+}
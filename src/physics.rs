@@ -0,0 +1,103 @@
+use glam::{Mat4, Quat, Vec3};
+use rapier3d::prelude::*;
+use crate::scene::{ColliderShape, Scene};
+
+const TIME_STEP: f32 = 1.0 / 60.0;
+const MAX_STEPS_PER_FRAME: u32 = 8; // Drops simulation time instead of spiraling after a long frame
+
+/// Rapier simulation of the scene's rigid bodies. Steps at a fixed rate and writes the
+/// poses of the dynamic bodies back into their scene objects, which the renderer then
+/// pushes through the TLAS update like any other animated object.
+pub struct PhysicsWorld {
+    pipeline: PhysicsPipeline,
+    integration_parameters: IntegrationParameters,
+    islands: IslandManager,
+    broad_phase: DefaultBroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    impulse_joints: ImpulseJointSet,
+    multibody_joints: MultibodyJointSet,
+    ccd_solver: CCDSolver,
+    dynamic_bodies: Vec<(usize, RigidBodyHandle, Vec3)>, // Scene object, body, render scale
+    accumulator: f32,
+}
+
+impl PhysicsWorld {
+    /// Builds the world with every body at its registered start pose.
+    pub fn new(scene: &Scene) -> Self {
+        let mut bodies = RigidBodySet::new();
+        let mut colliders = ColliderSet::new();
+        let mut dynamic_bodies = Vec::new();
+
+        for desc in &scene.rigid_bodies {
+            // Scale only affects rendering, the collider shape is given in world units
+            let (scale, rotation, translation) = desc.start.to_scale_rotation_translation();
+            let axis_angle = rotation.to_scaled_axis();
+            let builder = if desc.dynamic { RigidBodyBuilder::dynamic() } else { RigidBodyBuilder::fixed() };
+            let handle = bodies.insert(builder
+                .translation(vector![translation.x, translation.y, translation.z])
+                .rotation(vector![axis_angle.x, axis_angle.y, axis_angle.z])
+                .build());
+
+            let collider = match desc.shape {
+                ColliderShape::Ball { radius } => ColliderBuilder::ball(radius),
+                ColliderShape::Cuboid { half_extents } => ColliderBuilder::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            };
+            colliders.insert_with_parent(collider.restitution(desc.restitution).build(), handle, &mut bodies);
+
+            if desc.dynamic {
+                dynamic_bodies.push((desc.object_index, handle, scale));
+            }
+        }
+
+        Self {
+            pipeline: PhysicsPipeline::new(),
+            integration_parameters: IntegrationParameters { dt: TIME_STEP, ..Default::default() },
+            islands: IslandManager::new(),
+            broad_phase: DefaultBroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            bodies,
+            colliders,
+            impulse_joints: ImpulseJointSet::new(),
+            multibody_joints: MultibodyJointSet::new(),
+            ccd_solver: CCDSolver::new(),
+            dynamic_bodies,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Advances the simulation by `dt` seconds and updates the dynamic objects' transforms.
+    pub fn step(&mut self, dt: f32, scene: &mut Scene) {
+        let gravity = vector![0.0, -9.81, 0.0];
+        self.accumulator = (self.accumulator + dt).min(TIME_STEP * MAX_STEPS_PER_FRAME as f32);
+        while self.accumulator >= TIME_STEP {
+            self.pipeline.step(
+                &gravity,
+                &self.integration_parameters,
+                &mut self.islands,
+                &mut self.broad_phase,
+                &mut self.narrow_phase,
+                &mut self.bodies,
+                &mut self.colliders,
+                &mut self.impulse_joints,
+                &mut self.multibody_joints,
+                &mut self.ccd_solver,
+                None,
+                &(),
+                &(),
+            );
+            self.accumulator -= TIME_STEP;
+        }
+
+        for &(object_index, handle, scale) in &self.dynamic_bodies {
+            let body = &self.bodies[handle];
+            let (t, r) = (body.translation(), body.rotation());
+            scene.objects[object_index].transform = Mat4::from_scale_rotation_translation(
+                scale,
+                Quat::from_xyzw(r.i, r.j, r.k, r.w),
+                Vec3::new(t.x, t.y, t.z),
+            );
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use glam::Vec4;
+use winit::keyboard::KeyCode;
+use crate::args;
+
+/// One artistic post effect: whether it is on, and how strong it is when it is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PostEffect {
+    pub enabled: bool,
+    pub strength: f32,
+}
+
+/// Artistic effects the post pass applies to the exposed, clamped display image, after heat
+/// haze and motion blur: a vignette darkening the corners, film grain over the midtones and
+/// chromatic aberration fringing edges towards the corners. Each has a toggle (Ctrl+1 to
+/// Ctrl+3) and a strength (`--vignette <strength>`, `--film-grain <strength>` and
+/// `--chromatic-aberration <pixels>`, which also turn it on). They reach the post pass in
+/// its push constants as `PostEffectParams`; a new effect is a field here and in
+/// `PostEffectParams`, an entry in `effects_mut`, and its code in `post.comp`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PostEffects {
+    pub vignette: PostEffect, // Darkening at the corners, 0..1
+    pub film_grain: PostEffect, // Noise amplitude, 0..1
+    pub chromatic_aberration: PostEffect, // Red/blue separation at the corners, pixels
+}
+
+/// GPU layout of `PostEffects`, the end of the post pass's push constants. x of each is its strength, 0 while it is off.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PostEffectParams {
+    pub vignette: Vec4,
+    pub film_grain: Vec4,
+    pub chromatic_aberration: Vec4,
+}
+
+impl PostEffects {
+    pub fn new() -> Self {
+        let mut effects = Self {
+            vignette: PostEffect { enabled: false, strength: 0.4 },
+            film_grain: PostEffect { enabled: false, strength: 0.08 },
+            chromatic_aberration: PostEffect { enabled: false, strength: 3.0 },
+        };
+        for (name, effect) in effects.effects_mut() {
+            let flag = format!("--{}", name.replace(' ', "-"));
+            let Some(value) = args::value(&flag) else {
+                continue;
+            };
+            match value.parse::<f32>() {
+                Ok(strength) if strength >= 0.0 => *effect = PostEffect { enabled: true, strength },
+                _ => log::warn!("Ignoring {} {}, expected a strength of 0 or more", flag, value),
+            }
+        }
+        effects
+    }
+
+    /// The effects with their names, in the order of their Ctrl+digit toggles.
+    fn effects_mut(&mut self) -> [(&'static str, &mut PostEffect); 3] {
+        [
+            ("vignette", &mut self.vignette),
+            ("film grain", &mut self.film_grain),
+            ("chromatic aberration", &mut self.chromatic_aberration),
+        ]
+    }
+
+    /// Whether any effect is on, i.e. the post pass needs to run for them.
+    pub fn any(&self) -> bool {
+        self.vignette.enabled || self.film_grain.enabled || self.chromatic_aberration.enabled
+    }
+
+    /// Handles a key pressed with Ctrl held. Returns false for keys it doesn't use.
+    pub fn handle_input(&mut self, key: KeyCode) -> bool {
+        let index = match key {
+            KeyCode::Digit1 => 0,
+            KeyCode::Digit2 => 1,
+            KeyCode::Digit3 => 2,
+            _ => return false,
+        };
+        let (name, effect) = self.effects_mut().into_iter().nth(index).unwrap();
+        effect.enabled = !effect.enabled;
+        log::info!("Post effect {}: {}", name, if effect.enabled { "on" } else { "off" });
+        true
+    }
+
+    pub fn params(&self) -> PostEffectParams {
+        let strength = |effect: PostEffect| Vec4::new(if effect.enabled { effect.strength } else { 0.0 }, 0.0, 0.0, 0.0);
+        PostEffectParams {
+            vignette: strength(self.vignette),
+            film_grain: strength(self.film_grain),
+            chromatic_aberration: strength(self.chromatic_aberration),
+        }
+    }
+}
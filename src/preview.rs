@@ -0,0 +1,86 @@
+use glam::{Mat4, Vec3};
+use crate::scene::{Mesh, RayMask, Scene};
+
+/// Pick ray range, as traced by pick.rgen.
+const T_MIN: f32 = 0.001;
+const T_MAX: f32 = 10000.0;
+
+/// The closest surface along a pick ray.
+pub struct PickHit {
+    pub instance_index: usize, // Numbered like the TLAS: the scene objects, then the batch instances
+    pub primitive_index: usize,
+    pub position: Vec3,
+    pub distance: f32,
+}
+
+/// Picking for the raster preview, the fallback on GPUs without ray tracing pipelines, where
+/// pick.rgen can't run: casts the ray on the CPU against every instance camera rays see at its current
+/// transform. Skinned meshes are tested in their rest pose, which is how the preview draws them.
+pub fn pick(scene: &Scene, origin: Vec3, direction: Vec3) -> Option<PickHit> {
+    let objects = scene.objects.iter().map(|obj| (obj.mesh_index, obj.transform, obj.visible && obj.rays.contains(RayMask::CAMERA)));
+    let batches = scene.instance_batches.iter().flat_map(|batch| {
+        batch.instances.iter().map(move |inst| (batch.mesh_index, inst.transform, true))
+    });
+    let mut closest: Option<PickHit> = None;
+    for (instance_index, (mesh_index, transform, visible)) in objects.chain(batches).enumerate() {
+        if !visible {
+            continue;
+        }
+        let t_max = closest.as_ref().map_or(T_MAX, |hit| hit.distance);
+        if let Some((primitive_index, distance)) = intersect_mesh(&scene.meshes[mesh_index], transform, origin, direction, t_max) {
+            closest = Some(PickHit { instance_index, primitive_index, position: origin + direction * distance, distance });
+        }
+    }
+    closest
+}
+
+/// Closest triangle of `mesh` placed by `transform` hit before `t_max`. The ray is moved to
+/// object space unnormalized, so distances along it stay world distances.
+pub fn intersect_mesh(mesh: &Mesh, transform: Mat4, origin: Vec3, direction: Vec3, t_max: f32) -> Option<(usize, f32)> {
+    let to_object = transform.inverse();
+    let origin = to_object.transform_point3(origin);
+    let direction = to_object.transform_vector3(direction);
+
+    // Slab test against the mesh bounds first
+    let (min, max) = mesh.bounds();
+    let inv_dir = direction.recip();
+    let (t0, t1) = ((min - origin) * inv_dir, (max - origin) * inv_dir);
+    let near = t0.min(t1).max_element().max(T_MIN);
+    let far = t0.max(t1).min_element().min(t_max);
+    if near > far {
+        return None;
+    }
+
+    let mut closest = None;
+    let mut t_max = t_max;
+    for (primitive_index, triangle) in mesh.indices.chunks_exact(3).enumerate() {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(mesh.vertices[triangle[i] as usize].pos));
+        if let Some(t) = intersect_triangle(origin, direction, a, b, c).filter(|&t| t > T_MIN && t < t_max) {
+            closest = Some((primitive_index, t));
+            t_max = t;
+        }
+    }
+    closest
+}
+
+/// Möller–Trumbore, double-sided like the TLAS instances.
+fn intersect_triangle(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    let (edge1, edge2) = (b - a, c - a);
+    let p = direction.cross(edge2);
+    let det = edge1.dot(p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = origin - a;
+    let u = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some(edge2.dot(q) * inv_det)
+}
@@ -0,0 +1,69 @@
+use std::time::Duration;
+use crate::locale::Bundle;
+
+/// Noise below which a progressive render counts as converged: the standard error of the mean
+/// luminance is under 1% of the mean.
+pub const CONVERGED_NOISE: f32 = 0.01;
+
+/// How often the noise of a progressive render is estimated. Each estimate reads back the
+/// accumulation image, which waits for the GPU.
+pub const NOISE_ESTIMATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What a progressive render is accumulating.
+#[derive(Clone, Copy, Debug)]
+pub enum ProgressiveKind {
+    LongExposure,
+    Photo { tile: u32, tiles: u32 }, // Tile is 1-based
+}
+
+/// State of a progressive render, for the HUD.
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    pub kind: ProgressiveKind,
+    pub samples: u32, // Accumulated so far (in the current tile)
+    pub target: u32,
+    pub elapsed: f32, // Seconds
+    pub noise: Option<f32>, // Last estimate from `estimate_noise`, none before the first
+}
+
+impl Progress {
+    /// One-line summary in `text`'s language, e.g. "Photo: tile 2/4, 512/1024 samples, 12.3s, noise 0.8%".
+    pub fn describe(&self, text: &Bundle) -> String {
+        let mut line = match self.kind {
+            ProgressiveKind::LongExposure => format!("{}: ", text.long_exposure),
+            ProgressiveKind::Photo { tile, tiles } if tiles > 1 => format!("{}: {} {}/{}, ", text.photo, text.tile, tile, tiles),
+            ProgressiveKind::Photo { .. } => format!("{}: ", text.photo),
+        };
+        line += &format!("{}/{} {}, {:.1}s", self.samples, self.target, text.samples, self.elapsed);
+        if let Some(noise) = self.noise {
+            line += &format!(", {} {:.1}%", text.noise, noise * 100.0);
+            if noise < CONVERGED_NOISE {
+                line += &format!(" ({})", text.converged);
+            }
+        }
+        line
+    }
+}
+
+/// Estimates how far an accumulated image is from converging, from the accumulation image's
+/// sums: RGB of the samples and, in alpha, their squared luminance. Each pixel's variance gives
+/// the standard error of its mean luminance; the result is the RMS of those errors relative
+/// to the mean luminance of the image. Needs at least two samples.
+pub fn estimate_noise(sums: &[f32], samples: u32) -> Option<f32> {
+    if samples < 2 {
+        return None;
+    }
+    let n = samples as f64;
+    let (mut mean_sum, mut error_sum, mut pixels) = (0.0f64, 0.0f64, 0usize);
+    for pixel in sums.chunks_exact(4) {
+        let mean = (0.2126 * pixel[0] + 0.7152 * pixel[1] + 0.0722 * pixel[2]) as f64 / n;
+        let variance = (pixel[3] as f64 / n - mean * mean).max(0.0) * n / (n - 1.0);
+        mean_sum += mean;
+        error_sum += variance / n;
+        pixels += 1;
+    }
+    if pixels == 0 || mean_sum <= 0.0 {
+        return None;
+    }
+    Some(((error_sum / pixels as f64).sqrt() / (mean_sum / pixels as f64)) as f32)
+}
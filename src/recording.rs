@@ -0,0 +1,209 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Instant;
+use crate::image_io;
+use crate::args;
+
+/// Frames read back but not yet written out as PNGs before the render loop waits for the writer.
+const PNG_QUEUE: usize = 8;
+
+/// Where recorded frames go.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RecordingSink {
+    Png, // Numbered PNGs
+    Ffmpeg, // Raw frames piped to an ffmpeg child process encoding an H.264 MP4
+}
+
+/// Recording settings, from `--record-every <n>`, `--record-ffmpeg` and `--record-fps <fps>`.
+#[derive(Clone, Copy, Debug)]
+pub struct RecordingOptions {
+    pub every: u32, // Keep every Nth rendered frame
+    pub sink: RecordingSink,
+    pub fps: u32, // Frame rate of the ffmpeg video
+}
+
+impl RecordingOptions {
+    pub fn from_args() -> Self {
+        Self {
+            every: args::number::<u32>("--record-every").unwrap_or(1).max(1),
+            sink: if args::flag("--record-ffmpeg") { RecordingSink::Ffmpeg } else { RecordingSink::Png },
+            fps: args::number::<u32>("--record-fps").unwrap_or(30).max(1),
+        }
+    }
+}
+
+/// A recording in progress: every `every`th frame shown on screen is read back and saved into a
+/// `recording_<timestamp>` directory (with a `_<n>` suffix if that exists already), together with `timing.csv` listing for each saved frame
+/// the rendered frame it came from, the wall clock and scene time and the time since the
+/// previous saved frame, so a fly-through can be retimed when it is turned into a video.
+pub struct Recording {
+    options: RecordingOptions,
+    dir: PathBuf,
+    width: u32,
+    height: u32,
+    frames_seen: u32,
+    frames_saved: u32,
+    started: Instant,
+    last_saved: f32, // Wall clock seconds
+    timing: std::io::BufWriter<std::fs::File>,
+    ffmpeg: Option<Child>,
+    png: Option<PngFrameWriter>,
+}
+
+impl Recording {
+    pub fn start(options: RecordingOptions, width: u32, height: u32) -> Result<Self, Box<dyn std::error::Error>> {
+        let stamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+        let dir = create_unique_dir(&format!("recording_{}", stamp))?;
+        let mut timing = std::io::BufWriter::new(std::fs::File::create(dir.join("timing.csv"))?);
+        writeln!(timing, "frame,rendered_frame,wall_seconds,scene_seconds,frame_seconds")?;
+
+        let ffmpeg = match options.sink {
+            RecordingSink::Png => None,
+            RecordingSink::Ffmpeg => Some(Command::new("ffmpeg")
+                .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pixel_format", "bgra"])
+                .args(["-video_size", &format!("{}x{}", width, height), "-framerate", &options.fps.to_string(), "-i", "-"])
+                // H.264 in 4:2:0 needs even dimensions
+                .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", "-c:v", "libx264", "-pix_fmt", "yuv420p"])
+                .arg(dir.join("video.mp4"))
+                .stdin(Stdio::piped())
+                .spawn()
+                .map_err(|e| format!("failed to start ffmpeg (is it installed and on the PATH?): {}", e))?),
+        };
+        let png = (options.sink == RecordingSink::Png).then(|| PngFrameWriter::start(width, height));
+
+        log::info!("Recording every {} frame(s) to {}{}", options.every, dir.display(), if ffmpeg.is_some() { "/video.mp4" } else { "" });
+        Ok(Self {
+            options,
+            dir,
+            width,
+            height,
+            frames_seen: 0,
+            frames_saved: 0,
+            started: Instant::now(),
+            last_saved: 0.0,
+            timing,
+            ffmpeg,
+            png,
+        })
+    }
+
+    /// Width and height of the frames.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Counts a rendered frame and returns whether it is one to save.
+    pub fn wants_frame(&mut self) -> bool {
+        self.frames_seen += 1;
+        (self.frames_seen - 1).is_multiple_of(self.options.every)
+    }
+
+    /// Saves a frame read back from the screen (BGRA8, at the recording size).
+    pub fn add_frame(&mut self, bgra: Vec<u8>, rendered_frame: u32, scene_time: f32) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(child) = &mut self.ffmpeg {
+            child.stdin.as_mut().ok_or("ffmpeg stdin closed")?.write_all(&bgra)?;
+        }
+        if let Some(png) = &mut self.png {
+            png.write(self.dir.join(format!("frame_{:05}.png", self.frames_saved)), bgra)?;
+        }
+
+        let wall = self.started.elapsed().as_secs_f32();
+        writeln!(self.timing, "{},{},{:.4},{:.4},{:.4}", self.frames_saved, rendered_frame, wall, scene_time, wall - self.last_saved)?;
+        self.last_saved = wall;
+        self.frames_saved += 1;
+        Ok(())
+    }
+
+    /// Closes the timing file and waits for ffmpeg to finish encoding, or the last PNGs to be
+    /// written.
+    pub fn finish(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.timing.flush()?;
+        if let Some(png) = self.png.take() {
+            png.finish()?;
+        }
+        if let Some(mut child) = self.ffmpeg.take() {
+            drop(child.stdin.take()); // End of input
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(format!("ffmpeg exited with {}", status).into());
+            }
+        }
+        log::info!("Recording stopped: {} frames in {:.1}s saved to {}", self.frames_saved, self.started.elapsed().as_secs_f32(), self.dir.display());
+        Ok(())
+    }
+}
+
+/// Creates the directory `name`, or `name_2`, `name_3` and so on if it exists, so recordings
+/// started within the same second don't share one.
+fn create_unique_dir(name: &str) -> std::io::Result<PathBuf> {
+    let mut attempt = 1;
+    loop {
+        let dir = PathBuf::from(if attempt == 1 { name.to_string() } else { format!("{}_{}", name, attempt) });
+        match std::fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Encodes and writes recorded frames as PNGs on a thread of its own, so the render loop only
+/// waits for it when it falls `PNG_QUEUE` frames behind.
+struct PngFrameWriter {
+    frames: Option<mpsc::SyncSender<(PathBuf, Vec<u8>)>>, // BGRA8 frames and where they go
+    writer: Option<JoinHandle<std::io::Result<()>>>,
+}
+
+impl PngFrameWriter {
+    fn start(width: u32, height: u32) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<(PathBuf, Vec<u8>)>(PNG_QUEUE);
+        let writer = std::thread::spawn(move || {
+            for (path, bgra) in receiver {
+                write_frame(&path, width, height, &bgra)?;
+            }
+            Ok(())
+        });
+        Self { frames: Some(sender), writer: Some(writer) }
+    }
+
+    fn write(&mut self, path: PathBuf, bgra: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+        let sent = self.frames.as_ref().is_some_and(|sender| sender.send((path, bgra)).is_ok());
+        if !sent {
+            // The writer gave up; its error says why
+            self.frames = None;
+            self.join()?;
+            return Err("the PNG writer has stopped".into());
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.frames = None;
+        self.join()
+    }
+
+    /// Waits for the writer thread, which stops once the sender is dropped and the frames sent
+    /// before are written.
+    fn join(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.writer.take().map(JoinHandle::join) {
+            Some(Ok(result)) => Ok(result?),
+            Some(Err(_)) => Err("the PNG writer panicked".into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for PngFrameWriter {
+    fn drop(&mut self) {
+        self.frames = None;
+        let _ = self.join();
+    }
+}
+
+fn write_frame(path: &Path, width: u32, height: u32, bgra: &[u8]) -> std::io::Result<()> {
+    let rgba: Vec<u8> = bgra.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], 255]).collect();
+    image_io::write_png(path, width, height, &rgba)
+}
@@ -1,28 +1,412 @@
 use ash::vk;
 use crate::vulkan::VulkanContext;
-use crate::scene::{Scene, Vertex, Material};
-use crate::camera::Camera;
+use crate::scene::{Mesh, RayMask, Scene, Vertex, Material};
+use crate::animation::SkinVertex;
+use crate::texture::TextureData;
+use crate::image_io;
+use crate::aov::{self, Aovs};
+use crate::camera::{Camera, Projection, LENS_PROFILES};
+use crate::editor::{Editor, GizmoMode};
+use crate::material_preview::MaterialPreview;
+use crate::material_file::MaterialWatcher;
+use crate::weather::{Weather, WeatherKind};
+use crate::particles::Particles;
+use crate::grass::Wind;
+use crate::volume::Volume;
+use crate::scene_file;
+use crate::autosave::{self, Autosave, Session};
+use crate::accessibility::{self, Accessibility};
+use crate::teaching::{self, Teaching};
+use crate::photo::{PhotoCapture, PhotoProjection, PHOTO_SAMPLES, PHOTO_SCALES};
+use crate::recording::{Recording, RecordingOptions};
+use crate::rng;
+use crate::light::Light;
+use crate::exposure::{self, Exposure};
+use crate::post::{PostEffectParams, PostEffects};
+use crate::display::{self, DisplayOutput};
+use crate::color;
+use crate::upscale::{self, DynamicResolution, Upscaler};
+use crate::hybrid::{self, RasterDraw};
+use crate::preview;
+use crate::streaming::{self, Streaming};
+use crate::lod::{self, LodSelection};
+use crate::collision::{self, Navigation};
+use crate::split_screen::{self, SplitScreen, ViewState};
+use crate::memory::MemoryCategory;
+use crate::metrics::{FrameMetrics, GpuTimes, MetricsLog};
+use crate::stats::RenderStats;
+use crate::hud::{self, GlyphInstance, Hud};
+use crate::bookmarks::{self, Bookmarks};
+use crate::progress::{self, Progress, ProgressiveKind, NOISE_ESTIMATE_INTERVAL};
 use winit::window::Window;
 use winit::keyboard::KeyCode;
 use winit::event::ElementState;
+use std::collections::HashMap;
 use std::mem::size_of;
-use glam::{Mat4, Vec4};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use glam::{Mat4, UVec4, Vec2, Vec3, Vec4};
+use rayon::prelude::*;
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 struct CameraProperties {
     view_inverse: Mat4,
     proj_inverse: Mat4,
-    light_pos: Vec4,
+    light_pos: Vec4, // xyz: position, w: radius
+    light_color: Vec4, // rgb: color times intensity
+    photon_params: Vec4, // x: caustics, y: cell size, z: grid cells, w: caustic targets
+    output_params: Vec4, // x: light path channel, y: anaglyph, z: eye separation, w: convergence distance
+    sky_params: Vec4, // x: sky occlusion, y: cell size, z: cache cells, w: max samples per cell
+    lens_params: Vec4, // x: aperture radius (0: pinhole), y: focus distance, z: focus probe slot
+    distortion_params: Vec4, // x: k1, y: k2 (radial), z: p1, w: p2 (tangential)
+    gizmo_params: Vec4, // xyz: editor gizmo origin, w: size (0: hidden)
+    gizmo_state: Vec4, // x: mode (0: translate, 1: rotate, 2: scale), y: active axis, z: UI scale, w: high contrast
+    weather_params: Vec4, // x: overcast, y: wetness, z: snow cover
+    prev_view_proj: Mat4, // Previous frame's view-projection, for motion vectors
+    teaching_params: Vec4, // xy: probe pixel, z: teaching mode on, w: probe slot
+    tile_params: Vec4, // xy: size of the frame traced in tiles (the render size when untiled), zw: origin of the tile, pixels
+    viewport_params: Vec4, // x: first storage image column of this view (split screen), pixels
+    projection_params: Vec4, // x: projection (0: perspective, 1: equirectangular, 2: ODS top/bottom, 3: orthographic, 4: fisheye), y: its parameter
+    time_params: Vec4, // x: scene time, seconds, y: animation frame (frames the scene time advanced on)
+    studio_params: Vec4, // x: material preview on (the miss shader draws the studio)
+}
+
+/// Camera data that changes every frame, pushed with the frame's commands instead of uploaded
+/// to the camera UBO, so a frame still in flight never sees the next one's values.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct FramePushConstants {
     settings: Vec4, // x: soft_shadows, y: reflections, z: refraction, w: sss
+    exposure_params: Vec4, // x: accumulating (jitters the samples), y: frames accumulated before this one, z: camera rays per pixel, w: exposure
+    seed_params: UVec4, // x: frame seed, y: run seed (--seed), z: frame number in the random sequence
+    jitter_params: Vec4, // xy: offset of a lone camera ray from the pixel center (temporal upscaling), pixels; z: hybrid rendering
+    counter_params: UVec4, // xy: address of this frame's ray counter, 0 if rays aren't counted; z: candidate hits the traversal heatmap shows red, 0 without it
+}
+
+/// GPU layout of a `ProjectorLight`.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Projector {
+    view_proj: Mat4,
+    position: Vec4, // w: gobo texture index
+    color: Vec4,
+}
+
+/// GPU layout of a `HeatHazeVolume`.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct HeatVolume {
+    min: Vec4, // w: strength
+    max: Vec4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostPushConstants {
+    time: f32,
+    haze_amplitude: f32, // 0: no heat haze
+    blur_scale: f32, // Fraction of the motion vector blurred over, 0: no motion blur
+    max_blur: f32, // Pixels
+    size: [u32; 2], // Render extent
+    grain_seed: u32, // Of this frame's film grain
+    _pad: u32,
+    effects: PostEffectParams,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ExposurePushConstants {
+    size: [u32; 2], // Render extent
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    slot: u32, // Frame in flight
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomPushConstants {
+    source_size: [u32; 2],
+    target_size: [u32; 2], // Of the mip written, or the render extent when compositing
+    pass: u32, // 0: prefilter, 1: downsample, 2: upsample, 3: composite
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct UpscalePushConstants {
+    jitter: Vec2, // Of this frame's camera rays, render pixels
+    render_size: [u32; 2], // Render extent
+    output_size: [u32; 2], // Output extent, within the upscale image
+    from_post: u32, // 1: upscale the post image instead of the storage image
+    temporal: u32, // 0: bilinear, 1: temporal
+    reset_history: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GbufferPushConstants {
+    view_proj: Mat4, // Shifted by this frame's jitter, so pixels are rasterized where their camera rays go
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PreviewPushConstants {
+    view_proj: Mat4,
+    light_pos: Vec4, // xyz: position
+    camera_pos: Vec4, // xyz: position
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct HudPushConstants {
+    glyphs_address: u64, // This frame's slot of the glyph buffer
+    font_address: u64,
+    screen_size: Vec2, // Swapchain extent
+    glyph_size: Vec2, // Glyph cell, pixels
+    background_alpha: f32, // Of the cells behind the text
+    transfer: u32, // DisplayOutput::transfer
+    paper_white: f32, // Nits, for HDR output
+    _pad: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ResolvePushConstants {
+    source_size: [u32; 2], // Part of the source image holding the picture
+    source: u32, // 0: HDR image, 1: storage image, 2: post image, 3: upscale image
+    transfer: u32, // DisplayOutput::transfer
+    paper_white: f32, // Nits
+    sharpness: f32, // 0 (off) to 1
+}
+
+// Photon mapping (caustics)
+const PHOTON_COUNT: u32 = 1 << 18;
+const PHOTON_GRID_CELLS: u32 = 1 << 20;
+const PHOTON_CELL_SIZE: f32 = 0.05;
+
+// Sky visibility cache (environment occlusion)
+const SKY_CACHE_CELLS: u32 = 1 << 20;
+const SKY_CACHE_CELL_SIZE: f32 = 0.25;
+const SKY_CACHE_MAX_SAMPLES: u32 = 256;
+
+// Long exposure: scene time covered and number of sub-frames accumulated into one image
+const LONG_EXPOSURE_DURATION: f32 = 4.0;
+const LONG_EXPOSURE_FRAMES: u32 = 240;
+
+const ANIMATION_STEP: f32 = 1.0 / 60.0; // Scene time a single step advances while paused
+#[cfg(feature = "denoise")]
+const PHOTO_REVIEW_SECONDS: f32 = 5.0; // How long a denoised long exposure or photo stays on screen
+
+/// Ray unprojected from the cursor, filled in with what it hit by the pick shader.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PickQuery {
+    origin: Vec4,
+    direction: Vec4,
+    position: Vec4, // w: hit distance, negative on miss
+    instance_index: i32,
+    primitive_index: i32,
+    _pad: [i32; 2],
+}
+
+/// Surface under the cursor at the last click.
+#[derive(Clone, Copy)]
+pub struct Selection {
+    pub object_index: usize,
+    pub primitive_index: u32,
+    pub position: Vec3,
+}
+
+/// Progress of a long exposure capture. Scene time advances by a fixed step per
+/// frame so the result doesn't depend on the frame rate.
+struct LongExposure {
+    frames_done: u32,
+    time_step: f32,
+    started: Instant,
+}
+
+/// Camera rays per pixel F7 steps through. Long exposures and photos trace one per frame,
+/// since they average many frames anyway.
+pub const SAMPLES_PER_PIXEL: [u32; 4] = [1, 2, 4, 8];
+
+// Depth of field with autofocus on the center pixel's hit distance
+const DOF_APERTURE_RADIUS: f32 = 0.08;
+const AUTOFOCUS_SPEED: f32 = 4.0; // 1/s, exponential approach to the probed distance
+const AUTOFOCUS_MISS_DISTANCE: f32 = 100.0; // Focus distance when the probe ray hits the sky
+
+// Editor gizmo length (meters)
+const GIZMO_SIZE: f32 = 1.0;
+
+// Post pass (heat haze and motion blur)
+const HEAT_HAZE_AMPLITUDE: f32 = 3.0; // Pixels of displacement where the haze mask is 1
+const MOTION_BLUR_SHUTTER: f32 = 0.5; // Fraction of the frame interval the shutter is open (180 degree shutter)
+const MOTION_BLUR_MAX_PIXELS: f32 = 48.0;
+const PHOTO_SHUTTER: f32 = MOTION_BLUR_SHUTTER / 24.0; // Seconds a motion blurred photo's shutter is open, as a film camera's at 24 FPS
+
+// Bloom
+const BLOOM_MIPS: u32 = 6; // Of the mip chain, the first at half the render size
+const BLOOM_THRESHOLD: f32 = 1.0; // Brightness after exposure above which pixels glow: the display's white
+const BLOOM_KNEE: f32 = 0.5;
+const BLOOM_INTENSITY: f32 = 0.05; // Of the glow summed over all mips
+
+// Red/cyan anaglyph stereo (meters)
+const STEREO_EYE_SEPARATION: f32 = 0.065;
+const STEREO_CONVERGENCE_DISTANCE: f32 = 6.0;
+
+// GPU timestamps per frame in flight: frame start, scene passes done, post passes done, frame end
+const TIMESTAMPS_PER_FRAME: u32 = 4;
+
+// Memory budget. Per render pixel: storage, post (4 each), accumulation (16), haze mask, AOV
+// depth, instance ID, G-buffer depth, luminance (4 each), motion, AOV normal, albedo,
+// visibility, HDR (8 each) and the bloom mip chain (8 over a quarter of the pixels and a third
+// more for the smaller mips, rounded up to 3). Per window pixel: upscale (4) and the two upscale
+// histories (8 each).
+const RENDER_TARGET_BYTES_PER_PIXEL: u64 = 87;
+const WINDOW_TARGET_BYTES_PER_PIXEL: u64 = 20;
+const RENDER_TARGET_BUDGET_SHARE: f64 = 0.8; // Of the budget left after the scene, the rest is for the pipelines and per-frame buffers
+
+// Raster preview background, the miss shader's sky color overhead (linear)
+const PREVIEW_SKY_COLOR: [f32; 4] = [0.5, 0.7, 1.0, 1.0];
+
+/// Candidate hits per camera ray the traversal heatmap shows in red, the top of its scale.
+const TRAVERSAL_HEATMAP_MAX: u32 = 32;
+
+/// Light path channels that can be output instead of the full image (index = `output_params.x`).
+/// The teaching mode also uses 6 (albedo) and 7 (shadow term).
+const LIGHT_PATH_CHANNELS: [&str; 6] = [
+    "Beauty",
+    "Direct Diffuse",
+    "Indirect Diffuse",
+    "Indirect Specular",
+    "Transmission",
+    "Caustics",
+];
+
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 struct SceneDesc {
     vertex_addr: u64,
     index_addr: u64,
-    material_addr: u64,
+    index_size: u32, // Bytes per index, 2 or 4 (see `index_type`)
+    _pad: u32,
+}
+
+// InstanceData flags
+const INSTANCE_COLOR_OVERRIDE: u32 = 1; // `color` replaces the material color instead of tinting it
+
+/// Per TLAS instance data, indexed by gl_InstanceID in the hit shaders.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct InstanceData {
+    material_index: u32,
+    mesh_index: u32, // Entry in the per-mesh SceneDesc buffer
+    flags: u32,
+    _pad: u32,
+    color: [f32; 4], // Tint, or the replacement color with INSTANCE_COLOR_OVERRIDE
+}
+
+/// GPU side of a streamed mesh while it's loaded: its own geometry buffers and BLAS.
+struct StreamedMeshGpu {
+    vertices: (vk::Buffer, vk::DeviceMemory, u64),
+    indices: (vk::Buffer, vk::DeviceMemory, u64),
+    blas: Blas,
+}
+
+/// Streaming resources a frame in flight may still use: unloaded meshes and the scratch
+/// buffers of the BLAS builds it records.
+#[derive(Default)]
+struct StreamGarbage {
+    meshes: Vec<StreamedMeshGpu>,
+    scratch: Vec<(vk::Buffer, vk::DeviceMemory)>,
+}
+
+/// Host-visible storage (or uniform) buffer with one slot per frame in flight, bound with a dynamic offset so
+/// the host never rewrites data a frame in flight still reads. Slots that missed a change are
+/// stale until they're refreshed when their frame comes round.
+struct FrameSlotBuffer {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    stride: u64, // Slot size, aligned for dynamic offsets
+    stale: Vec<bool>, // Per slot
+}
+
+impl FrameSlotBuffer {
+    /// A storage buffer of `frames` slots, all holding `data`.
+    fn new<T: Copy>(ctx: &VulkanContext, data: &[T], frames: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_usage(ctx, data, frames, vk::BufferUsageFlags::STORAGE_BUFFER, MemoryCategory::Geometry)
+    }
+
+    /// A uniform buffer of `frames` slots, all holding `data`.
+    fn new_uniform<T: Copy>(ctx: &VulkanContext, data: &[T], frames: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_usage(ctx, data, frames, vk::BufferUsageFlags::UNIFORM_BUFFER, MemoryCategory::Buffers)
+    }
+
+    fn with_usage<T: Copy>(ctx: &VulkanContext, data: &[T], frames: usize, usage: vk::BufferUsageFlags, category: MemoryCategory) -> Result<Self, Box<dyn std::error::Error>> {
+        let limits = unsafe { ctx.instance.get_physical_device_properties(ctx.physical_device) }.limits;
+        let alignment = if usage.contains(vk::BufferUsageFlags::UNIFORM_BUFFER) {
+            limits.min_uniform_buffer_offset_alignment
+        } else {
+            limits.min_storage_buffer_offset_alignment
+        };
+        let stride = ((data.len() * size_of::<T>()) as u64).next_multiple_of(alignment.max(1));
+        let (buffer, memory, _) = create_buffer_with_addr(ctx,
+            frames as u64 * stride,
+            usage,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            category
+        )?;
+        for slot in 0..frames {
+            upload_data_at(ctx, memory, slot as u64 * stride, data);
+        }
+        Ok(Self { buffer, memory, stride, stale: vec![false; frames] })
+    }
+
+    /// Dynamic offset of `slot`.
+    fn offset(&self, slot: usize) -> u32 {
+        (slot as u64 * self.stride) as u32
+    }
+
+    /// Writes changed `data` to `slot`, leaving the other slots stale.
+    fn write<T: Copy>(&mut self, ctx: &VulkanContext, slot: usize, data: &[T]) {
+        self.refresh(ctx, slot, data);
+        self.stale.fill(true);
+        self.stale[slot] = false;
+    }
+
+    /// Brings a stale `slot` up to date with `data`.
+    fn refresh<T: Copy>(&mut self, ctx: &VulkanContext, slot: usize, data: &[T]) {
+        upload_data_at(ctx, self.memory, slot as u64 * self.stride, data);
+        self.stale[slot] = false;
+    }
+}
+
+/// GPU side of a skinned mesh. The skinning pass writes posed vertices into `posed_vertices`,
+/// which the mesh's BLAS and SceneDesc entries point at instead of the rest pose.
+struct SkinnedMeshGpu {
+    skin_index: usize,
+    mesh_index: usize,
+    vertex_count: u32,
+    primitive_count: u32,
+    rest_vertex_addr: u64,
+    index_addr: u64,
+    skin_vertices: (vk::Buffer, vk::DeviceMemory, u64),
+    posed_vertices: (vk::Buffer, vk::DeviceMemory, u64),
+    joint_buffers: Vec<(vk::Buffer, vk::DeviceMemory, u64)>, // One per frame in flight
+    blas_scratch: (vk::Buffer, vk::DeviceMemory, u64),
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkinningPushConstants {
+    rest_vertices: u64,
+    skin_vertices: u64,
+    joint_matrices: u64,
+    posed_vertices: u64,
+    vertex_count: u32,
+    _pad: u32,
 }
 
 #[allow(dead_code)]
@@ -34,13 +418,108 @@ pub struct Renderer {
     // Resources
     vertex_buffer: (vk::Buffer, vk::DeviceMemory),
     index_buffer: (vk::Buffer, vk::DeviceMemory),
-    material_buffer: (vk::Buffer, vk::DeviceMemory),
+    material_buffer: FrameSlotBuffer, // One slot per frame in flight, the weather changes materials every frame
     scene_desc_buffer: (vk::Buffer, vk::DeviceMemory),
-    uniform_buffer: (vk::Buffer, vk::DeviceMemory),
+    instance_data_buffer: FrameSlotBuffer, // One slot per frame in flight, rewritten with TLAS rebuilds
+    transform_buffer: FrameSlotBuffer, // This frame's TLAS instance transforms, for the G-buffer pass and raygen, one slot per frame in flight
+    prev_transform_buffer: FrameSlotBuffer, // Previous frame's TLAS instance transforms, one slot per frame in flight
+    last_instance_transforms: Vec<vk::TransformMatrixKHR>, // Uploaded to the previous transform buffer next frame
+    uniform_buffer: FrameSlotBuffer, // The views' camera UBOs, one slot per frame in flight
+    camera_ubo_stride: u64, // Offset between the views' camera UBOs in a slot of the uniform buffer
+    uploaded_camera: Vec<CameraProperties>, // Last contents of the uniform buffer per view, it's only written on changes
+    photon_grid_buffer: (vk::Buffer, vk::DeviceMemory),
+    caustic_target_buffer: (vk::Buffer, vk::DeviceMemory),
+    caustic_target_count: u32,
+    sky_cache_buffer: (vk::Buffer, vk::DeviceMemory),
+    volume_buffer: (vk::Buffer, vk::DeviceMemory),
+    focus_probe_buffer: (vk::Buffer, vk::DeviceMemory), // Center pixel hit distance, one slot per frame in flight
+    histogram_buffer: (vk::Buffer, vk::DeviceMemory), // Luminance histogram of the traced image, one slot per frame in flight
+    teaching_probe_buffer: (vk::Buffer, vk::DeviceMemory), // Shading terms of the teaching mode's probe pixel, one slot per frame in flight
+    projector_buffer: (vk::Buffer, vk::DeviceMemory),
+    heat_volume_buffer: (vk::Buffer, vk::DeviceMemory),
+    pick_buffer: (vk::Buffer, vk::DeviceMemory),
+    ray_counter_buffer: (vk::Buffer, vk::DeviceMemory), // Rays traced, one slot per frame in flight
+    ray_counter_address: u64,
+    rays_counted: u64, // Since the HUD last took the count
+    textures: Vec<(vk::Image, vk::DeviceMemory, vk::ImageView)>,
+    texture_sampler: vk::Sampler,
     
     // AS
     blas_list: Vec<(vk::AccelerationStructureKHR, vk::DeviceMemory, vk::Buffer)>,
     tlas: (vk::AccelerationStructureKHR, vk::DeviceMemory, vk::Buffer),
+    blas_addresses: Vec<u64>,
+    lod: LodSelection, // Mesh of every TLAS instance, by camera distance
+    navigation: Navigation, // Camera collision with the scene and walk mode
+
+    // Geometry streaming (`--streaming`)
+    streaming: Option<Streaming>,
+    streamed_meshes: Vec<Option<StreamedMeshGpu>>, // Per mesh, while a streamed one is loaded
+    stream_garbage: Vec<StreamGarbage>, // One per frame in flight, freed when its frame slot comes around again
+    scene_descs: Vec<SceneDesc>, // Contents of the scene desc buffer, which changes as streamed meshes come and go
+    tlas_instance_buffers: Vec<(vk::Buffer, vk::DeviceMemory, u64)>, // One per frame in flight
+    tlas_scratch: (vk::Buffer, vk::DeviceMemory, u64),
+    
+    // Skinning
+    skinned_meshes: Vec<SkinnedMeshGpu>,
+    skinning_pipeline: vk::Pipeline,
+    skinning_pipeline_layout: vk::PipelineLayout,
+    
+    // Post pass (heat haze, motion blur and the artistic post effects)
+    post_pipeline: vk::Pipeline,
+    post_pipeline_layout: vk::PipelineLayout,
+    post_descriptor_pool: vk::DescriptorPool,
+    post_descriptor_set: vk::DescriptorSet,
+    post_descriptor_set_layout: vk::DescriptorSetLayout,
+
+    // Exposure pass (auto exposure's luminance histogram)
+    exposure_pipeline: vk::Pipeline,
+    exposure_pipeline_layout: vk::PipelineLayout,
+    exposure_descriptor_pool: vk::DescriptorPool,
+    exposure_descriptor_set: vk::DescriptorSet,
+    exposure_descriptor_set_layout: vk::DescriptorSetLayout,
+
+    // Bloom (threshold, downsample, blur and composite over the HDR image)
+    bloom_pipeline: vk::Pipeline,
+    bloom_pipeline_layout: vk::PipelineLayout,
+    bloom_descriptor_pool: vk::DescriptorPool,
+    bloom_descriptor_sets: Vec<vk::DescriptorSet>, // Prefilter, downsamples, upsamples from the smallest mip, composite
+    bloom_descriptor_set_layout: vk::DescriptorSetLayout,
+
+    // Upscale pass (render scale below 1)
+    upscale_pipeline: vk::Pipeline,
+    upscale_pipeline_layout: vk::PipelineLayout,
+    upscale_descriptor_pool: vk::DescriptorPool,
+    upscale_descriptor_sets: [vk::DescriptorSet; 2], // Set i reads history image i and writes the other one
+    upscale_descriptor_set_layout: vk::DescriptorSetLayout,
+
+    // G-buffer pass (hybrid rendering)
+    gbuffer_pipeline: vk::Pipeline,
+    gbuffer_pipeline_layout: vk::PipelineLayout,
+    gbuffer_descriptor_pool: vk::DescriptorPool,
+    gbuffer_descriptor_set: vk::DescriptorSet,
+    gbuffer_descriptor_set_layout: vk::DescriptorSetLayout,
+    gbuffer_render_pass: vk::RenderPass,
+    gbuffer_framebuffer: vk::Framebuffer,
+    // Raster preview, drawn into the storage image instead of tracing on GPUs without ray tracing
+    preview_pipeline: vk::Pipeline,
+    preview_pipeline_layout: vk::PipelineLayout,
+    preview_render_pass: vk::RenderPass,
+    preview_framebuffer: vk::Framebuffer,
+    // Resolve pass, drawing the shown image onto the swapchain image, and the HUD text over it
+    resolve_pipeline: vk::Pipeline,
+    resolve_pipeline_layout: vk::PipelineLayout,
+    resolve_render_pass: vk::RenderPass, // Null with dynamic rendering (Vulkan 1.3)
+    resolve_framebuffers: Vec<vk::Framebuffer>, // One per swapchain image, none with dynamic rendering
+    resolve_descriptor_pool: vk::DescriptorPool,
+    resolve_descriptor_set: vk::DescriptorSet,
+    resolve_descriptor_set_layout: vk::DescriptorSetLayout,
+    display_output: DisplayOutput,
+    paper_white: f32, // Nits SDR white is shown at in HDR output
+    sharpness: f32, // Resolve pass sharpening, 0 (off) to 1
+    hud_pipeline: vk::Pipeline,
+    hud_pipeline_layout: vk::PipelineLayout,
+    hud_font_buffer: (vk::Buffer, vk::DeviceMemory, u64),
+    hud_glyph_buffer: (vk::Buffer, vk::DeviceMemory, u64), // MAX_GLYPHS instances per frame in flight
     
     // Pipeline
     pipeline: vk::Pipeline,
@@ -52,9 +531,36 @@ pub struct Renderer {
     // SBT
     sbt_buffer: (vk::Buffer, vk::DeviceMemory),
     sbt_regions: [vk::StridedDeviceAddressRegionKHR; 4],
+    photon_sbt_region: vk::StridedDeviceAddressRegionKHR,
+    pick_sbt_region: vk::StridedDeviceAddressRegionKHR,
     
     // Image
     storage_image: (vk::Image, vk::DeviceMemory, vk::ImageView),
+    accumulation_image: (vk::Image, vk::DeviceMemory, vk::ImageView),
+    haze_mask_image: (vk::Image, vk::DeviceMemory, vk::ImageView), // Per pixel haze amount, written by raygen
+    motion_image: (vk::Image, vk::DeviceMemory, vk::ImageView), // Per pixel screen motion since the previous frame, written by raygen
+    luminance_image: (vk::Image, vk::DeviceMemory, vk::ImageView), // Per pixel luminance before exposure, written by raygen
+    hdr_image: (vk::Image, vk::DeviceMemory, vk::ImageView), // Displayed color before it is clamped, written by raygen
+    bloom_image: (vk::Image, vk::DeviceMemory, Vec<vk::ImageView>), // Bloom mip chain from half the render size, one view per mip
+    // Auxiliary outputs (AOVs) of the last camera ray per pixel, for denoising, TAA and compositing
+    aov_depth_image: (vk::Image, vk::DeviceMemory, vk::ImageView),
+    aov_normal_image: (vk::Image, vk::DeviceMemory, vk::ImageView),
+    aov_albedo_image: (vk::Image, vk::DeviceMemory, vk::ImageView),
+    aov_instance_image: (vk::Image, vk::DeviceMemory, vk::ImageView),
+    post_image: (vk::Image, vk::DeviceMemory, vk::ImageView), // Post pass output, shown instead of the storage image
+    upscale_image: (vk::Image, vk::DeviceMemory, vk::ImageView), // Upscale pass output, swapchain size
+    upscale_history_images: [(vk::Image, vk::DeviceMemory, vk::ImageView); 2], // Temporal upscaler history, swapchain size, ping-ponged
+    visibility_image: (vk::Image, vk::DeviceMemory, vk::ImageView), // G-buffer pass output: TLAS instance + 1 and triangle per pixel
+    gbuffer_depth_image: (vk::Image, vk::DeviceMemory, vk::ImageView),
+    displayed_image: vk::Image, // Storage, post or upscale image, whichever the last frame showed
+    render_extent: vk::Extent2D, // Size everything is traced at, with the window's aspect ratio
+    output_extent: vk::Extent2D, // Size the upscale pass writes, the render extent scaled up toward the window size
+    max_render_extent: vk::Extent2D, // Size the render targets were created at
+    max_output_extent: vk::Extent2D, // Size the upscale image and history were created at, the window size at startup
+    render_scale: f32, // Share of the window size traced, unless dynamic resolution varies it
+    swapchain_extent: vk::Extent2D, // Window size, larger than the render extent with a render scale below 1
+    surface_format: vk::SurfaceFormatKHR,
+    swapchain_stale: bool, // Present found the swapchain out of date or suboptimal, it is recreated before the next frame
     
     // Swapchain & Sync
     swapchain: vk::SwapchainKHR,
@@ -63,23 +569,125 @@ pub struct Renderer {
     image_available_semaphores: Vec<vk::Semaphore>,
     render_finished_semaphores: Vec<vk::Semaphore>,
     in_flight_fences: Vec<vk::Fence>,
+    timestamp_pool: vk::QueryPool, // TIMESTAMPS_PER_FRAME per frame in flight, for dynamic resolution and the metrics
+    timestamp_period: f32, // Nanoseconds per timestamp tick
+    timestamps_written: [bool; 2], // Per frame in flight
+    metrics: Option<MetricsLog>, // With `--metrics <file>`
+    pending_metrics: [Option<FrameMetrics>; 2], // Per frame in flight, submitted and waiting for its GPU times
+    frame_started: Option<Instant>, // When the last frame started rendering, for the frame time
     
     // State
     pub camera: Camera,
+    pub bookmarks: Bookmarks,
+    ctrl_held: bool, // Control key, which makes the numpad digits store bookmarks
     pub settings: Vec4,
+    pub caustics: bool,
+    pub sky_occlusion: bool,
+    sky_cache_dirty: bool, // Cache must be cleared before the next trace
+    pub light_path_channel: usize,
+    traversal_heatmap: bool, // Ctrl+L: candidate hits per camera ray instead of the image
+    pub anaglyph: bool,
+    pub depth_of_field: bool,
+    pub heat_haze: bool,
+    pub motion_blur: bool,
+    pub bloom: bool,
+    pub post_effects: PostEffects,
+    pub samples_per_pixel: u32, // Jittered camera rays averaged per pixel and frame, one of SAMPLES_PER_PIXEL
+    upscaler: Upscaler, // With a render scale below 1
+    pub hybrid: bool, // Rasterize what the camera rays would hit, see `hybrid::hybrid_from_args`
+    split_screen: Option<SplitScreen>, // Second view beside the controlled one
+    show_memory: bool, // GPU memory totals on the HUD
+    upscale_history: usize, // History image the temporal upscaler reads next
+    upscale_history_valid: bool, // The history holds the last frame (temporal upscaling ran then)
+    dynamic_resolution: Option<DynamicResolution>, // Varies the render extent up to the size the images were created at
+    pub accessibility: Accessibility,
+    pub teaching: Teaching,
+    pub hud: Hud,
+    prev_view_proj: Option<Mat4>, // Camera of the previous frame, none until the first frame
+    pub editor: Editor,
+    material_preview: MaterialPreview,
+    material_watcher: MaterialWatcher,
+    pub weather: Weather,
+    particles: Particles,
+    wind: Wind,
+    pub animation_paused: bool, // Scene time stands still (except during a long exposure)
+    pub autosave: Autosave,
+    default_session: Option<Session>, // Settings before the saved session was restored, for Home
+    focus_distance: f32,
+    focus_point: Option<(usize, Vec3)>, // Clicked object and point on it (object space) that autofocus tracks instead of the screen center
+    pub selection: Option<Selection>,
+    cursor_position: Vec2, // Physical pixels
+    pick_request: Option<Vec2>, // Cursor position of a click not traced yet
+    pick_in_flight: Option<usize>, // Frame slot whose commands trace the pick ray
     pub current_frame: usize,
+    frame_index: u32,
+    scene_time: f32,
+    animation_frame: u32, // Frames the scene time advanced on, stands still while paused
+    animation_step: bool, // Step the paused scene by one frame
+    last_frame_time: Instant,
+    long_exposure: Option<LongExposure>,
+    photo: Option<PhotoCapture>,
+    pub photo_scale: u32, // Size of the next F9 photo, in multiples of the window size
+    pub photo_projection: PhotoProjection, // Of the next F9 photo
+    recording: Option<Recording>,
+    pub recording_options: RecordingOptions,
+    seed: u32, // Run seed of the shaders' random numbers and the weather
+    pub light: Light,
+    light_controls: bool, // Keys adjust the light (see `Light::handle_input`)
+    exposure: Exposure,
+    noise_estimate: Option<f32>, // Of the image being accumulated, see `progress::estimate_noise`
+    last_noise_estimate: Instant,
+    photo_review_until: Option<Instant>, // A saved photo is shown instead of the live render until then
+    #[cfg(feature = "denoise")]
+    pub denoise: bool, // Denoise long exposures with OIDN
+    #[cfg(feature = "physics")]
+    physics: crate::physics::PhysicsWorld,
     
     scene: Scene,
 }
 
 impl Renderer {
     pub fn new(window: &Window) -> Result<Self, Box<dyn std::error::Error>> {
-        let ctx = VulkanContext::new(window)?;
+        let mut ctx = VulkanContext::new(window)?;
 
-        log::info!("Creating scene...");
-        let scene = Scene::new();
+        log::info!("Creating scene and compiling shaders...");
+        // The shaders compile on worker threads while the scene is built
+        let ray_tracing = ctx.ray_tracing;
+        let defines = shader_defines(&ctx);
+        let (mut scene, shaders) = rayon::join(Scene::new, || compile_shaders(&shader_sources(ray_tracing), &defines));
+        let mut shaders = shaders?;
+        if std::path::Path::new(scene_file::SCENE_FILE).exists() {
+            match scene_file::load(&mut scene, scene_file::SCENE_FILE) {
+                Ok(()) => log::info!("Loaded scene edits from {}", scene_file::SCENE_FILE),
+                Err(e) => log::warn!("Ignoring {}: {}", scene_file::SCENE_FILE, e),
+            }
+        }
+        if autosave::has_unsaved_edits() {
+            match scene_file::load(&mut scene, autosave::AUTOSAVE_FILE) {
+                Ok(()) => log::info!("Recovered unsaved edits from {}", autosave::AUTOSAVE_FILE),
+                Err(e) => log::warn!("Ignoring {}: {}", autosave::AUTOSAVE_FILE, e),
+            }
+        }
+        // A scene too big for the device's acceleration structures gets the raster preview
+        // instead of failing to build them
+        let max_triangles = scene.meshes.iter().map(|mesh| mesh.indices.len() / 3).max().unwrap_or(0);
+        if ctx.ray_tracing {
+            if let Some(reason) = ctx.capabilities.scene_exceeds_limits(scene.instance_count(), max_triangles) {
+                log::warn!("The scene exceeds the GPU's acceleration structure limits ({}), falling back to a flat shaded raster preview", reason);
+                ctx.ray_tracing = false;
+                shaders = compile_shaders(&shader_sources(false), &defines)?;
+            }
+        }
+        let material_watcher = MaterialWatcher::new(&mut scene);
         let camera = Camera::new();
         let settings = Vec4::new(1.0, 1.0, 1.0, 1.0);
+        // The raster preview draws every mesh every frame, so it can't stream them
+        let mut streaming = streaming::streaming_from_args().map(|distance| Streaming::new(&scene, distance));
+        if streaming.is_some() && !ctx.ray_tracing {
+            log::warn!("--streaming needs ray tracing pipelines, uploading every mesh");
+            streaming = None;
+        }
+        let streamed = |mesh_index: usize| streaming.as_ref().is_some_and(|s| s.is_streamed(mesh_index));
 
         log::info!("Creating command pool...");
         let command_pool_info = vk::CommandPoolCreateInfo {
@@ -101,111 +709,369 @@ impl Renderer {
 
         log::info!("Creating scene buffers...");
         // 1. Create Buffers (Scene)
+        // Geometry and instances are also acceleration structure build inputs, except in the raster preview
+        let as_input_usage = if ctx.ray_tracing { vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR } else { vk::BufferUsageFlags::empty() };
+        // Streamed meshes get their own buffers when they're loaded
+        let resident_meshes = || scene.meshes.iter().enumerate().filter(|&(i, _)| !streamed(i)).map(|(_, m)| m);
         let (vertex_buffer, vertex_mem, vertex_addr) = create_buffer_with_addr(&ctx, 
-            (scene.meshes.iter().map(|m| m.vertices.len()).sum::<usize>() * size_of::<Vertex>()) as u64,
-            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
+            (resident_meshes().map(|m| m.vertices.len()).sum::<usize>() * size_of::<Vertex>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS | as_input_usage,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            MemoryCategory::Geometry
         )?;
         
         let (index_buffer, index_mem, index_addr) = create_buffer_with_addr(&ctx,
-            (scene.meshes.iter().map(|m| m.indices.len()).sum::<usize>() * size_of::<u32>()) as u64,
-             vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
-             vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
+            resident_meshes().map(gpu_indices_size).sum::<usize>() as u64,
+             vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS | as_input_usage,
+             vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+             MemoryCategory::Geometry
         )?;
 
-        let (material_buffer, material_mem, material_addr) = create_buffer_with_addr(&ctx,
-            (scene.materials.len() * size_of::<Material>()) as u64,
-            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
-        )?;
+        let material_buffer = FrameSlotBuffer::new(&ctx, &gpu_materials(&scene.materials), max_frames)?;
+
+        let resident: Vec<&Mesh> = resident_meshes().collect();
+        upload_data(&ctx, vertex_mem, &resident.par_iter().flat_map_iter(|m| m.vertices.iter().copied()).collect::<Vec<_>>());
+        upload_data(&ctx, index_mem, &resident.par_iter().flat_map_iter(|m| gpu_indices(m)).collect::<Vec<_>>());
+        let small_meshes = resident.iter().filter(|m| index_size(m) == 2).count();
+        let index_bytes_32 = resident.iter().map(|m| m.indices.len() * size_of::<u32>()).sum::<usize>();
+        log::info!("Index buffer: {} KiB ({} KiB with 32-bit indices only, {} of {} meshes use 16-bit indices)",
+            resident_meshes().map(gpu_indices_size).sum::<usize>() / 1024, index_bytes_32 / 1024, small_meshes, resident.len());
+
+        // Vertex and index addresses of every uploaded mesh
+        let mut mesh_addresses = Vec::new();
+        let (mut v_off, mut i_off) = (0, 0);
+        for (mesh_index, mesh) in scene.meshes.iter().enumerate() {
+            if streamed(mesh_index) {
+                mesh_addresses.push(None);
+                continue;
+            }
+            mesh_addresses.push(Some((vertex_addr + (v_off * size_of::<Vertex>()) as u64, index_addr + i_off as u64)));
+            v_off += mesh.vertices.len();
+            i_off += gpu_indices_size(mesh);
+        }
+
+        // Skinned meshes get their own posed vertex buffer (starting in the rest pose) and skin weights
+        let mut skinned_meshes = Vec::new();
+        for (skin_index, skin) in scene.skins.iter().enumerate() {
+            let (rest_vertex_addr, mesh_index_addr) = mesh_addresses[skin.mesh_index].expect("skinned meshes aren't streamed");
+            let mesh = &scene.meshes[skin.mesh_index];
+
+            let (posed_buf, posed_mem, posed_addr) = create_buffer_with_addr(&ctx,
+                (mesh.vertices.len() * size_of::<Vertex>()) as u64,
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS | as_input_usage,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                MemoryCategory::Geometry
+            )?;
+            upload_data(&ctx, posed_mem, &mesh.vertices);
 
-        upload_data(&ctx, vertex_mem, &scene.meshes.iter().flat_map(|m| m.vertices.clone()).collect::<Vec<_>>());
-        upload_data(&ctx, index_mem, &scene.meshes.iter().flat_map(|m| m.indices.clone()).collect::<Vec<_>>());
-        upload_data(&ctx, material_mem, &scene.materials);
+            let (skin_buf, skin_mem, skin_addr) = create_buffer_with_addr(&ctx,
+                (skin.vertices.len() * size_of::<SkinVertex>()) as u64,
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                MemoryCategory::Geometry
+            )?;
+            upload_data(&ctx, skin_mem, &skin.vertices);
+
+            let mut joint_buffers = Vec::new();
+            for _ in 0..max_frames {
+                joint_buffers.push(create_buffer_with_addr(&ctx,
+                    (skin.skeleton.joints.len() * size_of::<Mat4>()) as u64,
+                    vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                    MemoryCategory::Geometry
+                )?);
+            }
+
+            skinned_meshes.push(SkinnedMeshGpu {
+                skin_index,
+                mesh_index: skin.mesh_index,
+                vertex_count: mesh.vertices.len() as u32,
+                primitive_count: (mesh.indices.len() / 3) as u32,
+                rest_vertex_addr,
+                index_addr: mesh_index_addr,
+                skin_vertices: (skin_buf, skin_mem, skin_addr),
+                posed_vertices: (posed_buf, posed_mem, posed_addr),
+                joint_buffers,
+                blas_scratch: (vk::Buffer::null(), vk::DeviceMemory::null(), 0), // Filled in by the BLAS build
+            });
+        }
 
         let (scene_desc_buffer, scene_desc_mem, _) = create_buffer_with_addr(&ctx,
-            (scene.objects.len() * size_of::<SceneDesc>()) as u64,
+            (scene.meshes.len() * size_of::<SceneDesc>()) as u64,
             vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            MemoryCategory::Geometry
         )?;
         
+        // One entry per mesh; instances point at theirs through their InstanceData. Streamed
+        // meshes get their addresses when they're loaded.
         let mut scene_descs = Vec::new();
-        for obj in &scene.objects {
-            // Find correct offset for this object's mesh
-            let mut v_off = 0;
-            let mut i_off = 0;
-             for (idx, mesh) in scene.meshes.iter().enumerate() {
-                 if idx == obj.mesh_index {
-                     break;
-                 }
-                 v_off += mesh.vertices.len();
-                 i_off += mesh.indices.len();
-            }
+        for (mesh_index, addresses) in mesh_addresses.iter().enumerate() {
+            let skinned = skinned_meshes.iter().find(|s: &&SkinnedMeshGpu| s.mesh_index == mesh_index);
+            let (mesh_vertex_addr, mesh_index_addr) = addresses.unwrap_or((0, 0));
             scene_descs.push(SceneDesc {
-                vertex_addr: vertex_addr + (v_off * size_of::<Vertex>()) as u64,
-                index_addr: index_addr + (i_off * size_of::<u32>()) as u64,
-                material_addr,
+                vertex_addr: skinned.map_or(mesh_vertex_addr, |s| s.posed_vertices.2),
+                index_addr: mesh_index_addr,
+                index_size: index_size(&scene.meshes[mesh_index]) as u32,
+                _pad: 0,
             });
         }
         upload_data(&ctx, scene_desc_mem, &scene_descs);
 
-        log::info!("Building Bottom-Level Acceleration Structures (BLAS) for {} meshes...", scene.meshes.len());
-        // 2. BLAS
-        let mut blas_list = Vec::new();
-        let mut cur_v = 0;
-        let mut cur_i = 0;
+        // Per-instance data in TLAS instance order, refreshed whenever the TLAS is rebuilt
+        let lod = LodSelection::new(&scene, lod::lod_bias_from_args());
+        let instance_data = instance_data(&scene, lod.meshes());
+        log::info!("Scene has {} TLAS instances ({} objects, {} batch instances)",
+            scene.instance_count(), scene.objects.len(), scene.instance_count() - scene.objects.len());
+        let instance_data_buffer = FrameSlotBuffer::new(&ctx, &instance_data, max_frames)?;
+
+        // Photon map: hash grid of (r, g, b, count) cells plus the bounding spheres the photons are aimed at
+        let (photon_grid_buffer, photon_grid_mem, _) = create_buffer_with_addr(&ctx,
+            (PHOTON_GRID_CELLS as usize * 4 * size_of::<u32>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryCategory::Buffers
+        )?;
+
+        let caustic_targets = scene.caustic_targets();
+        log::info!("Found {} caustic caster(s) for the photon pass", caustic_targets.len());
+        let (caustic_target_buffer, caustic_target_mem, _) = create_buffer_with_addr(&ctx,
+            (caustic_targets.len().max(1) * size_of::<[f32; 4]>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            MemoryCategory::Buffers
+        )?;
+        upload_data(&ctx, caustic_target_mem, &caustic_targets);
+
+        // Participating medium: header and dense voxels, a header marking it absent without --volume
+        let volume_data = Volume::gpu_data(scene.volume.as_ref());
+        let (volume_buffer, volume_mem, _) = create_buffer_with_addr(&ctx,
+            (volume_data.len() * size_of::<u32>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            MemoryCategory::Buffers
+        )?;
+        upload_data(&ctx, volume_mem, &volume_data);
+
+        // Sky visibility cache: hash grid of (visible, total) sky sample counts per surface patch
+        let (sky_cache_buffer, sky_cache_mem, _) = create_buffer_with_addr(&ctx,
+            (SKY_CACHE_CELLS as usize * 2 * size_of::<u32>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryCategory::Buffers
+        )?;
+
+        // Autofocus probe: raygen writes the center pixel's hit distance into the slot of the
+        // frame being rendered, read back once that frame's fence has signaled
+        let (focus_probe_buffer, focus_probe_mem, _) = create_buffer_with_addr(&ctx,
+            (max_frames * size_of::<f32>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            MemoryCategory::Buffers
+        )?;
+        upload_data(&ctx, focus_probe_mem, &vec![-1.0f32; max_frames]);
+
+        // Auto exposure: the exposure pass adds the traced image's luminance histogram into the
+        // slot of the frame being rendered, read back and cleared once that frame's fence has signaled
+        let (histogram_buffer, histogram_mem, _) = create_buffer_with_addr(&ctx,
+            (max_frames * exposure::HISTOGRAM_BINS * size_of::<u32>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            MemoryCategory::Buffers
+        )?;
+        upload_data(&ctx, histogram_mem, &vec![0u32; max_frames * exposure::HISTOGRAM_BINS]);
+
+        // Teaching probe: same scheme, the closest hit shader writes every shading term of the probe pixel
+        let (teaching_probe_buffer, teaching_probe_mem, _) = create_buffer_with_addr(&ctx,
+            (max_frames * teaching::PROBE_TERMS * size_of::<Vec4>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            MemoryCategory::Buffers
+        )?;
+        upload_data(&ctx, teaching_probe_mem, &vec![Vec4::ZERO; max_frames * teaching::PROBE_TERMS]);
+
+        // Projector lights; a zero intensity entry stands in when there are none
+        let mut projectors: Vec<Projector> = scene.projectors.iter().map(|light| Projector {
+            view_proj: light.view_proj(),
+            position: light.position.extend(light.texture_index as f32),
+            color: light.color.extend(0.0),
+        }).collect();
+        if projectors.is_empty() {
+            projectors.push(bytemuck::Zeroable::zeroed());
+        }
+        let (projector_buffer, projector_mem, _) = create_buffer_with_addr(&ctx,
+            (projectors.len() * size_of::<Projector>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            MemoryCategory::Buffers
+        )?;
+        upload_data(&ctx, projector_mem, &projectors);
+
+        // Heat haze volumes; a zero strength entry stands in when there are none
+        let mut heat_volumes: Vec<HeatVolume> = scene.heat_volumes.iter().map(|volume| HeatVolume {
+            min: volume.min.extend(volume.strength),
+            max: volume.max.extend(0.0),
+        }).collect();
+        if heat_volumes.is_empty() {
+            heat_volumes.push(bytemuck::Zeroable::zeroed());
+        }
+        let (heat_volume_buffer, heat_volume_mem, _) = create_buffer_with_addr(&ctx,
+            (heat_volumes.len() * size_of::<HeatVolume>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            MemoryCategory::Buffers
+        )?;
+        upload_data(&ctx, heat_volume_mem, &heat_volumes);
+
+        // Picking: one query at a time, written before the frame that traces it and read back after its fence
+        let (pick_buffer, pick_mem, _) = create_buffer_with_addr(&ctx,
+            size_of::<PickQuery>() as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            MemoryCategory::Buffers
+        )?;
+
+        // Ray counter: the shaders count every ray they trace into the slot of the frame being
+        // rendered (ray_count.glsl), read back and cleared once that frame's fence has signaled
+        let (ray_counter_buffer, ray_counter_mem, ray_counter_address) = create_buffer_with_addr(&ctx,
+            (max_frames * size_of::<u32>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            MemoryCategory::Buffers
+        )?;
+        upload_data(&ctx, ray_counter_mem, &vec![0u32; max_frames]);
+
+        log::info!("Uploading {} textures...", scene.textures.len());
+        let mut textures = Vec::new();
+        for texture in &scene.textures {
+            if texture.is_block_compressed() && !ctx.texture_compression_bc {
+                // Keep the slot so material texture indices stay valid
+                log::warn!("GPU does not support BC texture compression, replacing a {:?} texture with a flat normal map", texture.format);
+                textures.push(create_texture(&ctx, command_pool, command_buffers[0], &TextureData::flat_normal_map())?);
+                continue;
+            }
+            textures.push(create_texture(&ctx, command_pool, command_buffers[0], texture)?);
+        }
+        if textures.is_empty() {
+            // The texture array binding needs at least one valid descriptor
+            textures.push(create_texture(&ctx, command_pool, command_buffers[0], &TextureData::flat_normal_map())?);
+        }
+
+        let sampler_info = vk::SamplerCreateInfo {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode_u: vk::SamplerAddressMode::REPEAT,
+            address_mode_v: vk::SamplerAddressMode::REPEAT,
+            address_mode_w: vk::SamplerAddressMode::REPEAT,
+            max_lod: vk::LOD_CLAMP_NONE,
+            ..Default::default()
+        };
+        let texture_sampler = unsafe { ctx.device.create_sampler(&sampler_info, None)? };
+
         let setup_cmd_buffer = command_buffers[0]; // Use first for setup
+        // 2. BLAS. The raster preview has none, its TLAS instances reference null BLASes
+        let (blas_list, blas_addresses) = if ctx.ray_tracing {
+            log::info!("Building Bottom-Level Acceleration Structures (BLAS) for {} meshes...", scene.meshes.len());
+            let mut blas_list = Vec::new();
+            // All builds go into one submission, each with its own scratch buffer so they need
+            // no barriers between them. Scratch buffers of static meshes are freed after it.
+            let mut build_scratch = Vec::new();
+            begin_single_time_command(&ctx, command_pool, setup_cmd_buffer);
         
-        for mesh in &scene.meshes {
-            let max_vertex = mesh.vertices.len() as u32;
-            let primitive_count = (mesh.indices.len() / 3) as u32;
+            for (mesh_index, mesh) in scene.meshes.iter().enumerate() {
+                // Streamed meshes get their BLAS when they're loaded
+                let Some((mesh_vertex_addr, mesh_index_addr)) = mesh_addresses[mesh_index] else {
+                    blas_list.push((vk::AccelerationStructureKHR::null(), vk::DeviceMemory::null(), vk::Buffer::null()));
+                    continue;
+                };
+                // Skinned meshes are built from their posed vertices and refit every frame
+                let mut skinned = skinned_meshes.iter_mut().find(|s| s.mesh_index == mesh_index);
+                let (mesh_vertex_addr, flags) = match &skinned {
+                    Some(s) => (s.posed_vertices.2, vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_BUILD | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE),
+                    None => (mesh_vertex_addr, vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE),
+                };
+                let (blas, (scratch_buf, scratch_mem, scratch_addr)) = create_blas(&ctx, setup_cmd_buffer, mesh, mesh_vertex_addr, mesh_index_addr, flags, &format!("the BLAS of mesh {}", mesh_index))?;
+
+                match skinned.as_mut() {
+                    Some(s) => s.blas_scratch = (scratch_buf, scratch_mem, scratch_addr),
+                    None => build_scratch.push((scratch_buf, scratch_mem)),
+                }
+                blas_list.push(blas);
+            }
+            end_single_time_command(&ctx, command_pool, setup_cmd_buffer, ctx.queue);
+            for (scratch_buf, scratch_mem) in build_scratch {
+                unsafe {
+                    ctx.device.destroy_buffer(scratch_buf, None);
+                    ctx.device.free_memory(scratch_mem, None);
+                }
+                ctx.memory.forget(scratch_mem);
+            }
+
+            let blas_addresses: Vec<u64> = blas_list.iter().map(|blas| blas_address(&ctx, blas.0)).collect();
+            (blas_list, blas_addresses)
+        } else {
+            (Vec::new(), vec![0; scene.meshes.len()])
+        };
+
+        // 3. TLAS
+        // Built with ALLOW_UPDATE so animated objects only need a refit each frame
+        let instances = tlas_instances(&scene, lod.meshes(), &blas_addresses);
+
+        let mut tlas_instance_buffers = Vec::new();
+        for _ in 0..max_frames {
+            let (inst_buf, inst_mem, inst_addr) = create_buffer_with_addr(&ctx, (instances.len() * size_of::<vk::AccelerationStructureInstanceKHR>()) as u64, as_input_usage | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT, MemoryCategory::Geometry)?;
+            upload_data(&ctx, inst_mem, &instances);
+            tlas_instance_buffers.push((inst_buf, inst_mem, inst_addr));
+        }
 
-            let triangles = vk::AccelerationStructureGeometryTrianglesDataKHR {
-                vertex_format: vk::Format::R32G32B32_SFLOAT,
-                vertex_data: vk::DeviceOrHostAddressConstKHR { device_address: vertex_addr + (cur_v * size_of::<Vertex>()) as u64 },
-                vertex_stride: size_of::<Vertex>() as u64,
-                max_vertex,
-                index_type: vk::IndexType::UINT32,
-                index_data: vk::DeviceOrHostAddressConstKHR { device_address: index_addr + (cur_i * size_of::<u32>()) as u64 },
+        // Instance transforms of the previous frame, for the motion vectors of moving objects
+        let instance_transforms: Vec<vk::TransformMatrixKHR> = instances.iter().map(|instance| instance.transform).collect();
+        let prev_transform_buffer = FrameSlotBuffer::new(&ctx, &instance_transforms, max_frames)?;
+        // and of this frame, which the G-buffer pass draws with
+        let transform_buffer = FrameSlotBuffer::new(&ctx, &instance_transforms, max_frames)?;
+
+        let (tlas_res, tlas_scratch) = if ctx.ray_tracing {
+            log::info!("Building Top-Level Acceleration Structure (TLAS)...");
+            let instances_data = vk::AccelerationStructureGeometryInstancesDataKHR {
+                data: vk::DeviceOrHostAddressConstKHR { device_address: tlas_instance_buffers[0].2 },
                 ..Default::default()
             };
 
             let geometry = vk::AccelerationStructureGeometryKHR {
-                geometry_type: vk::GeometryTypeKHR::TRIANGLES,
-                geometry: vk::AccelerationStructureGeometryDataKHR { triangles },
-                flags: vk::GeometryFlagsKHR::OPAQUE,
+                geometry_type: vk::GeometryTypeKHR::INSTANCES,
+                geometry: vk::AccelerationStructureGeometryDataKHR { instances: instances_data },
                 ..Default::default()
             };
-
-            let geometries = [geometry];
-            
+        
             let build_info = vk::AccelerationStructureBuildGeometryInfoKHR {
-                ty: vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
-                flags: vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE,
+                ty: vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+                flags: vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE,
                 mode: vk::BuildAccelerationStructureModeKHR::BUILD,
                 geometry_count: 1,
-                p_geometries: geometries.as_ptr(),
+                p_geometries: &geometry,
                 ..Default::default()
             };
-
+        
+            let primitive_count = instances.len() as u32;
             let mut size_info = vk::AccelerationStructureBuildSizesInfoKHR::default();
             unsafe { ctx.as_loader.get_acceleration_structure_build_sizes(vk::AccelerationStructureBuildTypeKHR::DEVICE, &build_info, &[primitive_count], &mut size_info) };
+            // Scratch memory is kept for the per-frame updates
+            let scratch_size = size_info.build_scratch_size.max(size_info.update_scratch_size);
+            ensure_budget(&ctx, size_info.acceleration_structure_size + scratch_size, "the TLAS")?;
 
-            let (as_buffer, as_mem, _) = create_buffer_with_addr(&ctx, size_info.acceleration_structure_size, vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::DEVICE_LOCAL)?;
-            
-            let create_info = vk::AccelerationStructureCreateInfoKHR {
-                buffer: as_buffer,
+            let (tlas_buf, tlas_mem, _) = create_buffer_with_addr(&ctx, size_info.acceleration_structure_size, vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::AccelerationStructures)?;
+            let tlas_create_info = vk::AccelerationStructureCreateInfoKHR {
+                buffer: tlas_buf,
                 size: size_info.acceleration_structure_size,
-                ty: vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+                ty: vk::AccelerationStructureTypeKHR::TOP_LEVEL,
                 ..Default::default()
             };
-            
-            let accel_struct = unsafe { ctx.as_loader.create_acceleration_structure(&create_info, None)? };
-            let (scratch_buf, scratch_mem, scratch_addr) = create_buffer_with_addr(&ctx, size_info.build_scratch_size, vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::DEVICE_LOCAL)?;
-
+            let tlas = unsafe { ctx.as_loader.create_acceleration_structure(&tlas_create_info, None)? };
+            let (scratch_buf, scratch_mem, scratch_addr) = create_buffer_with_addr(&ctx, scratch_size, vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::AccelerationStructures)?;
             let mut build_info = build_info;
             build_info.scratch_data = vk::DeviceOrHostAddressKHR { device_address: scratch_addr };
-            build_info.dst_acceleration_structure = accel_struct;
+            build_info.dst_acceleration_structure = tlas;
 
             let build_range = vk::AccelerationStructureBuildRangeInfoKHR {
                 primitive_count,
@@ -213,127 +1079,24 @@ impl Renderer {
                 first_vertex: 0,
                 transform_offset: 0,
             };
-            
+        
             begin_single_time_command(&ctx, command_pool, setup_cmd_buffer);
             unsafe { ctx.as_loader.cmd_build_acceleration_structures(setup_cmd_buffer, &[build_info], &[&[build_range]]) };
             end_single_time_command(&ctx, command_pool, setup_cmd_buffer, ctx.queue);
-
-            unsafe { ctx.device.destroy_buffer(scratch_buf, None); ctx.device.free_memory(scratch_mem, None); }
-            blas_list.push((accel_struct, as_mem, as_buffer));
-            
-            cur_v += mesh.vertices.len();
-            cur_i += mesh.indices.len();
-        }
-
-        log::info!("Building Top-Level Acceleration Structure (TLAS)...");
-        // 3. TLAS
-        let mut instances = Vec::new();
-        for (_i, obj) in scene.objects.iter().enumerate() {
-             let transform = obj.transform.to_cols_array_2d();
-             let vk_transform = vk::TransformMatrixKHR {
-                 matrix: [
-                     transform[0][0], transform[1][0], transform[2][0], transform[3][0],
-                     transform[0][1], transform[1][1], transform[2][1], transform[3][1],
-                     transform[0][2], transform[1][2], transform[2][2], transform[3][2],
-                 ]
-             };
-             let instance = vk::AccelerationStructureInstanceKHR {
-                 transform: vk_transform,
-                 instance_custom_index_and_mask: vk::Packed24_8::new(obj.material_index as u32, 0xFF), 
-                 instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(0, vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE.as_raw() as u8),
-                 acceleration_structure_reference: vk::AccelerationStructureReferenceKHR { 
-                     device_handle: unsafe { ctx.as_loader.get_acceleration_structure_device_address(&vk::AccelerationStructureDeviceAddressInfoKHR { 
-                         acceleration_structure: blas_list[obj.mesh_index].0,
-                         ..Default::default()
-                     }) }
-                 },
-             };
-             instances.push(instance);
-        }
-
-        let (inst_buf, inst_mem, inst_addr) = create_buffer_with_addr(&ctx, (instances.len() * size_of::<vk::AccelerationStructureInstanceKHR>()) as u64, vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT)?;
-        upload_data(&ctx, inst_mem, &instances);
-
-        let instances_data = vk::AccelerationStructureGeometryInstancesDataKHR {
-            data: vk::DeviceOrHostAddressConstKHR { device_address: inst_addr },
-            ..Default::default()
-        };
-
-        let geometry = vk::AccelerationStructureGeometryKHR {
-            geometry_type: vk::GeometryTypeKHR::INSTANCES,
-            geometry: vk::AccelerationStructureGeometryDataKHR { instances: instances_data },
-            ..Default::default()
-        };
-        
-        let build_info = vk::AccelerationStructureBuildGeometryInfoKHR {
-            ty: vk::AccelerationStructureTypeKHR::TOP_LEVEL,
-            flags: vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE,
-            mode: vk::BuildAccelerationStructureModeKHR::BUILD,
-            geometry_count: 1,
-            p_geometries: &geometry,
-            ..Default::default()
-        };
         
-        let primitive_count = instances.len() as u32;
-        let mut size_info = vk::AccelerationStructureBuildSizesInfoKHR::default();
-        unsafe { ctx.as_loader.get_acceleration_structure_build_sizes(vk::AccelerationStructureBuildTypeKHR::DEVICE, &build_info, &[primitive_count], &mut size_info) };
-
-        let (tlas_buf, tlas_mem, _) = create_buffer_with_addr(&ctx, size_info.acceleration_structure_size, vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::DEVICE_LOCAL)?;
-        let tlas_create_info = vk::AccelerationStructureCreateInfoKHR {
-            buffer: tlas_buf,
-            size: size_info.acceleration_structure_size,
-            ty: vk::AccelerationStructureTypeKHR::TOP_LEVEL,
-            ..Default::default()
-        };
-        let tlas = unsafe { ctx.as_loader.create_acceleration_structure(&tlas_create_info, None)? };
-
-        let (scratch_buf, scratch_mem, scratch_addr) = create_buffer_with_addr(&ctx, size_info.build_scratch_size, vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::DEVICE_LOCAL)?;
-        let mut build_info = build_info;
-        build_info.scratch_data = vk::DeviceOrHostAddressKHR { device_address: scratch_addr };
-        build_info.dst_acceleration_structure = tlas;
-
-        let build_range = vk::AccelerationStructureBuildRangeInfoKHR {
-            primitive_count,
-            primitive_offset: 0,
-            first_vertex: 0,
-            transform_offset: 0,
+            ((tlas, tlas_mem, tlas_buf), (scratch_buf, scratch_mem, scratch_addr))
+        } else {
+            Default::default() // Null handles
         };
-        
-        begin_single_time_command(&ctx, command_pool, setup_cmd_buffer);
-        unsafe { ctx.as_loader.cmd_build_acceleration_structures(setup_cmd_buffer, &[build_info], &[&[build_range]]) };
-        end_single_time_command(&ctx, command_pool, setup_cmd_buffer, ctx.queue);
-        
-        unsafe { ctx.device.destroy_buffer(scratch_buf, None); ctx.device.free_memory(scratch_mem, None); ctx.device.destroy_buffer(inst_buf, None); ctx.device.free_memory(inst_mem, None); }
-        let tlas_res = (tlas, tlas_mem, tlas_buf);
 
         log::info!("Creating storage image and swapchain...");
         // 4. Images & Swapchain
         let capabilities = unsafe { ctx.surface_loader.get_physical_device_surface_capabilities(ctx.physical_device, ctx.surface)? };
+        // Display images: sRGB encoded by the shaders that write them (storage images can't be _SRGB), see color.rs
         let format = vk::Format::B8G8R8A8_UNORM;
 
-        // Handle special case where surface extent is u32::MAX (means we should use window size)
-        let extent = if capabilities.current_extent.width == u32::MAX {
-            // On platforms where current_extent is u32::MAX (some Linux/Wayland),
-            // we use window.inner_size() which already returns the correct size
-            let window_size = window.inner_size();
-
-            log::info!("Surface extent is undefined ({}), using window size: {}x{}",
-                u32::MAX, window_size.width, window_size.height);
-
-            vk::Extent2D {
-                width: window_size.width.clamp(
-                    capabilities.min_image_extent.width,
-                    capabilities.max_image_extent.width
-                ),
-                height: window_size.height.clamp(
-                    capabilities.min_image_extent.height,
-                    capabilities.max_image_extent.height
-                ),
-            }
-        } else {
-            log::info!("Surface extent: {}x{}", capabilities.current_extent.width, capabilities.current_extent.height);
-            capabilities.current_extent
-        };
+        let extent = surface_extent(&capabilities, window.inner_size());
+        log::info!("Surface extent: {}x{}", extent.width, extent.height);
 
         // Validate extent
         if extent.width == 0 || extent.height == 0 {
@@ -341,10 +1104,20 @@ impl Renderer {
                 extent.width, extent.height).into());
         }
 
+        // Everything below traces at the render scale, only the swapchain has the window size.
+        // The images keep the size they are created at here: in a window resized larger later
+        // the traced image only grows up to it (see fit_extents).
+        let swapchain_extent = extent;
+        let render_scale = fit_render_scale(&ctx, swapchain_extent, upscale::render_scale_from_args())?;
+        let extent = upscale::scaled_extent(swapchain_extent, render_scale);
+        if extent != swapchain_extent {
+            log::info!("Render scale: tracing {}x{}, upscaled to {}x{}", extent.width, extent.height, swapchain_extent.width, swapchain_extent.height);
+        }
+
         let storage_size_mb = (extent.width as u64 * extent.height as u64 * 4) / (1024 * 1024);
         log::info!("Creating storage image ({} MB)...", storage_size_mb);
 
-        let (storage_image, storage_mem) = create_image(&ctx, extent.width, extent.height, format, vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC)?;
+        let (storage_image, storage_mem) = create_image(&ctx, extent.width, extent.height, 1, format, vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::COLOR_ATTACHMENT)?; // The raster preview draws into it
         let storage_view_info = vk::ImageViewCreateInfo {
             image: storage_image,
             view_type: vk::ImageViewType::TYPE_2D,
@@ -377,236 +1150,1669 @@ impl Renderer {
         unsafe { ctx.device.cmd_pipeline_barrier(setup_cmd_buffer, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TOP_OF_PIPE, vk::DependencyFlags::empty(), &[], &[], &[barrier]) };
         end_single_time_command(&ctx, command_pool, setup_cmd_buffer, ctx.queue);
 
-        let swapchain_create_info = vk::SwapchainCreateInfoKHR {
-            surface: ctx.surface,
-            min_image_count: std::cmp::max(3, capabilities.min_image_count),
-            image_format: format,
-            image_color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
-            image_extent: extent,
-            image_array_layers: 1,
-            image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST,
-            pre_transform: vk::SurfaceTransformFlagsKHR::IDENTITY,
-            composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
-            present_mode: vk::PresentModeKHR::FIFO,
-            clipped: vk::TRUE,
-            ..Default::default()
-        };
-        let swapchain = unsafe { ctx.swapchain_loader.create_swapchain(&swapchain_create_info, None)? };
-        let swapchain_images = unsafe { ctx.swapchain_loader.get_swapchain_images(swapchain)? };
-        let swapchain_image_views: Vec<vk::ImageView> = swapchain_images.iter().map(|&img| {
-            unsafe { ctx.device.create_image_view(&vk::ImageViewCreateInfo {
-                image: img,
-                view_type: vk::ImageViewType::TYPE_2D,
-                format,
-                subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    base_mip_level: 0,
-                    level_count: 1,
-                    base_array_layer: 0,
-                    layer_count: 1,
-                },
-                ..Default::default()
-            }, None).unwrap() }
-        }).collect();
-
-        log::info!("Creating descriptors and ray tracing pipeline...");
-        // 5. Descriptors & Pipeline
-        let descriptor_pool_sizes = [
-            vk::DescriptorPoolSize { ty: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR, descriptor_count: 1 },
-            vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1 },
-            vk::DescriptorPoolSize { ty: vk::DescriptorType::UNIFORM_BUFFER, descriptor_count: 1 },
-            vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1 },
-        ];
-        let descriptor_pool_info = vk::DescriptorPoolCreateInfo {
-            max_sets: 1,
-            pool_size_count: descriptor_pool_sizes.len() as u32,
-            p_pool_sizes: descriptor_pool_sizes.as_ptr(),
+        // Accumulation image (long exposure), float so hundreds of frames can be summed
+        let accumulation_format = vk::Format::R32G32B32A32_SFLOAT;
+        let (accumulation_image, accumulation_mem) = create_image(&ctx, extent.width, extent.height, 1, accumulation_format, vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC)?;
+        let accumulation_view_info = vk::ImageViewCreateInfo {
+            image: accumulation_image,
+            view_type: vk::ImageViewType::TYPE_2D,
+            format: accumulation_format,
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
             ..Default::default()
         };
-        let descriptor_pool = unsafe { ctx.device.create_descriptor_pool(&descriptor_pool_info, None)? };
+        let accumulation_view = unsafe { ctx.device.create_image_view(&accumulation_view_info, None)? };
 
-        let dsl_bindings = [
-            vk::DescriptorSetLayoutBinding { binding: 0, descriptor_type: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
-            vk::DescriptorSetLayoutBinding { binding: 1, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
-            vk::DescriptorSetLayoutBinding { binding: 2, descriptor_type: vk::DescriptorType::UNIFORM_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
-            vk::DescriptorSetLayoutBinding { binding: 3, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
-        ];
-        let descriptor_set_layout_info = vk::DescriptorSetLayoutCreateInfo {
-            binding_count: dsl_bindings.len() as u32,
-            p_bindings: dsl_bindings.as_ptr(),
+        begin_single_time_command(&ctx, command_pool, setup_cmd_buffer);
+        let barrier = vk::ImageMemoryBarrier {
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::GENERAL,
+            image: accumulation_image,
+            subresource_range: accumulation_view_info.subresource_range,
             ..Default::default()
         };
-        let descriptor_set_layout = unsafe { ctx.device.create_descriptor_set_layout(&descriptor_set_layout_info, None)? };
+        unsafe { ctx.device.cmd_pipeline_barrier(setup_cmd_buffer, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TOP_OF_PIPE, vk::DependencyFlags::empty(), &[], &[], &[barrier]) };
+        end_single_time_command(&ctx, command_pool, setup_cmd_buffer, ctx.queue);
 
-        let alloc_info = vk::DescriptorSetAllocateInfo {
-            descriptor_pool,
-            descriptor_set_count: 1,
-            p_set_layouts: &descriptor_set_layout,
+        // Heat haze: raygen writes the haze mask, the post pass distorts the storage image into the post image
+        let haze_mask_image = create_storage_image(&ctx, command_pool, setup_cmd_buffer, extent, vk::Format::R32_SFLOAT, vk::ImageUsageFlags::STORAGE)?;
+        // Motion blur: raygen writes motion vectors, the same post pass blurs along them
+        let motion_image = create_storage_image(&ctx, command_pool, setup_cmd_buffer, extent, vk::Format::R16G16B16A16_SFLOAT, vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC)?;
+        // Auto exposure: raygen writes luminance before exposure, the exposure pass builds its histogram
+        let luminance_image = create_storage_image(&ctx, command_pool, setup_cmd_buffer, extent, vk::Format::R32_SFLOAT, vk::ImageUsageFlags::STORAGE)?;
+        // Bloom: raygen writes the color before it is clamped to the storage image, the bloom
+        // passes blur its bright parts through the mip chain and add them back
+        let hdr_image = create_storage_image(&ctx, command_pool, setup_cmd_buffer, extent, vk::Format::R16G16B16A16_SFLOAT, vk::ImageUsageFlags::STORAGE)?;
+        let bloom_extent = vk::Extent2D { width: (extent.width / 2).max(1), height: (extent.height / 2).max(1) };
+        let bloom_image = create_storage_mip_chain(&ctx, command_pool, setup_cmd_buffer, bloom_extent, BLOOM_MIPS, vk::Format::R16G16B16A16_SFLOAT)?;
+        // AOVs: raygen writes depth, closest hit (raygen on a miss) normal, albedo and instance ID
+        let aov_usage = vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC;
+        let aov_depth_image = create_storage_image(&ctx, command_pool, setup_cmd_buffer, extent, vk::Format::R32_SFLOAT, aov_usage)?;
+        let aov_normal_image = create_storage_image(&ctx, command_pool, setup_cmd_buffer, extent, vk::Format::R16G16B16A16_SFLOAT, aov_usage)?;
+        let aov_albedo_image = create_storage_image(&ctx, command_pool, setup_cmd_buffer, extent, vk::Format::R16G16B16A16_SFLOAT, aov_usage)?;
+        let aov_instance_image = create_storage_image(&ctx, command_pool, setup_cmd_buffer, extent, vk::Format::R32_UINT, aov_usage)?;
+        let post_image = create_storage_image(&ctx, command_pool, setup_cmd_buffer, extent, format, vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC)?;
+        let upscale_image = create_storage_image(&ctx, command_pool, setup_cmd_buffer, swapchain_extent, format, vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC)?;
+        let upscale_history_images = [
+            create_storage_image(&ctx, command_pool, setup_cmd_buffer, swapchain_extent, vk::Format::R16G16B16A16_SFLOAT, vk::ImageUsageFlags::STORAGE)?,
+            create_storage_image(&ctx, command_pool, setup_cmd_buffer, swapchain_extent, vk::Format::R16G16B16A16_SFLOAT, vk::ImageUsageFlags::STORAGE)?,
+        ];
+        // Hybrid rendering: the G-buffer pass rasterizes instance and triangle IDs, raygen reads them
+        let visibility_image = create_storage_image(&ctx, command_pool, setup_cmd_buffer, extent, vk::Format::R32G32_UINT, vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::COLOR_ATTACHMENT)?;
+        let depth_format = vk::Format::D32_SFLOAT;
+        let (gbuffer_depth, gbuffer_depth_mem) = create_image(&ctx, extent.width, extent.height, 1, depth_format, vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)?;
+        let gbuffer_depth_view = unsafe { ctx.device.create_image_view(&vk::ImageViewCreateInfo {
+            image: gbuffer_depth,
+            view_type: vk::ImageViewType::TYPE_2D,
+            format: depth_format,
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::DEPTH,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
             ..Default::default()
+        }, None)? };
+
+        // The swapchain is SDR unless --hdr asks for an HDR output the surface offers
+        let surface_formats = unsafe { ctx.surface_loader.get_physical_device_surface_formats(ctx.physical_device, ctx.surface)? };
+        let (display_output, surface_format) = display::choose_output(&surface_formats, ctx.swapchain_colorspace);
+        let paper_white = display::paper_white_from_args();
+        if display_output.is_hdr() {
+            log::info!("Presenting {} ({:?}), SDR white at {} nits", display_output.name(), surface_format.format, paper_white);
+        }
+
+        let (swapchain, swapchain_images, swapchain_image_views) = create_swapchain(&ctx, swapchain_extent, surface_format, vk::SwapchainKHR::null())?;
+
+        let entry_name = std::ffi::CStr::from_bytes_with_nul(b"main\0").unwrap();
+        // One camera UBO per split screen view in each frame's slot, picked by the dynamic offset the descriptor set is bound with
+        let ubo_alignment = unsafe { ctx.instance.get_physical_device_properties(ctx.physical_device) }.limits.min_uniform_buffer_offset_alignment;
+        let camera_ubo_stride = (size_of::<CameraProperties>() as u64).next_multiple_of(ubo_alignment.max(1));
+        let uniform_buffer = FrameSlotBuffer::new_uniform(&ctx, &vec![0u8; 2 * camera_ubo_stride as usize], max_frames)?;
+
+        let (descriptor_pool, descriptor_set_layout, descriptor_set, pipeline_layout, pipeline, group_count) = if ctx.ray_tracing {
+            log::info!("Creating descriptors and ray tracing pipeline...");
+            // 5. Descriptors & Pipeline
+            let descriptor_pool_sizes = [
+                vk::DescriptorPoolSize { ty: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR, descriptor_count: 1 },
+                vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 11 },
+                vk::DescriptorPoolSize { ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, descriptor_count: 1 },
+                vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 10 },
+                vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, descriptor_count: 4 },
+                vk::DescriptorPoolSize { ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER, descriptor_count: textures.len() as u32 },
+            ];
+            let descriptor_pool_info = vk::DescriptorPoolCreateInfo {
+                max_sets: 1,
+                pool_size_count: descriptor_pool_sizes.len() as u32,
+                p_pool_sizes: descriptor_pool_sizes.as_ptr(),
+                ..Default::default()
+            };
+            let descriptor_pool = unsafe { ctx.device.create_descriptor_pool(&descriptor_pool_info, None)? };
+
+            let dsl_bindings = [
+                vk::DescriptorSetLayoutBinding { binding: 0, descriptor_type: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 1, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 2, descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR | vk::ShaderStageFlags::MISS_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 3, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR | vk::ShaderStageFlags::ANY_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 4, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 5, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 6, descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER, descriptor_count: textures.len() as u32, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR | vk::ShaderStageFlags::ANY_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 7, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 8, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 9, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 10, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 11, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 12, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 13, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 14, descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR | vk::ShaderStageFlags::ANY_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 15, descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 16, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 17, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 18, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 19, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 20, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 21, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 22, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 23, descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 24, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR | vk::ShaderStageFlags::MISS_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 25, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 26, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR, ..Default::default() },
+                vk::DescriptorSetLayoutBinding { binding: 27, descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR | vk::ShaderStageFlags::ANY_HIT_KHR, ..Default::default() },
+            ];
+            let descriptor_set_layout_info = vk::DescriptorSetLayoutCreateInfo {
+                binding_count: dsl_bindings.len() as u32,
+                p_bindings: dsl_bindings.as_ptr(),
+                ..Default::default()
+            };
+            let descriptor_set_layout = unsafe { ctx.device.create_descriptor_set_layout(&descriptor_set_layout_info, None)? };
+
+            let alloc_info = vk::DescriptorSetAllocateInfo {
+                descriptor_pool,
+                descriptor_set_count: 1,
+                p_set_layouts: &descriptor_set_layout,
+                ..Default::default()
+            };
+            let descriptor_set = unsafe { ctx.device.allocate_descriptor_sets(&alloc_info)?[0] };
+
+            let texture_infos: Vec<vk::DescriptorImageInfo> = textures.iter().map(|&(_, _, view)| vk::DescriptorImageInfo {
+                sampler: texture_sampler,
+                image_view: view,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            }).collect();
+
+            let mut tlas_write = vk::WriteDescriptorSetAccelerationStructureKHR {
+                acceleration_structure_count: 1,
+                p_acceleration_structures: &tlas_res.0,
+                ..Default::default()
+            };
+            let descriptor_writes = [
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 0,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+                    p_next: &mut tlas_write as *mut _ as *mut _,
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 1,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &vk::DescriptorImageInfo {
+                        image_view: storage_view,
+                        image_layout: vk::ImageLayout::GENERAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 2,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: uniform_buffer.buffer,
+                        offset: 0,
+                        range: size_of::<CameraProperties>() as u64,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 3,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: scene_desc_buffer,
+                        offset: 0,
+                        range: vk::WHOLE_SIZE,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 4,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: photon_grid_buffer,
+                        offset: 0,
+                        range: vk::WHOLE_SIZE,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 5,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: caustic_target_buffer,
+                        offset: 0,
+                        range: vk::WHOLE_SIZE,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 6,
+                    descriptor_count: texture_infos.len() as u32,
+                    descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    p_image_info: texture_infos.as_ptr(),
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 7,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: sky_cache_buffer,
+                        offset: 0,
+                        range: vk::WHOLE_SIZE,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 8,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &vk::DescriptorImageInfo {
+                        image_view: accumulation_view,
+                        image_layout: vk::ImageLayout::GENERAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 9,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: focus_probe_buffer,
+                        offset: 0,
+                        range: vk::WHOLE_SIZE,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 10,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: projector_buffer,
+                        offset: 0,
+                        range: vk::WHOLE_SIZE,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 11,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: heat_volume_buffer,
+                        offset: 0,
+                        range: vk::WHOLE_SIZE,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 12,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &vk::DescriptorImageInfo {
+                        image_view: haze_mask_image.2,
+                        image_layout: vk::ImageLayout::GENERAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 13,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: pick_buffer,
+                        offset: 0,
+                        range: vk::WHOLE_SIZE,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 14,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: instance_data_buffer.buffer,
+                        offset: 0,
+                        range: instance_data_buffer.stride,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 15,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: prev_transform_buffer.buffer,
+                        offset: 0,
+                        range: prev_transform_buffer.stride,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 16,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &vk::DescriptorImageInfo {
+                        image_view: motion_image.2,
+                        image_layout: vk::ImageLayout::GENERAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 17,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &vk::DescriptorImageInfo {
+                        image_view: aov_depth_image.2,
+                        image_layout: vk::ImageLayout::GENERAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 18,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &vk::DescriptorImageInfo {
+                        image_view: aov_normal_image.2,
+                        image_layout: vk::ImageLayout::GENERAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 19,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &vk::DescriptorImageInfo {
+                        image_view: aov_albedo_image.2,
+                        image_layout: vk::ImageLayout::GENERAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 20,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &vk::DescriptorImageInfo {
+                        image_view: aov_instance_image.2,
+                        image_layout: vk::ImageLayout::GENERAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 21,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: teaching_probe_buffer,
+                        offset: 0,
+                        range: vk::WHOLE_SIZE,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 22,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &vk::DescriptorImageInfo {
+                        image_view: visibility_image.2,
+                        image_layout: vk::ImageLayout::GENERAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 23,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: transform_buffer.buffer,
+                        offset: 0,
+                        range: transform_buffer.stride,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 24,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: volume_buffer,
+                        offset: 0,
+                        range: vk::WHOLE_SIZE,
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 25,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &vk::DescriptorImageInfo {
+                        image_view: luminance_image.2,
+                        image_layout: vk::ImageLayout::GENERAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 26,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &vk::DescriptorImageInfo {
+                        image_view: hdr_image.2,
+                        image_layout: vk::ImageLayout::GENERAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: 27,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+                    p_buffer_info: &vk::DescriptorBufferInfo {
+                        buffer: material_buffer.buffer,
+                        offset: 0,
+                        range: material_buffer.stride,
+                    },
+                    ..Default::default()
+                },
+            ];
+            unsafe { ctx.device.update_descriptor_sets(&descriptor_writes, &[]); }
+
+            let frame_push_constants = vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+                offset: 0,
+                size: size_of::<FramePushConstants>() as u32,
+            };
+            let pipeline_layout_info = vk::PipelineLayoutCreateInfo {
+                set_layout_count: 1,
+                p_set_layouts: &descriptor_set_layout,
+                push_constant_range_count: 1,
+                p_push_constant_ranges: &frame_push_constants,
+                ..Default::default()
+            };
+            let pipeline_layout = unsafe { ctx.device.create_pipeline_layout(&pipeline_layout_info, None)? };
+
+            let rgen_code = shaders.remove("src/shaders/raygen.rgen").unwrap();
+            let rmiss_code = shaders.remove("src/shaders/miss.rmiss").unwrap();
+            let rchit_code = shaders.remove("src/shaders/closesthit.rchit").unwrap();
+            let shadow_miss_code = shaders.remove("src/shaders/shadow.rmiss").unwrap();
+            let photon_rgen_code = shaders.remove("src/shaders/photon.rgen").unwrap();
+            let hitinfo_miss_code = shaders.remove("src/shaders/hitinfo.rmiss").unwrap();
+            let hitinfo_chit_code = shaders.remove("src/shaders/hitinfo.rchit").unwrap();
+            let pick_rgen_code = shaders.remove("src/shaders/pick.rgen").unwrap();
+            let alpha_ahit_code = shaders.remove("src/shaders/alpha.rahit").unwrap();
+            let traversal_ahit_code = shaders.remove("src/shaders/traversal.rahit").unwrap();
+
+            let shader_stages = [
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::RAYGEN_KHR,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: rgen_code.len() * 4, p_code: rgen_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::MISS_KHR,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: rmiss_code.len() * 4, p_code: rmiss_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: rchit_code.len() * 4, p_code: rchit_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::MISS_KHR,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: shadow_miss_code.len() * 4, p_code: shadow_miss_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::RAYGEN_KHR,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: photon_rgen_code.len() * 4, p_code: photon_rgen_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::MISS_KHR,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: hitinfo_miss_code.len() * 4, p_code: hitinfo_miss_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: hitinfo_chit_code.len() * 4, p_code: hitinfo_chit_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::RAYGEN_KHR,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: pick_rgen_code.len() * 4, p_code: pick_rgen_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::ANY_HIT_KHR,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: alpha_ahit_code.len() * 4, p_code: alpha_ahit_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::ANY_HIT_KHR,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: traversal_ahit_code.len() * 4, p_code: traversal_ahit_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+            ];
+
+            // The shading and hit info hit groups alpha test non-opaque instances with the any-hit
+            // shader (stage 8). The traversal heatmap's camera rays shade with the closest hit
+            // shader of the shading group and count their candidate hits with stage 9.
+            let shader_groups = [
+                vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::GENERAL, general_shader: 0, closest_hit_shader: vk::SHADER_UNUSED_KHR, any_hit_shader: vk::SHADER_UNUSED_KHR, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() }, 
+                vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::GENERAL, general_shader: 1, closest_hit_shader: vk::SHADER_UNUSED_KHR, any_hit_shader: vk::SHADER_UNUSED_KHR, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() },
+                vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::TRIANGLES_HIT_GROUP, general_shader: vk::SHADER_UNUSED_KHR, closest_hit_shader: 2, any_hit_shader: 8, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() },
+                vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::GENERAL, general_shader: 3, closest_hit_shader: vk::SHADER_UNUSED_KHR, any_hit_shader: vk::SHADER_UNUSED_KHR, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() },
+                vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::GENERAL, general_shader: 4, closest_hit_shader: vk::SHADER_UNUSED_KHR, any_hit_shader: vk::SHADER_UNUSED_KHR, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() },
+                vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::GENERAL, general_shader: 5, closest_hit_shader: vk::SHADER_UNUSED_KHR, any_hit_shader: vk::SHADER_UNUSED_KHR, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() },
+                vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::TRIANGLES_HIT_GROUP, general_shader: vk::SHADER_UNUSED_KHR, closest_hit_shader: 6, any_hit_shader: 8, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() },
+                vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::GENERAL, general_shader: 7, closest_hit_shader: vk::SHADER_UNUSED_KHR, any_hit_shader: vk::SHADER_UNUSED_KHR, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() },
+                vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::TRIANGLES_HIT_GROUP, general_shader: vk::SHADER_UNUSED_KHR, closest_hit_shader: 2, any_hit_shader: 9, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() },
+            ];
+
+            let pipeline_info = vk::RayTracingPipelineCreateInfoKHR {
+                stage_count: shader_stages.len() as u32,
+                p_stages: shader_stages.as_ptr(),
+                group_count: shader_groups.len() as u32,
+                p_groups: shader_groups.as_ptr(),
+                max_pipeline_ray_recursion_depth: ctx.capabilities.ray_recursion_depth(),
+                layout: pipeline_layout,
+                ..Default::default()
+            };
+            let pipeline = unsafe { ctx.rt_pipeline_loader.create_ray_tracing_pipelines(vk::DeferredOperationKHR::null(), vk::PipelineCache::null(), &[pipeline_info], None).map_err(|(_, err)| err)?[0] };
+            (descriptor_pool, descriptor_set_layout, descriptor_set, pipeline_layout, pipeline, shader_groups.len() as u32)
+        } else {
+            log::info!("Skipping the ray tracing pipeline, the raster preview draws the scene");
+            Default::default()
         };
-        let descriptor_set = unsafe { ctx.device.allocate_descriptor_sets(&alloc_info)?[0] };
 
-        let (uniform_buffer, uniform_mem, _) = create_buffer_with_addr(&ctx, size_of::<CameraProperties>() as u64, vk::BufferUsageFlags::UNIFORM_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT)?;
+        // Skinning compute pipeline, all buffers are passed by device address
+        let skinning_push_constants = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            size: size_of::<SkinningPushConstants>() as u32,
+        };
+        let skinning_pipeline_layout_info = vk::PipelineLayoutCreateInfo {
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &skinning_push_constants,
+            ..Default::default()
+        };
+        let skinning_pipeline_layout = unsafe { ctx.device.create_pipeline_layout(&skinning_pipeline_layout_info, None)? };
+        let skinning_code = shaders.remove("src/shaders/skinning.comp").unwrap();
+        let skinning_pipeline_info = vk::ComputePipelineCreateInfo {
+            stage: vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::COMPUTE,
+                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: skinning_code.len() * 4, p_code: skinning_code.as_ptr(), ..Default::default() }, None)? },
+                p_name: entry_name.as_ptr(),
+                ..Default::default()
+            },
+            layout: skinning_pipeline_layout,
+            ..Default::default()
+        };
+        let skinning_pipeline = unsafe { ctx.device.create_compute_pipelines(vk::PipelineCache::null(), &[skinning_pipeline_info], None).map_err(|(_, err)| err)?[0] };
 
-        let mut tlas_write = vk::WriteDescriptorSetAccelerationStructureKHR {
-            acceleration_structure_count: 1,
-            p_acceleration_structures: &tlas,
+        // Post pass compute pipeline: storage image, haze mask and motion vectors in, post image
+        // out. The post effects come with the push constants.
+        let post_pool_sizes = [
+            vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 4 },
+        ];
+        let post_descriptor_pool = unsafe { ctx.device.create_descriptor_pool(&vk::DescriptorPoolCreateInfo {
+            max_sets: 1,
+            pool_size_count: post_pool_sizes.len() as u32,
+            p_pool_sizes: post_pool_sizes.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        let post_bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..4).map(|binding| vk::DescriptorSetLayoutBinding {
+            binding,
+            descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            ..Default::default()
+        }).collect();
+        let post_descriptor_set_layout = unsafe { ctx.device.create_descriptor_set_layout(&vk::DescriptorSetLayoutCreateInfo {
+            binding_count: post_bindings.len() as u32,
+            p_bindings: post_bindings.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        let post_descriptor_set = unsafe { ctx.device.allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+            descriptor_pool: post_descriptor_pool,
+            descriptor_set_count: 1,
+            p_set_layouts: &post_descriptor_set_layout,
             ..Default::default()
+        })?[0] };
+        let post_image_infos = [storage_view, haze_mask_image.2, post_image.2, motion_image.2].map(|image_view| vk::DescriptorImageInfo {
+            image_view,
+            image_layout: vk::ImageLayout::GENERAL,
+            ..Default::default()
+        });
+        let post_writes: Vec<vk::WriteDescriptorSet> = post_image_infos.iter().enumerate().map(|(binding, info)| vk::WriteDescriptorSet {
+            dst_set: post_descriptor_set,
+            dst_binding: binding as u32,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+            p_image_info: info,
+            ..Default::default()
+        }).collect();
+        unsafe { ctx.device.update_descriptor_sets(&post_writes, &[]); }
+
+        let post_push_constants = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            size: size_of::<PostPushConstants>() as u32,
         };
-        let descriptor_writes = [
-            vk::WriteDescriptorSet {
-                dst_set: descriptor_set,
-                dst_binding: 0,
-                descriptor_count: 1,
-                descriptor_type: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
-                p_next: &mut tlas_write as *mut _ as *mut _,
+        let post_pipeline_layout = unsafe { ctx.device.create_pipeline_layout(&vk::PipelineLayoutCreateInfo {
+            set_layout_count: 1,
+            p_set_layouts: &post_descriptor_set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &post_push_constants,
+            ..Default::default()
+        }, None)? };
+        let post_code = shaders.remove("src/shaders/post.comp").unwrap();
+        let post_pipeline_info = vk::ComputePipelineCreateInfo {
+            stage: vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::COMPUTE,
+                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: post_code.len() * 4, p_code: post_code.as_ptr(), ..Default::default() }, None)? },
+                p_name: entry_name.as_ptr(),
                 ..Default::default()
             },
+            layout: post_pipeline_layout,
+            ..Default::default()
+        };
+        let post_pipeline = unsafe { ctx.device.create_compute_pipelines(vk::PipelineCache::null(), &[post_pipeline_info], None).map_err(|(_, err)| err)?[0] };
+
+        // Exposure pass compute pipeline: luminance image in, histogram buffer out
+        let exposure_pool_sizes = [
+            vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1 },
+            vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1 },
+        ];
+        let exposure_descriptor_pool = unsafe { ctx.device.create_descriptor_pool(&vk::DescriptorPoolCreateInfo {
+            max_sets: 1,
+            pool_size_count: exposure_pool_sizes.len() as u32,
+            p_pool_sizes: exposure_pool_sizes.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        let exposure_bindings = [
+            vk::DescriptorSetLayoutBinding { binding: 0, descriptor_type: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::COMPUTE, ..Default::default() },
+            vk::DescriptorSetLayoutBinding { binding: 1, descriptor_type: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1, stage_flags: vk::ShaderStageFlags::COMPUTE, ..Default::default() },
+        ];
+        let exposure_descriptor_set_layout = unsafe { ctx.device.create_descriptor_set_layout(&vk::DescriptorSetLayoutCreateInfo {
+            binding_count: exposure_bindings.len() as u32,
+            p_bindings: exposure_bindings.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        let exposure_descriptor_set = unsafe { ctx.device.allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+            descriptor_pool: exposure_descriptor_pool,
+            descriptor_set_count: 1,
+            p_set_layouts: &exposure_descriptor_set_layout,
+            ..Default::default()
+        })?[0] };
+        let exposure_writes = [
             vk::WriteDescriptorSet {
-                dst_set: descriptor_set,
-                dst_binding: 1,
+                dst_set: exposure_descriptor_set,
+                dst_binding: 0,
                 descriptor_count: 1,
                 descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
                 p_image_info: &vk::DescriptorImageInfo {
-                    image_view: storage_view,
+                    image_view: luminance_image.2,
                     image_layout: vk::ImageLayout::GENERAL,
                     ..Default::default()
                 },
                 ..Default::default()
             },
             vk::WriteDescriptorSet {
-                dst_set: descriptor_set,
-                dst_binding: 2,
-                descriptor_count: 1,
-                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-                p_buffer_info: &vk::DescriptorBufferInfo {
-                    buffer: uniform_buffer,
-                    offset: 0,
-                    range: vk::WHOLE_SIZE,
-                },
-                ..Default::default()
-            },
-            vk::WriteDescriptorSet {
-                dst_set: descriptor_set,
-                dst_binding: 3,
+                dst_set: exposure_descriptor_set,
+                dst_binding: 1,
                 descriptor_count: 1,
                 descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
                 p_buffer_info: &vk::DescriptorBufferInfo {
-                    buffer: scene_desc_buffer,
+                    buffer: histogram_buffer,
                     offset: 0,
                     range: vk::WHOLE_SIZE,
                 },
                 ..Default::default()
             },
         ];
-        unsafe { ctx.device.update_descriptor_sets(&descriptor_writes, &[]); }
+        unsafe { ctx.device.update_descriptor_sets(&exposure_writes, &[]); }
 
-        let pipeline_layout_info = vk::PipelineLayoutCreateInfo {
+        let exposure_push_constants = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            size: size_of::<ExposurePushConstants>() as u32,
+        };
+        let exposure_pipeline_layout = unsafe { ctx.device.create_pipeline_layout(&vk::PipelineLayoutCreateInfo {
             set_layout_count: 1,
-            p_set_layouts: &descriptor_set_layout,
+            p_set_layouts: &exposure_descriptor_set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &exposure_push_constants,
+            ..Default::default()
+        }, None)? };
+        let exposure_code = shaders.remove("src/shaders/exposure.comp").unwrap();
+        let exposure_pipeline_info = vk::ComputePipelineCreateInfo {
+            stage: vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::COMPUTE,
+                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: exposure_code.len() * 4, p_code: exposure_code.as_ptr(), ..Default::default() }, None)? },
+                p_name: entry_name.as_ptr(),
+                ..Default::default()
+            },
+            layout: exposure_pipeline_layout,
             ..Default::default()
         };
-        let pipeline_layout = unsafe { ctx.device.create_pipeline_layout(&pipeline_layout_info, None)? };
+        let exposure_pipeline = unsafe { ctx.device.create_compute_pipelines(vk::PipelineCache::null(), &[exposure_pipeline_info], None).map_err(|(_, err)| err)?[0] };
 
-        let rgen_code = compile_shader("src/shaders/raygen.rgen", shaderc::ShaderKind::RayGeneration, "main")?;
-        let rmiss_code = compile_shader("src/shaders/miss.rmiss", shaderc::ShaderKind::Miss, "main")?;
-        let rchit_code = compile_shader("src/shaders/closesthit.rchit", shaderc::ShaderKind::ClosestHit, "main")?;
-        let shadow_miss_code = compile_shader("src/shaders/shadow.rmiss", shaderc::ShaderKind::Miss, "main")?;
+        // Bloom compute pipeline: one descriptor set per step of the chain, each binding the
+        // image it reads, the mip it writes and the storage and HDR images the composite adds to
+        let bloom_mips = bloom_image.2.len();
+        let mut bloom_steps = vec![(hdr_image.2, bloom_image.2[0])];
+        bloom_steps.extend((1..bloom_mips).map(|mip| (bloom_image.2[mip - 1], bloom_image.2[mip])));
+        bloom_steps.extend((0..bloom_mips - 1).rev().map(|mip| (bloom_image.2[mip + 1], bloom_image.2[mip])));
+        bloom_steps.push((bloom_image.2[0], bloom_image.2[0]));
+        let bloom_pool_sizes = [
+            vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 4 * bloom_steps.len() as u32 },
+        ];
+        let bloom_descriptor_pool = unsafe { ctx.device.create_descriptor_pool(&vk::DescriptorPoolCreateInfo {
+            max_sets: bloom_steps.len() as u32,
+            pool_size_count: bloom_pool_sizes.len() as u32,
+            p_pool_sizes: bloom_pool_sizes.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        let bloom_bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..4).map(|binding| vk::DescriptorSetLayoutBinding {
+            binding,
+            descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            ..Default::default()
+        }).collect();
+        let bloom_descriptor_set_layout = unsafe { ctx.device.create_descriptor_set_layout(&vk::DescriptorSetLayoutCreateInfo {
+            binding_count: bloom_bindings.len() as u32,
+            p_bindings: bloom_bindings.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        let bloom_set_layouts = vec![bloom_descriptor_set_layout; bloom_steps.len()];
+        let bloom_descriptor_sets = unsafe { ctx.device.allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+            descriptor_pool: bloom_descriptor_pool,
+            descriptor_set_count: bloom_set_layouts.len() as u32,
+            p_set_layouts: bloom_set_layouts.as_ptr(),
+            ..Default::default()
+        })? };
+        for (&set, &(source, target)) in bloom_descriptor_sets.iter().zip(&bloom_steps) {
+            let infos = [source, target, storage_view, hdr_image.2].map(|image_view| vk::DescriptorImageInfo {
+                image_view,
+                image_layout: vk::ImageLayout::GENERAL,
+                ..Default::default()
+            });
+            let writes: Vec<vk::WriteDescriptorSet> = infos.iter().enumerate().map(|(binding, info)| vk::WriteDescriptorSet {
+                dst_set: set,
+                dst_binding: binding as u32,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                p_image_info: info,
+                ..Default::default()
+            }).collect();
+            unsafe { ctx.device.update_descriptor_sets(&writes, &[]); }
+        }
 
-        let entry_name = std::ffi::CStr::from_bytes_with_nul(b"main\0").unwrap();
-        let shader_stages = [
-            vk::PipelineShaderStageCreateInfo {
-                stage: vk::ShaderStageFlags::RAYGEN_KHR,
-                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: rgen_code.len() * 4, p_code: rgen_code.as_ptr(), ..Default::default() }, None)? },
+        let bloom_push_constants = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            size: size_of::<BloomPushConstants>() as u32,
+        };
+        let bloom_pipeline_layout = unsafe { ctx.device.create_pipeline_layout(&vk::PipelineLayoutCreateInfo {
+            set_layout_count: 1,
+            p_set_layouts: &bloom_descriptor_set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &bloom_push_constants,
+            ..Default::default()
+        }, None)? };
+        let bloom_code = shaders.remove("src/shaders/bloom.comp").unwrap();
+        let bloom_pipeline_info = vk::ComputePipelineCreateInfo {
+            stage: vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::COMPUTE,
+                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: bloom_code.len() * 4, p_code: bloom_code.as_ptr(), ..Default::default() }, None)? },
                 p_name: entry_name.as_ptr(),
                 ..Default::default()
             },
-            vk::PipelineShaderStageCreateInfo {
-                stage: vk::ShaderStageFlags::MISS_KHR,
-                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: rmiss_code.len() * 4, p_code: rmiss_code.as_ptr(), ..Default::default() }, None)? },
+            layout: bloom_pipeline_layout,
+            ..Default::default()
+        };
+        let bloom_pipeline = unsafe { ctx.device.create_compute_pipelines(vk::PipelineCache::null(), &[bloom_pipeline_info], None).map_err(|(_, err)| err)?[0] };
+
+        // Upscale pass compute pipeline: storage or post image, motion vectors, depth and history in,
+        // upscale image (swapchain size) and history out
+        let upscale_pool_sizes = [
+            vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 14 },
+        ];
+        let upscale_descriptor_pool = unsafe { ctx.device.create_descriptor_pool(&vk::DescriptorPoolCreateInfo {
+            max_sets: 2,
+            pool_size_count: upscale_pool_sizes.len() as u32,
+            p_pool_sizes: upscale_pool_sizes.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        let upscale_bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..7).map(|binding| vk::DescriptorSetLayoutBinding {
+            binding,
+            descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            ..Default::default()
+        }).collect();
+        let upscale_descriptor_set_layout = unsafe { ctx.device.create_descriptor_set_layout(&vk::DescriptorSetLayoutCreateInfo {
+            binding_count: upscale_bindings.len() as u32,
+            p_bindings: upscale_bindings.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        let upscale_set_layouts = [upscale_descriptor_set_layout; 2];
+        let upscale_descriptor_sets: [vk::DescriptorSet; 2] = unsafe { ctx.device.allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+            descriptor_pool: upscale_descriptor_pool,
+            descriptor_set_count: 2,
+            p_set_layouts: upscale_set_layouts.as_ptr(),
+            ..Default::default()
+        })? }.try_into().map_err(|_| "expected two upscale descriptor sets")?;
+        for (i, &set) in upscale_descriptor_sets.iter().enumerate() {
+            let views = [storage_view, post_image.2, upscale_image.2, motion_image.2, aov_depth_image.2, upscale_history_images[i].2, upscale_history_images[1 - i].2];
+            let upscale_image_infos = views.map(|image_view| vk::DescriptorImageInfo {
+                image_view,
+                image_layout: vk::ImageLayout::GENERAL,
+                ..Default::default()
+            });
+            let upscale_writes: Vec<vk::WriteDescriptorSet> = upscale_image_infos.iter().enumerate().map(|(binding, info)| vk::WriteDescriptorSet {
+                dst_set: set,
+                dst_binding: binding as u32,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                p_image_info: info,
+                ..Default::default()
+            }).collect();
+            unsafe { ctx.device.update_descriptor_sets(&upscale_writes, &[]); }
+        }
+
+        let upscale_push_constants = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            size: size_of::<UpscalePushConstants>() as u32,
+        };
+        let upscale_pipeline_layout = unsafe { ctx.device.create_pipeline_layout(&vk::PipelineLayoutCreateInfo {
+            set_layout_count: 1,
+            p_set_layouts: &upscale_descriptor_set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &upscale_push_constants,
+            ..Default::default()
+        }, None)? };
+        let upscale_code = shaders.remove("src/shaders/upscale.comp").unwrap();
+        let upscale_pipeline_info = vk::ComputePipelineCreateInfo {
+            stage: vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::COMPUTE,
+                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: upscale_code.len() * 4, p_code: upscale_code.as_ptr(), ..Default::default() }, None)? },
                 p_name: entry_name.as_ptr(),
                 ..Default::default()
             },
+            layout: upscale_pipeline_layout,
+            ..Default::default()
+        };
+        let upscale_pipeline = unsafe { ctx.device.create_compute_pipelines(vk::PipelineCache::null(), &[upscale_pipeline_info], None).map_err(|(_, err)| err)?[0] };
+
+        // G-buffer pass (hybrid rendering): rasterizes the instances into the visibility image,
+        // pulling vertices through the scene descriptions with this frame's transforms and alpha
+        // testing with the materials and textures
+        let gbuffer_pool_sizes = [
+            vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 1 },
+            vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, descriptor_count: 3 },
+            vk::DescriptorPoolSize { ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER, descriptor_count: textures.len() as u32 },
+        ];
+        let gbuffer_descriptor_pool = unsafe { ctx.device.create_descriptor_pool(&vk::DescriptorPoolCreateInfo {
+            max_sets: 1,
+            pool_size_count: gbuffer_pool_sizes.len() as u32,
+            p_pool_sizes: gbuffer_pool_sizes.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        // (binding, descriptor type, buffer, range), the per-frame buffers bound at a slot by dynamic offset
+        let gbuffer_buffers = [
+            (3, vk::DescriptorType::STORAGE_BUFFER, scene_desc_buffer, vk::WHOLE_SIZE),
+            (14, vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, instance_data_buffer.buffer, instance_data_buffer.stride),
+            (23, vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, transform_buffer.buffer, transform_buffer.stride),
+            (27, vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, material_buffer.buffer, material_buffer.stride),
+        ];
+        let mut gbuffer_bindings: Vec<vk::DescriptorSetLayoutBinding> = gbuffer_buffers.iter().map(|&(binding, descriptor_type, _, _)| vk::DescriptorSetLayoutBinding {
+            binding,
+            descriptor_type,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            ..Default::default()
+        }).collect();
+        gbuffer_bindings.push(vk::DescriptorSetLayoutBinding {
+            binding: 6,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: textures.len() as u32,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        });
+        let gbuffer_descriptor_set_layout = unsafe { ctx.device.create_descriptor_set_layout(&vk::DescriptorSetLayoutCreateInfo {
+            binding_count: gbuffer_bindings.len() as u32,
+            p_bindings: gbuffer_bindings.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        let gbuffer_descriptor_set = unsafe { ctx.device.allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+            descriptor_pool: gbuffer_descriptor_pool,
+            descriptor_set_count: 1,
+            p_set_layouts: &gbuffer_descriptor_set_layout,
+            ..Default::default()
+        })?[0] };
+        let gbuffer_buffer_infos = gbuffer_buffers.map(|(_, _, buffer, range)| vk::DescriptorBufferInfo {
+            buffer,
+            offset: 0,
+            range,
+        });
+        let gbuffer_texture_infos: Vec<vk::DescriptorImageInfo> = textures.iter().map(|&(_, _, view)| vk::DescriptorImageInfo {
+            sampler: texture_sampler,
+            image_view: view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }).collect();
+        let mut gbuffer_writes: Vec<vk::WriteDescriptorSet> = gbuffer_buffers.iter().zip(&gbuffer_buffer_infos).map(|(&(binding, descriptor_type, _, _), info)| vk::WriteDescriptorSet {
+            dst_set: gbuffer_descriptor_set,
+            dst_binding: binding,
+            descriptor_count: 1,
+            descriptor_type,
+            p_buffer_info: info,
+            ..Default::default()
+        }).collect();
+        gbuffer_writes.push(vk::WriteDescriptorSet {
+            dst_set: gbuffer_descriptor_set,
+            dst_binding: 6,
+            descriptor_count: gbuffer_texture_infos.len() as u32,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            p_image_info: gbuffer_texture_infos.as_ptr(),
+            ..Default::default()
+        });
+        unsafe { ctx.device.update_descriptor_sets(&gbuffer_writes, &[]); }
+
+        // The visibility image stays in GENERAL for raygen, the depth buffer is only needed during the pass
+        let gbuffer_attachments = [
+            vk::AttachmentDescription {
+                format: vk::Format::R32G32_UINT,
+                samples: vk::SampleCountFlags::TYPE_1,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::STORE,
+                stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+                stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+                initial_layout: vk::ImageLayout::GENERAL,
+                final_layout: vk::ImageLayout::GENERAL,
+                ..Default::default()
+            },
+            vk::AttachmentDescription {
+                format: depth_format,
+                samples: vk::SampleCountFlags::TYPE_1,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::DONT_CARE,
+                stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+                stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                ..Default::default()
+            },
+        ];
+        let visibility_ref = vk::AttachmentReference { attachment: 0, layout: vk::ImageLayout::GENERAL };
+        let depth_ref = vk::AttachmentReference { attachment: 1, layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL };
+        let gbuffer_subpass = vk::SubpassDescription {
+            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+            color_attachment_count: 1,
+            p_color_attachments: &visibility_ref,
+            p_depth_stencil_attachment: &depth_ref,
+            ..Default::default()
+        };
+        let gbuffer_render_pass = unsafe { ctx.device.create_render_pass(&vk::RenderPassCreateInfo {
+            attachment_count: gbuffer_attachments.len() as u32,
+            p_attachments: gbuffer_attachments.as_ptr(),
+            subpass_count: 1,
+            p_subpasses: &gbuffer_subpass,
+            ..Default::default()
+        }, None)? };
+        let gbuffer_framebuffer_views = [visibility_image.2, gbuffer_depth_view];
+        let gbuffer_framebuffer = unsafe { ctx.device.create_framebuffer(&vk::FramebufferCreateInfo {
+            render_pass: gbuffer_render_pass,
+            attachment_count: gbuffer_framebuffer_views.len() as u32,
+            p_attachments: gbuffer_framebuffer_views.as_ptr(),
+            width: extent.width,
+            height: extent.height,
+            layers: 1,
+            ..Default::default()
+        }, None)? };
+
+        let gbuffer_push_constants = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: size_of::<GbufferPushConstants>() as u32,
+        };
+        let gbuffer_pipeline_layout = unsafe { ctx.device.create_pipeline_layout(&vk::PipelineLayoutCreateInfo {
+            set_layout_count: 1,
+            p_set_layouts: &gbuffer_descriptor_set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &gbuffer_push_constants,
+            ..Default::default()
+        }, None)? };
+        let gbuffer_vert_code = shaders.remove("src/shaders/gbuffer.vert").unwrap();
+        let gbuffer_frag_code = shaders.remove("src/shaders/gbuffer.frag").unwrap();
+        let gbuffer_stages = [
             vk::PipelineShaderStageCreateInfo {
-                stage: vk::ShaderStageFlags::CLOSEST_HIT_KHR,
-                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: rchit_code.len() * 4, p_code: rchit_code.as_ptr(), ..Default::default() }, None)? },
+                stage: vk::ShaderStageFlags::VERTEX,
+                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: gbuffer_vert_code.len() * 4, p_code: gbuffer_vert_code.as_ptr(), ..Default::default() }, None)? },
                 p_name: entry_name.as_ptr(),
                 ..Default::default()
             },
             vk::PipelineShaderStageCreateInfo {
-                stage: vk::ShaderStageFlags::MISS_KHR,
-                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: shadow_miss_code.len() * 4, p_code: shadow_miss_code.as_ptr(), ..Default::default() }, None)? },
+                stage: vk::ShaderStageFlags::FRAGMENT,
+                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: gbuffer_frag_code.len() * 4, p_code: gbuffer_frag_code.as_ptr(), ..Default::default() }, None)? },
                 p_name: entry_name.as_ptr(),
                 ..Default::default()
             },
         ];
-
-        let shader_groups = [
-            vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::GENERAL, general_shader: 0, closest_hit_shader: vk::SHADER_UNUSED_KHR, any_hit_shader: vk::SHADER_UNUSED_KHR, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() }, 
-            vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::GENERAL, general_shader: 1, closest_hit_shader: vk::SHADER_UNUSED_KHR, any_hit_shader: vk::SHADER_UNUSED_KHR, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() },
-            vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::TRIANGLES_HIT_GROUP, general_shader: vk::SHADER_UNUSED_KHR, closest_hit_shader: 2, any_hit_shader: vk::SHADER_UNUSED_KHR, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() },
-            vk::RayTracingShaderGroupCreateInfoKHR { ty: vk::RayTracingShaderGroupTypeKHR::GENERAL, general_shader: 3, closest_hit_shader: vk::SHADER_UNUSED_KHR, any_hit_shader: vk::SHADER_UNUSED_KHR, intersection_shader: vk::SHADER_UNUSED_KHR, ..Default::default() },
-        ];
-
-        let pipeline_info = vk::RayTracingPipelineCreateInfoKHR {
-            stage_count: shader_stages.len() as u32,
-            p_stages: shader_stages.as_ptr(),
-            group_count: shader_groups.len() as u32,
-            p_groups: shader_groups.as_ptr(),
-            max_pipeline_ray_recursion_depth: 10,
-            layout: pipeline_layout,
+        // No vertex input (the vertex shader pulls them) and no culling, like the TLAS instances
+        let vertex_input = vk::PipelineVertexInputStateCreateInfo::default();
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
             ..Default::default()
         };
-        let pipeline = unsafe { ctx.rt_pipeline_loader.create_ray_tracing_pipelines(vk::DeferredOperationKHR::null(), vk::PipelineCache::null(), &[pipeline_info], None).map_err(|(_, err)| err)?[0] };
-
-        // 6. SBT (Corrected)
-        let group_count = shader_groups.len() as u32;
-        let prog_size = 32;
-        let sbt_size = (group_count * prog_size) as u64;
-        let (sbt_buffer, sbt_mem, sbt_addr) = create_buffer_with_addr(&ctx, sbt_size, vk::BufferUsageFlags::SHADER_BINDING_TABLE_KHR | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS | vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT)?;
-        
-        let handles = unsafe { ctx.rt_pipeline_loader.get_ray_tracing_shader_group_handles(pipeline, 0, group_count, group_count as usize * 32)? };
-        let mut sbt_data = vec![0u8; sbt_size as usize];
-        sbt_data[0..32].copy_from_slice(&handles[0..32]); // Gen (Group 0)
-        sbt_data[32..64].copy_from_slice(&handles[32..64]); // Miss 0 (Group 1)
-        sbt_data[64..96].copy_from_slice(&handles[96..128]); // Miss 1 (Group 3 - Shadow)
-        sbt_data[96..128].copy_from_slice(&handles[64..96]); // Hit (Group 2)
-        upload_data(&ctx, sbt_mem, &sbt_data);
-        
-        let sbt_regions = [
-            vk::StridedDeviceAddressRegionKHR { device_address: sbt_addr, stride: 32, size: 32 }, // Gen
-            vk::StridedDeviceAddressRegionKHR { device_address: sbt_addr + 32, stride: 32, size: 64 }, // Miss (2 shaders)
-            vk::StridedDeviceAddressRegionKHR { device_address: sbt_addr + 96, stride: 32, size: 32 }, // Hit
-            vk::StridedDeviceAddressRegionKHR { device_address: 0, stride: 0, size: 0 },
-        ];
-
-        // Sync Objects
-        let mut image_available_semaphores = Vec::new();
-        let mut render_finished_semaphores = Vec::new();
-        let mut in_flight_fences = Vec::new();
-        let semaphore_info = vk::SemaphoreCreateInfo::default();
-        let fence_info = vk::FenceCreateInfo {
-            flags: vk::FenceCreateFlags::SIGNALED,
+        let viewport_state = vk::PipelineViewportStateCreateInfo {
+            viewport_count: 1,
+            scissor_count: 1,
             ..Default::default()
         };
-        
-        for _ in 0..max_frames {
-            image_available_semaphores.push(unsafe { ctx.device.create_semaphore(&semaphore_info, None)? });
-            render_finished_semaphores.push(unsafe { ctx.device.create_semaphore(&semaphore_info, None)? });
-            in_flight_fences.push(unsafe { ctx.device.create_fence(&fence_info, None)? });
-        }
+        let rasterization = vk::PipelineRasterizationStateCreateInfo {
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            line_width: 1.0,
+            ..Default::default()
+        };
+        let multisample = vk::PipelineMultisampleStateCreateInfo {
+            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            ..Default::default()
+        };
+        let depth_stencil = vk::PipelineDepthStencilStateCreateInfo {
+            depth_test_enable: vk::TRUE,
+            depth_write_enable: vk::TRUE,
+            depth_compare_op: vk::CompareOp::LESS,
+            ..Default::default()
+        };
+        let visibility_blend = vk::PipelineColorBlendAttachmentState {
+            color_write_mask: vk::ColorComponentFlags::R | vk::ColorComponentFlags::G,
+            ..Default::default()
+        };
+        let color_blend = vk::PipelineColorBlendStateCreateInfo {
+            attachment_count: 1,
+            p_attachments: &visibility_blend,
+            ..Default::default()
+        };
+        // The render extent varies with dynamic resolution
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo {
+            dynamic_state_count: dynamic_states.len() as u32,
+            p_dynamic_states: dynamic_states.as_ptr(),
+            ..Default::default()
+        };
+        let gbuffer_pipeline_info = vk::GraphicsPipelineCreateInfo {
+            stage_count: gbuffer_stages.len() as u32,
+            p_stages: gbuffer_stages.as_ptr(),
+            p_vertex_input_state: &vertex_input,
+            p_input_assembly_state: &input_assembly,
+            p_viewport_state: &viewport_state,
+            p_rasterization_state: &rasterization,
+            p_multisample_state: &multisample,
+            p_depth_stencil_state: &depth_stencil,
+            p_color_blend_state: &color_blend,
+            p_dynamic_state: &dynamic_state,
+            layout: gbuffer_pipeline_layout,
+            render_pass: gbuffer_render_pass,
+            subpass: 0,
+            ..Default::default()
+        };
+        let gbuffer_pipeline = unsafe { ctx.device.create_graphics_pipelines(vk::PipelineCache::null(), &[gbuffer_pipeline_info], None).map_err(|(_, err)| err)?[0] };
+
+        // Raster preview: the G-buffer pass's geometry setup, shading into the storage image with
+        // the G-buffer depth buffer, which hybrid rendering won't need without ray tracing
+        let (preview_render_pass, preview_framebuffer, preview_pipeline_layout, preview_pipeline) = if !ctx.ray_tracing {
+            let preview_attachments = [
+                vk::AttachmentDescription {
+                    format,
+                    samples: vk::SampleCountFlags::TYPE_1,
+                    load_op: vk::AttachmentLoadOp::CLEAR,
+                    store_op: vk::AttachmentStoreOp::STORE,
+                    stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+                    stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+                    initial_layout: vk::ImageLayout::GENERAL,
+                    final_layout: vk::ImageLayout::GENERAL,
+                    ..Default::default()
+                },
+                gbuffer_attachments[1],
+            ];
+            let color_ref = vk::AttachmentReference { attachment: 0, layout: vk::ImageLayout::GENERAL };
+            let preview_subpass = vk::SubpassDescription {
+                pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+                color_attachment_count: 1,
+                p_color_attachments: &color_ref,
+                p_depth_stencil_attachment: &depth_ref,
+                ..Default::default()
+            };
+            let preview_render_pass = unsafe { ctx.device.create_render_pass(&vk::RenderPassCreateInfo {
+                attachment_count: preview_attachments.len() as u32,
+                p_attachments: preview_attachments.as_ptr(),
+                subpass_count: 1,
+                p_subpasses: &preview_subpass,
+                ..Default::default()
+            }, None)? };
+            let preview_framebuffer_views = [storage_view, gbuffer_depth_view];
+            let preview_framebuffer = unsafe { ctx.device.create_framebuffer(&vk::FramebufferCreateInfo {
+                render_pass: preview_render_pass,
+                attachment_count: preview_framebuffer_views.len() as u32,
+                p_attachments: preview_framebuffer_views.as_ptr(),
+                width: extent.width,
+                height: extent.height,
+                layers: 1,
+                ..Default::default()
+            }, None)? };
+
+            let preview_push_constants = vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                offset: 0,
+                size: size_of::<PreviewPushConstants>() as u32,
+            };
+            let preview_pipeline_layout = unsafe { ctx.device.create_pipeline_layout(&vk::PipelineLayoutCreateInfo {
+                set_layout_count: 1,
+                p_set_layouts: &gbuffer_descriptor_set_layout,
+                push_constant_range_count: 1,
+                p_push_constant_ranges: &preview_push_constants,
+                ..Default::default()
+            }, None)? };
+            let preview_vert_code = shaders.remove("src/shaders/preview.vert").unwrap();
+            let preview_frag_code = shaders.remove("src/shaders/preview.frag").unwrap();
+            let preview_stages = [
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::VERTEX,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: preview_vert_code.len() * 4, p_code: preview_vert_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+                vk::PipelineShaderStageCreateInfo {
+                    stage: vk::ShaderStageFlags::FRAGMENT,
+                    module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: preview_frag_code.len() * 4, p_code: preview_frag_code.as_ptr(), ..Default::default() }, None)? },
+                    p_name: entry_name.as_ptr(),
+                    ..Default::default()
+                },
+            ];
+            let color_blend_attachment = vk::PipelineColorBlendAttachmentState {
+                color_write_mask: vk::ColorComponentFlags::RGBA,
+                ..Default::default()
+            };
+            let preview_color_blend = vk::PipelineColorBlendStateCreateInfo {
+                attachment_count: 1,
+                p_attachments: &color_blend_attachment,
+                ..Default::default()
+            };
+            let preview_pipeline_info = vk::GraphicsPipelineCreateInfo {
+                stage_count: preview_stages.len() as u32,
+                p_stages: preview_stages.as_ptr(),
+                p_color_blend_state: &preview_color_blend,
+                layout: preview_pipeline_layout,
+                render_pass: preview_render_pass,
+                ..gbuffer_pipeline_info
+            };
+            let preview_pipeline = unsafe { ctx.device.create_graphics_pipelines(vk::PipelineCache::null(), &[preview_pipeline_info], None).map_err(|(_, err)| err)?[0] };
+            (preview_render_pass, preview_framebuffer, preview_pipeline_layout, preview_pipeline)
+        } else {
+            Default::default()
+        };
+
+        // Swapchain render pass: the resolve pass draws the shown image onto the swapchain image,
+        // then the HUD's text quads are blended over it. The clear leaves black bars where the
+        // image is letterboxed. On Vulkan 1.3 the pass uses dynamic rendering instead, and the
+        // render pass and its framebuffers are only the 1.2 path.
+        let resolve_attachment = vk::AttachmentDescription {
+            format: surface_format.format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            ..Default::default()
+        };
+        let resolve_color_ref = vk::AttachmentReference { attachment: 0, layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL };
+        let resolve_subpass = vk::SubpassDescription {
+            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+            color_attachment_count: 1,
+            p_color_attachments: &resolve_color_ref,
+            ..Default::default()
+        };
+        // The swapchain image is written once the acquire semaphore has signaled
+        let resolve_dependency = vk::SubpassDependency {
+            src_subpass: vk::SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ..Default::default()
+        };
+        let resolve_render_pass = if ctx.vulkan13 {
+            vk::RenderPass::null()
+        } else {
+            unsafe { ctx.device.create_render_pass(&vk::RenderPassCreateInfo {
+                attachment_count: 1,
+                p_attachments: &resolve_attachment,
+                subpass_count: 1,
+                p_subpasses: &resolve_subpass,
+                dependency_count: 1,
+                p_dependencies: &resolve_dependency,
+                ..Default::default()
+            }, None)? }
+        };
+        let resolve_framebuffers = create_swapchain_framebuffers(&ctx, resolve_render_pass, &swapchain_image_views, swapchain_extent)?;
+        // What the pipelines drawing to the swapchain render into without a render pass
+        let swapchain_rendering = vk::PipelineRenderingCreateInfo {
+            color_attachment_count: 1,
+            p_color_attachment_formats: &surface_format.format,
+            ..Default::default()
+        };
+        let swapchain_rendering_next: *const std::ffi::c_void = if ctx.vulkan13 { &swapchain_rendering as *const _ as *const _ } else { std::ptr::null() };
+
+        let hud_push_constants = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: size_of::<HudPushConstants>() as u32,
+        };
+        let hud_pipeline_layout = unsafe { ctx.device.create_pipeline_layout(&vk::PipelineLayoutCreateInfo {
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &hud_push_constants,
+            ..Default::default()
+        }, None)? };
+        let hud_vert_code = shaders.remove("src/shaders/hud.vert").unwrap();
+        let hud_frag_code = shaders.remove("src/shaders/hud.frag").unwrap();
+        let hud_stages = [
+            vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::VERTEX,
+                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: hud_vert_code.len() * 4, p_code: hud_vert_code.as_ptr(), ..Default::default() }, None)? },
+                p_name: entry_name.as_ptr(),
+                ..Default::default()
+            },
+            vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::FRAGMENT,
+                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: hud_frag_code.len() * 4, p_code: hud_frag_code.as_ptr(), ..Default::default() }, None)? },
+                p_name: entry_name.as_ptr(),
+                ..Default::default()
+            },
+        ];
+        let hud_blend_attachment = vk::PipelineColorBlendAttachmentState {
+            blend_enable: vk::TRUE,
+            src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+            dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ZERO,
+            dst_alpha_blend_factor: vk::BlendFactor::ONE,
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+        };
+        let hud_color_blend = vk::PipelineColorBlendStateCreateInfo {
+            attachment_count: 1,
+            p_attachments: &hud_blend_attachment,
+            ..Default::default()
+        };
+        let hud_pipeline_info = vk::GraphicsPipelineCreateInfo {
+            stage_count: hud_stages.len() as u32,
+            p_stages: hud_stages.as_ptr(),
+            p_depth_stencil_state: std::ptr::null(),
+            p_color_blend_state: &hud_color_blend,
+            layout: hud_pipeline_layout,
+            render_pass: resolve_render_pass,
+            p_next: swapchain_rendering_next,
+            ..gbuffer_pipeline_info
+        };
+        let hud_pipeline = unsafe { ctx.device.create_graphics_pipelines(vk::PipelineCache::null(), &[hud_pipeline_info], None).map_err(|(_, err)| err)?[0] };
+
+        // Resolve pass: a triangle over the letterboxed part of the swapchain image that scales
+        // the shown image to it, sharpens it if asked and encodes it for the swapchain's output
+        let resolve_pool_sizes = [
+            vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 4 },
+        ];
+        let resolve_descriptor_pool = unsafe { ctx.device.create_descriptor_pool(&vk::DescriptorPoolCreateInfo {
+            max_sets: 1,
+            pool_size_count: resolve_pool_sizes.len() as u32,
+            p_pool_sizes: resolve_pool_sizes.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        let resolve_bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..4).map(|binding| vk::DescriptorSetLayoutBinding {
+            binding,
+            descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        }).collect();
+        let resolve_descriptor_set_layout = unsafe { ctx.device.create_descriptor_set_layout(&vk::DescriptorSetLayoutCreateInfo {
+            binding_count: resolve_bindings.len() as u32,
+            p_bindings: resolve_bindings.as_ptr(),
+            ..Default::default()
+        }, None)? };
+        let resolve_descriptor_set = unsafe { ctx.device.allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+            descriptor_pool: resolve_descriptor_pool,
+            descriptor_set_count: 1,
+            p_set_layouts: &resolve_descriptor_set_layout,
+            ..Default::default()
+        })?[0] };
+        let resolve_infos = [hdr_image.2, storage_view, post_image.2, upscale_image.2].map(|image_view| vk::DescriptorImageInfo {
+            image_view,
+            image_layout: vk::ImageLayout::GENERAL,
+            ..Default::default()
+        });
+        let resolve_writes: Vec<vk::WriteDescriptorSet> = resolve_infos.iter().enumerate().map(|(binding, info)| vk::WriteDescriptorSet {
+            dst_set: resolve_descriptor_set,
+            dst_binding: binding as u32,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+            p_image_info: info,
+            ..Default::default()
+        }).collect();
+        unsafe { ctx.device.update_descriptor_sets(&resolve_writes, &[]); }
+
+        let resolve_push_constants = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: size_of::<ResolvePushConstants>() as u32,
+        };
+        let resolve_pipeline_layout = unsafe { ctx.device.create_pipeline_layout(&vk::PipelineLayoutCreateInfo {
+            set_layout_count: 1,
+            p_set_layouts: &resolve_descriptor_set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &resolve_push_constants,
+            ..Default::default()
+        }, None)? };
+        let resolve_vert_code = shaders.remove("src/shaders/resolve.vert").unwrap();
+        let resolve_frag_code = shaders.remove("src/shaders/resolve.frag").unwrap();
+        let resolve_stages = [
+            vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::VERTEX,
+                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: resolve_vert_code.len() * 4, p_code: resolve_vert_code.as_ptr(), ..Default::default() }, None)? },
+                p_name: entry_name.as_ptr(),
+                ..Default::default()
+            },
+            vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::FRAGMENT,
+                module: unsafe { ctx.device.create_shader_module(&vk::ShaderModuleCreateInfo { code_size: resolve_frag_code.len() * 4, p_code: resolve_frag_code.as_ptr(), ..Default::default() }, None)? },
+                p_name: entry_name.as_ptr(),
+                ..Default::default()
+            },
+        ];
+        let resolve_blend_attachment = vk::PipelineColorBlendAttachmentState {
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+            ..Default::default()
+        };
+        let resolve_color_blend = vk::PipelineColorBlendStateCreateInfo {
+            attachment_count: 1,
+            p_attachments: &resolve_blend_attachment,
+            ..Default::default()
+        };
+        let resolve_pipeline_info = vk::GraphicsPipelineCreateInfo {
+            stage_count: resolve_stages.len() as u32,
+            p_stages: resolve_stages.as_ptr(),
+            p_depth_stencil_state: std::ptr::null(),
+            p_color_blend_state: &resolve_color_blend,
+            layout: resolve_pipeline_layout,
+            render_pass: resolve_render_pass,
+            p_next: swapchain_rendering_next,
+            ..gbuffer_pipeline_info
+        };
+        let resolve_pipeline = unsafe { ctx.device.create_graphics_pipelines(vk::PipelineCache::null(), &[resolve_pipeline_info], None).map_err(|(_, err)| err)?[0] };
+
+        let font = hud::font_words();
+        let hud_font_buffer = create_buffer_with_addr(&ctx, (font.len() * size_of::<u32>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT, MemoryCategory::Buffers)?;
+        upload_data(&ctx, hud_font_buffer.1, &font);
+        let hud_glyph_buffer = create_buffer_with_addr(&ctx, (max_frames * hud::MAX_GLYPHS * size_of::<GlyphInstance>()) as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT, MemoryCategory::Buffers)?;
+
+        // 6. SBT: every region starts at shaderGroupBaseAlignment, its handles at
+        // shaderGroupHandleAlignment, in device local memory filled through a staging buffer
+        let (sbt_buffer, sbt_mem, sbt_regions, photon_sbt_region, pick_sbt_region) = if ctx.ray_tracing {
+            let mut rt_props = vk::PhysicalDeviceRayTracingPipelinePropertiesKHR::default();
+            let mut props2 = vk::PhysicalDeviceProperties2 {
+                p_next: &mut rt_props as *mut _ as *mut _,
+                ..Default::default()
+            };
+            unsafe { ctx.instance.get_physical_device_properties2(ctx.physical_device, &mut props2) };
+            let handle_size = rt_props.shader_group_handle_size as usize;
+            let handle_stride = rt_props.shader_group_handle_size.next_multiple_of(rt_props.shader_group_handle_alignment) as u64;
+            let base_alignment = rt_props.shader_group_base_alignment as u64;
+
+            // Groups of each region, as numbered in the pipeline
+            let region_groups: [&[usize]; 5] = [
+                &[0], // Gen
+                &[1, 3, 5], // Miss: primary, shadow, hit info
+                &[2, 6, 8], // Hit: primary, hit info, traversal heatmap
+                &[4], // Photon Gen
+                &[7], // Pick Gen
+            ];
+            let mut region_offsets = [0u64; 5];
+            let mut sbt_size = 0;
+            for (offset, groups) in region_offsets.iter_mut().zip(region_groups) {
+                *offset = sbt_size;
+                sbt_size = (sbt_size + groups.len() as u64 * handle_stride).next_multiple_of(base_alignment);
+            }
+
+            let handles = unsafe { ctx.rt_pipeline_loader.get_ray_tracing_shader_group_handles(pipeline, 0, group_count, group_count as usize * handle_size)? };
+            let mut sbt_data = vec![0u8; sbt_size as usize];
+            for (offset, groups) in region_offsets.iter().zip(region_groups) {
+                for (i, &group) in groups.iter().enumerate() {
+                    let start = (offset + i as u64 * handle_stride) as usize;
+                    sbt_data[start..start + handle_size].copy_from_slice(&handles[group * handle_size..(group + 1) * handle_size]);
+                }
+            }
+
+            // Buffer addresses are only guaranteed the buffer's own alignment, so the table is
+            // placed at the first base aligned address within
+            let (sbt_buffer, sbt_mem, buffer_addr) = create_buffer_with_addr(&ctx, sbt_size + base_alignment, vk::BufferUsageFlags::SHADER_BINDING_TABLE_KHR | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS | vk::BufferUsageFlags::TRANSFER_DST, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::ShaderBindingTable)?;
+            let sbt_addr = buffer_addr.next_multiple_of(base_alignment);
+            let (staging_buf, staging_mem, _) = create_buffer_with_addr(&ctx, sbt_size, vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT, MemoryCategory::Buffers)?;
+            upload_data(&ctx, staging_mem, &sbt_data);
+
+            begin_single_time_command(&ctx, command_pool, setup_cmd_buffer);
+            unsafe {
+                let region = vk::BufferCopy { src_offset: 0, dst_offset: sbt_addr - buffer_addr, size: sbt_size };
+                ctx.device.cmd_copy_buffer(setup_cmd_buffer, staging_buf, sbt_buffer, &[region]);
+                let barrier = vk::MemoryBarrier {
+                    src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                    dst_access_mask: vk::AccessFlags::SHADER_READ,
+                    ..Default::default()
+                };
+                ctx.device.cmd_pipeline_barrier(setup_cmd_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::DependencyFlags::empty(), &[barrier], &[], &[]);
+            }
+            end_single_time_command(&ctx, command_pool, setup_cmd_buffer, ctx.queue);
+            unsafe {
+                ctx.device.destroy_buffer(staging_buf, None);
+                ctx.device.free_memory(staging_mem, None);
+            }
+            ctx.memory.forget(staging_mem);
+
+            let region = |index: usize| {
+                let size = region_groups[index].len() as u64 * handle_stride;
+                // A raygen region's size must equal its stride, which holds as each has one group
+                vk::StridedDeviceAddressRegionKHR { device_address: sbt_addr + region_offsets[index], stride: handle_stride, size }
+            };
+            let sbt_regions = [
+                region(0), // Gen
+                region(1), // Miss (3 shaders)
+                region(2), // Hit (3 groups)
+                vk::StridedDeviceAddressRegionKHR { device_address: 0, stride: 0, size: 0 },
+            ];
+            (sbt_buffer, sbt_mem, sbt_regions, region(3), region(4))
+        } else {
+            Default::default()
+        };
+
+        // Sync Objects
+        let mut image_available_semaphores = Vec::new();
+        let mut render_finished_semaphores = Vec::new();
+        let mut in_flight_fences = Vec::new();
+        let semaphore_info = vk::SemaphoreCreateInfo::default();
+        let fence_info = vk::FenceCreateInfo {
+            flags: vk::FenceCreateFlags::SIGNALED,
+            ..Default::default()
+        };
+        
+        for _ in 0..max_frames {
+            image_available_semaphores.push(unsafe { ctx.device.create_semaphore(&semaphore_info, None)? });
+            render_finished_semaphores.push(unsafe { ctx.device.create_semaphore(&semaphore_info, None)? });
+            in_flight_fences.push(unsafe { ctx.device.create_fence(&fence_info, None)? });
+        }
+
+        // Dynamic resolution steers by GPU frame times, measured with timestamps
+        let mut dynamic_resolution = DynamicResolution::from_args(render_scale);
+        if dynamic_resolution.is_some() && !ctx.capabilities.timestamps {
+            log::warn!("Dynamic resolution needs GPU timestamps, which this device doesn't support");
+            dynamic_resolution = None;
+        }
+        if let Some(dynamic) = &dynamic_resolution {
+            log::info!("Dynamic resolution: targeting {} FPS", dynamic.target_fps);
+        }
+        let metrics = MetricsLog::from_args();
+        if metrics.is_some() && !ctx.capabilities.timestamps {
+            log::warn!("GPU pass times need GPU timestamps, which this device doesn't support, the metrics leave them out");
+        }
+        let timestamp_pool = unsafe { ctx.device.create_query_pool(&vk::QueryPoolCreateInfo {
+            query_type: vk::QueryType::TIMESTAMP,
+            query_count: TIMESTAMPS_PER_FRAME * max_frames as u32,
+            ..Default::default()
+        }, None)? };
 
-        Ok(Self {
+        let seed = rng::seed_from_args();
+        log::info!("Random seed: {}", seed);
+
+        log::info!("GPU memory after scene upload: {}", ctx.memory.describe());
+        ctx.log_heap_budgets();
+
+        let mut renderer = Self {
             ctx,
             command_pool,
             command_buffers,
             vertex_buffer: (vertex_buffer, vertex_mem),
             index_buffer: (index_buffer, index_mem),
-            material_buffer: (material_buffer, material_mem),
+            material_buffer,
             scene_desc_buffer: (scene_desc_buffer, scene_desc_mem),
-            uniform_buffer: (uniform_buffer, uniform_mem),
+            instance_data_buffer,
+            transform_buffer,
+            prev_transform_buffer,
+            last_instance_transforms: instance_transforms,
+            uniform_buffer,
+            camera_ubo_stride,
+            uploaded_camera: Vec::new(),
+            photon_grid_buffer: (photon_grid_buffer, photon_grid_mem),
+            caustic_target_buffer: (caustic_target_buffer, caustic_target_mem),
+            caustic_target_count: caustic_targets.len() as u32,
+            sky_cache_buffer: (sky_cache_buffer, sky_cache_mem),
+            volume_buffer: (volume_buffer, volume_mem),
+            focus_probe_buffer: (focus_probe_buffer, focus_probe_mem),
+            histogram_buffer: (histogram_buffer, histogram_mem),
+            teaching_probe_buffer: (teaching_probe_buffer, teaching_probe_mem),
+            projector_buffer: (projector_buffer, projector_mem),
+            heat_volume_buffer: (heat_volume_buffer, heat_volume_mem),
+            pick_buffer: (pick_buffer, pick_mem),
+            ray_counter_buffer: (ray_counter_buffer, ray_counter_mem),
+            ray_counter_address,
+            rays_counted: 0,
+            textures,
+            texture_sampler,
             blas_list,
             tlas: tlas_res,
+            blas_addresses,
+            lod,
+            navigation: Navigation::new(&scene, collision::navigation_from_args()),
+            streamed_meshes: (0..scene.meshes.len()).map(|_| None).collect(),
+            streaming,
+            stream_garbage: (0..max_frames).map(|_| StreamGarbage::default()).collect(),
+            scene_descs,
+            tlas_instance_buffers,
+            tlas_scratch,
+            skinned_meshes,
+            skinning_pipeline,
+            skinning_pipeline_layout,
+            post_pipeline,
+            post_pipeline_layout,
+            post_descriptor_pool,
+            post_descriptor_set,
+            post_descriptor_set_layout,
+            exposure_pipeline,
+            exposure_pipeline_layout,
+            exposure_descriptor_pool,
+            exposure_descriptor_set,
+            exposure_descriptor_set_layout,
+            bloom_pipeline,
+            bloom_pipeline_layout,
+            bloom_descriptor_pool,
+            bloom_descriptor_sets,
+            bloom_descriptor_set_layout,
+            upscale_pipeline,
+            upscale_pipeline_layout,
+            upscale_descriptor_pool,
+            upscale_descriptor_sets,
+            upscale_descriptor_set_layout,
+            gbuffer_pipeline,
+            gbuffer_pipeline_layout,
+            gbuffer_descriptor_pool,
+            gbuffer_descriptor_set,
+            gbuffer_descriptor_set_layout,
+            gbuffer_render_pass,
+            gbuffer_framebuffer,
+            preview_pipeline,
+            preview_pipeline_layout,
+            preview_render_pass,
+            preview_framebuffer,
+            resolve_pipeline,
+            resolve_pipeline_layout,
+            resolve_render_pass,
+            resolve_framebuffers,
+            resolve_descriptor_pool,
+            resolve_descriptor_set,
+            resolve_descriptor_set_layout,
+            display_output,
+            paper_white,
+            sharpness: display::sharpness_from_args(),
+            hud_pipeline,
+            hud_pipeline_layout,
+            hud_font_buffer,
+            hud_glyph_buffer,
             pipeline,
             pipeline_layout,
             descriptor_pool,
@@ -614,192 +2820,2380 @@ impl Renderer {
             descriptor_set_layout,
             sbt_buffer: (sbt_buffer, sbt_mem),
             sbt_regions,
+            photon_sbt_region,
+            pick_sbt_region,
             storage_image: (storage_image, storage_mem, storage_view),
+            accumulation_image: (accumulation_image, accumulation_mem, accumulation_view),
+            haze_mask_image,
+            motion_image,
+            luminance_image,
+            hdr_image,
+            bloom_image,
+            aov_depth_image,
+            aov_normal_image,
+            aov_albedo_image,
+            aov_instance_image,
+            post_image,
+            upscale_image,
+            upscale_history_images,
+            visibility_image,
+            gbuffer_depth_image: (gbuffer_depth, gbuffer_depth_mem, gbuffer_depth_view),
+            displayed_image: storage_image,
+            render_extent: extent,
+            output_extent: swapchain_extent,
+            max_render_extent: extent,
+            max_output_extent: swapchain_extent,
+            render_scale,
+            swapchain_extent,
+            surface_format,
+            swapchain_stale: false,
             swapchain,
             swapchain_images,
             swapchain_image_views,
             image_available_semaphores,
             render_finished_semaphores,
             in_flight_fences,
+            timestamp_pool,
+            timestamp_period: ctx.capabilities.timestamp_period,
+            timestamps_written: [false; 2],
+            metrics,
+            pending_metrics: [None; 2],
+            frame_started: None,
             camera,
+            bookmarks: Bookmarks::new(bookmarks::transition_time_from_args()),
+            ctrl_held: false,
             settings,
+            caustics: false,
+            sky_occlusion: true,
+            sky_cache_dirty: true,
+            light_path_channel: 0,
+            traversal_heatmap: false,
+            anaglyph: false,
+            depth_of_field: false,
+            heat_haze: true,
+            motion_blur: false,
+            bloom: true,
+            post_effects: PostEffects::new(),
+            samples_per_pixel: 1,
+            upscaler: Upscaler::from_args(),
+            hybrid: hybrid::hybrid_from_args(),
+            split_screen: None,
+            show_memory: false,
+            upscale_history: 0,
+            upscale_history_valid: false,
+            dynamic_resolution,
+            accessibility: Accessibility::new(),
+            teaching: Teaching::new(),
+            hud: Hud::new(),
+            prev_view_proj: None,
+            editor: Editor::new(),
+            material_preview: MaterialPreview::new(),
+            material_watcher,
+            weather: Weather::new(&scene, seed),
+            particles: Particles::new(&scene, seed),
+            wind: Wind::new(&scene),
+            animation_paused: false,
+            autosave: Autosave::new(),
+            default_session: None,
+            focus_distance: 10.0,
+            focus_point: None,
+            selection: None,
+            cursor_position: Vec2::new(swapchain_extent.width as f32, swapchain_extent.height as f32) * 0.5,
+            pick_request: None,
+            pick_in_flight: None,
             current_frame: 0,
+            frame_index: 0,
+            scene_time: 0.0,
+            animation_frame: 0,
+            animation_step: false,
+            last_frame_time: Instant::now(),
+            long_exposure: None,
+            photo: None,
+            photo_scale: 1,
+            photo_projection: PhotoProjection::from_args(),
+            recording: None,
+            recording_options: RecordingOptions::from_args(),
+            seed,
+            light: Light::new(),
+            light_controls: false,
+            exposure: Exposure::new(),
+            noise_estimate: None,
+            last_noise_estimate: Instant::now(),
+            photo_review_until: None,
+            #[cfg(feature = "denoise")]
+            denoise: true,
+            #[cfg(feature = "physics")]
+            physics: crate::physics::PhysicsWorld::new(&scene),
             scene,
-        })
+        };
+
+        renderer.default_session = Some(renderer.session());
+        if std::path::Path::new(autosave::SESSION_FILE).exists() {
+            let mut session = renderer.session();
+            match autosave::load_session(&mut session, autosave::SESSION_FILE) {
+                Ok(()) => {
+                    renderer.apply_session(&session);
+                    log::info!("Restored the previous session from {}", autosave::SESSION_FILE);
+                }
+                Err(e) => log::warn!("Ignoring {}: {}", autosave::SESSION_FILE, e),
+            }
+        }
+        renderer.accessibility.apply_args();
+        renderer.camera.apply_projection_args();
+        if hybrid::hybrid_from_args() {
+            renderer.hybrid = true;
+        }
+        if split_screen::split_screen_from_args() {
+            renderer.toggle_split_screen();
+        }
+        renderer.warn_hdr_limits();
+        let stats = renderer.stats();
+        log::info!("Scene: {}", stats.scene);
+        log::info!("Scene on the GPU: {}", stats);
+        Ok(renderer)
+    }
+
+    /// HDR output resolves the HDR image raygen writes, so the passes working on the display
+    /// image don't show in it. Says which of them are on.
+    fn warn_hdr_limits(&self) {
+        if !self.display_output.is_hdr() {
+            return;
+        }
+        let mut skipped = Vec::new();
+        if self.heat_haze && !self.scene.heat_volumes.is_empty() {
+            skipped.push("heat haze");
+        }
+        if self.motion_blur {
+            skipped.push("motion blur");
+        }
+        if self.post_effects.any() {
+            skipped.push("the post effects");
+        }
+        if self.render_scale < 1.0 || self.dynamic_resolution.is_some() {
+            skipped.push("the upscaler (the HDR image is scaled bilinearly)");
+        }
+        if !skipped.is_empty() {
+            log::warn!("{} output doesn't show {}", self.display_output.name(), skipped.join(", "));
+        }
+    }
+
+    /// Camera and render settings to autosave.
+    pub fn session(&self) -> Session {
+        // Sessions keep the view of the scene, not of the material preview's studio
+        let pose = self.material_preview.scene_pose(self.camera.pose());
+        Session {
+            camera_position: pose.position,
+            camera_orientation: pose.orientation,
+            lens_profile: self.camera.lens_profile,
+            projection: self.camera.projection,
+            physical_lens: self.camera.physical_lens,
+            ortho_height: self.camera.ortho_height,
+            mouse_sensitivity: self.camera.mouse_sensitivity,
+            invert_y: self.camera.invert_y,
+            speed: self.camera.speed,
+            settings: self.settings,
+            caustics: self.caustics,
+            sky_occlusion: self.sky_occlusion,
+            heat_haze: self.heat_haze,
+            motion_blur: self.motion_blur,
+            bloom: self.bloom,
+            samples_per_pixel: self.samples_per_pixel,
+            accessibility: self.accessibility,
+            depth_of_field: self.depth_of_field,
+            weather: self.weather.kind,
+            light: self.light,
+            hybrid: self.hybrid,
+            anaglyph: self.anaglyph,
+            light_path_channel: self.light_path_channel,
+            bookmarks: self.bookmarks.slots,
+        }
+    }
+
+    pub fn apply_session(&mut self, session: &Session) {
+        self.camera.position = session.camera_position;
+        self.camera.orientation = session.camera_orientation;
+        self.camera.update_vectors();
+        self.camera.lens_profile = session.lens_profile.min(LENS_PROFILES.len() - 1);
+        self.camera.projection = session.projection;
+        self.camera.physical_lens = session.physical_lens;
+        self.camera.ortho_height = session.ortho_height;
+        self.camera.set_mouse_sensitivity(session.mouse_sensitivity);
+        self.camera.invert_y = session.invert_y;
+        self.camera.set_speed(session.speed);
+        self.settings = session.settings;
+        self.caustics = session.caustics;
+        self.set_sky_occlusion(session.sky_occlusion);
+        self.heat_haze = session.heat_haze;
+        self.motion_blur = session.motion_blur;
+        self.bloom = session.bloom;
+        self.samples_per_pixel = session.samples_per_pixel.clamp(1, SAMPLES_PER_PIXEL[SAMPLES_PER_PIXEL.len() - 1]);
+        self.accessibility = session.accessibility;
+        self.accessibility.set_ui_scale(session.accessibility.ui_scale);
+        self.depth_of_field = session.depth_of_field;
+        self.set_weather(session.weather);
+        self.light = session.light;
+        self.hybrid = session.hybrid;
+        self.anaglyph = session.anaglyph;
+        self.light_path_channel = session.light_path_channel.min(LIGHT_PATH_CHANNELS.len() - 1);
+        self.bookmarks.slots = session.bookmarks;
+        self.bookmarks.cancel();
+    }
+
+    /// Puts the camera and render settings back to how they are without a saved session,
+    /// keeping the accessibility options, the mouse and movement preferences and the bookmarks.
+    fn reset_session(&mut self) {
+        let Some(mut defaults) = self.default_session.clone() else {
+            return;
+        };
+        defaults.accessibility = self.accessibility;
+        defaults.mouse_sensitivity = self.camera.mouse_sensitivity;
+        defaults.invert_y = self.camera.invert_y;
+        defaults.speed = self.camera.speed;
+        defaults.bookmarks = self.bookmarks.slots;
+        self.apply_session(&defaults);
+        self.focus_point = None;
+        self.prev_view_proj = None;
+        log::info!("Camera and settings reset to defaults");
+    }
+
+    /// Writes the session (and unsaved scene edits) now rather than at the next autosave, so
+    /// the next run starts from exactly where this one ended.
+    pub fn save_session(&mut self) {
+        let session = self.session();
+        self.autosave.save_now(&self.scene, &session);
+    }
+    
+    /// Picks up after rendering was paused (the window was minimized): scene time carries on
+    /// from where it stopped rather than jumping over the pause.
+    pub fn resume(&mut self) {
+        self.last_frame_time = Instant::now();
+        self.rays_counted = 0;
+    }
+
+    /// Recreates the swapchain at the window's new size. The render targets keep their size, the
+    /// traced image takes on the window's aspect ratio within them (see `fit_extents`).
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if let Err(e) = self.recreate_swapchain(winit::dpi::PhysicalSize::new(width, height)) {
+            log::error!("Failed to recreate the swapchain: {}", e);
+        }
+    }
+
+    fn recreate_swapchain(&mut self, window_size: winit::dpi::PhysicalSize<u32>) -> Result<(), Box<dyn std::error::Error>> {
+        let capabilities = unsafe { self.ctx.surface_loader.get_physical_device_surface_capabilities(self.ctx.physical_device, self.ctx.surface)? };
+        let extent = surface_extent(&capabilities, window_size);
+        // Minimized, nothing is drawn until the window comes back
+        if extent.width == 0 || extent.height == 0 {
+            return Ok(());
+        }
+        unsafe { self.ctx.device.device_wait_idle()?; }
+        let (swapchain, images, views) = create_swapchain(&self.ctx, extent, self.surface_format, self.swapchain)?;
+        let framebuffers = create_swapchain_framebuffers(&self.ctx, self.resolve_render_pass, &views, extent)?;
+        unsafe {
+            for &framebuffer in &self.resolve_framebuffers {
+                self.ctx.device.destroy_framebuffer(framebuffer, None);
+            }
+            for &view in &self.swapchain_image_views {
+                self.ctx.device.destroy_image_view(view, None);
+            }
+            self.ctx.swapchain_loader.destroy_swapchain(self.swapchain, None);
+        }
+        self.swapchain = swapchain;
+        self.swapchain_images = images;
+        self.swapchain_image_views = views;
+        self.resolve_framebuffers = framebuffers;
+        self.swapchain_extent = extent;
+        self.swapchain_stale = false;
+        self.fit_extents();
+        log::debug!("Swapchain recreated at {}x{}, tracing {}x{}", extent.width, extent.height, self.render_extent.width, self.render_extent.height);
+        Ok(())
+    }
+
+    /// Sizes the traced image after the window: the render scale (dynamic resolution's while it
+    /// is on) of the window size, shrunk to fit the render targets keeping the window's aspect
+    /// ratio, which the camera then takes on. Unless the render extent has to hold still, then a
+    /// window of another shape letterboxes it. The upscale pass's output is the render extent
+    /// scaled up to fill the window, or as much of it as the upscale image has room for.
+    fn fit_extents(&mut self) {
+        if !self.render_extent_held() {
+            let scale = self.dynamic_resolution.as_ref().map_or(self.render_scale, |dynamic| dynamic.scale);
+            self.render_extent = upscale::fit_extent(upscale::scaled_extent(self.swapchain_extent, scale), self.max_render_extent);
+        }
+        let output_extent = upscale::fit_extent(display::letterbox(self.render_extent, self.swapchain_extent).extent, self.max_output_extent);
+        if output_extent != self.output_extent {
+            // The history is laid out for the old output size
+            self.output_extent = output_extent;
+            self.upscale_history_valid = false;
+        }
+    }
+
+    /// Whether the render extent has to stay put: accumulated frames, a photo on review and the
+    /// teaching mode's probed pixels all rely on it.
+    fn render_extent_held(&self) -> bool {
+        let photo_review = self.photo_review_until.is_some_and(|until| Instant::now() < until);
+        self.accumulating() || self.teaching.active || photo_review
+    }
+
+    /// Size of the picture in the displayed image.
+    fn displayed_extent(&self) -> vk::Extent2D {
+        if self.displayed_image == self.upscale_image.0 { self.output_extent } else { self.render_extent }
+    }
+
+    /// Splits the screen into two views, both starting out like the current one, or goes back
+    /// to the controlled view alone.
+    fn toggle_split_screen(&mut self) {
+        if self.split_screen.take().is_some() {
+            log::info!("Split screen: off");
+            return;
+        }
+        if !self.ctx.ray_tracing {
+            log::warn!("Split screen needs ray tracing pipelines");
+            return;
+        }
+        self.split_screen = Some(SplitScreen::new(ViewState {
+            camera: self.camera.clone(),
+            settings: self.settings,
+            light_path_channel: self.light_path_channel,
+            prev_view_proj: None,
+        }));
+        log::info!("Split screen: on, controlling the left view (8 switches)");
+    }
+
+    /// Hands the keyboard, mouse and feature toggles over to the other split screen view.
+    fn switch_split_view(&mut self) {
+        let Some(split) = &mut self.split_screen else {
+            return;
+        };
+        let other = &mut split.other;
+        std::mem::swap(&mut self.camera, &mut other.camera);
+        std::mem::swap(&mut self.settings, &mut other.settings);
+        std::mem::swap(&mut self.light_path_channel, &mut other.light_path_channel);
+        std::mem::swap(&mut self.prev_view_proj, &mut other.prev_view_proj);
+        split.right_active = !split.right_active;
+        log::info!("Split screen: controlling the {} view", if split.right_active { "right" } else { "left" });
+    }
+
+    pub fn set_sky_occlusion(&mut self, enabled: bool) {
+        // Start from an empty cache so stale visibility is never shown
+        self.sky_cache_dirty |= enabled && !self.sky_occlusion;
+        self.sky_occlusion = enabled;
+    }
+
+    pub fn set_weather(&mut self, kind: WeatherKind) {
+        self.weather.set(kind, &mut self.scene);
+    }
+
+    /// Jumps the animated scene to `time` seconds.
+    pub fn set_scene_time(&mut self, time: f32) {
+        self.scene_time = time;
+    }
+
+    pub fn render_resolution(&self) -> (u32, u32) {
+        (self.render_extent.width, self.render_extent.height)
+    }
+
+    pub fn gpu_name(&self) -> String {
+        let props = unsafe { self.ctx.instance.get_physical_device_properties(self.ctx.physical_device) };
+        unsafe { std::ffi::CStr::from_ptr(props.device_name.as_ptr()) }.to_string_lossy().into_owned()
+    }
+
+    pub fn handle_input(&mut self, key: KeyCode, state: ElementState) {
+        if state == ElementState::Pressed {
+            let selected_object = self.selection.map(|selection| selection.object_index);
+            if self.material_preview.active && self.material_preview.handle_input(key, &mut self.scene, selected_object) {
+                return;
+            }
+            if self.light_controls && self.photo.is_none() && self.light.handle_input(key) {
+                return;
+            }
+            if self.editor.active && self.editor.handle_input(key, &mut self.scene, selected_object) {
+                return;
+            }
+            if self.camera.adjust_controls(key) {
+                return;
+            }
+            if self.exposure.handle_input(key) {
+                return;
+            }
+            if self.ctrl_held && self.post_effects.handle_input(key) {
+                return;
+            }
+            let camera_free = !self.teaching.active && !self.accumulating();
+            if let Some(slot) = bookmarks::bookmark_slot(key) {
+                if self.ctrl_held {
+                    self.bookmarks.store(slot, self.camera.pose());
+                } else if camera_free {
+                    self.bookmarks.recall(slot, self.camera.pose());
+                }
+                return;
+            }
+            if key == KeyCode::Numpad0 && camera_free {
+                self.bookmarks.start_tour(self.camera.pose());
+            }
+            if !self.teaching.active && self.photo.is_none() {
+                let before = self.camera.position;
+                self.camera.handle_input(key);
+                if self.camera.position != before && !self.material_preview.active {
+                    // Moving takes over from a bookmark transition
+                    self.bookmarks.cancel();
+                    self.camera.position = self.navigation.move_camera(&self.scene, before, self.camera.position - before);
+                }
+            }
+            match key {
+                KeyCode::Digit1 => self.settings.x = 1.0 - self.settings.x,
+                KeyCode::Digit2 => self.settings.y = 1.0 - self.settings.y,
+                KeyCode::Digit3 => self.settings.z = 1.0 - self.settings.z,
+                KeyCode::Digit4 => self.settings.w = 1.0 - self.settings.w,
+                KeyCode::Digit5 => {
+                    self.caustics = !self.caustics;
+                    log::info!("Caustics: {}", if self.caustics { "on" } else { "off" });
+                }
+                KeyCode::Digit6 => {
+                    self.set_sky_occlusion(!self.sky_occlusion);
+                    log::info!("Sky occlusion: {}", if self.sky_occlusion { "on" } else { "off" });
+                }
+                KeyCode::F9 if self.long_exposure.is_none() => {
+                    if let Some(photo) = self.photo.take() {
+                        photo.cancel();
+                    } else {
+                        let (width, height) = self.photo_size();
+                        if let Err(e) = self.start_photo(width, height, self.photo_projection, PHOTO_SAMPLES) {
+                            log::error!("Failed to start photo: {}", e);
+                        }
+                    }
+                }
+                KeyCode::F8 => {
+                    if self.recording.is_some() {
+                        self.stop_recording();
+                    } else {
+                        let extent = self.displayed_extent();
+                        match Recording::start(self.recording_options, extent.width, extent.height) {
+                            Ok(recording) => self.recording = Some(recording),
+                            Err(e) => log::error!("Failed to start recording: {}", e),
+                        }
+                    }
+                }
+                KeyCode::F12 => self.navigation.cycle_mode(),
+                KeyCode::Home if !self.accumulating() => self.reset_session(),
+                KeyCode::Digit7 => self.toggle_split_screen(),
+                KeyCode::Digit8 => self.switch_split_view(),
+                KeyCode::F10 => {
+                    let next = PHOTO_SCALES.iter().position(|&scale| scale == self.photo_scale).map_or(0, |i| (i + 1) % PHOTO_SCALES.len());
+                    self.photo_scale = PHOTO_SCALES[next];
+                    let (width, height) = self.photo_size();
+                    log::info!("Photo size: {}x{}", width, height);
+                }
+                KeyCode::Digit0 => {
+                    self.camera.projection = self.camera.projection.next();
+                    log::info!("Camera projection: {}", self.camera.projection.name());
+                }
+                KeyCode::Digit9 => {
+                    self.photo_projection = self.photo_projection.next();
+                    let (width, height) = self.photo_size();
+                    log::info!("Photo projection: {} ({}x{})", self.photo_projection.label(), width, height);
+                }
+                KeyCode::KeyT if !self.ctx.ray_tracing => {
+                    log::warn!("Long exposures need a GPU with ray tracing");
+                }
+                KeyCode::KeyT if self.long_exposure.is_none() && self.photo.is_none() => {
+                    log::info!("Long exposure: accumulating {} frames over {}s of scene time...", LONG_EXPOSURE_FRAMES, LONG_EXPOSURE_DURATION);
+                    self.long_exposure = Some(LongExposure {
+                        frames_done: 0,
+                        time_step: LONG_EXPOSURE_DURATION / LONG_EXPOSURE_FRAMES as f32,
+                        started: Instant::now(),
+                    });
+                    self.noise_estimate = None;
+                }
+                KeyCode::KeyI => {
+                    let center = Vec2::new(self.render_extent.width as f32, self.render_extent.height as f32) * 0.5;
+                    self.animation_paused = self.teaching.toggle(center, self.animation_paused);
+                    if self.teaching.active {
+                        log::info!("Teaching mode: on (scene frozen, the mouse moves the probe, O: next stage)");
+                    } else {
+                        log::info!("Teaching mode: off");
+                    }
+                }
+                // Teaching mode holds the scene paused until it's turned off
+                KeyCode::Space if !self.teaching.active => {
+                    self.animation_paused = !self.animation_paused;
+                    log::info!("Animation: {}", if self.animation_paused { "paused (\\: step one frame)" } else { "playing" });
+                }
+                KeyCode::Backslash if self.animation_paused => {
+                    self.animation_step = true;
+                    log::info!("Animation: stepped to {:.3}s", self.scene_time + ANIMATION_STEP);
+                }
+                KeyCode::KeyO if self.teaching.active => {
+                    self.teaching.next_stage();
+                }
+                KeyCode::KeyL if self.ctrl_held => {
+                    if self.ctx.ray_tracing {
+                        self.traversal_heatmap = !self.traversal_heatmap;
+                        log::info!("Traversal heatmap: {}", if self.traversal_heatmap {
+                            format!("on (candidate hits per camera ray, blue: none, red: {} or more)", TRAVERSAL_HEATMAP_MAX)
+                        } else {
+                            "off".to_string()
+                        });
+                    } else {
+                        log::warn!("The traversal heatmap needs ray tracing pipelines");
+                    }
+                }
+                KeyCode::KeyL => {
+                    self.light_path_channel = (self.light_path_channel + 1) % LIGHT_PATH_CHANNELS.len();
+                    log::info!("Light path channel: {}", LIGHT_PATH_CHANNELS[self.light_path_channel]);
+                }
+                #[cfg(feature = "physics")]
+                KeyCode::KeyR => {
+                    self.physics = crate::physics::PhysicsWorld::new(&self.scene);
+                    log::info!("Physics: dropping the rigid bodies again");
+                }
+                #[cfg(feature = "denoise")]
+                KeyCode::KeyN => {
+                    self.denoise = !self.denoise;
+                    log::info!("Long exposure denoising: {}", if self.denoise { "on" } else { "off" });
+                }
+                KeyCode::KeyC => {
+                    self.camera.lens_profile = (self.camera.lens_profile + 1) % LENS_PROFILES.len();
+                    log::info!("Camera profile: {}", self.camera.lens().name);
+                }
+                KeyCode::KeyF => {
+                    self.depth_of_field = !self.depth_of_field;
+                    self.focus_point = None;
+                    log::info!("Depth of field (autofocus): {}", if self.depth_of_field { "on" } else { "off" });
+                }
+                KeyCode::KeyV => {
+                    self.anaglyph = !self.anaglyph;
+                    log::info!("Anaglyph stereo: {}", if self.anaglyph { "on" } else { "off" });
+                }
+                KeyCode::KeyJ => {
+                    self.hybrid = !self.hybrid;
+                    log::info!("Hybrid rendering: {}", if self.hybrid { "on" } else { "off" });
+                }
+                KeyCode::KeyU => {
+                    self.show_memory = !self.show_memory;
+                    log::info!("GPU memory: {}", self.ctx.memory.describe());
+                }
+                KeyCode::Insert if !self.teaching.active && !self.accumulating() => {
+                    let material_index = self.selection.map_or(self.material_preview.material_index, |selection| self.scene.objects[selection.object_index].material_index);
+                    let pose = self.material_preview.toggle(&mut self.scene, material_index, self.camera.pose());
+                    self.camera.set_pose(pose);
+                    self.bookmarks.cancel();
+                    log::info!("Material preview: {}", if self.material_preview.active { "on ([/]: material, Enter: apply to the selected object)" } else { "off" });
+                }
+                KeyCode::Tab => {
+                    self.editor.active = !self.editor.active;
+                    self.light_controls &= !self.editor.active;
+                    log::info!("Editor: {}", if self.editor.active { "on" } else { "off" });
+                }
+                KeyCode::KeyG => {
+                    // The editor shares the arrow and bracket keys
+                    self.light_controls = !self.light_controls;
+                    self.editor.active &= !self.light_controls;
+                    log::info!("Light controls: {}", if self.light_controls { "on (arrows, Page Up/Down: move, +/-: intensity, [/]: radius, K: color)" } else { "off" });
+                }
+                KeyCode::KeyP => {
+                    self.weather.cycle(&mut self.scene);
+                    log::info!("Weather: {:?}", self.weather.kind);
+                }
+                KeyCode::End => {
+                    self.particles.enabled = !self.particles.enabled;
+                    log::info!("Sparks: {} ({} particles)", if self.particles.enabled { "on" } else { "off" }, self.scene.particles.len());
+                }
+                KeyCode::KeyH => {
+                    self.heat_haze = !self.heat_haze;
+                    log::info!("Heat haze: {}", if self.heat_haze { "on" } else { "off" });
+                }
+                KeyCode::KeyB => {
+                    self.motion_blur = !self.motion_blur;
+                    log::info!("Motion blur: {}", if self.motion_blur { "on" } else { "off" });
+                }
+                KeyCode::Delete => {
+                    self.bloom = !self.bloom;
+                    log::info!("Bloom: {}", if self.bloom { "on" } else { "off" });
+                }
+                KeyCode::F7 => {
+                    let next = SAMPLES_PER_PIXEL.iter().position(|&spp| spp == self.samples_per_pixel).map_or(0, |i| (i + 1) % SAMPLES_PER_PIXEL.len());
+                    self.samples_per_pixel = SAMPLES_PER_PIXEL[next];
+                    log::info!("Samples per pixel: {}", self.samples_per_pixel);
+                }
+                KeyCode::F3 => {
+                    self.accessibility.reduced_motion = !self.accessibility.reduced_motion;
+                    log::info!("Reduced motion: {}", if self.accessibility.reduced_motion { "on" } else { "off" });
+                }
+                KeyCode::F4 => {
+                    self.accessibility.high_contrast = !self.accessibility.high_contrast;
+                    log::info!("High contrast: {}", if self.accessibility.high_contrast { "on" } else { "off" });
+                }
+                KeyCode::F6 => {
+                    self.accessibility.next_ui_scale();
+                    log::info!("UI scale: {}x", self.accessibility.ui_scale);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Mouse look, or in teaching mode moving the probe over the frozen image. The camera holds
+    /// still during a photo.
+    pub fn handle_mouse_motion(&mut self, dx: f64, dy: f64) {
+        if self.photo.is_some() {
+            return;
+        }
+        if self.teaching.active {
+            let extent = Vec2::new(self.render_extent.width as f32, self.render_extent.height as f32);
+            // The probe is a traced pixel, the mouse moves in window pixels
+            let picture = display::letterbox(self.render_extent, self.swapchain_extent);
+            let scale = (self.render_extent.width as f64) / (picture.extent.width as f64);
+            self.teaching.move_probe(dx * scale, dy * scale, extent);
+        } else {
+            self.bookmarks.cancel();
+            self.camera.handle_mouse_input(dx, dy);
+        }
+    }
+    
+    pub fn handle_window_event(&mut self, event: &winit::event::WindowEvent) {
+        match event {
+            winit::event::WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Vec2::new(position.x as f32, position.y as f32);
+            }
+            winit::event::WindowEvent::ModifiersChanged(modifiers) => {
+                self.ctrl_held = modifiers.state().control_key();
+            }
+            winit::event::WindowEvent::MouseInput { state: ElementState::Pressed, button: winit::event::MouseButton::Left, .. } => {
+                // While the cursor is captured for mouse look it stays put, so this picks the screen center
+                self.pick_request = Some(self.cursor_position);
+            }
+            _ => {}
+        }
+    }
+
+    /// Selects the object the last pick ray hit; with depth of field on, focus follows the hit point.
+    fn finish_pick(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let query = unsafe {
+            let ptr = self.ctx.device.map_memory(self.pick_buffer.1, 0, size_of::<PickQuery>() as u64, vk::MemoryMapFlags::empty())?;
+            let query = *(ptr as *const PickQuery);
+            self.ctx.device.unmap_memory(self.pick_buffer.1);
+            query
+        };
+        if query.position.w < 0.0 || query.instance_index < 0 {
+            log::info!("Picked: nothing");
+            self.selection = None;
+            self.focus_point = None;
+            return Ok(());
+        }
+        if query.instance_index as usize >= self.scene.objects.len() {
+            // Batch instances aren't scene objects, so they can't be edited or followed by the focus
+            log::info!("Picked: batch instance {}, triangle {} at {:.2} m",
+                query.instance_index as usize - self.scene.objects.len(), query.primitive_index, query.position.w);
+            self.selection = None;
+            self.focus_point = None;
+            return Ok(());
+        }
+
+        let selection = Selection {
+            object_index: query.instance_index as usize,
+            primitive_index: query.primitive_index as u32,
+            position: query.position.truncate(),
+        };
+        let object = &self.scene.objects[selection.object_index];
+        log::info!("Picked: object {} (mesh {}, material {}), triangle {} at {:.2} m",
+            selection.object_index, object.mesh_index, object.material_index, selection.primitive_index, query.position.w);
+        if self.depth_of_field {
+            self.focus_point = Some((selection.object_index, object.transform.inverse().transform_point3(selection.position)));
+        }
+        self.selection = Some(selection);
+        Ok(())
+    }
+
+    pub fn render(&mut self, window: &Window) -> Result<(), Box<dyn std::error::Error>> {
+        self.camera.update_vectors();
+        
+        unsafe { self.ctx.device.wait_for_fences(&[self.in_flight_fences[self.current_frame]], true, u64::MAX)?; }
+        self.free_stream_garbage();
+        if self.pick_in_flight == Some(self.current_frame) {
+            self.pick_in_flight = None;
+            self.finish_pick()?;
+        }
+        let rays = self.read_ray_count()?;
+        self.rays_counted += rays;
+        let gpu_times = if std::mem::take(&mut self.timestamps_written[self.current_frame]) {
+            let gpu_times = self.read_gpu_times()?;
+            self.update_dynamic_resolution(gpu_times.total_ms());
+            Some(gpu_times)
+        } else {
+            None
+        };
+        if let Some(mut frame) = self.pending_metrics[self.current_frame].take() {
+            frame.gpu = gpu_times;
+            frame.rays = rays;
+            if let Some(metrics) = &mut self.metrics {
+                metrics.write(&frame);
+            }
+        }
+        let cpu_started = Instant::now();
+        let frame_ms = self.frame_started.replace(cpu_started).map_or(0.0, |started| (cpu_started - started).as_secs_f32() * 1000.0);
+        if self.swapchain_stale {
+            self.recreate_swapchain(window.inner_size())?;
+        }
+        // Picks up a render extent held through a resize once it is free to follow the window again
+        self.fit_extents();
+        
+        let (image_index, _) = match unsafe { self.ctx.swapchain_loader.acquire_next_image(self.swapchain, u64::MAX, self.image_available_semaphores[self.current_frame], vk::Fence::null()) } {
+            Ok(result) => result,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.swapchain_stale = true;
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        unsafe { self.ctx.device.reset_fences(&[self.in_flight_fences[self.current_frame]])?; }
+
+        let cmd_buffer = self.command_buffers[self.current_frame];
+        unsafe { self.ctx.device.reset_command_buffer(cmd_buffer, vk::CommandBufferResetFlags::empty())?; }
+
+        // Advance scene time: a fixed step per frame during a long exposure, wall clock otherwise
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_frame_time).as_secs_f32();
+        self.last_frame_time = now;
+        let time_step = match &self.long_exposure {
+            Some(exposure) if exposure.frames_done > 0 => exposure.time_step,
+            Some(_) => 0.0,
+            None if self.photo.is_some() => 0.0,
+            None if self.animation_paused => if std::mem::take(&mut self.animation_step) { ANIMATION_STEP } else { 0.0 },
+            None => dt,
+        };
+        self.scene_time += time_step;
+        // A motion blurred photo poses the animated objects, skinned meshes and grass again for
+        // each sample, at a time across its shutter interval. Simulations hold still.
+        let shutter_moved = match &self.photo {
+            Some(photo) => {
+                let sample_time = photo.sample_time();
+                let moved = sample_time != self.scene_time;
+                self.scene_time = sample_time;
+                moved
+            }
+            None => false,
+        };
+        if time_step > 0.0 {
+            self.animation_frame = self.animation_frame.wrapping_add(1);
+        }
+        self.scene.animate(self.scene_time);
+        #[cfg(feature = "physics")]
+        self.physics.step(time_step, &mut self.scene);
+        // Material file edits only need the material buffer uploaded again
+        let materials_reloaded = self.material_watcher.poll(&mut self.scene);
+        if materials_reloaded {
+            self.weather.materials_reloaded(&self.scene);
+        }
+        let weather_active = self.weather.active();
+        if weather_active {
+            self.weather.update(time_step, self.camera.position, &mut self.scene);
+        }
+        if weather_active || materials_reloaded {
+            self.material_buffer.write(&self.ctx, self.current_frame, &gpu_materials(&self.scene.materials));
+        } else if self.material_buffer.stale[self.current_frame] {
+            self.material_buffer.refresh(&self.ctx, self.current_frame, &gpu_materials(&self.scene.materials));
+        }
+        let particles_active = self.particles.active();
+        if particles_active {
+            self.particles.update(time_step, &mut self.scene);
+        }
+        // The grass stands still while time does, e.g. while paused
+        let wind_active = self.wind.active() && (time_step > 0.0 || shutter_moved);
+        if wind_active {
+            self.wind.update(self.scene_time, &mut self.scene);
+        }
+
+        // Editor changes reach disk before the TLAS rebuild they trigger, the likeliest place for a driver reset
+        let edited = self.editor.take_moved();
+        let studio_changed = self.material_preview.take_changed();
+        let alpha_testing_changed = self.material_watcher.take_instances_changed();
+        if edited {
+            self.navigation.rebuild(&self.scene);
+        }
+        // Walking falls under gravity while the camera is free to move, unless it's gliding to a bookmark
+        if !self.teaching.active && !self.material_preview.active && self.photo.is_none() && self.long_exposure.is_none() {
+            if let Some(pose) = self.bookmarks.update(dt) {
+                self.camera.set_pose(pose);
+            } else {
+                self.camera.position = self.navigation.update(&self.scene, self.camera.position, dt);
+            }
+        }
+        let session = self.session();
+        if edited {
+            self.autosave.edited(&self.scene, &session);
+        } else {
+            self.autosave.tick(&self.scene, &session);
+        }
+
+        // Autofocus: ease towards the clicked point's depth, or else the hit distance probed
+        // the last time this frame slot rendered
+        if self.depth_of_field || self.camera.projection == Projection::ThinLens {
+            let target = if let Some((object_index, local_point)) = self.focus_point {
+                let point = self.scene.objects[object_index].transform.transform_point3(local_point);
+                (point - self.camera.position).dot(self.camera.forward).max(0.1)
+            } else {
+                let probe = unsafe {
+                    let ptr = self.ctx.device.map_memory(self.focus_probe_buffer.1, (self.current_frame * size_of::<f32>()) as u64, size_of::<f32>() as u64, vk::MemoryMapFlags::empty())?;
+                    let value = *(ptr as *const f32);
+                    self.ctx.device.unmap_memory(self.focus_probe_buffer.1);
+                    value
+                };
+                if probe > 0.0 { probe } else { AUTOFOCUS_MISS_DISTANCE }
+            };
+            if self.accessibility.reduced_motion {
+                self.focus_distance = target;
+            } else {
+                self.focus_distance += (target - self.focus_distance) * (1.0 - (-dt * AUTOFOCUS_SPEED).exp());
+            }
+        }
+
+        // Auto exposure: adapt to the histogram of the last time this frame slot rendered, then
+        // clear it for this frame's exposure pass. Accumulated images keep the exposure they started with.
+        if self.exposure.auto {
+            let slot_size = (exposure::HISTOGRAM_BINS * size_of::<u32>()) as u64;
+            let histogram = unsafe {
+                let ptr = self.ctx.device.map_memory(self.histogram_buffer.1, self.current_frame as u64 * slot_size, slot_size, vk::MemoryMapFlags::empty())?;
+                let value = *(ptr as *const [u32; exposure::HISTOGRAM_BINS]);
+                std::ptr::write_bytes(ptr as *mut u32, 0, exposure::HISTOGRAM_BINS);
+                self.ctx.device.unmap_memory(self.histogram_buffer.1);
+                value
+            };
+            if !self.accumulating() {
+                self.exposure.adapt(&histogram, dt, self.accessibility.reduced_motion);
+            }
+        }
+
+        // Teaching probe: the terms of the probe pixel the last time this frame slot rendered
+        if self.teaching.active {
+            let slot_size = (teaching::PROBE_TERMS * size_of::<Vec4>()) as u64;
+            let terms = unsafe {
+                let ptr = self.ctx.device.map_memory(self.teaching_probe_buffer.1, self.current_frame as u64 * slot_size, slot_size, vk::MemoryMapFlags::empty())?;
+                let value = *(ptr as *const [Vec4; teaching::PROBE_TERMS]);
+                self.ctx.device.unmap_memory(self.teaching_probe_buffer.1);
+                value
+            };
+            self.teaching.read_back(self.current_frame, terms);
+            // This frame traces the probe where it is now
+            self.teaching.traced(self.current_frame);
+        }
+
+        // Update Uniforms
+        let samples_done = self.accumulated_samples().unwrap_or(0);
+        // Accumulated images number their random sequence by sample, so they come out the same
+        // whenever they are started. Reduced motion and the frozen teaching mode keep the noise
+        // pattern still.
+        let rng_frame = if self.accumulating() {
+            samples_done
+        } else if self.accessibility.reduced_motion || self.teaching.active {
+            0
+        } else {
+            self.frame_index
+        };
+
+        // While a photo is on review the post image holds it instead of post pass output
+        let photo_review = self.photo_review_until.is_some_and(|until| Instant::now() < until);
+        // Temporal upscaling jitters the camera rays; accumulated captures jitter them on their own
+        let upscaling = self.render_extent != self.output_extent;
+        // The raster preview has no motion vectors to reproject with
+        let temporal_upscaling = upscaling && self.upscaler == Upscaler::Temporal && !self.accumulating() && !photo_review && self.ctx.ray_tracing;
+        let jitter = if temporal_upscaling {
+            upscale::jitter(self.frame_index, upscale::jitter_phases(self.render_extent.width, self.output_extent.width))
+        } else {
+            Vec2::ZERO
+        };
+
+        // Split screen views are traced side by side, the controlled one's first; photos show the controlled view alone
+        let viewports = match (&self.split_screen, &self.photo) {
+            (Some(split), None) => Some(split.viewports(self.render_extent.width)),
+            _ => None,
+        };
+        // A photo's frame has its own aspect ratio, traced tile by tile
+        let aspect = match &self.photo {
+            Some(photo) => photo.width as f32 / photo.height as f32,
+            None => viewports.map_or(self.render_extent.width, |viewports| viewports[0].1) as f32 / self.render_extent.height as f32,
+        };
+        let proj = self.camera.proj_matrix(aspect);
+        let view = self.camera.view_matrix();
+        let view_proj = proj * view;
+        // Panorama photos see all around from the camera position, without a lens. Of the
+        // camera's projections only the perspective ones have a lens.
+        let projection_params = match &self.photo {
+            Some(photo) if photo.projection != PhotoProjection::Perspective => photo.projection.params(),
+            _ => self.camera.projection_params(),
+        };
+        let perspective = projection_params == Vec4::ZERO;
+        // The material preview lights its studio with a key light of its own
+        let light = self.material_preview.light(&self.light);
+        let ubo = CameraProperties {
+            view_inverse: view.inverse(),
+            proj_inverse: proj.inverse(),
+            light_pos: light.position_param(),
+            light_color: light.color_param(),
+            photon_params: Vec4::new(
+                if self.caustics { 1.0 } else { 0.0 },
+                PHOTON_CELL_SIZE,
+                PHOTON_GRID_CELLS as f32,
+                self.caustic_target_count as f32,
+            ),
+            output_params: Vec4::new(
+                if self.teaching.active { self.teaching.stage.channel() } else { self.light_path_channel as f32 },
+                if self.anaglyph && perspective { 1.0 } else { 0.0 },
+                STEREO_EYE_SEPARATION,
+                STEREO_CONVERGENCE_DISTANCE,
+            ),
+            sky_params: Vec4::new(
+                if self.sky_occlusion { 1.0 } else { 0.0 },
+                SKY_CACHE_CELL_SIZE,
+                SKY_CACHE_CELLS as f32,
+                SKY_CACHE_MAX_SAMPLES as f32,
+            ),
+            lens_params: Vec4::new(
+                match self.camera.projection {
+                    _ if !perspective => 0.0,
+                    Projection::ThinLens => self.camera.physical_lens.aperture_radius(),
+                    _ if self.depth_of_field => DOF_APERTURE_RADIUS,
+                    _ => 0.0,
+                },
+                self.focus_distance,
+                self.current_frame as f32,
+                0.0,
+            ),
+            distortion_params: if perspective { self.camera.distortion_params() } else { Vec4::ZERO },
+            gizmo_params: match self.selection {
+                Some(selection) if self.editor.active => {
+                    self.scene.objects[selection.object_index].transform.w_axis.truncate().extend(GIZMO_SIZE)
+                }
+                _ => Vec4::ZERO,
+            },
+            gizmo_state: Vec4::new(
+                match self.editor.mode {
+                    GizmoMode::Translate => 0.0,
+                    GizmoMode::Rotate => 1.0,
+                    GizmoMode::Scale => 2.0,
+                },
+                self.editor.axis as f32,
+                self.accessibility.ui_scale,
+                if self.accessibility.high_contrast { 1.0 } else { 0.0 },
+            ),
+            // The studio is indoors
+            weather_params: if self.material_preview.active {
+                Vec4::ZERO
+            } else {
+                Vec4::new(self.weather.overcast, self.weather.wetness, self.weather.snow_cover, 0.0)
+            },
+            prev_view_proj: self.prev_view_proj.replace(view_proj).unwrap_or(view_proj),
+            teaching_params: Vec4::new(
+                self.teaching.probe.x.floor(),
+                self.teaching.probe.y.floor(),
+                if self.teaching.active { 1.0 } else { 0.0 },
+                self.current_frame as f32,
+            ),
+            tile_params: match &self.photo {
+                Some(photo) => photo.tile_params(),
+                None => Vec4::new(viewports.map_or(self.render_extent.width, |viewports| viewports[0].1) as f32, self.render_extent.height as f32, 0.0, 0.0),
+            },
+            viewport_params: Vec4::new(viewports.map_or(0, |viewports| viewports[0].0) as f32, 0.0, 0.0, 0.0),
+            projection_params,
+            // Only changes while the scene is animated, so a paused scene keeps the UBO untouched
+            time_params: Vec4::new(self.scene_time, self.animation_frame as f32, 0.0, 0.0),
+            studio_params: Vec4::new(if self.material_preview.active { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0),
+        };
+        let mut ubos = vec![ubo];
+        let mut other_settings = self.settings;
+        if let (Some(split), Some(viewports)) = (&mut self.split_screen, viewports) {
+            // The other view: its own camera, toggles and channel. Depth of field and its autofocus probe follow the controlled view.
+            let other = &mut split.other;
+            let (x, width) = viewports[1];
+            let proj = other.camera.proj_matrix(width as f32 / self.render_extent.height as f32);
+            let view = other.camera.view_matrix();
+            let mut output_params = ubo.output_params;
+            output_params.x = other.light_path_channel as f32;
+            ubos.push(CameraProperties {
+                view_inverse: view.inverse(),
+                proj_inverse: proj.inverse(),
+                output_params,
+                lens_params: Vec4::new(0.0, ubo.lens_params.y, ubo.lens_params.z, 0.0),
+                distortion_params: other.camera.distortion_params(),
+                projection_params: other.camera.projection_params(),
+                prev_view_proj: other.prev_view_proj.replace(proj * view).unwrap_or(proj * view),
+                tile_params: Vec4::new(width as f32, self.render_extent.height as f32, 0.0, 0.0),
+                viewport_params: Vec4::new(x as f32, 0.0, 0.0, 0.0),
+                ..ubo
+            });
+            other_settings = other.settings;
+        }
+        // Frames in flight read their own slot, one that missed a change catches up when its frame comes round
+        let camera_changed = bytemuck::cast_slice::<CameraProperties, u8>(&self.uploaded_camera) != bytemuck::cast_slice::<CameraProperties, u8>(&ubos);
+        if camera_changed || self.uniform_buffer.stale[self.current_frame] {
+            let stride = self.camera_ubo_stride as usize;
+            let mut bytes = vec![0u8; ubos.len() * stride];
+            for (slot, ubo) in bytes.chunks_exact_mut(stride).zip(&ubos) {
+                slot[..size_of::<CameraProperties>()].copy_from_slice(bytemuck::bytes_of(ubo));
+            }
+            if camera_changed {
+                self.uniform_buffer.write(&self.ctx, self.current_frame, &bytes);
+            } else {
+                self.uniform_buffer.refresh(&self.ctx, self.current_frame, &bytes);
+            }
+            self.uploaded_camera = ubos;
+        }
+        // Hybrid rendering covers a lone pinhole camera ray per pixel, which the G-buffer pass can
+        // stand in for. Other frames are fully traced.
+        let hybrid = self.hybrid && self.ctx.ray_tracing && !self.traversal_heatmap && !self.accumulating() && self.samples_per_pixel == 1
+            && !self.depth_of_field && !self.anaglyph && ubo.distortion_params == Vec4::ZERO && viewports.is_none()
+            && self.camera.projection == Projection::Perspective;
+        let frame_params = FramePushConstants {
+            settings: self.settings,
+            exposure_params: Vec4::new(
+                if self.accumulating() { 1.0 } else { 0.0 },
+                samples_done as f32,
+                if self.accumulating() { 1.0 } else { self.samples_per_pixel as f32 },
+                self.exposure.multiplier(),
+            ),
+            seed_params: UVec4::new(rng::frame_seed(self.seed, rng_frame), self.seed, rng_frame, 0),
+            jitter_params: jitter.extend(if hybrid { 1.0 } else { 0.0 }).extend(0.0),
+            counter_params: {
+                let address = self.ray_counter_address + (self.current_frame * size_of::<u32>()) as u64;
+                let heatmap_max = if self.traversal_heatmap { TRAVERSAL_HEATMAP_MAX } else { 0 };
+                UVec4::new(address as u32, (address >> 32) as u32, heatmap_max, 0)
+            },
+        };
+        // Camera UBO slot, width and push constants of each view's trace
+        let view_traces = match viewports {
+            Some(viewports) => vec![
+                (0, viewports[0].1, frame_params),
+                (1, viewports[1].1, FramePushConstants { settings: other_settings, ..frame_params }),
+            ],
+            None => vec![(0, self.render_extent.width, frame_params)],
+        };
+
+        // Picking: unproject the clicked pixel into the ray the pick shader traces this frame
+        let pick_cursor = if self.pick_in_flight.is_none() { self.pick_request.take() } else { None };
+        if let Some(cursor) = pick_cursor {
+            // The cursor is in window pixels, whatever the render scale, and the picture may be letterboxed
+            let picture = display::letterbox(self.render_extent, self.swapchain_extent);
+            let cursor = cursor - Vec2::new(picture.offset.x as f32, picture.offset.y as f32);
+            let extent = Vec2::new(picture.extent.width as f32, picture.extent.height as f32);
+            let mut ndc = (cursor + 0.5) / extent * 2.0 - 1.0;
+            let mut camera = &self.camera;
+            let mut pick_aspect = aspect;
+            if let (Some(split), Some(viewports)) = (&self.split_screen, viewports) {
+                // Clicks pick through whichever view they land in
+                let active = split.in_active_view(cursor.x, extent.x);
+                let (x, width) = viewports[if active { 0 } else { 1 }];
+                let scale = extent.x / self.render_extent.width as f32;
+                ndc.x = (cursor.x + 0.5 - x as f32 * scale) / (width as f32 * scale) * 2.0 - 1.0;
+                pick_aspect = width as f32 / self.render_extent.height as f32;
+                if !active {
+                    camera = &split.other.camera;
+                }
+            }
+            let (origin, direction) = camera.primary_ray(ndc, pick_aspect);
+            let mut query = PickQuery {
+                origin: origin.extend(1.0),
+                direction: direction.extend(0.0),
+                position: Vec4::new(0.0, 0.0, 0.0, -1.0),
+                instance_index: -1,
+                primitive_index: -1,
+                _pad: [0; 2],
+            };
+            // The raster preview can't trace the pick ray, it's answered here and read back all the same
+            if !self.ctx.ray_tracing {
+                if let Some(hit) = preview::pick(&self.scene, origin, direction) {
+                    query.position = hit.position.extend(hit.distance);
+                    query.instance_index = hit.instance_index as i32;
+                    query.primitive_index = hit.primitive_index as i32;
+                }
+            }
+            upload_data(&self.ctx, self.pick_buffer.1, &[query]);
+            self.pick_in_flight = Some(self.current_frame);
+        }
+
+        let begin_info = vk::CommandBufferBeginInfo {
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            ..Default::default()
+        };
+        unsafe { self.ctx.device.begin_command_buffer(cmd_buffer, &begin_info)?; }
+        let timestamps = (self.dynamic_resolution.is_some() || self.metrics.is_some()) && self.ctx.capabilities.timestamps;
+        if timestamps {
+            unsafe {
+                self.ctx.device.cmd_reset_query_pool(cmd_buffer, self.timestamp_pool, self.timestamp_query(0), TIMESTAMPS_PER_FRAME);
+                self.ctx.device.cmd_write_timestamp(cmd_buffer, vk::PipelineStageFlags::TOP_OF_PIPE, self.timestamp_pool, self.timestamp_query(0));
+            }
+        }
+        self.ctx.checkpoint(cmd_buffer, c"frame start");
+
+        // Trace Rays
+        unsafe {
+            // The raster preview draws skinned meshes in their rest pose
+            if !self.skinned_meshes.is_empty() && self.ctx.ray_tracing {
+                self.ctx.checkpoint(cmd_buffer, c"skinning");
+                self.skin_meshes(cmd_buffer);
+            }
+            // Streamed meshes come and go with their BLASes, which changes TLAS instances' references
+            let streamed = self.stream_geometry(cmd_buffer, edited)?;
+            // So do level of detail switches
+            let lod_changed = self.lod.update(&self.scene, self.camera.position);
+            // Editor moves, respawning precipitation and particles and the material preview's studio appearing can be arbitrarily large, so they rebuild the TLAS instead of refitting it.
+            // So do materials switching alpha testing, for their instances' opaque flags.
+            let rebuild = edited || studio_changed || alpha_testing_changed || weather_active || particles_active || streamed || lod_changed;
+            if rebuild || wind_active || !self.scene.animations.is_empty() || !self.skinned_meshes.is_empty() || !self.scene.rigid_bodies.is_empty() {
+                self.ctx.checkpoint(cmd_buffer, c"TLAS update");
+                self.update_tlas(cmd_buffer, rebuild);
+            } else {
+                // Nothing moves this frame: previous and current transforms are the same again, in
+                // the frame slots that missed the last move too
+                if self.prev_transform_buffer.stale[self.current_frame] {
+                    self.prev_transform_buffer.refresh(&self.ctx, self.current_frame, &self.last_instance_transforms);
+                }
+                if self.transform_buffer.stale[self.current_frame] {
+                    self.transform_buffer.refresh(&self.ctx, self.current_frame, &self.last_instance_transforms);
+                }
+            }
+            // The frame slots that missed the last rebuild catch up as their frames come round
+            if !rebuild && self.instance_data_buffer.stale[self.current_frame] {
+                self.instance_data_buffer.refresh(&self.ctx, self.current_frame, &instance_data(&self.scene, self.lod.meshes()));
+            }
+
+            if hybrid {
+                let shift = Vec3::new(-2.0 * jitter.x / self.render_extent.width as f32, -2.0 * jitter.y / self.render_extent.height as f32, 0.0);
+                self.ctx.checkpoint(cmd_buffer, c"G-buffer pass");
+                self.rasterize_gbuffer(cmd_buffer, Mat4::from_translation(shift) * view_proj);
+            }
+
+            if !self.ctx.ray_tracing {
+                self.ctx.checkpoint(cmd_buffer, c"raster preview");
+                self.draw_preview(cmd_buffer, view_proj);
+            } else {
+                // Bound after the skinning and G-buffer passes, whose push constants would replace the frame's
+                self.ctx.device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::RAY_TRACING_KHR, self.pipeline);
+                self.ctx.device.cmd_bind_descriptor_sets(cmd_buffer, vk::PipelineBindPoint::RAY_TRACING_KHR, self.pipeline_layout, 0, &[self.descriptor_set], &self.descriptor_offsets(0));
+                self.ctx.device.cmd_push_constants(cmd_buffer, self.pipeline_layout, vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, 0, bytemuck::bytes_of(&frame_params));
+
+                // Sky cache: only cleared when (re)enabled, it keeps converging across frames
+                if self.sky_occlusion && self.sky_cache_dirty {
+                    let clear_barrier = vk::MemoryBarrier {
+                        src_access_mask: vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                        dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                        ..Default::default()
+                    };
+                    self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::PipelineStageFlags::TRANSFER, vk::DependencyFlags::empty(), &[clear_barrier], &[], &[]);
+                    self.ctx.device.cmd_fill_buffer(cmd_buffer, self.sky_cache_buffer.0, 0, vk::WHOLE_SIZE, 0);
+
+                    let trace_barrier = vk::MemoryBarrier {
+                        src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                        dst_access_mask: vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                        ..Default::default()
+                    };
+                    self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::DependencyFlags::empty(), &[trace_barrier], &[], &[]);
+                    self.sky_cache_dirty = false;
+                }
+
+                // Photon pass: clear the hash grid, then deposit this frame's caustic photons
+                if self.caustics && self.caustic_target_count > 0 {
+                    let clear_barrier = vk::MemoryBarrier {
+                        src_access_mask: vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                        dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                        ..Default::default()
+                    };
+                    self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::PipelineStageFlags::TRANSFER, vk::DependencyFlags::empty(), &[clear_barrier], &[], &[]);
+                    self.ctx.device.cmd_fill_buffer(cmd_buffer, self.photon_grid_buffer.0, 0, vk::WHOLE_SIZE, 0);
+
+                    let trace_barrier = vk::MemoryBarrier {
+                        src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                        dst_access_mask: vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                        ..Default::default()
+                    };
+                    self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::DependencyFlags::empty(), &[trace_barrier], &[], &[]);
+                    self.ctx.checkpoint(cmd_buffer, c"photon trace");
+                    self.ctx.rt_pipeline_loader.cmd_trace_rays(
+                        cmd_buffer,
+                        &self.photon_sbt_region,
+                        &self.sbt_regions[1],
+                        &self.sbt_regions[2],
+                        &self.sbt_regions[3],
+                        PHOTON_COUNT, 1, 1
+                    );
+
+                    let gather_barrier = vk::MemoryBarrier {
+                        src_access_mask: vk::AccessFlags::SHADER_WRITE,
+                        dst_access_mask: vk::AccessFlags::SHADER_READ,
+                        ..Default::default()
+                    };
+                    self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::DependencyFlags::empty(), &[gather_barrier], &[], &[]);
+                }
+
+                self.ctx.checkpoint(cmd_buffer, c"trace rays");
+                // Split screen views write disjoint columns of the images, no barrier between them
+                for &(slot, width, params) in &view_traces {
+                    self.ctx.device.cmd_bind_descriptor_sets(cmd_buffer, vk::PipelineBindPoint::RAY_TRACING_KHR, self.pipeline_layout, 0, &[self.descriptor_set], &self.descriptor_offsets(slot));
+                    self.ctx.device.cmd_push_constants(cmd_buffer, self.pipeline_layout, vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR, 0, bytemuck::bytes_of(&params));
+                    self.ctx.rt_pipeline_loader.cmd_trace_rays(
+                        cmd_buffer,
+                        &self.sbt_regions[0],
+                        &self.sbt_regions[1],
+                        &self.sbt_regions[2],
+                        &self.sbt_regions[3],
+                        width, self.render_extent.height, 1
+                    );
+                }
+
+                self.ctx.checkpoint(cmd_buffer, c"trace rays done");
+
+                if pick_cursor.is_some() {
+                    self.ctx.checkpoint(cmd_buffer, c"pick trace");
+                    self.ctx.rt_pipeline_loader.cmd_trace_rays(
+                        cmd_buffer,
+                        &self.pick_sbt_region,
+                        &self.sbt_regions[1],
+                        &self.sbt_regions[2],
+                        &self.sbt_regions[3],
+                        1, 1, 1
+                    );
+                    let readback_barrier = vk::MemoryBarrier {
+                        src_access_mask: vk::AccessFlags::SHADER_WRITE,
+                        dst_access_mask: vk::AccessFlags::HOST_READ,
+                        ..Default::default()
+                    };
+                    self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::PipelineStageFlags::HOST, vk::DependencyFlags::empty(), &[readback_barrier], &[], &[]);
+                }
+
+                // The ray count is read back after the fence
+                let count_barrier = vk::MemoryBarrier {
+                    src_access_mask: vk::AccessFlags::SHADER_WRITE,
+                    dst_access_mask: vk::AccessFlags::HOST_READ,
+                    ..Default::default()
+                };
+                self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::PipelineStageFlags::HOST, vk::DependencyFlags::empty(), &[count_barrier], &[], &[]);
+            }
+        }
+
+        if timestamps {
+            unsafe { self.ctx.device.cmd_write_timestamp(cmd_buffer, vk::PipelineStageFlags::BOTTOM_OF_PIPE, self.timestamp_pool, self.timestamp_query(1)) };
+        }
+
+        let (trace_stage, trace_access) = self.trace_stage();
+
+        // Exposure pass: the luminance histogram auto exposure adapts to, read back by `update`
+        if self.exposure.auto && !photo_review && self.ctx.ray_tracing {
+            unsafe {
+                let exposure_barrier = vk::MemoryBarrier {
+                    src_access_mask: vk::AccessFlags::SHADER_WRITE,
+                    dst_access_mask: vk::AccessFlags::SHADER_READ,
+                    ..Default::default()
+                };
+                self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::PipelineStageFlags::COMPUTE_SHADER, vk::DependencyFlags::empty(), &[exposure_barrier], &[], &[]);
+
+                self.ctx.checkpoint(cmd_buffer, c"exposure pass");
+                self.ctx.device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::COMPUTE, self.exposure_pipeline);
+                self.ctx.device.cmd_bind_descriptor_sets(cmd_buffer, vk::PipelineBindPoint::COMPUTE, self.exposure_pipeline_layout, 0, &[self.exposure_descriptor_set], &[]);
+                let push_constants = ExposurePushConstants {
+                    size: [self.render_extent.width, self.render_extent.height],
+                    min_log_luminance: exposure::MIN_LOG_LUMINANCE,
+                    log_luminance_range: exposure::MAX_LOG_LUMINANCE - exposure::MIN_LOG_LUMINANCE,
+                    slot: self.current_frame as u32,
+                };
+                self.ctx.device.cmd_push_constants(cmd_buffer, self.exposure_pipeline_layout, vk::ShaderStageFlags::COMPUTE, 0, bytemuck::bytes_of(&push_constants));
+                self.ctx.device.cmd_dispatch(cmd_buffer, self.render_extent.width.div_ceil(16), self.render_extent.height.div_ceil(16), 1);
+
+                let readback_barrier = vk::MemoryBarrier {
+                    src_access_mask: vk::AccessFlags::SHADER_WRITE,
+                    dst_access_mask: vk::AccessFlags::HOST_READ,
+                    ..Default::default()
+                };
+                self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::HOST, vk::DependencyFlags::empty(), &[readback_barrier], &[], &[]);
+            }
+        }
+
+        // Bloom: blur what is brighter than the display's white down and back up the mip chain
+        // and add it over the storage image, before the post pass distorts and blurs it along
+        if self.bloom && !photo_review && self.ctx.ray_tracing {
+            unsafe {
+                // Also waits for the previous frame's bloom passes
+                let bloom_barrier = vk::MemoryBarrier {
+                    src_access_mask: vk::AccessFlags::SHADER_WRITE,
+                    dst_access_mask: vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                    ..Default::default()
+                };
+                self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR | vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, vk::DependencyFlags::empty(), &[bloom_barrier], &[], &[]);
+
+                self.ctx.checkpoint(cmd_buffer, c"bloom");
+                self.ctx.device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::COMPUTE, self.bloom_pipeline);
+                let render_size = [self.render_extent.width, self.render_extent.height];
+                let mip_size = |mip: usize| [((render_size[0] / 2) >> mip).max(1), ((render_size[1] / 2) >> mip).max(1)];
+                let mips = self.bloom_image.2.len();
+                // (pass, source size, target size) of each step, in the order of the descriptor sets
+                let mut steps = vec![(0, render_size, mip_size(0))];
+                steps.extend((1..mips).map(|mip| (1, mip_size(mip - 1), mip_size(mip))));
+                steps.extend((0..mips - 1).rev().map(|mip| (2, mip_size(mip + 1), mip_size(mip))));
+                steps.push((3, mip_size(0), render_size));
+                for (&set, (pass, source_size, target_size)) in self.bloom_descriptor_sets.iter().zip(steps) {
+                    self.ctx.device.cmd_bind_descriptor_sets(cmd_buffer, vk::PipelineBindPoint::COMPUTE, self.bloom_pipeline_layout, 0, &[set], &[]);
+                    let push_constants = BloomPushConstants {
+                        source_size,
+                        target_size,
+                        pass,
+                        threshold: BLOOM_THRESHOLD,
+                        knee: BLOOM_KNEE,
+                        intensity: BLOOM_INTENSITY,
+                    };
+                    self.ctx.device.cmd_push_constants(cmd_buffer, self.bloom_pipeline_layout, vk::ShaderStageFlags::COMPUTE, 0, bytemuck::bytes_of(&push_constants));
+                    self.ctx.device.cmd_dispatch(cmd_buffer, target_size[0].div_ceil(8), target_size[1].div_ceil(8), 1);
+                    // Each step reads what the one before wrote; after the composite, the post pass reads the storage image
+                    self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, vk::DependencyFlags::empty(), &[bloom_barrier], &[], &[]);
+                }
+            }
+        }
+
+        // Post pass: distort the traced image where the haze mask is set and blur it along the
+        // motion vectors, then apply the post effects. Long exposures and photos integrate motion
+        // over their shutter instead.
+        let heat_haze = self.heat_haze && !self.scene.heat_volumes.is_empty();
+        let motion_blur = self.motion_blur && !self.accumulating() && !self.accessibility.reduced_motion;
+        // Haze and blur need the traced haze mask and motion vectors
+        let post_pass = (heat_haze || motion_blur || self.post_effects.any()) && !photo_review && self.ctx.ray_tracing;
+        if post_pass {
+            unsafe {
+                let post_barrier = vk::MemoryBarrier {
+                    src_access_mask: vk::AccessFlags::SHADER_WRITE,
+                    dst_access_mask: vk::AccessFlags::SHADER_READ,
+                    ..Default::default()
+                };
+                // Also waits for the previous frame's resolve pass out of the post image
+                self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR | vk::PipelineStageFlags::FRAGMENT_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, vk::DependencyFlags::empty(), &[post_barrier], &[], &[]);
+
+                self.ctx.checkpoint(cmd_buffer, c"post pass");
+                self.ctx.device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::COMPUTE, self.post_pipeline);
+                self.ctx.device.cmd_bind_descriptor_sets(cmd_buffer, vk::PipelineBindPoint::COMPUTE, self.post_pipeline_layout, 0, &[self.post_descriptor_set], &[]);
+                let haze_scale = if self.accessibility.reduced_motion { accessibility::REDUCED_MOTION_HAZE } else { 1.0 };
+                let push_constants = PostPushConstants {
+                    time: self.scene_time * haze_scale,
+                    haze_amplitude: if heat_haze { HEAT_HAZE_AMPLITUDE * haze_scale } else { 0.0 },
+                    blur_scale: if motion_blur { MOTION_BLUR_SHUTTER } else { 0.0 },
+                    max_blur: MOTION_BLUR_MAX_PIXELS,
+                    size: [self.render_extent.width, self.render_extent.height],
+                    grain_seed: rng::frame_seed(self.seed, rng_frame),
+                    _pad: 0,
+                    effects: self.post_effects.params(),
+                };
+                self.ctx.device.cmd_push_constants(cmd_buffer, self.post_pipeline_layout, vk::ShaderStageFlags::COMPUTE, 0, bytemuck::bytes_of(&push_constants));
+                self.ctx.device.cmd_dispatch(cmd_buffer, self.render_extent.width.div_ceil(8), self.render_extent.height.div_ceil(8), 1);
+            }
+        }
+
+        // Upscale pass: with a render scale below 1, bring whatever is shown to the swapchain size
+        if upscaling {
+            unsafe {
+                let upscale_barrier = vk::MemoryBarrier {
+                    src_access_mask: trace_access | vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::SHADER_READ,
+                    dst_access_mask: vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                    ..Default::default()
+                };
+                // Also waits for the previous frame's resolve pass out of the upscale image and its history write
+                self.ctx.device.cmd_pipeline_barrier(cmd_buffer, trace_stage | vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, vk::DependencyFlags::empty(), &[upscale_barrier], &[], &[]);
+
+                self.ctx.checkpoint(cmd_buffer, c"upscale pass");
+                self.ctx.device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::COMPUTE, self.upscale_pipeline);
+                self.ctx.device.cmd_bind_descriptor_sets(cmd_buffer, vk::PipelineBindPoint::COMPUTE, self.upscale_pipeline_layout, 0, &[self.upscale_descriptor_sets[self.upscale_history]], &[]);
+                let push_constants = UpscalePushConstants {
+                    jitter,
+                    render_size: [self.render_extent.width, self.render_extent.height],
+                    output_size: [self.output_extent.width, self.output_extent.height],
+                    from_post: (post_pass || photo_review) as u32,
+                    temporal: temporal_upscaling as u32,
+                    reset_history: !self.upscale_history_valid as u32,
+                };
+                self.ctx.device.cmd_push_constants(cmd_buffer, self.upscale_pipeline_layout, vk::ShaderStageFlags::COMPUTE, 0, bytemuck::bytes_of(&push_constants));
+                self.ctx.device.cmd_dispatch(cmd_buffer, self.output_extent.width.div_ceil(8), self.output_extent.height.div_ceil(8), 1);
+                if temporal_upscaling {
+                    self.upscale_history = 1 - self.upscale_history;
+                }
+                self.upscale_history_valid = temporal_upscaling;
+            }
+        }
+
+        // The display images stay in GENERAL, they are only ever used by their passes, photo review,
+        // readbacks and the resolve pass
+        let (displayed_image, display_source) = if upscaling {
+            (self.upscale_image.0, 3)
+        } else if post_pass || photo_review {
+            (self.post_image.0, 2)
+        } else {
+            (self.storage_image.0, 1)
+        };
+        self.displayed_image = displayed_image;
+        // HDR output resolves the HDR image raygen writes before clamping; the raster preview and
+        // the reviewed photo only have their display images
+        let resolve_source = if self.display_output.is_hdr() && self.ctx.ray_tracing && !photo_review { 0 } else { display_source };
+
+        unsafe {
+            if timestamps {
+                self.ctx.device.cmd_write_timestamp(cmd_buffer, vk::PipelineStageFlags::BOTTOM_OF_PIPE, self.timestamp_pool, self.timestamp_query(2));
+            }
+            let resolve_barrier = vk::MemoryBarrier {
+                src_access_mask: trace_access | vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                ..Default::default()
+            };
+            self.ctx.device.cmd_pipeline_barrier(cmd_buffer, trace_stage | vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::FRAGMENT_SHADER, vk::DependencyFlags::empty(), &[resolve_barrier], &[], &[]);
+            // The swapchain pass leaves the swapchain image ready to present
+            self.ctx.checkpoint(cmd_buffer, c"resolve to swapchain");
+            self.resolve_to_swapchain(cmd_buffer, image_index as usize, resolve_source)?;
+        }
+
+        unsafe {
+             if timestamps {
+                 self.ctx.device.cmd_write_timestamp(cmd_buffer, vk::PipelineStageFlags::BOTTOM_OF_PIPE, self.timestamp_pool, self.timestamp_query(3));
+                 self.timestamps_written[self.current_frame] = true;
+             }
+             self.ctx.checkpoint(cmd_buffer, c"frame end");
+        
+             self.ctx.device.end_command_buffer(cmd_buffer)?;
+        }
+
+        let submit_info = vk::SubmitInfo {
+            wait_semaphore_count: 1,
+            p_wait_semaphores: &self.image_available_semaphores[self.current_frame],
+            p_wait_dst_stage_mask: &vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            command_buffer_count: 1,
+            p_command_buffers: &cmd_buffer,
+            signal_semaphore_count: 1,
+            p_signal_semaphores: &self.render_finished_semaphores[self.current_frame],
+            ..Default::default()
+        };
+
+        unsafe { self.ctx.device.queue_submit(self.ctx.queue, &[submit_info], self.in_flight_fences[self.current_frame])?; }
+
+        let present_info = vk::PresentInfoKHR {
+            wait_semaphore_count: 1,
+            p_wait_semaphores: &self.render_finished_semaphores[self.current_frame],
+            swapchain_count: 1,
+            p_swapchains: &self.swapchain,
+            p_image_indices: &image_index,
+            ..Default::default()
+        };
+
+        match unsafe { self.ctx.swapchain_loader.queue_present(self.ctx.present_queue, &present_info) } {
+             Ok(suboptimal) => self.swapchain_stale |= suboptimal,
+             Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.swapchain_stale = true,
+             Err(e) => return Err(e.into()),
+        }
+
+        if let Some(metrics) = &self.metrics {
+            let samples_per_pixel = if self.accumulating() { 1 } else { self.samples_per_pixel };
+            self.pending_metrics[self.current_frame] = Some(FrameMetrics {
+                frame: self.frame_index,
+                time: metrics.elapsed(),
+                cpu_ms: cpu_started.elapsed().as_secs_f32() * 1000.0,
+                frame_ms,
+                gpu: None,
+                render_width: self.render_extent.width,
+                render_height: self.render_extent.height,
+                samples_per_pixel,
+                rays: 0, // Counted by the shaders, read back with the GPU times
+                vram_bytes: self.vram_used(),
+            });
+        }
+
+        self.current_frame = (self.current_frame + 1) % 2;
+        self.frame_index = self.frame_index.wrapping_add(1);
+
+        if let Some(exposure) = &mut self.long_exposure {
+            exposure.frames_done += 1;
+            if exposure.frames_done == LONG_EXPOSURE_FRAMES {
+                self.long_exposure = None;
+                if let Err(e) = self.save_long_exposure(LONG_EXPOSURE_FRAMES) {
+                    log::error!("Failed to save long exposure: {}", e);
+                }
+            }
+        }
+
+        if let Some(mut photo) = self.photo.take() {
+            photo.samples_done += 1;
+            if photo.samples_done < photo.samples {
+                self.photo = Some(photo);
+            } else {
+                match self.finish_photo_tile(&mut photo) {
+                    Ok(true) => {
+                        if let Err(e) = self.save_photo(photo) {
+                            log::error!("Failed to save photo: {}", e);
+                        }
+                    }
+                    Ok(false) => self.photo = Some(photo),
+                    Err(e) => {
+                        log::error!("Photo mode failed: {}", e);
+                        photo.cancel();
+                    }
+                }
+            }
+        }
+
+        if let Some(samples) = self.accumulated_samples().filter(|&samples| samples >= 2) {
+            if self.last_noise_estimate.elapsed() >= NOISE_ESTIMATE_INTERVAL {
+                self.last_noise_estimate = Instant::now();
+                match self.read_accumulation() {
+                    Ok(sums) => self.noise_estimate = progress::estimate_noise(&sums, samples),
+                    Err(e) => log::error!("Failed to estimate noise: {}", e),
+                }
+            }
+        }
+
+        if let Some(mut recording) = self.recording.take() {
+            if recording.wants_frame() {
+                // The storage, post and upscale images are BGRA, like the recording expects
+                let extent = self.displayed_extent();
+                // Its frames all have the size it started at
+                let saved = if (extent.width, extent.height) == recording.size() {
+                    self.read_back_image_extent(self.displayed_image, extent, 4)
+                        .and_then(|bgra| recording.add_frame(bgra, self.frame_index.wrapping_sub(1), self.scene_time))
+                } else {
+                    Err("the window was resized".into())
+                };
+                if let Err(e) = saved {
+                    log::error!("Recording failed: {}", e);
+                    self.recording = Some(recording);
+                    self.stop_recording();
+                    return Ok(());
+                }
+            }
+            self.recording = Some(recording);
+        }
+
+        Ok(())
+    }
+
+    /// Poses the skinned meshes for the current scene time with the skinning compute pass and
+    /// refits their BLASes. The TLAS update that follows picks up the new bounds.
+    fn skin_meshes(&self, cmd_buffer: vk::CommandBuffer) {
+        for gpu in &self.skinned_meshes {
+            let matrices = self.scene.skins[gpu.skin_index].joint_matrices(self.scene_time);
+            upload_data(&self.ctx, gpu.joint_buffers[self.current_frame].1, &matrices);
+        }
+
+        unsafe {
+            // The previous frame may still be reading the posed vertices
+            let skin_barrier = vk::MemoryBarrier {
+                src_access_mask: vk::AccessFlags::SHADER_READ,
+                dst_access_mask: vk::AccessFlags::SHADER_WRITE,
+                ..Default::default()
+            };
+            self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR | vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR, vk::PipelineStageFlags::COMPUTE_SHADER, vk::DependencyFlags::empty(), &[skin_barrier], &[], &[]);
+
+            self.ctx.device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::COMPUTE, self.skinning_pipeline);
+            for gpu in &self.skinned_meshes {
+                let push_constants = SkinningPushConstants {
+                    rest_vertices: gpu.rest_vertex_addr,
+                    skin_vertices: gpu.skin_vertices.2,
+                    joint_matrices: gpu.joint_buffers[self.current_frame].2,
+                    posed_vertices: gpu.posed_vertices.2,
+                    vertex_count: gpu.vertex_count,
+                    _pad: 0,
+                };
+                self.ctx.device.cmd_push_constants(cmd_buffer, self.skinning_pipeline_layout, vk::ShaderStageFlags::COMPUTE, 0, bytemuck::bytes_of(&push_constants));
+                self.ctx.device.cmd_dispatch(cmd_buffer, gpu.vertex_count.div_ceil(64), 1, 1);
+            }
+
+            let refit_barrier = vk::MemoryBarrier {
+                src_access_mask: vk::AccessFlags::SHADER_WRITE,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                ..Default::default()
+            };
+            self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR | vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::DependencyFlags::empty(), &[refit_barrier], &[], &[]);
+
+            for gpu in &self.skinned_meshes {
+                let blas = self.blas_list[gpu.mesh_index].0;
+                let geometry = vk::AccelerationStructureGeometryKHR {
+                    geometry_type: vk::GeometryTypeKHR::TRIANGLES,
+                    geometry: vk::AccelerationStructureGeometryDataKHR {
+                        triangles: vk::AccelerationStructureGeometryTrianglesDataKHR {
+                            vertex_format: vk::Format::R32G32B32_SFLOAT,
+                            vertex_data: vk::DeviceOrHostAddressConstKHR { device_address: gpu.posed_vertices.2 },
+                            vertex_stride: size_of::<Vertex>() as u64,
+                            max_vertex: gpu.vertex_count,
+                            index_type: index_type(&self.scene.meshes[gpu.mesh_index]),
+                            index_data: vk::DeviceOrHostAddressConstKHR { device_address: gpu.index_addr },
+                            ..Default::default()
+                        },
+                    },
+                    flags: vk::GeometryFlagsKHR::OPAQUE,
+                    ..Default::default()
+                };
+                let build_info = vk::AccelerationStructureBuildGeometryInfoKHR {
+                    ty: vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+                    flags: vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_BUILD | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE,
+                    mode: vk::BuildAccelerationStructureModeKHR::UPDATE,
+                    src_acceleration_structure: blas,
+                    dst_acceleration_structure: blas,
+                    geometry_count: 1,
+                    p_geometries: &geometry,
+                    scratch_data: vk::DeviceOrHostAddressKHR { device_address: gpu.blas_scratch.2 },
+                    ..Default::default()
+                };
+                let build_range = vk::AccelerationStructureBuildRangeInfoKHR {
+                    primitive_count: gpu.primitive_count,
+                    primitive_offset: 0,
+                    first_vertex: 0,
+                    transform_offset: 0,
+                };
+                self.ctx.as_loader.cmd_build_acceleration_structures(cmd_buffer, &[build_info], &[&[build_range]]);
+            }
+        }
+    }
+
+    /// Hybrid rendering: rasterizes the visible instances into the visibility image with the
+    /// jittered `view_proj`, for raygen to shade instead of tracing the camera rays.
+    fn rasterize_gbuffer(&self, cmd_buffer: vk::CommandBuffer, view_proj: Mat4) {
+        // Unloaded streamed meshes have no geometry to draw, like they have no BLAS to trace
+        let draws: Vec<RasterDraw> = hybrid::raster_draws(&self.scene, self.lod.meshes()).into_iter()
+            .filter(|draw| self.streaming.as_ref().is_none_or(|streaming| streaming.is_loaded(draw.mesh_index)))
+            .collect();
+        let extent = self.render_extent;
+        unsafe {
+            // Skinned vertices are written by the skinning pass, the visibility image was read by the last trace
+            let raster_barrier = vk::MemoryBarrier {
+                src_access_mask: vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                dst_access_mask: vk::AccessFlags::SHADER_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                ..Default::default()
+            };
+            self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::PipelineStageFlags::VERTEX_SHADER | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT, vk::DependencyFlags::empty(), &[raster_barrier], &[], &[]);
+
+            let clear_values = [
+                vk::ClearValue { color: vk::ClearColorValue { uint32: [0; 4] } },
+                vk::ClearValue { depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 } },
+            ];
+            let render_area = vk::Rect2D { offset: vk::Offset2D::default(), extent };
+            self.ctx.device.cmd_begin_render_pass(cmd_buffer, &vk::RenderPassBeginInfo {
+                render_pass: self.gbuffer_render_pass,
+                framebuffer: self.gbuffer_framebuffer,
+                render_area,
+                clear_value_count: clear_values.len() as u32,
+                p_clear_values: clear_values.as_ptr(),
+                ..Default::default()
+            }, vk::SubpassContents::INLINE);
+            self.ctx.device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::GRAPHICS, self.gbuffer_pipeline);
+            self.ctx.device.cmd_bind_descriptor_sets(cmd_buffer, vk::PipelineBindPoint::GRAPHICS, self.gbuffer_pipeline_layout, 0, &[self.gbuffer_descriptor_set], &self.gbuffer_descriptor_offsets());
+            self.ctx.device.cmd_set_viewport(cmd_buffer, 0, &[vk::Viewport {
+                x: 0.0,
+                y: 0.0,
+                width: extent.width as f32,
+                height: extent.height as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            }]);
+            self.ctx.device.cmd_set_scissor(cmd_buffer, 0, &[render_area]);
+            let push_constants = GbufferPushConstants { view_proj };
+            self.ctx.device.cmd_push_constants(cmd_buffer, self.gbuffer_pipeline_layout, vk::ShaderStageFlags::VERTEX, 0, bytemuck::bytes_of(&push_constants));
+            for draw in &draws {
+                self.ctx.device.cmd_draw(cmd_buffer, draw.index_count, draw.instance_count, 0, draw.first_instance);
+            }
+            self.ctx.device.cmd_end_render_pass(cmd_buffer);
+
+            let trace_barrier = vk::MemoryBarrier {
+                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                ..Default::default()
+            };
+            self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::DependencyFlags::empty(), &[trace_barrier], &[], &[]);
+        }
+    }
+
+    /// Stage and access the frame's image is written with: the trace, or the raster preview.
+    fn trace_stage(&self) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+        if self.ctx.ray_tracing {
+            (vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::AccessFlags::SHADER_WRITE)
+        } else {
+            (vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT, vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        }
+    }
+
+    /// Raster preview, in place of the trace on GPUs without ray tracing pipelines: draws the
+    /// visible instances flat shaded into the storage image.
+    fn draw_preview(&self, cmd_buffer: vk::CommandBuffer, view_proj: Mat4) {
+        let draws: Vec<RasterDraw> = hybrid::raster_draws(&self.scene, self.lod.meshes());
+        let extent = self.render_extent;
+        unsafe {
+            // The previous frame's upscale pass or resolve pass read the storage image
+            let draw_barrier = vk::MemoryBarrier {
+                src_access_mask: vk::AccessFlags::SHADER_READ,
+                dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                ..Default::default()
+            };
+            self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER, vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT, vk::DependencyFlags::empty(), &[draw_barrier], &[], &[]);
+
+            let clear_values = [
+                vk::ClearValue { color: vk::ClearColorValue { float32: color::linear_to_srgb_rgba(PREVIEW_SKY_COLOR) } },
+                vk::ClearValue { depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 } },
+            ];
+            let render_area = vk::Rect2D { offset: vk::Offset2D::default(), extent };
+            self.ctx.device.cmd_begin_render_pass(cmd_buffer, &vk::RenderPassBeginInfo {
+                render_pass: self.preview_render_pass,
+                framebuffer: self.preview_framebuffer,
+                render_area,
+                clear_value_count: clear_values.len() as u32,
+                p_clear_values: clear_values.as_ptr(),
+                ..Default::default()
+            }, vk::SubpassContents::INLINE);
+            self.ctx.device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::GRAPHICS, self.preview_pipeline);
+            self.ctx.device.cmd_bind_descriptor_sets(cmd_buffer, vk::PipelineBindPoint::GRAPHICS, self.preview_pipeline_layout, 0, &[self.gbuffer_descriptor_set], &self.gbuffer_descriptor_offsets());
+            self.ctx.device.cmd_set_viewport(cmd_buffer, 0, &[vk::Viewport {
+                x: 0.0,
+                y: 0.0,
+                width: extent.width as f32,
+                height: extent.height as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            }]);
+            self.ctx.device.cmd_set_scissor(cmd_buffer, 0, &[render_area]);
+            let push_constants = PreviewPushConstants {
+                view_proj,
+                light_pos: self.material_preview.light(&self.light).position_param(),
+                camera_pos: self.camera.position.extend(1.0),
+            };
+            self.ctx.device.cmd_push_constants(cmd_buffer, self.preview_pipeline_layout, vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT, 0, bytemuck::bytes_of(&push_constants));
+            for draw in &draws {
+                self.ctx.device.cmd_draw(cmd_buffer, draw.index_count, draw.instance_count, 0, draw.first_instance);
+            }
+            self.ctx.device.cmd_end_render_pass(cmd_buffer);
+        }
+    }
+
+    /// Draws the frame onto swapchain image `image_index`: the resolve pass scales image `source`
+    /// (`ResolvePushConstants::source`) to the largest rect of its aspect ratio the window has,
+    /// with black bars around it, then the HUD's glyphs, laid out into this frame's slot of the
+    /// glyph buffer, are blended over the whole window. The image is then handed over for
+    /// present, by the render pass or, with dynamic rendering, a layout transition.
+    fn resolve_to_swapchain(&self, cmd_buffer: vk::CommandBuffer, image_index: usize, source: u32) -> Result<(), Box<dyn std::error::Error>> {
+        let extent = self.swapchain_extent;
+        // Whole pixels per font pixel keep the bitmap glyphs crisp
+        let scale = self.accessibility.ui_scale.round().max(1.0);
+        let glyphs = self.hud.layout(extent.width, extent.height, scale);
+        let slot_size = (hud::MAX_GLYPHS * size_of::<GlyphInstance>()) as u64;
+        if !glyphs.is_empty() {
+            upload_data_at(&self.ctx, self.hud_glyph_buffer.1, self.current_frame as u64 * slot_size, &glyphs);
+        }
+        unsafe {
+            let render_area = vk::Rect2D { offset: vk::Offset2D::default(), extent };
+            let clear_value = vk::ClearValue { color: vk::ClearColorValue { float32: [0.0, 0.0, 0.0, 1.0] } };
+            if self.ctx.vulkan13 {
+                // The swapchain image is written once the acquire semaphore has signaled
+                self.swapchain_layout_barrier(cmd_buffer, image_index, vk::ImageLayout::UNDEFINED, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+                let color_attachment = vk::RenderingAttachmentInfo {
+                    image_view: self.swapchain_image_views[image_index],
+                    image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    load_op: vk::AttachmentLoadOp::CLEAR,
+                    store_op: vk::AttachmentStoreOp::STORE,
+                    clear_value,
+                    ..Default::default()
+                };
+                self.ctx.device.cmd_begin_rendering(cmd_buffer, &vk::RenderingInfo {
+                    render_area,
+                    layer_count: 1,
+                    color_attachment_count: 1,
+                    p_color_attachments: &color_attachment,
+                    ..Default::default()
+                });
+            } else {
+                self.ctx.device.cmd_begin_render_pass(cmd_buffer, &vk::RenderPassBeginInfo {
+                    render_pass: self.resolve_render_pass,
+                    framebuffer: self.resolve_framebuffers[image_index],
+                    render_area,
+                    clear_value_count: 1,
+                    p_clear_values: &clear_value,
+                    ..Default::default()
+                }, vk::SubpassContents::INLINE);
+            }
+
+            // The upscale image holds the picture in the output extent, the others in the render extent
+            let source_extent = if source == 3 { self.output_extent } else { self.render_extent };
+            let picture = display::letterbox(source_extent, extent);
+            self.ctx.device.cmd_set_viewport(cmd_buffer, 0, &[vk::Viewport {
+                x: picture.offset.x as f32,
+                y: picture.offset.y as f32,
+                width: picture.extent.width as f32,
+                height: picture.extent.height as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            }]);
+            self.ctx.device.cmd_set_scissor(cmd_buffer, 0, &[picture]);
+            self.ctx.device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::GRAPHICS, self.resolve_pipeline);
+            self.ctx.device.cmd_bind_descriptor_sets(cmd_buffer, vk::PipelineBindPoint::GRAPHICS, self.resolve_pipeline_layout, 0, &[self.resolve_descriptor_set], &[]);
+            let push_constants = ResolvePushConstants {
+                source_size: [source_extent.width, source_extent.height],
+                source,
+                transfer: self.display_output.transfer(),
+                paper_white: self.paper_white,
+                sharpness: self.sharpness,
+            };
+            self.ctx.device.cmd_push_constants(cmd_buffer, self.resolve_pipeline_layout, vk::ShaderStageFlags::FRAGMENT, 0, bytemuck::bytes_of(&push_constants));
+            self.ctx.device.cmd_draw(cmd_buffer, 3, 1, 0, 0);
+
+            if !glyphs.is_empty() {
+                self.ctx.device.cmd_set_viewport(cmd_buffer, 0, &[vk::Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: extent.width as f32,
+                    height: extent.height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                }]);
+                self.ctx.device.cmd_set_scissor(cmd_buffer, 0, &[render_area]);
+                self.ctx.device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::GRAPHICS, self.hud_pipeline);
+                let push_constants = HudPushConstants {
+                    glyphs_address: self.hud_glyph_buffer.2 + self.current_frame as u64 * slot_size,
+                    font_address: self.hud_font_buffer.2,
+                    screen_size: Vec2::new(extent.width as f32, extent.height as f32),
+                    glyph_size: Vec2::new(hud::GLYPH_WIDTH as f32, hud::GLYPH_HEIGHT as f32) * scale,
+                    background_alpha: if self.accessibility.high_contrast { 0.85 } else { 0.6 },
+                    transfer: self.display_output.transfer(),
+                    paper_white: self.paper_white,
+                    _pad: 0.0,
+                };
+                self.ctx.device.cmd_push_constants(cmd_buffer, self.hud_pipeline_layout, vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT, 0, bytemuck::bytes_of(&push_constants));
+                self.ctx.device.cmd_draw(cmd_buffer, 6, glyphs.len() as u32, 0, 0);
+            }
+            if self.ctx.vulkan13 {
+                self.ctx.device.cmd_end_rendering(cmd_buffer);
+                self.swapchain_layout_barrier(cmd_buffer, image_index, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::ImageLayout::PRESENT_SRC_KHR);
+            } else {
+                self.ctx.device.cmd_end_render_pass(cmd_buffer);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves swapchain image `image_index` between the layouts around the dynamic rendering
+    /// swapchain pass: from the acquire (UNDEFINED, its contents are cleared anyway) to a color
+    /// attachment, and from that to present. Needs synchronization2 (Vulkan 1.3).
+    fn swapchain_layout_barrier(&self, cmd_buffer: vk::CommandBuffer, image_index: usize, old_layout: vk::ImageLayout, new_layout: vk::ImageLayout) {
+        let to_attachment = new_layout == vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
+        let barrier = vk::ImageMemoryBarrier2 {
+            // Waits on the acquire semaphore, which is signaled for the color attachment output
+            // stage, before writing; the present waits on the render finished semaphore
+            src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            src_access_mask: if to_attachment { vk::AccessFlags2::NONE } else { vk::AccessFlags2::COLOR_ATTACHMENT_WRITE },
+            dst_stage_mask: if to_attachment { vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT } else { vk::PipelineStageFlags2::NONE },
+            dst_access_mask: if to_attachment { vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE } else { vk::AccessFlags2::NONE },
+            old_layout,
+            new_layout,
+            image: self.swapchain_images[image_index],
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            ..Default::default()
+        };
+        unsafe {
+            self.ctx.device.cmd_pipeline_barrier2(cmd_buffer, &vk::DependencyInfo {
+                image_memory_barrier_count: 1,
+                p_image_memory_barriers: &barrier,
+                ..Default::default()
+            });
+        }
+    }
+
+    /// Geometry streaming: unloads the streamed meshes the camera left behind and uploads and
+    /// builds the BLASes of the ones it came near, recording the builds into `cmd_buffer` ahead
+    /// of the TLAS update. Returns whether any BLAS came or went.
+    fn stream_geometry(&mut self, cmd_buffer: vk::CommandBuffer, edited: bool) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(streaming) = self.streaming.as_mut() else {
+            return Ok(false);
+        };
+        if edited {
+            streaming.refresh(&self.scene);
+        }
+        let changes = streaming.update(self.camera.position);
+        if changes.load.is_empty() && changes.unload.is_empty() {
+            return Ok(false);
+        }
+
+        // Frames in flight may still trace unloaded meshes, they're freed once this frame slot comes around
+        // again. Their scene descs stay as they are, no instance references them anymore.
+        for &mesh_index in &changes.unload {
+            if let Some(gpu) = self.streamed_meshes[mesh_index].take() {
+                self.stream_garbage[self.current_frame].meshes.push(gpu);
+            }
+            self.blas_addresses[mesh_index] = 0;
+            streaming.set_loaded(mesh_index, false);
+        }
+
+        let mut loaded_any = false;
+        for &mesh_index in &changes.load {
+            match load_streamed_mesh(&self.ctx, cmd_buffer, &self.scene.meshes[mesh_index], mesh_index, &mut self.stream_garbage[self.current_frame]) {
+                Ok(gpu) => {
+                    self.blas_addresses[mesh_index] = blas_address(&self.ctx, gpu.blas.0);
+                    self.scene_descs[mesh_index].vertex_addr = gpu.vertices.2;
+                    self.scene_descs[mesh_index].index_addr = gpu.indices.2;
+                    self.streamed_meshes[mesh_index] = Some(gpu);
+                    streaming.set_loaded(mesh_index, true);
+                    loaded_any = true;
+                }
+                Err(e) => {
+                    log::warn!("Failed to stream in mesh {}: {}", mesh_index, e);
+                    streaming.load_failed(mesh_index);
+                }
+            }
+        }
+        if loaded_any {
+            upload_data(&self.ctx, self.scene_desc_buffer.1, &self.scene_descs);
+        }
+
+        let (loaded, streamed) = streaming.loaded_count();
+        log::debug!("Geometry streaming: {} of {} streamed meshes loaded", loaded, streamed);
+        Ok(loaded_any || !changes.unload.is_empty())
+    }
+
+    /// Frees the streaming resources the frame that last used this frame slot left behind,
+    /// once its fence has been waited on.
+    fn free_stream_garbage(&mut self) {
+        let garbage = std::mem::take(&mut self.stream_garbage[self.current_frame]);
+        let buffers = garbage.meshes.into_iter()
+            .flat_map(|gpu| {
+                unsafe { self.ctx.as_loader.destroy_acceleration_structure(gpu.blas.0, None) };
+                [(gpu.blas.2, gpu.blas.1), (gpu.vertices.0, gpu.vertices.1), (gpu.indices.0, gpu.indices.1)]
+            })
+            .chain(garbage.scratch);
+        for (buffer, memory) in buffers {
+            unsafe {
+                self.ctx.device.destroy_buffer(buffer, None);
+                self.ctx.device.free_memory(memory, None);
+            }
+            self.ctx.memory.forget(memory);
+        }
+    }
+
+    /// Re-poses the animated objects by updating the TLAS in place with this frame's instance transforms,
+    /// or rebuilds it from scratch when `rebuild` is set. The transforms it replaces become the previous
+    /// transforms the motion vectors are measured against.
+    fn update_tlas(&mut self, cmd_buffer: vk::CommandBuffer, rebuild: bool) {
+        let instances = tlas_instances(&self.scene, self.lod.meshes(), &self.blas_addresses);
+        let instance_buffer = self.tlas_instance_buffers[self.current_frame];
+        upload_data(&self.ctx, instance_buffer.1, &instances);
+        self.prev_transform_buffer.refresh(&self.ctx, self.current_frame, &self.last_instance_transforms);
+        self.last_instance_transforms = instances.iter().map(|instance| instance.transform).collect();
+        self.transform_buffer.write(&self.ctx, self.current_frame, &self.last_instance_transforms);
+        // Once nothing moves, every slot's previous transforms must catch up with these, this one's included
+        self.prev_transform_buffer.stale.fill(true);
+        // Rebuilds come with edits, weather changes and particles, which may change instance materials
+        if rebuild {
+            self.instance_data_buffer.write(&self.ctx, self.current_frame, &instance_data(&self.scene, self.lod.meshes()));
+        }
+        // The raster preview only draws with the transforms
+        if !self.ctx.ray_tracing {
+            return;
+        }
+
+        let geometry = vk::AccelerationStructureGeometryKHR {
+            geometry_type: vk::GeometryTypeKHR::INSTANCES,
+            geometry: vk::AccelerationStructureGeometryDataKHR {
+                instances: vk::AccelerationStructureGeometryInstancesDataKHR {
+                    data: vk::DeviceOrHostAddressConstKHR { device_address: instance_buffer.2 },
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        };
+        let build_info = vk::AccelerationStructureBuildGeometryInfoKHR {
+            ty: vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+            flags: vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE,
+            mode: if rebuild { vk::BuildAccelerationStructureModeKHR::BUILD } else { vk::BuildAccelerationStructureModeKHR::UPDATE },
+            src_acceleration_structure: if rebuild { vk::AccelerationStructureKHR::null() } else { self.tlas.0 },
+            dst_acceleration_structure: self.tlas.0,
+            geometry_count: 1,
+            p_geometries: &geometry,
+            scratch_data: vk::DeviceOrHostAddressKHR { device_address: self.tlas_scratch.2 },
+            ..Default::default()
+        };
+        let build_range = vk::AccelerationStructureBuildRangeInfoKHR {
+            primitive_count: instances.len() as u32,
+            primitive_offset: 0,
+            first_vertex: 0,
+            transform_offset: 0,
+        };
+
+        unsafe {
+            // The previous frame may still be tracing against the TLAS or using the scratch buffer
+            let build_barrier = vk::MemoryBarrier {
+                src_access_mask: vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR | vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR,
+                dst_access_mask: vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR | vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR,
+                ..Default::default()
+            };
+            self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR | vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR, vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR, vk::DependencyFlags::empty(), &[build_barrier], &[], &[]);
+            self.ctx.as_loader.cmd_build_acceleration_structures(cmd_buffer, &[build_info], &[&[build_range]]);
+
+            let trace_barrier = vk::MemoryBarrier {
+                src_access_mask: vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR,
+                dst_access_mask: vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR,
+                ..Default::default()
+            };
+            self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::DependencyFlags::empty(), &[trace_barrier], &[], &[]);
+        }
+    }
+
+    /// Dynamic offsets the ray tracing descriptor set is bound with, in binding order: the camera
+    /// UBO of split screen view `view`, then this frame's slots of the per-frame buffers.
+    fn descriptor_offsets(&self, view: u64) -> [u32; 5] {
+        let slot = self.current_frame;
+        [
+            self.uniform_buffer.offset(slot) + (view * self.camera_ubo_stride) as u32,
+            self.instance_data_buffer.offset(slot),
+            self.prev_transform_buffer.offset(slot),
+            self.transform_buffer.offset(slot),
+            self.material_buffer.offset(slot),
+        ]
+    }
+
+    /// Dynamic offsets the G-buffer descriptor set is bound with: this frame's slots of the per-frame buffers.
+    fn gbuffer_descriptor_offsets(&self) -> [u32; 3] {
+        let slot = self.current_frame;
+        [self.instance_data_buffer.offset(slot), self.transform_buffer.offset(slot), self.material_buffer.offset(slot)]
+    }
+
+    /// Copies a render-size storage image (in GENERAL layout) written by the last frame back to the host.
+    fn read_back_image(&self, image: vk::Image, texel_size: u64) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        self.read_back_image_extent(image, self.render_extent, texel_size)
+    }
+
+    /// Copies the `extent` corner of a storage image (in GENERAL layout) back to the host.
+    fn read_back_image_extent(&self, image: vk::Image, extent: vk::Extent2D, texel_size: u64) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let (width, height) = (extent.width, extent.height);
+        let size = width as u64 * height as u64 * texel_size;
+        let (readback_buf, readback_mem, _) = create_buffer_with_addr(&self.ctx, size, vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT, MemoryCategory::Buffers)?;
+
+        let cmd_buffer = self.command_buffers[self.current_frame];
+        unsafe { self.ctx.device.device_wait_idle()?; }
+        begin_single_time_command(&self.ctx, self.command_pool, cmd_buffer);
+        let (trace_stage, trace_access) = self.trace_stage();
+        unsafe {
+            let barrier = vk::MemoryBarrier {
+                src_access_mask: trace_access,
+                dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+                ..Default::default()
+            };
+            self.ctx.device.cmd_pipeline_barrier(cmd_buffer, trace_stage, vk::PipelineStageFlags::TRANSFER, vk::DependencyFlags::empty(), &[barrier], &[], &[]);
+            let region = vk::BufferImageCopy {
+                image_subresource: vk::ImageSubresourceLayers { aspect_mask: vk::ImageAspectFlags::COLOR, mip_level: 0, base_array_layer: 0, layer_count: 1 },
+                image_extent: vk::Extent3D { width, height, depth: 1 },
+                ..Default::default()
+            };
+            self.ctx.device.cmd_copy_image_to_buffer(cmd_buffer, image, vk::ImageLayout::GENERAL, readback_buf, &[region]);
+        }
+        end_single_time_command(&self.ctx, self.command_pool, cmd_buffer, self.ctx.queue);
+
+        let mut data = vec![0u8; size as usize];
+        unsafe {
+            let ptr = self.ctx.device.map_memory(readback_mem, 0, size, vk::MemoryMapFlags::empty())?;
+            std::ptr::copy_nonoverlapping(ptr as *const u8, data.as_mut_ptr(), data.len());
+            self.ctx.device.unmap_memory(readback_mem);
+            self.ctx.device.destroy_buffer(readback_buf, None);
+            self.ctx.device.free_memory(readback_mem, None);
+            self.ctx.memory.forget(readback_mem);
+        }
+        Ok(data)
+    }
+
+    /// Reads back the accumulation image and writes the averaged long exposure as EXR (linear, with
+    /// the last frame's AOVs as further layers) and PNG. With the `denoise` feature on it also writes
+    /// a denoised copy and shows it for a while.
+    fn save_long_exposure(&mut self, frames: u32) -> Result<(), Box<dyn std::error::Error>> {
+        let (width, height) = (self.render_extent.width, self.render_extent.height);
+        let pixels = average_samples(&self.read_accumulation()?, frames);
+
+        let stamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+        let prefix = format!("long_exposure_{}", stamp);
+        let aovs = self.read_aovs()?;
+        save_exr_and_png(&prefix, width, height, &pixels, Some(&aovs))?;
+        log::info!("Long exposure saved to {}.exr (with AOV layers) and {}.png", prefix, prefix);
+
+        #[cfg(feature = "denoise")]
+        if self.denoise {
+            let started = Instant::now();
+            let denoised = crate::denoise::denoise(width, height, &pixels, &aovs.albedo, &aovs.normal)?;
+            let denoised_prefix = format!("{}_denoised", prefix);
+            let rgba8 = save_exr_and_png(&denoised_prefix, width, height, &denoised, None)?;
+            log::info!("Denoised in {:.1}s, saved to {}.exr and {}.png", started.elapsed().as_secs_f32(), denoised_prefix, denoised_prefix);
+            self.show_photo(&rgba8)?;
+        }
+        Ok(())
+    }
+
+    /// Index of the current frame slot's timestamp `i` (of TIMESTAMPS_PER_FRAME) in the pool.
+    fn timestamp_query(&self, i: u32) -> u32 {
+        TIMESTAMPS_PER_FRAME * self.current_frame as u32 + i
+    }
+
+    /// GPU times of the passes of the frame last rendered in the current frame slot, which has
+    /// finished.
+    fn read_gpu_times(&self) -> Result<GpuTimes, vk::Result> {
+        let mut ticks = [0u64; TIMESTAMPS_PER_FRAME as usize];
+        unsafe {
+            self.ctx.device.get_query_pool_results(self.timestamp_pool, self.timestamp_query(0), &mut ticks, vk::QueryResultFlags::TYPE_64)?;
+        }
+        let ms = |from: usize| ticks[from + 1].saturating_sub(ticks[from]) as f32 * self.timestamp_period / 1e6;
+        Ok(GpuTimes { scene_ms: ms(0), post_ms: ms(1), resolve_ms: ms(2) })
+    }
+
+    /// Feeds the GPU time of the frame last rendered in the current frame slot to the dynamic
+    /// resolution, and applies the render scale it picks. The scale holds still while the render
+    /// extent has to (`render_extent_held`).
+    fn update_dynamic_resolution(&mut self, gpu_ms: f32) {
+        if self.render_extent_held() {
+            return;
+        }
+        let Some(dynamic) = &mut self.dynamic_resolution else { return };
+        if let Some(scale) = dynamic.add_frame(gpu_ms) {
+            self.fit_extents();
+            log::debug!("Dynamic resolution: {:.0}% ({}x{})", scale * 100.0, self.render_extent.width, self.render_extent.height);
+        }
+    }
+
+    /// Device local memory in use: what the driver reports with memory budgets, else what the
+    /// renderer allocated.
+    fn vram_used(&self) -> u64 {
+        self.ctx.heap_budgets().iter()
+            .filter(|heap| heap.device_local)
+            .map(|heap| heap.usage)
+            .sum::<Option<u64>>()
+            .unwrap_or_else(|| self.ctx.memory.total())
+    }
+
+    /// Rays the frame last rendered in this frame slot traced, clearing its count for this frame.
+    fn read_ray_count(&self) -> Result<u64, vk::Result> {
+        unsafe {
+            let ptr = self.ctx.device.map_memory(self.ray_counter_buffer.1, (self.current_frame * size_of::<u32>()) as u64, size_of::<u32>() as u64, vk::MemoryMapFlags::empty())?;
+            let rays = *(ptr as *const u32);
+            *(ptr as *mut u32) = 0;
+            self.ctx.device.unmap_memory(self.ray_counter_buffer.1);
+            Ok(rays as u64)
+        }
+    }
+
+    /// Rays traced since the last call, for the HUD's rays per second. None in the raster
+    /// preview, which traces none.
+    pub fn take_ray_count(&mut self) -> Option<u64> {
+        let rays = std::mem::take(&mut self.rays_counted);
+        self.ctx.ray_tracing.then_some(rays)
+    }
+
+    /// Writes out the metrics not in the file yet, at exit.
+    pub fn flush_metrics(&mut self) {
+        if let Some(metrics) = &mut self.metrics {
+            metrics.flush();
+        }
     }
-    
-    pub fn resize(&mut self, _width: u32, _height: u32) {
-        // Placeholder for resize logic (requires device idle, cleanup swapchain, recreate)
+
+    /// Whether frames are summed in the accumulation image (long exposure or photo).
+    fn accumulating(&self) -> bool {
+        self.long_exposure.is_some() || self.photo.is_some()
     }
 
-    pub fn handle_input(&mut self, key: KeyCode, state: ElementState) {
-        if state == ElementState::Pressed {
-            self.camera.handle_input(key);
-            match key {
-                KeyCode::Digit1 => self.settings.x = 1.0 - self.settings.x,
-                KeyCode::Digit2 => self.settings.y = 1.0 - self.settings.y,
-                KeyCode::Digit3 => self.settings.z = 1.0 - self.settings.z,
-                KeyCode::Digit4 => self.settings.w = 1.0 - self.settings.w,
-                _ => {}
-            }
+    /// Samples summed in the accumulation image so far, if accumulating.
+    fn accumulated_samples(&self) -> Option<u32> {
+        match (&self.long_exposure, &self.photo) {
+            (Some(exposure), _) => Some(exposure.frames_done),
+            (None, Some(photo)) => Some(photo.samples_done),
+            (None, None) => None,
         }
     }
-    
-    pub fn handle_window_event(&mut self, _event: &winit::event::WindowEvent) {}
 
-    pub fn render(&mut self, _window: &Window) -> Result<(), Box<dyn std::error::Error>> {
-        self.camera.update_vectors();
-        
-        unsafe { self.ctx.device.wait_for_fences(&[self.in_flight_fences[self.current_frame]], true, u64::MAX)?; }
-        
-        let (image_index, _) = match unsafe { self.ctx.swapchain_loader.acquire_next_image(self.swapchain, u64::MAX, self.image_available_semaphores[self.current_frame], vk::Fence::null()) } {
-            Ok(result) => result,
-            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return Ok(()), // Should resize
-            Err(e) => return Err(e.into()),
-        };
+    /// Reads back the sums in the accumulation image: RGB and squared luminance per pixel.
+    fn read_accumulation(&self) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        Ok(self.read_back_image(self.accumulation_image.0, 4 * size_of::<f32>() as u64)?
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect())
+    }
 
-        unsafe { self.ctx.device.reset_fences(&[self.in_flight_fences[self.current_frame]])?; }
+    /// Logs where the GPU stopped, see `VulkanContext::log_checkpoints`.
+    pub fn log_checkpoints(&self) {
+        self.ctx.log_checkpoints();
+    }
 
-        let cmd_buffer = self.command_buffers[self.current_frame];
-        unsafe { self.ctx.device.reset_command_buffer(cmd_buffer, vk::CommandBufferResetFlags::empty())?; }
+    /// Scene size and what its geometry, acceleration structures and textures take on the GPU,
+    /// logged after loading and queryable by applications embedding the renderer.
+    pub fn stats(&self) -> RenderStats {
+        let memory = &self.ctx.memory;
+        let streamed_blases = self.streamed_meshes.iter().flatten().map(|gpu| gpu.blas.1);
+        let blases: Vec<vk::DeviceMemory> = self.blas_list.iter().map(|blas| blas.1).chain(streamed_blases)
+            .filter(|&blas_memory| blas_memory != vk::DeviceMemory::null())
+            .collect();
+        RenderStats {
+            scene: self.scene.stats(),
+            blas_count: blases.len(),
+            blas_bytes: blases.iter().map(|&blas_memory| memory.size(blas_memory)).sum(),
+            tlas_bytes: memory.size(self.tlas.1),
+            vertex_buffer_bytes: memory.size(self.vertex_buffer.1),
+            index_buffer_bytes: memory.size(self.index_buffer.1),
+            geometry_bytes: memory.category_total(MemoryCategory::Geometry),
+            texture_bytes: self.textures.iter().map(|texture| memory.size(texture.1)).sum(),
+            total_bytes: memory.total(),
+        }
+    }
 
-        // Update Uniforms
-        let proj = self.camera.proj_matrix(1280.0/720.0); // Fixed aspect for now
-        let view = self.camera.view_matrix();
-        let ubo = CameraProperties {
-            view_inverse: view.inverse(),
-            proj_inverse: proj.inverse(),
-            light_pos: Vec4::new(10.0, 10.0, 10.0, 1.0),
-            settings: self.settings,
-        };
-        upload_data(&self.ctx, self.uniform_buffer.1, &vec![ubo]);
+    /// Living GPU memory breakdown for the HUD, while toggled on with U.
+    pub fn memory_summary(&self) -> Option<String> {
+        self.show_memory.then(|| self.ctx.memory.describe())
+    }
 
-        let begin_info = vk::CommandBufferBeginInfo {
-            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
-            ..Default::default()
+    /// Samples, target, elapsed time and noise of the long exposure or photo being accumulated.
+    pub fn progress(&self) -> Option<Progress> {
+        let (kind, samples, target, elapsed) = match (&self.long_exposure, &self.photo) {
+            (Some(exposure), _) => (ProgressiveKind::LongExposure, exposure.frames_done, LONG_EXPOSURE_FRAMES, exposure.started.elapsed().as_secs_f32()),
+            (None, Some(photo)) => (
+                ProgressiveKind::Photo { tile: photo.current_tile() + 1, tiles: photo.tile_count() },
+                photo.samples_done,
+                photo.samples,
+                photo.elapsed(),
+            ),
+            (None, None) => return None,
         };
-        unsafe { self.ctx.device.begin_command_buffer(cmd_buffer, &begin_info)?; }
+        Some(Progress { kind, samples, target, elapsed, noise: self.noise_estimate })
+    }
 
-        // Trace Rays
-        unsafe {
-            self.ctx.device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::RAY_TRACING_KHR, self.pipeline);
-            self.ctx.device.cmd_bind_descriptor_sets(cmd_buffer, vk::PipelineBindPoint::RAY_TRACING_KHR, self.pipeline_layout, 0, &[self.descriptor_set], &[]);
-            self.ctx.rt_pipeline_loader.cmd_trace_rays(
-                cmd_buffer,
-                &self.sbt_regions[0],
-                &self.sbt_regions[1],
-                &self.sbt_regions[2],
-                &self.sbt_regions[3],
-                1280, 720, 1
-            );
-        }
-
-        // Blit to Swapchain
-        let subresource = vk::ImageSubresourceRange {
-            aspect_mask: vk::ImageAspectFlags::COLOR,
-            base_mip_level: 0,
-            level_count: 1,
-            base_array_layer: 0,
-            layer_count: 1,
-        };
-        
-        // Transition Storage to Transfer Src
-        let barrier1 = vk::ImageMemoryBarrier {
-            old_layout: vk::ImageLayout::GENERAL,
-            new_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-            image: self.storage_image.0,
-            subresource_range: subresource,
-            src_access_mask: vk::AccessFlags::SHADER_WRITE,
-            dst_access_mask: vk::AccessFlags::TRANSFER_READ,
-            ..Default::default()
-        };
-        
-        // Transition Swapchain to Transfer Dst
-        let barrier2_fix = vk::ImageMemoryBarrier {
-            old_layout: vk::ImageLayout::UNDEFINED,
-            new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            image: self.swapchain_images[image_index as usize],
-            subresource_range: subresource,
-            src_access_mask: vk::AccessFlags::empty(),
-            dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
-            ..Default::default()
-        };
+    /// Size of the next F9 photo: the window size times the photo scale, or for panoramas that
+    /// wide.
+    fn photo_size(&self) -> (u32, u32) {
+        self.photo_projection.size(self.swapchain_extent.width * self.photo_scale, self.swapchain_extent.height * self.photo_scale)
+    }
 
-        unsafe {
-            self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR, vk::PipelineStageFlags::TRANSFER, vk::DependencyFlags::empty(), &[], &[], &[barrier1, barrier2_fix]);
-            
-            let blit = vk::ImageBlit {
-                src_offsets: [vk::Offset3D { x: 0, y: 0, z: 0 }, vk::Offset3D { x: 1280, y: 720, z: 1 }],
-                src_subresource: vk::ImageSubresourceLayers { aspect_mask: vk::ImageAspectFlags::COLOR, mip_level: 0, base_array_layer: 0, layer_count: 1 },
-                dst_offsets: [vk::Offset3D { x: 0, y: 0, z: 0 }, vk::Offset3D { x: 1280, y: 720, z: 1 }],
-                dst_subresource: vk::ImageSubresourceLayers { aspect_mask: vk::ImageAspectFlags::COLOR, mip_level: 0, base_array_layer: 0, layer_count: 1 },
-            };
-            
-            self.ctx.device.cmd_blit_image(cmd_buffer, self.storage_image.0, vk::ImageLayout::TRANSFER_SRC_OPTIMAL, self.swapchain_images[image_index as usize], vk::ImageLayout::TRANSFER_DST_OPTIMAL, &[blit], vk::Filter::NEAREST);
-            
-            // Transition Swapchain to Present
-             let barrier3 = vk::ImageMemoryBarrier {
-                old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                new_layout: vk::ImageLayout::PRESENT_SRC_KHR,
-                image: self.swapchain_images[image_index as usize],
-                subresource_range: subresource,
-                src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
-                dst_access_mask: vk::AccessFlags::empty(),
-                ..Default::default()
-            };
-            
-            // Transition Storage back to General
-             let barrier4 = vk::ImageMemoryBarrier {
-                old_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-                new_layout: vk::ImageLayout::GENERAL,
-                image: self.storage_image.0,
-                subresource_range: subresource,
-                src_access_mask: vk::AccessFlags::TRANSFER_READ,
-                dst_access_mask: vk::AccessFlags::empty(),
-                ..Default::default()
-            };
-
-             self.ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::BOTTOM_OF_PIPE, vk::DependencyFlags::empty(), &[], &[], &[barrier3, barrier4]);
-        
-             self.ctx.device.end_command_buffer(cmd_buffer)?;
+    /// Starts a `width` x `height` photo of the current view (or a panorama around the camera)
+    /// with `samples` samples per pixel, saved as `photo_<timestamp>.exr` and `.png`. Sizes
+    /// beyond the render resolution are traced in tiles.
+    pub fn start_photo(&mut self, width: u32, height: u32, projection: PhotoProjection, samples: u32) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.ctx.ray_tracing {
+            return Err("photos need a GPU with ray tracing".into());
+        }
+        let stamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+        let mut photo = PhotoCapture::start(format!("photo_{}", stamp), width, height, projection, self.render_extent.width, self.render_extent.height, samples)?;
+        let shutter = if self.motion_blur && !self.animation_paused && !self.accessibility.reduced_motion { PHOTO_SHUTTER } else { 0.0 };
+        photo.set_shutter(self.scene_time, shutter);
+        log::info!("Photo mode: rendering a {}x{} {} ({} tiles), {} samples per pixel...", width, height, projection.label(), photo.tile_count(), samples);
+        self.photo = Some(photo);
+        self.noise_estimate = None;
+        Ok(())
+    }
+
+    /// Stops the recording, if there is one, and waits for its files to be complete.
+    pub fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            if let Err(e) = recording.finish() {
+                log::error!("Failed to finish recording: {}", e);
+            }
         }
+    }
 
-        let submit_info = vk::SubmitInfo {
-            wait_semaphore_count: 1,
-            p_wait_semaphores: &self.image_available_semaphores[self.current_frame],
-            p_wait_dst_stage_mask: &vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-            command_buffer_count: 1,
-            p_command_buffers: &cmd_buffer,
-            signal_semaphore_count: 1,
-            p_signal_semaphores: &self.render_finished_semaphores[self.current_frame],
-            ..Default::default()
-        };
+    pub fn photo_in_progress(&self) -> bool {
+        self.photo.is_some()
+    }
 
-        unsafe { self.ctx.device.queue_submit(self.ctx.queue, &[submit_info], self.in_flight_fences[self.current_frame])?; }
+    /// Adds the finished tile to the photo, which moves on to the next one. Once all tiles are
+    /// done, completes the files and returns `true`. With the `denoise` feature it also writes
+    /// a denoised copy, shown for a while if the photo is at the render resolution.
+    fn finish_photo_tile(&mut self, photo: &mut PhotoCapture) -> Result<bool, Box<dyn std::error::Error>> {
+        let tile = average_samples(&self.read_accumulation()?, photo.samples);
+        self.noise_estimate = None;
+        photo.add_tile_aovs(&self.read_aovs()?);
+        Ok(photo.add_tile(&tile)?)
+    }
 
-        let present_info = vk::PresentInfoKHR {
-            wait_semaphore_count: 1,
-            p_wait_semaphores: &self.render_finished_semaphores[self.current_frame],
-            swapchain_count: 1,
-            p_swapchains: &self.swapchain,
-            p_image_indices: &image_index,
-            ..Default::default()
-        };
+    /// Completes a photo whose last tile is in.
+    fn save_photo(&mut self, photo: PhotoCapture) -> Result<(), Box<dyn std::error::Error>> {
+        let saved = photo.finish()?;
+        log::info!("Photo ({}x{}) rendered in {:.1}s, saved to {}.exr and {}.png", saved.width, saved.height, saved.render_time, saved.prefix, saved.prefix);
 
-        match unsafe { self.ctx.swapchain_loader.queue_present(self.ctx.queue, &present_info) } {
-             Ok(_) => {},
-             Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {},
-             Err(e) => return Err(e.into()),
+        #[cfg(feature = "denoise")]
+        if let Some(whole) = saved.whole.as_ref().filter(|_| self.denoise) {
+            let (width, height) = (saved.width, saved.height);
+            let denoised = crate::denoise::denoise(width, height, &whole.color, &whole.aovs.albedo, &whole.aovs.normal)?;
+            let denoised_prefix = format!("{}_denoised", saved.prefix);
+            let rgba8 = save_exr_and_png(&denoised_prefix, width, height, &denoised, None)?;
+            log::info!("Photo denoised, saved to {}.exr and {}.png", denoised_prefix, denoised_prefix);
+            if (width, height) == (self.render_extent.width, self.render_extent.height) {
+                self.show_photo(&rgba8)?;
+            }
         }
+        Ok(())
+    }
 
-        self.current_frame = (self.current_frame + 1) % 2;
+    /// Reads back the AOVs of the last frame as RGBA floats.
+    fn read_aovs(&self) -> Result<Aovs, Box<dyn std::error::Error>> {
+        let floats = |data: Vec<u8>| -> Vec<f32> {
+            data.chunks_exact(4).map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect()
+        };
+        let halves = |data: Vec<u8>| -> Vec<f32> {
+            data.chunks_exact(2).map(|bytes| exr::prelude::f16::from_bits(u16::from_le_bytes([bytes[0], bytes[1]])).to_f32()).collect()
+        };
+
+        Ok(Aovs {
+            depth: floats(self.read_back_image(self.aov_depth_image.0, 4)?)
+                .into_iter()
+                .flat_map(|d| [d, d, d, 1.0])
+                .collect(),
+            normal: halves(self.read_back_image(self.aov_normal_image.0, 8)?),
+            albedo: halves(self.read_back_image(self.aov_albedo_image.0, 8)?),
+            motion: halves(self.read_back_image(self.motion_image.0, 8)?),
+            instance: self.read_back_image(self.aov_instance_image.0, 4)?
+                .chunks_exact(4)
+                .flat_map(|bytes| {
+                    let id = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    [if id == u32::MAX { -1.0 } else { id as f32 }, 0.0, 0.0, 1.0]
+                })
+                .collect(),
+        })
+    }
+
+    /// Uploads an RGBA8 image into the post image and shows it on the screen instead of the live
+    /// render for `PHOTO_REVIEW_SECONDS`.
+    #[cfg(feature = "denoise")]
+    fn show_photo(&mut self, rgba8: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        // The post image is BGRA
+        let bgra: Vec<u8> = rgba8.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+        let (staging_buf, staging_mem, _) = create_buffer_with_addr(&self.ctx, bgra.len() as u64, vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT, MemoryCategory::Buffers)?;
+        upload_data(&self.ctx, staging_mem, &bgra);
+
+        let cmd_buffer = self.command_buffers[self.current_frame];
+        unsafe { self.ctx.device.device_wait_idle()?; }
+        begin_single_time_command(&self.ctx, self.command_pool, cmd_buffer);
+        unsafe {
+            let region = vk::BufferImageCopy {
+                image_subresource: vk::ImageSubresourceLayers { aspect_mask: vk::ImageAspectFlags::COLOR, mip_level: 0, base_array_layer: 0, layer_count: 1 },
+                image_extent: vk::Extent3D { width: self.render_extent.width, height: self.render_extent.height, depth: 1 },
+                ..Default::default()
+            };
+            self.ctx.device.cmd_copy_buffer_to_image(cmd_buffer, staging_buf, self.post_image.0, vk::ImageLayout::GENERAL, &[region]);
+        }
+        end_single_time_command(&self.ctx, self.command_pool, cmd_buffer, self.ctx.queue);
+        unsafe {
+            self.ctx.device.destroy_buffer(staging_buf, None);
+            self.ctx.device.free_memory(staging_mem, None);
+            self.ctx.memory.forget(staging_mem);
+        }
 
+        self.photo_review_until = Some(Instant::now() + std::time::Duration::from_secs_f32(PHOTO_REVIEW_SECONDS));
         Ok(())
     }
 }
 
+/// Averages the sums read from the accumulation image into linear RGBA, with opaque alpha.
+fn average_samples(sums: &[f32], samples: u32) -> Vec<f32> {
+    sums.chunks_exact(4)
+        .flat_map(|sum| [sum[0] / samples as f32, sum[1] / samples as f32, sum[2] / samples as f32, 1.0])
+        .collect()
+}
+
+/// Writes linear RGBA floats as `<prefix>.exr`, with the AOVs as further layers if given, and
+/// clamped to 8-bit sRGB as `<prefix>.png`. Returns the 8-bit pixels.
+fn save_exr_and_png(prefix: &str, width: u32, height: u32, pixels: &[f32], aovs: Option<&Aovs>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    aov::write_layered_exr(format!("{}.exr", prefix), width, height, pixels, aovs)?;
+    let rgba8 = color::linear_to_srgb8(pixels);
+    image_io::write_png(format!("{}.png", prefix), width, height, &rgba8)?;
+    Ok(rgba8)
+}
+
 // Helpers (Same as before)
-fn create_buffer_with_addr(ctx: &VulkanContext, size: u64, usage: vk::BufferUsageFlags, props: vk::MemoryPropertyFlags) -> Result<(vk::Buffer, vk::DeviceMemory, u64), Box<dyn std::error::Error>> {
+fn create_buffer_with_addr(ctx: &VulkanContext, size: u64, usage: vk::BufferUsageFlags, props: vk::MemoryPropertyFlags, category: MemoryCategory) -> Result<(vk::Buffer, vk::DeviceMemory, u64), Box<dyn std::error::Error>> {
     let create_info = vk::BufferCreateInfo {
         size,
         usage,
@@ -830,10 +5224,12 @@ fn create_buffer_with_addr(ctx: &VulkanContext, size: u64, usage: vk::BufferUsag
         Err(e) => {
             log::error!("Failed to allocate {} bytes of GPU memory (usage: {:?}, props: {:?})",
                 mem_req.size, usage, props);
+            log::error!("GPU memory allocated so far: {}", ctx.memory.describe());
             return Err(format!("Memory allocation failed: {} - requested {} MB",
                 e, mem_req.size / (1024 * 1024)).into());
         }
     };
+    ctx.memory.record(memory, category, mem_req.size);
 
     unsafe { ctx.device.bind_buffer_memory(buffer, memory, 0)? };
 
@@ -846,12 +5242,12 @@ fn create_buffer_with_addr(ctx: &VulkanContext, size: u64, usage: vk::BufferUsag
     Ok((buffer, memory, addr))
 }
 
-fn create_image(ctx: &VulkanContext, width: u32, height: u32, format: vk::Format, usage: vk::ImageUsageFlags) -> Result<(vk::Image, vk::DeviceMemory), Box<dyn std::error::Error>> {
+fn create_image(ctx: &VulkanContext, width: u32, height: u32, mip_levels: u32, format: vk::Format, usage: vk::ImageUsageFlags) -> Result<(vk::Image, vk::DeviceMemory), Box<dyn std::error::Error>> {
     let create_info = vk::ImageCreateInfo {
         image_type: vk::ImageType::TYPE_2D,
         format,
         extent: vk::Extent3D { width, height, depth: 1 },
-        mip_levels: 1,
+        mip_levels,
         array_layers: 1,
         samples: vk::SampleCountFlags::TYPE_1,
         tiling: vk::ImageTiling::OPTIMAL,
@@ -879,16 +5275,463 @@ fn create_image(ctx: &VulkanContext, width: u32, height: u32, format: vk::Format
         Err(e) => {
             log::error!("Failed to allocate image memory: {} MB for {}x{} image",
                 mem_req.size / (1024 * 1024), width, height);
+            log::error!("GPU memory allocated so far: {}", ctx.memory.describe());
             return Err(format!("Image allocation failed: {} - requested {} MB",
                 e, mem_req.size / (1024 * 1024)).into());
         }
     };
+    ctx.memory.record(memory, MemoryCategory::Images, mem_req.size);
 
     unsafe { ctx.device.bind_image_memory(image, memory, 0)? };
 
     Ok((image, memory))
 }
 
+/// Creates a single mip storage image of the given size and moves it to GENERAL layout.
+fn create_storage_image(ctx: &VulkanContext, pool: vk::CommandPool, cmd_buffer: vk::CommandBuffer, extent: vk::Extent2D, format: vk::Format, usage: vk::ImageUsageFlags) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), Box<dyn std::error::Error>> {
+    let (image, memory) = create_image(ctx, extent.width, extent.height, 1, format, usage)?;
+    let subresource_range = vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    };
+    let view = unsafe { ctx.device.create_image_view(&vk::ImageViewCreateInfo {
+        image,
+        view_type: vk::ImageViewType::TYPE_2D,
+        format,
+        subresource_range,
+        ..Default::default()
+    }, None)? };
+
+    begin_single_time_command(ctx, pool, cmd_buffer);
+    let barrier = vk::ImageMemoryBarrier {
+        old_layout: vk::ImageLayout::UNDEFINED,
+        new_layout: vk::ImageLayout::GENERAL,
+        image,
+        subresource_range,
+        ..Default::default()
+    };
+    unsafe { ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TOP_OF_PIPE, vk::DependencyFlags::empty(), &[], &[], &[barrier]) };
+    end_single_time_command(ctx, pool, cmd_buffer, ctx.queue);
+
+    Ok((image, memory, view))
+}
+
+/// Creates a storage image with `mip_levels` mips (fewer if it is too small for them), one
+/// view per mip, and moves it to GENERAL layout.
+fn create_storage_mip_chain(ctx: &VulkanContext, pool: vk::CommandPool, cmd_buffer: vk::CommandBuffer, extent: vk::Extent2D, mip_levels: u32, format: vk::Format) -> Result<(vk::Image, vk::DeviceMemory, Vec<vk::ImageView>), Box<dyn std::error::Error>> {
+    let mip_levels = mip_levels.min(32 - extent.width.max(extent.height).leading_zeros());
+    let (image, memory) = create_image(ctx, extent.width, extent.height, mip_levels, format, vk::ImageUsageFlags::STORAGE)?;
+    let views = (0..mip_levels).map(|mip| unsafe { ctx.device.create_image_view(&vk::ImageViewCreateInfo {
+        image,
+        view_type: vk::ImageViewType::TYPE_2D,
+        format,
+        subresource_range: vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: mip,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+        ..Default::default()
+    }, None) }).collect::<Result<Vec<_>, _>>()?;
+
+    begin_single_time_command(ctx, pool, cmd_buffer);
+    let barrier = vk::ImageMemoryBarrier {
+        old_layout: vk::ImageLayout::UNDEFINED,
+        new_layout: vk::ImageLayout::GENERAL,
+        image,
+        subresource_range: vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: mip_levels,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+        ..Default::default()
+    };
+    unsafe { ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TOP_OF_PIPE, vk::DependencyFlags::empty(), &[], &[], &[barrier]) };
+    end_single_time_command(ctx, pool, cmd_buffer, ctx.queue);
+
+    Ok((image, memory, views))
+}
+
+/// Uploads all mip levels of a texture through a staging buffer and returns a
+/// shader-readable image and view.
+fn create_texture(ctx: &VulkanContext, pool: vk::CommandPool, cmd_buffer: vk::CommandBuffer, texture: &TextureData) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), Box<dyn std::error::Error>> {
+    let mip_levels = texture.levels.len() as u32;
+    let staging_data: Vec<u8> = texture.levels.concat();
+    let (staging_buf, staging_mem, _) = create_buffer_with_addr(ctx, staging_data.len() as u64, vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT, MemoryCategory::Buffers)?;
+    upload_data(ctx, staging_mem, &staging_data);
+
+    let (image, memory) = create_image(ctx, texture.width, texture.height, mip_levels, texture.format, vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)?;
+    let subresource_range = vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: mip_levels,
+        base_array_layer: 0,
+        layer_count: 1,
+    };
+
+    let mut offset = 0u64;
+    let regions: Vec<vk::BufferImageCopy> = texture.levels.iter().enumerate().map(|(level, data)| {
+        let region = vk::BufferImageCopy {
+            buffer_offset: offset,
+            image_subresource: vk::ImageSubresourceLayers { aspect_mask: vk::ImageAspectFlags::COLOR, mip_level: level as u32, base_array_layer: 0, layer_count: 1 },
+            image_extent: vk::Extent3D { width: (texture.width >> level).max(1), height: (texture.height >> level).max(1), depth: 1 },
+            ..Default::default()
+        };
+        offset += data.len() as u64;
+        region
+    }).collect();
+
+    begin_single_time_command(ctx, pool, cmd_buffer);
+    unsafe {
+        let to_transfer = vk::ImageMemoryBarrier {
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            image,
+            subresource_range,
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            ..Default::default()
+        };
+        ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TRANSFER, vk::DependencyFlags::empty(), &[], &[], &[to_transfer]);
+        ctx.device.cmd_copy_buffer_to_image(cmd_buffer, staging_buf, image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, &regions);
+
+        let to_shader = vk::ImageMemoryBarrier {
+            old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            image,
+            subresource_range,
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            ..Default::default()
+        };
+        let shader_stage = if ctx.ray_tracing { vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR } else { vk::PipelineStageFlags::FRAGMENT_SHADER };
+        ctx.device.cmd_pipeline_barrier(cmd_buffer, vk::PipelineStageFlags::TRANSFER, shader_stage, vk::DependencyFlags::empty(), &[], &[], &[to_shader]);
+    }
+    end_single_time_command(ctx, pool, cmd_buffer, ctx.queue);
+
+    unsafe { ctx.device.destroy_buffer(staging_buf, None); ctx.device.free_memory(staging_mem, None); }
+    ctx.memory.forget(staging_mem);
+
+    let view_info = vk::ImageViewCreateInfo {
+        image,
+        view_type: vk::ImageViewType::TYPE_2D,
+        format: texture.format,
+        subresource_range,
+        ..Default::default()
+    };
+    let view = unsafe { ctx.device.create_image_view(&view_info, None)? };
+
+    Ok((image, memory, view))
+}
+
+/// TLAS instances for the scene objects, then the instance batches, at their current transforms,
+/// each referencing the BLAS of its mesh in `meshes`.
+fn tlas_instances(scene: &Scene, meshes: &[usize], blas_addresses: &[u64]) -> Vec<vk::AccelerationStructureInstanceKHR> {
+    // Instances with an alpha mask are forced non-opaque, so the any-hit shader alpha tests them
+    let instance = |transform: &Mat4, mesh_index: usize, rays: RayMask, material_index: usize| {
+        let mut flags = vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE;
+        if scene.materials[material_index].alpha[0] >= 0.0 {
+            flags |= vk::GeometryInstanceFlagsKHR::FORCE_NO_OPAQUE;
+        }
+        let transform = transform.to_cols_array_2d();
+        vk::AccelerationStructureInstanceKHR {
+            transform: vk::TransformMatrixKHR {
+                matrix: [
+                    transform[0][0], transform[1][0], transform[2][0], transform[3][0],
+                    transform[0][1], transform[1][1], transform[2][1], transform[3][1],
+                    transform[0][2], transform[1][2], transform[2][2], transform[3][2],
+                ]
+            },
+            instance_custom_index_and_mask: vk::Packed24_8::new(0, rays.0), // Everything else is in InstanceData
+            instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(0, flags.as_raw() as u8),
+            acceleration_structure_reference: vk::AccelerationStructureReferenceKHR { device_handle: blas_addresses[mesh_index] },
+        }
+    };
+    let objects = scene.objects.iter().map(|obj| (&obj.transform, if obj.visible { obj.rays } else { RayMask(0) }, obj.material_index));
+    let batches = scene.instance_batches.iter().flat_map(|batch| batch.instances.iter().map(|inst| (&inst.transform, RayMask::ALL, inst.material_index)));
+    objects.chain(batches).zip(meshes).map(|((transform, rays, material_index), &mesh_index)| instance(transform, mesh_index, rays, material_index)).collect()
+}
+
+/// Uploads a streamed mesh's geometry into buffers of its own and records its BLAS build into
+/// `cmd`, adding the build's scratch buffer to `garbage` to be freed once it has run.
+fn load_streamed_mesh(ctx: &VulkanContext, cmd: vk::CommandBuffer, mesh: &Mesh, mesh_index: usize, garbage: &mut StreamGarbage) -> Result<StreamedMeshGpu, Box<dyn std::error::Error>> {
+    let usage = vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR;
+    let host_visible = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+    let (vertex_buffer, vertex_mem, vertex_addr) = create_buffer_with_addr(ctx, (mesh.vertices.len() * size_of::<Vertex>()) as u64, usage, host_visible, MemoryCategory::Geometry)?;
+    upload_data(ctx, vertex_mem, &mesh.vertices);
+    let (index_buffer, index_mem, index_addr) = create_buffer_with_addr(ctx, gpu_indices_size(mesh) as u64, usage, host_visible, MemoryCategory::Geometry)?;
+    upload_data(ctx, index_mem, &gpu_indices(mesh));
+
+    let built = create_blas(ctx, cmd, mesh, vertex_addr, index_addr, vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE, &format!("the BLAS of streamed mesh {}", mesh_index));
+    let (blas, (scratch_buffer, scratch_mem, _)) = match built {
+        Ok(built) => built,
+        Err(e) => {
+            for (buffer, memory) in [(vertex_buffer, vertex_mem), (index_buffer, index_mem)] {
+                unsafe {
+                    ctx.device.destroy_buffer(buffer, None);
+                    ctx.device.free_memory(memory, None);
+                }
+                ctx.memory.forget(memory);
+            }
+            return Err(e);
+        }
+    };
+    garbage.scratch.push((scratch_buffer, scratch_mem));
+    Ok(StreamedMeshGpu { vertices: (vertex_buffer, vertex_mem, vertex_addr), indices: (index_buffer, index_mem, index_addr), blas })
+}
+
+/// How the GPU stores the mesh's indices: 16 bits for meshes of up to 65535 vertices (most
+/// small imported and procedural ones), halving their index memory, 32 bits otherwise.
+fn index_type(mesh: &Mesh) -> vk::IndexType {
+    if mesh.vertices.len() <= u16::MAX as usize { vk::IndexType::UINT16 } else { vk::IndexType::UINT32 }
+}
+
+/// Bytes per index of the mesh on the GPU.
+fn index_size(mesh: &Mesh) -> usize {
+    if index_type(mesh) == vk::IndexType::UINT16 { 2 } else { 4 }
+}
+
+/// The mesh's index buffer contents in its `index_type`, padded to a multiple of 4 bytes so the
+/// next mesh's indices in a shared buffer stay aligned for 32-bit reads.
+fn gpu_indices(mesh: &Mesh) -> Vec<u8> {
+    let mut bytes: Vec<u8> = if index_size(mesh) == 2 {
+        mesh.indices.iter().flat_map(|&i| (i as u16).to_le_bytes()).collect()
+    } else {
+        mesh.indices.iter().flat_map(|&i| i.to_le_bytes()).collect()
+    };
+    bytes.resize(gpu_indices_size(mesh), 0);
+    bytes
+}
+
+/// Size of `gpu_indices(mesh)` in bytes.
+fn gpu_indices_size(mesh: &Mesh) -> usize {
+    (mesh.indices.len() * index_size(mesh)).next_multiple_of(4)
+}
+
+/// Device address of `blas` for TLAS instances, 0 (an inactive instance) for a null one.
+fn blas_address(ctx: &VulkanContext, blas: vk::AccelerationStructureKHR) -> u64 {
+    if blas == vk::AccelerationStructureKHR::null() {
+        return 0;
+    }
+    unsafe {
+        ctx.as_loader.get_acceleration_structure_device_address(&vk::AccelerationStructureDeviceAddressInfoKHR {
+            acceleration_structure: blas,
+            ..Default::default()
+        })
+    }
+}
+
+/// A BLAS with the memory and buffer backing it.
+type Blas = (vk::AccelerationStructureKHR, vk::DeviceMemory, vk::Buffer);
+/// A build's scratch buffer, its memory and device address.
+type Scratch = (vk::Buffer, vk::DeviceMemory, u64);
+
+/// Creates the BLAS of `mesh`, whose vertices and indices are at the given addresses, and
+/// records its build into `cmd`. Also returns the scratch buffer, which must outlive the build
+/// (and is kept for the refits of updatable BLASes).
+fn create_blas(ctx: &VulkanContext, cmd: vk::CommandBuffer, mesh: &Mesh, vertex_addr: u64, index_addr: u64, flags: vk::BuildAccelerationStructureFlagsKHR, what: &str) -> Result<(Blas, Scratch), Box<dyn std::error::Error>> {
+    let primitive_count = (mesh.indices.len() / 3) as u32;
+    let triangles = vk::AccelerationStructureGeometryTrianglesDataKHR {
+        vertex_format: vk::Format::R32G32B32_SFLOAT,
+        vertex_data: vk::DeviceOrHostAddressConstKHR { device_address: vertex_addr },
+        vertex_stride: size_of::<Vertex>() as u64,
+        max_vertex: mesh.vertices.len() as u32,
+        index_type: index_type(mesh),
+        index_data: vk::DeviceOrHostAddressConstKHR { device_address: index_addr },
+        ..Default::default()
+    };
+
+    let geometry = vk::AccelerationStructureGeometryKHR {
+        geometry_type: vk::GeometryTypeKHR::TRIANGLES,
+        geometry: vk::AccelerationStructureGeometryDataKHR { triangles },
+        flags: vk::GeometryFlagsKHR::OPAQUE,
+        ..Default::default()
+    };
+
+    let mut build_info = vk::AccelerationStructureBuildGeometryInfoKHR {
+        ty: vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+        flags,
+        mode: vk::BuildAccelerationStructureModeKHR::BUILD,
+        geometry_count: 1,
+        p_geometries: &geometry,
+        ..Default::default()
+    };
+
+    let mut size_info = vk::AccelerationStructureBuildSizesInfoKHR::default();
+    unsafe { ctx.as_loader.get_acceleration_structure_build_sizes(vk::AccelerationStructureBuildTypeKHR::DEVICE, &build_info, &[primitive_count], &mut size_info) };
+    let scratch_size = size_info.build_scratch_size.max(size_info.update_scratch_size);
+    ensure_budget(ctx, size_info.acceleration_structure_size + scratch_size, what)?;
+
+    let (as_buffer, as_mem, _) = create_buffer_with_addr(ctx, size_info.acceleration_structure_size, vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::AccelerationStructures)?;
+    let create_info = vk::AccelerationStructureCreateInfoKHR {
+        buffer: as_buffer,
+        size: size_info.acceleration_structure_size,
+        ty: vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+        ..Default::default()
+    };
+    let accel_struct = unsafe { ctx.as_loader.create_acceleration_structure(&create_info, None)? };
+    let scratch = create_buffer_with_addr(ctx, scratch_size, vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::AccelerationStructures)?;
+
+    build_info.scratch_data = vk::DeviceOrHostAddressKHR { device_address: scratch.2 };
+    build_info.dst_acceleration_structure = accel_struct;
+    let build_range = vk::AccelerationStructureBuildRangeInfoKHR {
+        primitive_count,
+        primitive_offset: 0,
+        first_vertex: 0,
+        transform_offset: 0,
+    };
+    unsafe { ctx.as_loader.cmd_build_acceleration_structures(cmd, &[build_info], &[&[build_range]]) };
+    Ok(((accel_struct, as_mem, as_buffer), scratch))
+}
+
+/// The materials as the shaders take them, with their sRGB colors decoded to linear.
+fn gpu_materials(materials: &[Material]) -> Vec<Material> {
+    materials.iter().map(|material| Material { color: color::srgb_to_linear_rgba(material.color), ..*material }).collect()
+}
+
+/// Shader-side data of every TLAS instance, in the order of `tlas_instances`, with the mesh
+/// each one uses in `meshes`.
+fn instance_data(scene: &Scene, meshes: &[usize]) -> Vec<InstanceData> {
+    let objects = scene.objects.iter().map(|obj| InstanceData {
+        material_index: obj.material_index as u32,
+        mesh_index: 0,
+        flags: 0,
+        _pad: 0,
+        color: [1.0; 4],
+    });
+    let batches = scene.instance_batches.iter().flat_map(|batch| {
+        batch.instances.iter().map(move |inst| {
+            let color_override = inst.color[3] > 0.0;
+            InstanceData {
+                material_index: inst.material_index as u32,
+                mesh_index: 0,
+                flags: if color_override { INSTANCE_COLOR_OVERRIDE } else { 0 },
+                _pad: 0,
+                color: if color_override { color::srgb_to_linear_rgba(inst.color) } else { [1.0; 4] },
+            }
+        })
+    });
+    objects.chain(batches).zip(meshes).map(|(data, &mesh_index)| InstanceData { mesh_index: mesh_index as u32, ..data }).collect()
+}
+
+/// Size the swapchain images need for the surface, the window's inner size on platforms where
+/// the surface leaves it to the swapchain (some Linux/Wayland ones report u32::MAX).
+fn surface_extent(capabilities: &vk::SurfaceCapabilitiesKHR, window_size: winit::dpi::PhysicalSize<u32>) -> vk::Extent2D {
+    if capabilities.current_extent.width != u32::MAX {
+        return capabilities.current_extent;
+    }
+    vk::Extent2D {
+        width: window_size.width.clamp(capabilities.min_image_extent.width, capabilities.max_image_extent.width),
+        height: window_size.height.clamp(capabilities.min_image_extent.height, capabilities.max_image_extent.height),
+    }
+}
+
+/// Creates the swapchain at `extent`, replacing `old_swapchain` if it isn't null, and views of
+/// its images.
+fn create_swapchain(ctx: &VulkanContext, extent: vk::Extent2D, surface_format: vk::SurfaceFormatKHR, old_swapchain: vk::SwapchainKHR) -> Result<(vk::SwapchainKHR, Vec<vk::Image>, Vec<vk::ImageView>), Box<dyn std::error::Error>> {
+    let capabilities = unsafe { ctx.surface_loader.get_physical_device_surface_capabilities(ctx.physical_device, ctx.surface)? };
+    // With a separate present queue family the swapchain images are shared concurrently
+    // rather than transferred between the families every frame
+    let swapchain_queue_families = [ctx.queue_family_index, ctx.present_queue_family_index];
+    let separate_present = ctx.present_queue_family_index != ctx.queue_family_index;
+    let swapchain_create_info = vk::SwapchainCreateInfoKHR {
+        surface: ctx.surface,
+        min_image_count: std::cmp::max(3, capabilities.min_image_count),
+        image_format: surface_format.format,
+        image_color_space: surface_format.color_space,
+        image_extent: extent,
+        image_array_layers: 1,
+        image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        pre_transform: vk::SurfaceTransformFlagsKHR::IDENTITY,
+        composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
+        present_mode: vk::PresentModeKHR::FIFO,
+        clipped: vk::TRUE,
+        image_sharing_mode: if separate_present { vk::SharingMode::CONCURRENT } else { vk::SharingMode::EXCLUSIVE },
+        queue_family_index_count: if separate_present { 2 } else { 0 },
+        p_queue_family_indices: swapchain_queue_families.as_ptr(),
+        old_swapchain,
+        ..Default::default()
+    };
+    let swapchain = unsafe { ctx.swapchain_loader.create_swapchain(&swapchain_create_info, None)? };
+    let swapchain_images = unsafe { ctx.swapchain_loader.get_swapchain_images(swapchain)? };
+    let swapchain_image_views = swapchain_images.iter().map(|&image| unsafe {
+        ctx.device.create_image_view(&vk::ImageViewCreateInfo {
+            image,
+            view_type: vk::ImageViewType::TYPE_2D,
+            format: surface_format.format,
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            ..Default::default()
+        }, None)
+    }).collect::<Result<Vec<_>, _>>()?;
+    Ok((swapchain, swapchain_images, swapchain_image_views))
+}
+
+/// Framebuffers of the resolve render pass, one per swapchain image view. None without the
+/// render pass, with dynamic rendering.
+fn create_swapchain_framebuffers(ctx: &VulkanContext, render_pass: vk::RenderPass, views: &[vk::ImageView], extent: vk::Extent2D) -> Result<Vec<vk::Framebuffer>, vk::Result> {
+    if render_pass == vk::RenderPass::null() {
+        return Ok(Vec::new());
+    }
+    views.iter().map(|view| unsafe {
+        ctx.device.create_framebuffer(&vk::FramebufferCreateInfo {
+            render_pass,
+            attachment_count: 1,
+            p_attachments: view,
+            width: extent.width,
+            height: extent.height,
+            layers: 1,
+            ..Default::default()
+        }, None)
+    }).collect()
+}
+
+/// Fails with a readable error when `bytes` more device local memory would exceed the budget,
+/// rather than letting the allocation of `what` fail. Passes when the driver reports no budget.
+fn ensure_budget(ctx: &VulkanContext, bytes: u64, what: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match ctx.device_local_headroom() {
+        Some(headroom) if bytes > headroom => Err(format!(
+            "Not enough GPU memory for {}: needs {} MB, {} MB of the memory budget left. Try a smaller scene or close other GPU applications",
+            what, bytes.div_ceil(1024 * 1024), headroom / (1024 * 1024)).into()),
+        _ => Ok(()),
+    }
+}
+
+/// Device local memory of the images sized by the window and render scale.
+fn render_target_bytes(swapchain_extent: vk::Extent2D, render_scale: f32) -> u64 {
+    let extent = upscale::scaled_extent(swapchain_extent, render_scale);
+    extent.width as u64 * extent.height as u64 * RENDER_TARGET_BYTES_PER_PIXEL
+        + swapchain_extent.width as u64 * swapchain_extent.height as u64 * WINDOW_TARGET_BYTES_PER_PIXEL
+}
+
+/// Lowers `render_scale` until the render targets fit the memory budget with some to spare,
+/// fails if they don't even at the smallest scale.
+fn fit_render_scale(ctx: &VulkanContext, swapchain_extent: vk::Extent2D, render_scale: f32) -> Result<f32, Box<dyn std::error::Error>> {
+    let Some(headroom) = ctx.device_local_headroom() else {
+        return Ok(render_scale);
+    };
+    let available = (headroom as f64 * RENDER_TARGET_BUDGET_SHARE) as u64;
+    let mut scale = render_scale;
+    while render_target_bytes(swapchain_extent, scale) > available && scale > upscale::MIN_RENDER_SCALE {
+        scale = (scale * 0.75).max(upscale::MIN_RENDER_SCALE);
+    }
+    if scale != render_scale {
+        log::warn!("Render scale lowered from {:.2} to {:.2} to fit the GPU memory budget", render_scale, scale);
+    }
+    ensure_budget(ctx, render_target_bytes(swapchain_extent, scale), "the render targets")?;
+    Ok(scale)
+}
 
 fn find_memory_type(ctx: &VulkanContext, type_filter: u32, properties: vk::MemoryPropertyFlags) -> Result<u32, Box<dyn std::error::Error>> {
     let mem_properties = unsafe { ctx.instance.get_physical_device_memory_properties(ctx.physical_device) };
@@ -901,8 +5744,13 @@ fn find_memory_type(ctx: &VulkanContext, type_filter: u32, properties: vk::Memor
 }
 
 fn upload_data<T: Copy>(ctx: &VulkanContext, memory: vk::DeviceMemory, data: &[T]) {
+    upload_data_at(ctx, memory, 0, data);
+}
+
+/// Copies `data` into host-visible `memory`, `offset` bytes in.
+fn upload_data_at<T: Copy>(ctx: &VulkanContext, memory: vk::DeviceMemory, offset: u64, data: &[T]) {
     let size = (data.len() * size_of::<T>()) as u64;
-    let ptr = unsafe { ctx.device.map_memory(memory, 0, size, vk::MemoryMapFlags::empty()).unwrap() };
+    let ptr = unsafe { ctx.device.map_memory(memory, offset, size, vk::MemoryMapFlags::empty()).unwrap() };
     unsafe { std::ptr::copy_nonoverlapping(data.as_ptr() as *const u8, ptr as *mut u8, size as usize) };
     unsafe { ctx.device.unmap_memory(memory) };
 }
@@ -922,17 +5770,91 @@ fn end_single_time_command(ctx: &VulkanContext, _pool: vk::CommandPool, buffer:
         p_command_buffers: &buffer,
         ..Default::default()
     };
-    unsafe { ctx.device.queue_submit(queue, &[submit_info], vk::Fence::null()).unwrap() };
-    unsafe { ctx.device.queue_wait_idle(queue).unwrap() };
+    // Waits on a fence for just this submission rather than idling the whole queue
+    let fence = unsafe { ctx.device.create_fence(&vk::FenceCreateInfo::default(), None).unwrap() };
+    unsafe {
+        ctx.device.queue_submit(queue, &[submit_info], fence).unwrap();
+        ctx.device.wait_for_fences(&[fence], true, u64::MAX).unwrap();
+        ctx.device.destroy_fence(fence, None);
+    }
+}
+
+/// Shaders the renderer needs, the ray tracing ones or the raster preview's.
+fn shader_sources(ray_tracing: bool) -> Vec<(&'static str, shaderc::ShaderKind)> {
+    let mut sources = vec![
+        ("src/shaders/skinning.comp", shaderc::ShaderKind::Compute),
+        ("src/shaders/post.comp", shaderc::ShaderKind::Compute),
+        ("src/shaders/exposure.comp", shaderc::ShaderKind::Compute),
+        ("src/shaders/bloom.comp", shaderc::ShaderKind::Compute),
+        ("src/shaders/upscale.comp", shaderc::ShaderKind::Compute),
+        ("src/shaders/gbuffer.vert", shaderc::ShaderKind::Vertex),
+        ("src/shaders/gbuffer.frag", shaderc::ShaderKind::Fragment),
+        ("src/shaders/hud.vert", shaderc::ShaderKind::Vertex),
+        ("src/shaders/hud.frag", shaderc::ShaderKind::Fragment),
+        ("src/shaders/resolve.vert", shaderc::ShaderKind::Vertex),
+        ("src/shaders/resolve.frag", shaderc::ShaderKind::Fragment),
+    ];
+    if ray_tracing {
+        sources.extend([
+            ("src/shaders/raygen.rgen", shaderc::ShaderKind::RayGeneration),
+            ("src/shaders/miss.rmiss", shaderc::ShaderKind::Miss),
+            ("src/shaders/closesthit.rchit", shaderc::ShaderKind::ClosestHit),
+            ("src/shaders/shadow.rmiss", shaderc::ShaderKind::Miss),
+            ("src/shaders/photon.rgen", shaderc::ShaderKind::RayGeneration),
+            ("src/shaders/hitinfo.rmiss", shaderc::ShaderKind::Miss),
+            ("src/shaders/hitinfo.rchit", shaderc::ShaderKind::ClosestHit),
+            ("src/shaders/pick.rgen", shaderc::ShaderKind::RayGeneration),
+            ("src/shaders/alpha.rahit", shaderc::ShaderKind::AnyHit),
+            ("src/shaders/traversal.rahit", shaderc::ShaderKind::AnyHit),
+        ]);
+    } else {
+        sources.extend([
+            ("src/shaders/preview.vert", shaderc::ShaderKind::Vertex),
+            ("src/shaders/preview.frag", shaderc::ShaderKind::Fragment),
+        ]);
+    }
+    sources
 }
 
-fn compile_shader(path: &str, kind: shaderc::ShaderKind, entry: &str) -> Result<Vec<u32>, Box<dyn std::error::Error>> {
+/// Macros every shader is compiled with, for what depends on the device's capabilities.
+fn shader_defines(ctx: &VulkanContext) -> Vec<(&'static str, String)> {
+    vec![
+        ("MAX_BOUNCE_DEPTH", ctx.capabilities.bounce_depth().to_string()),
+        ("SUBGROUP_RAY_COUNT", (ctx.capabilities.subgroup_ray_tracing as u32).to_string()),
+        ("SHADER_PRINTF", (ctx.shader_printf as u32).to_string()),
+    ]
+}
+
+/// Compiles `sources` concurrently on rayon's thread pool, keyed by path.
+fn compile_shaders(sources: &[(&'static str, shaderc::ShaderKind)], defines: &[(&'static str, String)]) -> Result<HashMap<&'static str, Vec<u32>>, String> {
+    sources.par_iter()
+        .map(|&(path, kind)| compile_shader(path, kind, "main", defines).map(|code| (path, code)).map_err(|e| e.to_string()))
+        .collect()
+}
+
+fn compile_shader(path: &str, kind: shaderc::ShaderKind, entry: &str, defines: &[(&str, String)]) -> Result<Vec<u32>, Box<dyn std::error::Error>> {
     let source = std::fs::read_to_string(path)?;
     let compiler = shaderc::Compiler::new().unwrap();
     let mut options = shaderc::CompileOptions::new().unwrap();
     options.set_target_env(shaderc::TargetEnv::Vulkan, shaderc::EnvVersion::Vulkan1_2 as u32);
     options.set_target_spirv(shaderc::SpirvVersion::V1_4);
+    options.set_include_callback(resolve_shader_include);
+    for (name, value) in defines {
+        options.add_macro_definition(name, Some(value));
+    }
     
     let binary = compiler.compile_into_spirv(&source, kind, path, entry, Some(&options))?;
     Ok(binary.as_binary().to_vec())
+}
+
+/// Resolves a shader `#include`: `"header"` relative to the including file, `<header>` relative
+/// to `src/shaders`.
+fn resolve_shader_include(requested: &str, include_type: shaderc::IncludeType, requesting: &str, _depth: usize) -> Result<shaderc::ResolvedInclude, String> {
+    let dir = match include_type {
+        shaderc::IncludeType::Relative => Path::new(requesting).parent().map(Path::to_path_buf).unwrap_or_default(),
+        shaderc::IncludeType::Standard => PathBuf::from("src/shaders"),
+    };
+    let path = dir.join(requested);
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(shaderc::ResolvedInclude { resolved_name: path.to_string_lossy().into_owned(), content })
 }
\ No newline at end of file
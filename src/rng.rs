@@ -0,0 +1,26 @@
+use crate::args;
+
+/// Seed of everything random in a run, from `--seed <n>`. Without it the seed is 0, so runs
+/// are reproducible by default; other seeds give other noise patterns and precipitation.
+pub fn seed_from_args() -> u32 {
+    args::number("--seed").unwrap_or(0)
+}
+
+/// Tiny Encryption Algorithm with 16 rounds, the same hash the shaders seed their random
+/// numbers with.
+pub fn tea(val0: u32, val1: u32) -> u32 {
+    let (mut v0, mut v1, mut s0) = (val0, val1, 0u32);
+    for _ in 0..16 {
+        s0 = s0.wrapping_add(0x9e37_79b9);
+        v0 = v0.wrapping_add((v1 << 4).wrapping_add(0xa341_316c) ^ v1.wrapping_add(s0) ^ (v1 >> 5).wrapping_add(0xc801_3ea4));
+        v1 = v1.wrapping_add((v0 << 4).wrapping_add(0xad90_777d) ^ v0.wrapping_add(s0) ^ (v0 >> 5).wrapping_add(0x7e95_761e));
+    }
+    v0
+}
+
+/// Seed of one frame's random numbers: the run seed hashed with the frame's number in the
+/// random sequence (the sample index while accumulating), which the shaders hash again with
+/// each pixel.
+pub fn frame_seed(seed: u32, frame: u32) -> u32 {
+    tea(seed, frame)
+}
@@ -1,21 +1,60 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hasher};
+use std::ops::Range;
 use glam::{Vec3, Mat4};
 use bytemuck::{Pod, Zeroable};
+use crate::texture::{self, TextureData};
+use crate::animation::{self, AnimationClip, Easing, Keyframe, Skeleton, Skin, SkinVertex, Transform, TransformTrack};
+use crate::asset_cache;
+use crate::terrain::Terrain;
+use crate::particles;
+use crate::grass;
+use crate::material_preview;
+use crate::stats::SceneStats;
+use crate::import::ImportOptions;
+use crate::volume::Volume;
+use rayon::prelude::*;
 
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 pub struct Vertex {
     pub pos: [f32; 3],
     pub nrm: [f32; 3],
-    pub color: [f32; 3], // Basic vertex color
+    pub color: [f32; 3], // Linear RGB, multiplied with the material's albedo (white: untinted)
+    pub uv: [f32; 2],
+    pub tangent: [f32; 4], // xyz: tangent, w: bitangent sign (MikkTSpace convention)
+}
+
+impl Vertex {
+    pub fn new(pos: [f32; 3], nrm: [f32; 3], uv: [f32; 2]) -> Self {
+        Self { pos, nrm, color: [1.0, 1.0, 1.0], uv, tangent: [0.0; 4] }
+    }
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 pub struct Material {
     pub color: [f32; 4],
-    pub params: [f32; 4], // x: type, y: roughness, z: ior, w: sss_amount
+    pub params: [f32; 4], // x: type, y: roughness, z: ior, w: emission strength for emissive, rock material for terrain
+    pub textures: [f32; 4], // x: normal map index (-1: none), y: normal strength (ripple strength for water), z: uv scale, w: flicker (emissive, 0: steady)
+    pub alpha: [f32; 4], // x: alpha mask texture index (-1: opaque), y: cutoff
+    pub sss: [f32; 4], // xyz: mean free path per color channel for subsurface scattering, meters
+    pub film: [f32; 4], // x: thin film thickness, nm (0: no film), y: film ior, z: thickness variation across the surface, nm
+    pub dispersion: [f32; 4], // x: Abbe number (0: no dispersion), y: wavelengths sampled (0: 3)
+    pub lobes: [f32; 4], // x: clearcoat weight, y: clearcoat roughness, z: anisotropy (0..1, along the tangent), w: anisotropy rotation, radians
 }
 
+const NO_TEXTURES: [f32; 4] = [-1.0, 0.0, 1.0, 0.0];
+const OPAQUE: [f32; 4] = [-1.0, 0.5, 0.0, 0.0];
+const NO_SSS: [f32; 4] = [0.0; 4];
+const NO_FILM: [f32; 4] = [0.0, 1.0, 0.0, 0.0];
+const NO_DISPERSION: [f32; 4] = [0.0; 4];
+const NO_LOBES: [f32; 4] = [0.0; 4];
+const PRECIPITATION_PARTICLES: usize = 1200;
+const FOREST_SPACING: f32 = 3.0; // Meters between trees before jitter
+const FOREST_HALF_EXTENT: i32 = 20; // Grid cells on each side of the origin
+const FOREST_CLEARING: f32 = 14.0; // Half extent of the tree-free square around the street
+
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 pub struct SceneDesc {
@@ -29,49 +68,444 @@ pub struct Mesh {
     pub indices: Vec<u32>,
 }
 
+/// Per-vertex attributes as a mesh source provides them, one array per attribute. Only the
+/// positions are required; `Mesh::from_attributes` fills in the ones a source lacks.
+pub struct VertexAttributes {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub uvs: Option<Vec<[f32; 2]>>,
+    pub colors: Option<Vec<[f32; 3]>>,
+    pub tangents: Option<Vec<[f32; 4]>>,
+}
+
+impl Mesh {
+    /// Builds a mesh from whatever attributes a source has. Missing normals are generated
+    /// (see `generate_normals`, smoothed up to `crease_angle` degrees), missing UVs are 0 and
+    /// missing colors white. Missing tangents are left at 0 for `generate_tangents` to fill in.
+    /// Also returns the source vertex of every vertex of the mesh, which differ where normal
+    /// generation split vertices along creases, for sources with more per-vertex data (skins).
+    pub fn from_attributes(attributes: VertexAttributes, indices: Vec<u32>, crease_angle: f32) -> Result<(Self, Vec<u32>), Box<dyn std::error::Error>> {
+        let count = attributes.positions.len();
+        let lengths = [
+            attributes.normals.as_ref().map(Vec::len),
+            attributes.uvs.as_ref().map(Vec::len),
+            attributes.colors.as_ref().map(Vec::len),
+            attributes.tangents.as_ref().map(Vec::len),
+        ];
+        if lengths.iter().flatten().any(|&n| n != count) {
+            return Err("vertex attributes have mismatched lengths".into());
+        }
+        if indices.len() % 3 != 0 || indices.iter().any(|&i| i as usize >= count) {
+            return Err("indices don't form triangles of the vertices".into());
+        }
+
+        let (normals, indices, sources) = match attributes.normals {
+            Some(normals) => (normals, indices, (0..count as u32).collect()),
+            None => generate_normals(&attributes.positions, &indices, crease_angle),
+        };
+        let vertices = sources.iter().zip(&normals).map(|(&source, &nrm)| {
+            let i = source as usize;
+            Vertex {
+                pos: attributes.positions[i],
+                nrm,
+                color: attributes.colors.as_ref().map_or([1.0; 3], |colors| colors[i]),
+                uv: attributes.uvs.as_ref().map_or([0.0; 2], |uvs| uvs[i]),
+                tangent: attributes.tangents.as_ref().map_or([0.0; 4], |tangents| tangents[i]),
+            }
+        }).collect();
+        Ok((Mesh { vertices, indices }, sources))
+    }
+
+    /// Object-space axis-aligned bounding box (min, max).
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        self.vertices.iter().fold((Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)), |(min, max), v| {
+            let p = Vec3::from(v.pos);
+            (min.min(p), max.max(p))
+        })
+    }
+
+    /// World bounds of the mesh placed by `transform`, from the corners of its own bounds.
+    pub fn transformed_bounds(&self, transform: Mat4) -> (Vec3, Vec3) {
+        let (min, max) = self.bounds();
+        (0..8).map(|corner| {
+            let pick = |bit: usize, lo: f32, hi: f32| if corner & bit != 0 { hi } else { lo };
+            transform.transform_point3(Vec3::new(pick(1, min.x, max.x), pick(2, min.y, max.y), pick(4, min.z, max.z)))
+        }).fold((Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)), |(lo, hi), p| (lo.min(p), hi.max(p)))
+    }
+
+    /// Hash of the vertex and index data, for finding identical meshes.
+    fn geometry_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(bytemuck::cast_slice(&self.vertices));
+        hasher.write(bytemuck::cast_slice(&self.indices));
+        hasher.finish()
+    }
+
+    /// Whether both meshes have the same vertices and indices, bit for bit.
+    fn same_geometry(&self, other: &Mesh) -> bool {
+        bytemuck::cast_slice::<Vertex, u8>(&self.vertices) == bytemuck::cast_slice::<Vertex, u8>(&other.vertices) && self.indices == other.indices
+    }
+
+    /// Fills in per-vertex tangents with MikkTSpace, which the closest hit shader
+    /// needs to apply normal maps. Requires normals and UVs to be present. Meshes whose
+    /// vertices all have tangents already (imported ones that come with them) keep theirs.
+    pub fn generate_tangents(&mut self) {
+        if !self.vertices.is_empty() && self.vertices.iter().all(|v| v.tangent[3] != 0.0) {
+            return;
+        }
+        if !mikktspace::generate_tangents(self) {
+            log::warn!("Tangent generation failed for mesh with {} triangles", self.indices.len() / 3);
+        }
+    }
+}
+
+/// Vertex normals for a mesh that has none. Every triangle corner gets the normals of the
+/// triangles around its position (also those on other vertices there, e.g. across a UV seam)
+/// that are within `crease_angle` degrees of its own triangle's, each weighted by its angle at
+/// the corner. 0 gives faceted normals and 180 smooths everything. Vertices whose corners end
+/// up with different normals are split; vertices of no triangle are dropped. Returns the
+/// normals, the indices into them and the source vertex of each.
+fn generate_normals(positions: &[[f32; 3]], indices: &[u32], crease_angle: f32) -> (Vec<[f32; 3]>, Vec<u32>, Vec<u32>) {
+    let position = |corner: usize| Vec3::from(positions[indices[corner] as usize]);
+    let face_normals: Vec<Vec3> = (0..indices.len() / 3).map(|triangle| {
+        let [a, b, c] = [0, 1, 2].map(|k| position(triangle * 3 + k));
+        (b - a).cross(c - a).normalize_or_zero()
+    }).collect();
+    let corner_angles: Vec<f32> = (0..indices.len()).map(|corner| {
+        let first = corner - corner % 3;
+        let to_next = position(first + (corner + 1) % 3) - position(corner);
+        let to_previous = position(first + (corner + 2) % 3) - position(corner);
+        if to_next.length_squared() > 0.0 && to_previous.length_squared() > 0.0 { to_next.angle_between(to_previous) } else { 0.0 }
+    }).collect();
+    let mut corners_at: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (corner, &index) in indices.iter().enumerate() {
+        corners_at.entry(positions[index as usize].map(f32::to_bits)).or_default().push(corner);
+    }
+
+    let min_cos = crease_angle.to_radians().cos() - 1e-4; // Coplanar triangles always smooth
+    let (mut normals, mut new_indices, mut sources) = (Vec::new(), Vec::with_capacity(indices.len()), Vec::new());
+    let mut copies: HashMap<u32, Vec<u32>> = HashMap::new(); // Source vertex -> its vertices in the result
+    for (corner, &source) in indices.iter().enumerate() {
+        let face = face_normals[corner / 3];
+        let normal: Vec3 = corners_at[&positions[source as usize].map(f32::to_bits)].iter()
+            .filter(|&&other| face_normals[other / 3].dot(face) >= min_cos)
+            .map(|&other| face_normals[other / 3] * corner_angles[other])
+            .sum();
+        let normal = normal.try_normalize().or(face.try_normalize()).unwrap_or(Vec3::Y);
+
+        let vertex_copies = copies.entry(source).or_default();
+        let index = match vertex_copies.iter().find(|&&i| Vec3::from(normals[i as usize]).dot(normal) > 0.9999) {
+            Some(&i) => i,
+            None => {
+                normals.push(normal.to_array());
+                sources.push(source);
+                vertex_copies.push(normals.len() as u32 - 1);
+                normals.len() as u32 - 1
+            }
+        };
+        new_indices.push(index);
+    }
+    (normals, new_indices, sources)
+}
+
+impl mikktspace::Geometry for Mesh {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[self.indices[face * 3 + vert] as usize].pos
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[self.indices[face * 3 + vert] as usize].nrm
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertices[self.indices[face * 3 + vert] as usize].uv
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        let index = self.indices[face * 3 + vert] as usize;
+        self.vertices[index].tangent = tangent;
+    }
+}
+
 pub struct SceneObject {
     pub mesh_index: usize,
     pub transform: Mat4,
     pub material_index: usize,
+    pub visible: bool, // Hidden objects keep their TLAS instance but no ray can hit them
+    pub rays: RayMask, // Kinds of rays that hit it while it's visible
+}
+
+/// The kinds of rays that see an object, stored in its TLAS instance's mask; the bits match
+/// include/ray_masks.glsl. E.g. an object with only SHADOW casts shadows without being seen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RayMask(pub u8);
+
+impl RayMask {
+    pub const CAMERA: RayMask = RayMask(0x01); // Camera rays, the autofocus probe and picking
+    pub const SHADOW: RayMask = RayMask(0x02); // Shadow and sky visibility rays
+    pub const REFLECTION: RayMask = RayMask(0x04); // Reflection and refraction rays and caustic photons
+    pub const ALL: RayMask = RayMask(0x07);
+    const NAMES: [(&'static str, RayMask); 3] = [("camera", RayMask::CAMERA), ("shadow", RayMask::SHADOW), ("reflection", RayMask::REFLECTION)];
+
+    pub fn contains(self, other: RayMask) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Comma-separated kinds, as in the scene file: `camera,shadow`, or `none`.
+    pub fn parse(text: &str) -> Option<RayMask> {
+        if text == "none" {
+            return Some(RayMask(0));
+        }
+        text.split(',').try_fold(RayMask(0), |mask, name| {
+            let (_, kind) = RayMask::NAMES.iter().find(|(kind_name, _)| *kind_name == name)?;
+            Some(RayMask(mask.0 | kind.0))
+        })
+    }
+}
+
+impl std::fmt::Display for RayMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let names: Vec<&str> = RayMask::NAMES.iter().filter(|(_, kind)| self.contains(*kind)).map(|(name, _)| *name).collect();
+        if names.is_empty() { write!(f, "none") } else { write!(f, "{}", names.join(",")) }
+    }
+}
+
+/// One copy of an instance batch's mesh. `color` replaces the material's color when its
+/// alpha is above zero.
+#[derive(Clone, Copy)]
+pub struct Instance {
+    pub transform: Mat4,
+    pub material_index: usize,
+    pub color: [f32; 4],
+}
+
+/// Many instances of one mesh. They follow the scene objects in the TLAS and share their
+/// mesh's BLAS and geometry, so each one only costs an instance record and its
+/// per-instance data (material color override).
+pub struct InstanceBatch {
+    pub mesh_index: usize,
+    pub instances: Vec<Instance>,
+}
+
+/// A coarser stand-in for a mesh, used by instances at least `distance` (in multiples of
+/// their size) away from the camera.
+pub struct LodLevel {
+    pub mesh_index: usize,
+    pub distance: f32,
+}
+
+/// The coarser levels of `mesh_index`, by increasing distance. Instances of the mesh switch
+/// between its levels' BLASes and geometry as the camera moves.
+pub struct MeshLod {
+    pub mesh_index: usize,
+    pub levels: Vec<LodLevel>,
+}
+
+/// Rigid animation of a scene object. The animated transform is `track.sample(time) * base`,
+/// so the object's authored placement is its rest pose.
+pub struct ObjectAnimation {
+    pub object_index: usize,
+    pub base: Mat4,
+    pub track: TransformTrack,
+}
+
+/// Spotlight that projects a texture (gobo / cookie) onto the scene. Light falls off with
+/// the squared distance, `color` is the irradiance at 1m.
+pub struct ProjectorLight {
+    pub position: Vec3,
+    pub target: Vec3,
+    pub fov_y: f32, // Degrees
+    pub aspect: f32,
+    pub color: Vec3,
+    pub texture_index: usize,
+}
+
+impl ProjectorLight {
+    /// World to projector clip space.
+    pub fn view_proj(&self) -> Mat4 {
+        let forward = (self.target - self.position).normalize();
+        let up = if forward.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+        Mat4::perspective_rh(self.fov_y.to_radians(), self.aspect, 0.05, 100.0)
+            * Mat4::look_at_rh(self.position, self.target, up)
+    }
+}
+
+/// Box of hot air that makes the image behind it shimmer (heat haze post effect).
+/// `strength` is the distortion density per meter of ray travelled inside the box.
+pub struct HeatHazeVolume {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub strength: f32,
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(not(feature = "physics"), allow(dead_code))] // Only read by the physics module
+pub enum ColliderShape {
+    Ball { radius: f32 },
+    Cuboid { half_extents: Vec3 },
+}
+
+/// Scene object simulated as a rigid body (with the `physics` feature). Static bodies
+/// only act as colliders; dynamic bodies drive their object's transform.
+#[cfg_attr(not(feature = "physics"), allow(dead_code))]
+pub struct RigidBodyDesc {
+    pub object_index: usize,
+    pub shape: ColliderShape, // World units
+    pub dynamic: bool,
+    pub restitution: f32,
+    pub start: Mat4, // Pose the simulation starts (and restarts) from
 }
 
 pub struct Scene {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
+    pub textures: Vec<TextureData>,
+    pub projectors: Vec<ProjectorLight>,
+    pub heat_volumes: Vec<HeatHazeVolume>,
+    pub volume: Option<Volume>, // Smoke or cloud from --volume, ray marched by the shaders
     pub objects: Vec<SceneObject>,
+    pub animations: Vec<ObjectAnimation>,
+    pub skins: Vec<Skin>,
+    pub rigid_bodies: Vec<RigidBodyDesc>,
+    pub precipitation: Range<usize>, // Objects used as rain / snow particles by the weather controller
+    pub particles: Range<usize>, // Objects used as sparks by the particle system
+    pub grass: Option<usize>, // Instance batch of grass cards swayed by the wind
+    pub studio: Range<usize>, // Objects of the material preview: the ball, then the floor
+    pub instance_batches: Vec<InstanceBatch>,
+    pub lods: Vec<MeshLod>,
 }
 
+/// A KTX2 file and the procedural texture standing in for it when it's missing.
+type TextureSource = (&'static str, fn() -> TextureData);
+
 impl Scene {
     pub fn new() -> Self {
         let mut scene = Scene {
             meshes: Vec::new(),
             materials: Vec::new(),
+            textures: Vec::new(),
+            projectors: Vec::new(),
+            heat_volumes: Vec::new(),
+            volume: Volume::from_args(),
             objects: Vec::new(),
+            animations: Vec::new(),
+            skins: Vec::new(),
+            rigid_bodies: Vec::new(),
+            precipitation: 0..0,
+            particles: 0..0,
+            grass: None,
+            studio: 0..0,
+            instance_batches: Vec::new(),
+            lods: Vec::new(),
         };
 
+        // Textures (normal maps, gobos and alpha masks), KTX2 files in assets/textures replace the procedural ones.
+        // Loaded or generated in parallel.
+        let texture_sources: [TextureSource; 6] = [
+            ("assets/textures/brick_normal.ktx2", || texture::brick_normal_map(512)), // 0: Brick
+            ("assets/textures/bark_normal.ktx2", || texture::bark_normal_map(256)), // 1: Bark
+            ("assets/textures/asphalt_normal.ktx2", || texture::asphalt_normal_map(512)), // 2: Asphalt
+            ("assets/textures/window_gobo.ktx2", || texture::window_gobo(256)), // 3: Window gobo
+            ("assets/textures/breakup_gobo.ktx2", || texture::breakup_gobo(256)), // 4: Foliage breakup gobo
+            ("assets/textures/grass_card.ktx2", || texture::grass_card(256)), // 5: Grass card alpha mask
+        ];
+        scene.textures = texture_sources.par_iter().map(|&(path, generate)| texture::load_ktx2_or(path, generate)).collect();
+
+        // Projector lights
+        // Warm window light falling on the road in front of the house
+        scene.projectors.push(ProjectorLight {
+            position: Vec3::new(-6.0, 5.0, -1.0),
+            target: Vec3::new(-3.0, 0.0, 2.0),
+            fov_y: 30.0,
+            aspect: 1.0,
+            color: Vec3::new(1.0, 0.85, 0.6) * 35.0,
+            texture_index: 3,
+        });
+        // Cool stage spot with a breakup pattern on the person
+        scene.projectors.push(ProjectorLight {
+            position: Vec3::new(-2.0, 6.0, 6.0),
+            target: Vec3::new(-2.0, 1.0, 2.0),
+            fov_y: 25.0,
+            aspect: 1.0,
+            color: Vec3::new(0.4, 0.6, 1.0) * 30.0,
+            texture_index: 4,
+        });
+
+        // Heat haze: hot air over the sun-baked asphalt
+        // Along the road the car drives on
+        scene.heat_volumes.push(HeatHazeVolume {
+            min: Vec3::new(0.5, 0.0, -10.0),
+            max: Vec3::new(3.5, 0.6, 8.0),
+            strength: 0.6,
+        });
+        // Open lot next to the puddle
+        scene.heat_volumes.push(HeatHazeVolume {
+            min: Vec3::new(-1.0, 0.0, 5.0),
+            max: Vec3::new(9.0, 0.4, 10.0),
+            strength: 0.4,
+        });
+
         // Materials
         // 0: Gray Concrete
-        scene.materials.push(Material { color: [0.5, 0.5, 0.5, 1.0], params: [0.0, 1.0, 0.0, 0.0] }); 
+        scene.materials.push(Material { color: [0.5, 0.5, 0.5, 1.0], params: [0.0, 1.0, 0.0, 0.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES }); 
         // 1: Green Leaves
-        scene.materials.push(Material { color: [0.1, 0.8, 0.1, 1.0], params: [0.0, 1.0, 0.0, 0.0] });
+        scene.materials.push(Material { color: [0.1, 0.8, 0.1, 1.0], params: [0.0, 1.0, 0.0, 0.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
         // 2: Brown Bark
-        scene.materials.push(Material { color: [0.4, 0.2, 0.1, 1.0], params: [0.0, 1.0, 0.0, 0.0] });
+        scene.materials.push(Material { color: [0.4, 0.2, 0.1, 1.0], params: [0.0, 1.0, 0.0, 0.0], textures: [1.0, 1.0, 1.0, 0.0], alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
         // 3: Red Brick (House)
-        scene.materials.push(Material { color: [0.8, 0.3, 0.2, 1.0], params: [0.0, 1.0, 0.0, 0.0] });
-        // 4: Blue Car (Metallic)
-        scene.materials.push(Material { color: [0.2, 0.2, 0.9, 1.0], params: [1.0, 0.2, 0.0, 0.0] });
-        // 5: Glass (Window)
-        scene.materials.push(Material { color: [1.0, 1.0, 1.0, 1.0], params: [2.0, 0.0, 1.5, 0.0] });
-        // 6: Water (Puddle)
-        scene.materials.push(Material { color: [0.8, 0.8, 1.0, 1.0], params: [1.0, 0.05, 1.33, 0.0] }); // Metallic/Dielectric hybrid in shader?
-        // 7: Skin (SSS)
-        scene.materials.push(Material { color: [0.9, 0.7, 0.6, 1.0], params: [3.0, 0.5, 0.0, 1.0] });
+        scene.materials.push(Material { color: [0.8, 0.3, 0.2, 1.0], params: [0.0, 1.0, 0.0, 0.0], textures: [0.0, 1.0, 2.0, 0.0], alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 4: Blue Car (Metallic paint): a glossy metallic base under a clearcoat
+        scene.materials.push(Material { color: [0.2, 0.2, 0.9, 1.0], params: [1.0, 0.5, 0.0, 0.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: [1.0, 0.03, 0.0, 0.0] });
+        // 5: Glass (Window), a dense flint so its edges fringe visibly
+        scene.materials.push(Material { color: [1.0, 1.0, 1.0, 1.0], params: [2.0, 0.0, 1.5, 0.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: [30.0, 8.0, 0.0, 0.0], lobes: NO_LOBES });
+        // 6: Water (Puddle), animated ripples under an oil slick
+        scene.materials.push(Material { color: [0.8, 0.85, 0.95, 1.0], params: [5.0, 0.0, 1.33, 0.0], textures: [-1.0, 1.0, 1.0, 0.0], alpha: OPAQUE, sss: NO_SSS, film: [450.0, 1.45, 300.0, 0.0], dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 7: Skin (SSS), red light scattering furthest under the surface
+        scene.materials.push(Material { color: [0.9, 0.7, 0.6, 1.0], params: [3.0, 0.5, 0.0, 0.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: [0.0037, 0.0014, 0.0007, 0.0], film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
         // 8: Asphalt
-        scene.materials.push(Material { color: [0.2, 0.2, 0.2, 1.0], params: [0.0, 1.0, 0.0, 0.0] });
+        scene.materials.push(Material { color: [0.2, 0.2, 0.2, 1.0], params: [0.0, 1.0, 0.0, 0.0], textures: [2.0, 1.0, 8.0, 0.0], alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 9: Headlight (Emissive)
+        scene.materials.push(Material { color: [1.0, 0.95, 0.8, 1.0], params: [4.0, 0.0, 0.0, 4.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 10: Taillight (Emissive)
+        scene.materials.push(Material { color: [1.0, 0.1, 0.05, 1.0], params: [4.0, 0.0, 0.0, 3.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 11: Chrome
+        scene.materials.push(Material { color: [0.9, 0.9, 0.9, 1.0], params: [1.0, 0.05, 0.0, 0.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 12: Red Plastic
+        scene.materials.push(Material { color: [0.8, 0.1, 0.1, 1.0], params: [0.0, 1.0, 0.0, 0.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 13: Rain drop, dispersing like water
+        scene.materials.push(Material { color: [1.0, 1.0, 1.0, 1.0], params: [2.0, 0.0, 1.33, 0.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: [55.0, 3.0, 0.0, 0.0], lobes: NO_LOBES });
+        // 14: Snowflake
+        scene.materials.push(Material { color: [0.95, 0.95, 1.0, 1.0], params: [0.0, 1.0, 0.0, 0.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 15: Terrain (Grass, giving way to rock on steep slopes and hilltops)
+        scene.materials.push(Material { color: [0.2, 0.35, 0.1, 1.0], params: [6.0, 1.0, 0.0, 17.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 16: Porch lamp (Emissive, flickering)
+        scene.materials.push(Material { color: [1.0, 0.7, 0.35, 1.0], params: [4.0, 0.0, 0.0, 5.0], textures: [-1.0, 0.0, 1.0, 0.8], alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 17: Rock (Terrain)
+        scene.materials.push(Material { color: [0.42, 0.4, 0.37, 1.0], params: [0.0, 1.0, 0.0, 0.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 18: Spark (Emissive)
+        scene.materials.push(Material { color: [1.0, 0.75, 0.35, 1.0], params: [4.0, 0.0, 0.0, 12.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 19: Ember (Emissive)
+        scene.materials.push(Material { color: [1.0, 0.3, 0.05, 1.0], params: [4.0, 0.0, 0.0, 3.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 20: Grass (alpha-tested cards)
+        scene.materials.push(Material { color: [0.2, 0.45, 0.1, 1.0], params: [0.0, 1.0, 0.0, 0.0], textures: NO_TEXTURES, alpha: [5.0, 0.5, 0.0, 0.0], sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
+        // 21: Studio floor (material preview)
+        scene.materials.push(Material { color: [0.35, 0.35, 0.35, 1.0], params: [0.0, 1.0, 0.0, 0.0], textures: NO_TEXTURES, alpha: OPAQUE, sss: NO_SSS, film: NO_FILM, dispersion: NO_DISPERSION, lobes: NO_LOBES });
 
-        // Geometry Generation
-        let cube = create_cube();
-        let sphere = create_sphere(16, 16);
+        // Geometry Generation. The person is imported meanwhile, tangents are generated for all
+        // meshes at once below.
+        let terrain = Terrain::from_args();
+        let ((cube, sphere, sphere_lods, terrain_mesh), person) = rayon::join(
+            || (create_cube(), create_sphere(16, 16), [create_sphere(10, 10), create_sphere(6, 6)], terrain.mesh()),
+            || load_person("assets/models/person.glb"),
+        );
         
         scene.meshes.push(cube); // 0
         scene.meshes.push(sphere); // 1
@@ -81,13 +515,17 @@ impl Scene {
             mesh_index: 0,
             transform: Mat4::from_scale_rotation_translation(Vec3::new(20.0, 0.1, 20.0), Default::default(), Vec3::new(0.0, -0.1, 0.0)),
             material_index: 8,
+            visible: true,
+            rays: RayMask::ALL,
         });
 
-        // Puddle (Flat Cube slightly above ground)
+        // Puddle (Flat Cube slightly above ground). It lets the light through to the asphalt under it.
         scene.objects.push(SceneObject {
             mesh_index: 0,
             transform: Mat4::from_scale_rotation_translation(Vec3::new(3.0, 0.05, 3.0), Default::default(), Vec3::new(5.0, -0.05, 2.0)),
             material_index: 6,
+            visible: true,
+            rays: RayMask(RayMask::CAMERA.0 | RayMask::REFLECTION.0),
         });
 
         // House
@@ -96,12 +534,16 @@ impl Scene {
             mesh_index: 0,
             transform: Mat4::from_scale_rotation_translation(Vec3::new(4.0, 3.0, 4.0), Default::default(), Vec3::new(-5.0, 1.5, -5.0)),
             material_index: 3,
+            visible: true,
+            rays: RayMask::ALL,
         });
         // Window
         scene.objects.push(SceneObject {
             mesh_index: 0,
             transform: Mat4::from_scale_rotation_translation(Vec3::new(1.0, 1.0, 0.1), Default::default(), Vec3::new(-5.0, 1.5, -0.9)), // Front of house
             material_index: 5,
+            visible: true,
+            rays: RayMask::ALL,
         });
 
         // Tree
@@ -110,69 +552,518 @@ impl Scene {
             mesh_index: 0, // Cube for now as trunk
             transform: Mat4::from_scale_rotation_translation(Vec3::new(0.5, 2.0, 0.5), Default::default(), Vec3::new(5.0, 1.0, -5.0)),
             material_index: 2,
+            visible: true,
+            rays: RayMask::ALL,
         });
         // Leaves
         scene.objects.push(SceneObject {
             mesh_index: 1, // Sphere
             transform: Mat4::from_scale_rotation_translation(Vec3::new(2.0, 2.0, 2.0), Default::default(), Vec3::new(5.0, 3.0, -5.0)),
             material_index: 1,
+            visible: true,
+            rays: RayMask::ALL,
         });
 
-        // Car
+        // Car (drives along the road, lights included)
+        let car_start = scene.objects.len();
         scene.objects.push(SceneObject {
             mesh_index: 0,
             transform: Mat4::from_scale_rotation_translation(Vec3::new(1.5, 0.5, 3.0), Default::default(), Vec3::new(2.0, 0.5, 5.0)),
             material_index: 4,
+            visible: true,
+            rays: RayMask::ALL,
         });
+        for x in [1.5, 2.5] {
+            scene.objects.push(SceneObject {
+                mesh_index: 0,
+                transform: Mat4::from_scale_rotation_translation(Vec3::new(0.3, 0.15, 0.05), Default::default(), Vec3::new(x, 0.55, 6.52)),
+                material_index: 9,
+                visible: true,
+                rays: RayMask::ALL,
+            });
+            scene.objects.push(SceneObject {
+                mesh_index: 0,
+                transform: Mat4::from_scale_rotation_translation(Vec3::new(0.3, 0.15, 0.05), Default::default(), Vec3::new(x, 0.55, 3.48)),
+                material_index: 10,
+                visible: true,
+                rays: RayMask::ALL,
+            });
+        }
+        for object_index in car_start..scene.objects.len() {
+            scene.animations.push(ObjectAnimation {
+                object_index,
+                base: scene.objects[object_index].transform,
+                track: car_route(),
+            });
+        }
+
+        // Person (skinned, walk cycle)
+        let person_transform = Mat4::from_translation(Vec3::new(-2.0, 0.0, 2.0));
+        for (part, material_index) in person {
+            let SkinnedMesh { mesh, skin, skeleton, clip } = part;
+            scene.meshes.push(mesh);
+            scene.skins.push(Skin {
+                mesh_index: scene.meshes.len() - 1,
+                vertices: skin,
+                skeleton,
+                clip,
+            });
+            scene.objects.push(SceneObject {
+                mesh_index: scene.meshes.len() - 1,
+                transform: person_transform,
+                material_index,
+                visible: true,
+                rays: RayMask::ALL,
+            });
+        }
 
-        // Person
+        // Coarser spheres for distant leaves and rigid bodies
+        let mut levels = Vec::new();
+        for (mesh, distance) in sphere_lods.into_iter().zip([8.0, 20.0]) {
+            scene.meshes.push(mesh);
+            levels.push(LodLevel { mesh_index: scene.meshes.len() - 1, distance });
+        }
+        scene.lods.push(MeshLod { mesh_index: 1, levels });
+
+        scene.meshes.push(terrain_mesh);
+        let terrain_mesh_index = scene.meshes.len() - 1;
+        scene.meshes.push(grass::card_mesh());
+        let grass_mesh_index = scene.meshes.len() - 1;
+
+        scene.meshes.par_iter_mut().for_each(Mesh::generate_tangents);
+
+        // Rigid bodies: spheres dropped onto the asphalt, bouncing off the ground, house and tree
+        if cfg!(feature = "physics") {
+            scene.add_rigid_body(0, ColliderShape::Cuboid { half_extents: Vec3::new(10.0, 0.05, 10.0) }, false, 0.5); // Ground
+            scene.add_rigid_body(2, ColliderShape::Cuboid { half_extents: Vec3::new(2.0, 1.5, 2.0) }, false, 0.5); // House
+            scene.add_rigid_body(4, ColliderShape::Cuboid { half_extents: Vec3::new(0.25, 1.0, 0.25) }, false, 0.5); // Trunk
+            scene.add_rigid_body(5, ColliderShape::Ball { radius: 1.0 }, false, 0.3); // Leaves
+
+            let drops = [
+                (Vec3::new(4.0, 5.0, 2.0), 0.8, 11),
+                (Vec3::new(5.5, 7.0, 1.5), 0.6, 5),
+                (Vec3::new(3.0, 9.0, 3.0), 0.5, 12),
+                (Vec3::new(5.3, 11.0, -4.4), 0.7, 11), // Glances off the tree
+                (Vec3::new(-3.05, 8.0, -3.5), 0.6, 12), // Tips over the edge of the house roof
+            ];
+            for (position, diameter, material_index) in drops {
+                scene.objects.push(SceneObject {
+                    mesh_index: 1,
+                    transform: Mat4::from_scale_rotation_translation(Vec3::splat(diameter), Default::default(), position),
+                    material_index,
+                    visible: true,
+                    rays: RayMask::ALL,
+                });
+                scene.add_rigid_body(scene.objects.len() - 1, ColliderShape::Ball { radius: diameter * 0.5 }, true, 0.6);
+            }
+        }
+
+        // Forest around the street: hilly terrain whose flat middle lies below the asphalt, and a
+        // jittered grid of trees on it, drawn as two instance batches (trunks and leaves) with
+        // per-tree leaf colors
         scene.objects.push(SceneObject {
-            mesh_index: 1, // Sphere head
-            transform: Mat4::from_scale_rotation_translation(Vec3::new(0.3, 0.3, 0.3), Default::default(), Vec3::new(-2.0, 1.6, 2.0)),
-            material_index: 7,
+            mesh_index: terrain_mesh_index,
+            transform: Mat4::from_translation(Vec3::new(0.0, -0.15, 0.0)),
+            material_index: 15,
+            visible: true,
+            rays: RayMask::ALL,
         });
+        let forest_half_extent = FOREST_HALF_EXTENT.min((terrain.size * 0.5 / FOREST_SPACING) as i32 - 1);
+        let (mut trunks, mut leaves) = (Vec::new(), Vec::new());
+        for i in -forest_half_extent..forest_half_extent {
+            for j in -forest_half_extent..forest_half_extent {
+                let x = (i as f32 + texture::hash2(i, j)) * FOREST_SPACING;
+                let z = (j as f32 + texture::hash2(j, i)) * FOREST_SPACING;
+                if x.abs() < FOREST_CLEARING && z.abs() < FOREST_CLEARING {
+                    continue;
+                }
+                let size = 0.8 + 0.6 * texture::hash2(i + 1000, j);
+                let autumn = texture::hash2(i, j + 1000);
+                // Sunk a little so trunks on slopes stay rooted on their downhill side
+                let ground = terrain.height(x, z) - 0.3;
+                trunks.push(Instance {
+                    transform: Mat4::from_scale_rotation_translation(Vec3::new(0.5, 2.0, 0.5) * size, Default::default(), Vec3::new(x, ground + size, z)),
+                    material_index: 2,
+                    color: [0.0; 4],
+                });
+                leaves.push(Instance {
+                    transform: Mat4::from_scale_rotation_translation(Vec3::splat(2.0 * size), Default::default(), Vec3::new(x, ground + 3.0 * size, z)),
+                    material_index: 1,
+                    // Mostly green, with one tree in five turning yellow to red
+                    color: if autumn < 0.2 { [0.8, 0.25 + 2.0 * autumn, 0.05, 1.0] } else { [0.1, 0.5 + 0.4 * autumn, 0.1, 1.0] },
+                });
+            }
+        }
+        scene.add_instances(0, trunks);
+        scene.add_instances(1, leaves);
+
+        // Grass on the hillsides: alpha-tested cards, the largest instance batch, swayed by the wind
+        let tufts = grass::tufts(&terrain, 20, grass::count_from_args());
+        if !tufts.is_empty() {
+            scene.grass = Some(scene.add_instances(grass_mesh_index, tufts));
+        }
+
+        // Porch lamp on the front of the house, with a failing bulb
         scene.objects.push(SceneObject {
-            mesh_index: 0, // Cube body
-            transform: Mat4::from_scale_rotation_translation(Vec3::new(0.4, 0.7, 0.2), Default::default(), Vec3::new(-2.0, 0.7, 2.0)),
-            material_index: 0, // Clothes
+            mesh_index: 0,
+            transform: Mat4::from_scale_rotation_translation(Vec3::new(0.2, 0.3, 0.1), Default::default(), Vec3::new(-3.6, 2.2, -2.95)),
+            material_index: 16,
+            visible: true,
+            rays: RayMask::ALL,
         });
 
+        // Precipitation particles, hidden until the weather controller makes it rain or snow
+        let precipitation_start = scene.objects.len();
+        for _ in 0..PRECIPITATION_PARTICLES {
+            scene.objects.push(SceneObject {
+                mesh_index: 0,
+                transform: Mat4::IDENTITY,
+                material_index: 13,
+                visible: false,
+                rays: RayMask::ALL,
+            });
+        }
+        scene.precipitation = precipitation_start..scene.objects.len();
+
+        // Spark particles, hidden until the particle system spawns them. They glow without
+        // casting shadows.
+        let particles_start = scene.objects.len();
+        for _ in 0..particles::pool_size_from_args() {
+            scene.objects.push(SceneObject {
+                mesh_index: 0,
+                transform: Mat4::IDENTITY,
+                material_index: 18,
+                visible: false,
+                rays: RayMask(RayMask::CAMERA.0 | RayMask::REFLECTION.0),
+            });
+        }
+        scene.particles = particles_start..scene.objects.len();
+
+        // Material preview studio, hidden until the preview is turned on
+        let studio_start = scene.objects.len();
+        for (transform, mesh_index) in material_preview::studio_transforms().into_iter().zip([1, 0]) {
+            scene.objects.push(SceneObject {
+                mesh_index,
+                transform,
+                material_index: 21,
+                visible: false,
+                rays: RayMask::ALL,
+            });
+        }
+        scene.studio = studio_start..scene.objects.len();
+
+        scene.share_identical_meshes();
         scene
     }
+
+    /// Makes identical meshes one: objects, instance batches, skins and LODs using a copy of an
+    /// earlier mesh are pointed at that mesh and the copy is dropped, so a mesh an imported scene
+    /// repeats gets one set of buffers and one BLAS for all its instances. Meshes are matched
+    /// by a hash of their vertex and index data, then compared byte for byte. Skinned meshes are
+    /// kept apart, each skin poses its own. Returns the number of meshes dropped.
+    pub fn share_identical_meshes(&mut self) -> usize {
+        let skinned: Vec<usize> = self.skins.iter().map(|skin| skin.mesh_index).collect();
+        let mut by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+        let mut remap = Vec::with_capacity(self.meshes.len()); // Old mesh index -> new one
+        let mut keep = Vec::with_capacity(self.meshes.len());
+        let mut kept = 0;
+        for (mesh_index, mesh) in self.meshes.iter().enumerate() {
+            let original = if skinned.contains(&mesh_index) {
+                None
+            } else {
+                let candidates = by_hash.entry(mesh.geometry_hash()).or_default();
+                let original = candidates.iter().copied().find(|&other| self.meshes[other].same_geometry(mesh));
+                if original.is_none() {
+                    candidates.push(mesh_index);
+                }
+                original
+            };
+            keep.push(original.is_none());
+            match original {
+                Some(other) => remap.push(remap[other]),
+                None => {
+                    remap.push(kept);
+                    kept += 1;
+                }
+            }
+        }
+
+        let dropped = self.meshes.len() - kept;
+        if dropped == 0 {
+            return 0;
+        }
+        let dropped_bytes: usize = self.meshes.iter().zip(&keep).filter(|(_, &keep)| !keep)
+            .map(|(mesh, _)| mesh.vertices.len() * std::mem::size_of::<Vertex>() + mesh.indices.len() * std::mem::size_of::<u32>())
+            .sum();
+        let mut keep = keep.into_iter();
+        self.meshes.retain(|_| keep.next().unwrap());
+
+        for object in &mut self.objects {
+            object.mesh_index = remap[object.mesh_index];
+        }
+        for batch in &mut self.instance_batches {
+            batch.mesh_index = remap[batch.mesh_index];
+        }
+        for skin in &mut self.skins {
+            skin.mesh_index = remap[skin.mesh_index];
+        }
+        for lod in &mut self.lods {
+            lod.mesh_index = remap[lod.mesh_index];
+            for level in &mut lod.levels {
+                level.mesh_index = remap[level.mesh_index];
+            }
+        }
+        log::info!("Shared {} duplicate meshes, saving {} KiB of geometry", dropped, dropped_bytes / 1024);
+        dropped
+    }
+
+    fn add_rigid_body(&mut self, object_index: usize, shape: ColliderShape, dynamic: bool, restitution: f32) {
+        self.rigid_bodies.push(RigidBodyDesc {
+            object_index,
+            shape,
+            dynamic,
+            restitution,
+            start: self.objects[object_index].transform,
+        });
+    }
+
+    /// Adds a batch of instances of `mesh_index` and returns its index in `instance_batches`.
+    pub fn add_instances(&mut self, mesh_index: usize, instances: Vec<Instance>) -> usize {
+        self.instance_batches.push(InstanceBatch { mesh_index, instances });
+        self.instance_batches.len() - 1
+    }
+
+    /// Number of TLAS instances: the objects followed by every batch's instances.
+    pub fn instance_count(&self) -> usize {
+        self.objects.len() + self.instance_batches.iter().map(|batch| batch.instances.len()).sum::<usize>()
+    }
+
+    /// Mesh, instance, material and texture counts and sizes, for the log and embedders.
+    pub fn stats(&self) -> SceneStats {
+        let triangles = |mesh_index: usize| self.meshes[mesh_index].indices.len() / 3;
+        let object_triangles: usize = self.objects.iter().map(|obj| triangles(obj.mesh_index)).sum();
+        let batch_triangles: usize = self.instance_batches.iter().map(|batch| triangles(batch.mesh_index) * batch.instances.len()).sum();
+        SceneStats {
+            meshes: self.meshes.len(),
+            vertices: self.meshes.iter().map(|mesh| mesh.vertices.len()).sum(),
+            triangles: self.meshes.iter().map(|mesh| mesh.indices.len() / 3).sum(),
+            objects: self.objects.len(),
+            instances: self.instance_count(),
+            instanced_triangles: object_triangles + batch_triangles,
+            materials: self.materials.len(),
+            textures: self.textures.len(),
+            geometry_bytes: self.meshes.iter()
+                .map(|mesh| (mesh.vertices.len() * std::mem::size_of::<Vertex>() + mesh.indices.len() * std::mem::size_of::<u32>()) as u64)
+                .sum(),
+            texture_bytes: self.textures.iter().flat_map(|texture| &texture.levels).map(|level| level.len() as u64).sum(),
+        }
+    }
+
+    /// Poses all animated objects at `time` (seconds).
+    pub fn animate(&mut self, time: f32) {
+        for animation in &self.animations {
+            self.objects[animation.object_index].transform = animation.track.sample(time) * animation.base;
+        }
+    }
+
+    /// Transform an object has without animation: its animation's base if it has one.
+    pub fn rest_transform(&self, object_index: usize) -> Mat4 {
+        self.animations.iter()
+            .find(|animation| animation.object_index == object_index)
+            .map_or(self.objects[object_index].transform, |animation| animation.base)
+    }
+
+    /// Applies an edit to an object's transform, and to its animation base and rigid body
+    /// start pose so the change survives the next `animate` and physics reset.
+    pub fn edit_transform(&mut self, object_index: usize, edit: impl Fn(Mat4) -> Mat4) {
+        self.objects[object_index].transform = edit(self.objects[object_index].transform);
+        for animation in self.animations.iter_mut().filter(|animation| animation.object_index == object_index) {
+            animation.base = edit(animation.base);
+        }
+        for body in self.rigid_bodies.iter_mut().filter(|body| body.object_index == object_index) {
+            body.start = edit(body.start);
+        }
+    }
+
+    /// World-space bounding spheres (xyz: center, w: radius) of the objects that can
+    /// focus light into caustics, i.e. those with a refractive index (glass, water).
+    /// The photon pass aims its photons at these.
+    pub fn caustic_targets(&self) -> Vec<[f32; 4]> {
+        self.objects.iter()
+            .filter(|obj| self.materials[obj.material_index].params[2] > 0.0)
+            .map(|obj| {
+                let (min, max) = self.meshes[obj.mesh_index].bounds();
+                let center = obj.transform.transform_point3((min + max) * 0.5);
+                let radius = (0..8).map(|corner| {
+                    let local = Vec3::new(
+                        if corner & 1 == 0 { min.x } else { max.x },
+                        if corner & 2 == 0 { min.y } else { max.y },
+                        if corner & 4 == 0 { min.z } else { max.z },
+                    );
+                    obj.transform.transform_point3(local).distance(center)
+                }).fold(0.0, f32::max);
+                [center.x, center.y, center.z, radius]
+            })
+            .collect()
+    }
+}
+
+/// Bind-pose mesh with its per-vertex skin influences, skeleton and animation clip.
+pub struct SkinnedMesh {
+    pub mesh: Mesh,
+    pub skin: Vec<SkinVertex>,
+    pub skeleton: Skeleton,
+    pub clip: AnimationClip,
+}
+
+/// Skinned person parts with their material index. Loads a glTF character from `path`
+/// if present, imported and converted to meters and Y up as the import options say,
+/// otherwise builds the procedural walker.
+fn load_person(path: &str) -> Vec<(SkinnedMesh, usize)> {
+    if std::path::Path::new(path).exists() {
+        let options = ImportOptions::from_args();
+        match asset_cache::load_skinned(path, options.crease_angle) {
+            Ok(mut model) => {
+                options.apply_to_skinned(&mut model);
+                log::info!("Loaded {} ({} vertices, {} joints, {:.2}s animation)", path, model.mesh.vertices.len(), model.skeleton.joints.len(), model.clip.duration);
+                return vec![(model, 0)];
+            }
+            Err(e) => log::warn!("Failed to load {}: {}, using procedural walker", path, e),
+        }
+    }
+
+    vec![
+        (create_walker_body(), 0), // Clothes
+        (create_walker_head(), 7), // Skin
+    ]
+}
+
+/// Walker body: an octagonal tube per bone. Vertices near the start of a segment blend
+/// into the parent joint so knees, elbows and hips bend smoothly.
+fn create_walker_body() -> SkinnedMesh {
+    const SIDES: u32 = 8;
+    const RINGS: u32 = 6;
+    const BLEND: f32 = 0.3; // Fraction of the segment blended with the parent
+
+    let mut mesh = Mesh { vertices: Vec::new(), indices: Vec::new() };
+    let mut skin = Vec::new();
+
+    for (joint, parent, start, end, radius) in animation::walker_segments() {
+        let axis = (end - start).normalize();
+        let side = if axis.x.abs() < 0.9 { Vec3::X } else { Vec3::Z };
+        let b1 = axis.cross(side).normalize();
+        let b2 = axis.cross(b1);
+        let base = mesh.vertices.len() as u32;
+
+        for ring in 0..=RINGS {
+            let t = ring as f32 / RINGS as f32;
+            let center = start.lerp(end, t);
+            let parent_weight = if parent == joint { 0.0 } else { (1.0 - t / BLEND).max(0.0) * 0.5 };
+            for s in 0..=SIDES {
+                let angle = s as f32 / SIDES as f32 * std::f32::consts::TAU;
+                let normal = b1 * angle.cos() + b2 * angle.sin();
+                mesh.vertices.push(Vertex::new((center + normal * radius).into(), normal.into(), [s as f32 / SIDES as f32, t]));
+                skin.push(SkinVertex { joints: [joint as u32, parent as u32, 0, 0], weights: [1.0 - parent_weight, parent_weight, 0.0, 0.0] });
+            }
+        }
+        for ring in 0..RINGS {
+            for s in 0..SIDES {
+                let a = base + ring * (SIDES + 1) + s;
+                let b = a + SIDES + 1;
+                mesh.indices.extend_from_slice(&[a, b, a + 1, b, b + 1, a + 1]);
+            }
+        }
+
+        // End caps (fans), bound to the segment's own joint
+        for (center, normal, ring) in [(start, -axis, 0), (end, axis, RINGS)] {
+            let center_index = mesh.vertices.len() as u32;
+            mesh.vertices.push(Vertex::new(center.into(), normal.into(), [0.5, 0.5]));
+            skin.push(SkinVertex { joints: [joint as u32, 0, 0, 0], weights: [1.0, 0.0, 0.0, 0.0] });
+            let rim = mesh.vertices.len() as u32;
+            for s in 0..=SIDES {
+                let angle = s as f32 / SIDES as f32 * std::f32::consts::TAU;
+                let offset = (b1 * angle.cos() + b2 * angle.sin()) * radius;
+                let pos = start.lerp(end, ring as f32 / RINGS as f32) + offset;
+                mesh.vertices.push(Vertex::new(pos.into(), normal.into(), [0.5 + 0.5 * angle.cos(), 0.5 + 0.5 * angle.sin()]));
+                skin.push(SkinVertex { joints: [joint as u32, 0, 0, 0], weights: [1.0, 0.0, 0.0, 0.0] });
+            }
+            for s in 0..SIDES {
+                // Wind so the face normal points along the cap normal
+                if ring == 0 {
+                    mesh.indices.extend_from_slice(&[center_index, rim + s, rim + s + 1]);
+                } else {
+                    mesh.indices.extend_from_slice(&[center_index, rim + s + 1, rim + s]);
+                }
+            }
+        }
+    }
+
+    SkinnedMesh { mesh, skin, skeleton: animation::walker_skeleton(), clip: animation::walk_cycle() }
+}
+
+/// Walker head: a sphere rigidly bound to the head joint.
+fn create_walker_head() -> SkinnedMesh {
+    let (joint, center) = animation::walker_head();
+    let mut mesh = create_sphere(16, 16);
+    for vertex in &mut mesh.vertices {
+        vertex.pos = (Vec3::from(vertex.pos) * 0.26 + center).into();
+    }
+    let skin = vec![SkinVertex { joints: [joint as u32, 0, 0, 0], weights: [1.0, 0.0, 0.0, 0.0] }; mesh.vertices.len()];
+    SkinnedMesh { mesh, skin, skeleton: animation::walker_skeleton(), clip: animation::walk_cycle() }
+}
+
+/// Car loop: pulls away, cruises down the street at 4 m/s, brakes to a stop and waits,
+/// then starts over at the beginning of the road.
+fn car_route() -> TransformTrack {
+    let key = |time: f32, z: f32, easing: Easing| Keyframe {
+        time,
+        transform: Transform::from_translation(Vec3::new(0.0, 0.0, z)),
+        easing,
+    };
+    TransformTrack {
+        keyframes: vec![
+            key(0.0, -13.0, Easing::EaseIn),
+            key(1.5, -10.0, Easing::Linear),
+            key(3.5, -2.0, Easing::EaseOut),
+            key(5.0, 1.0, Easing::Hold),
+            key(6.0, 1.0, Easing::Hold),
+        ],
+        looping: true,
+    }
 }
 
 fn create_cube() -> Mesh {
     let vertices = vec![
         // Front
-        Vertex { pos: [-0.5, -0.5,  0.5], nrm: [ 0.0,  0.0,  1.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [ 0.5, -0.5,  0.5], nrm: [ 0.0,  0.0,  1.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [ 0.5,  0.5,  0.5], nrm: [ 0.0,  0.0,  1.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [-0.5,  0.5,  0.5], nrm: [ 0.0,  0.0,  1.0], color: [1.0, 1.0, 1.0] },
+        Vertex::new([-0.5, -0.5,  0.5], [ 0.0,  0.0,  1.0], [0.0, 1.0]),
+        Vertex::new([ 0.5, -0.5,  0.5], [ 0.0,  0.0,  1.0], [1.0, 1.0]),
+        Vertex::new([ 0.5,  0.5,  0.5], [ 0.0,  0.0,  1.0], [1.0, 0.0]),
+        Vertex::new([-0.5,  0.5,  0.5], [ 0.0,  0.0,  1.0], [0.0, 0.0]),
         // Back
-        Vertex { pos: [-0.5, -0.5, -0.5], nrm: [ 0.0,  0.0, -1.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [-0.5,  0.5, -0.5], nrm: [ 0.0,  0.0, -1.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [ 0.5,  0.5, -0.5], nrm: [ 0.0,  0.0, -1.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [ 0.5, -0.5, -0.5], nrm: [ 0.0,  0.0, -1.0], color: [1.0, 1.0, 1.0] },
+        Vertex::new([-0.5, -0.5, -0.5], [ 0.0,  0.0, -1.0], [1.0, 1.0]),
+        Vertex::new([-0.5,  0.5, -0.5], [ 0.0,  0.0, -1.0], [1.0, 0.0]),
+        Vertex::new([ 0.5,  0.5, -0.5], [ 0.0,  0.0, -1.0], [0.0, 0.0]),
+        Vertex::new([ 0.5, -0.5, -0.5], [ 0.0,  0.0, -1.0], [0.0, 1.0]),
         // Top
-        Vertex { pos: [-0.5,  0.5, -0.5], nrm: [ 0.0,  1.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [-0.5,  0.5,  0.5], nrm: [ 0.0,  1.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [ 0.5,  0.5,  0.5], nrm: [ 0.0,  1.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [ 0.5,  0.5, -0.5], nrm: [ 0.0,  1.0,  0.0], color: [1.0, 1.0, 1.0] },
+        Vertex::new([-0.5,  0.5, -0.5], [ 0.0,  1.0,  0.0], [0.0, 0.0]),
+        Vertex::new([-0.5,  0.5,  0.5], [ 0.0,  1.0,  0.0], [0.0, 1.0]),
+        Vertex::new([ 0.5,  0.5,  0.5], [ 0.0,  1.0,  0.0], [1.0, 1.0]),
+        Vertex::new([ 0.5,  0.5, -0.5], [ 0.0,  1.0,  0.0], [1.0, 0.0]),
         // Bottom
-        Vertex { pos: [-0.5, -0.5, -0.5], nrm: [ 0.0, -1.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [ 0.5, -0.5, -0.5], nrm: [ 0.0, -1.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [ 0.5, -0.5,  0.5], nrm: [ 0.0, -1.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [-0.5, -0.5,  0.5], nrm: [ 0.0, -1.0,  0.0], color: [1.0, 1.0, 1.0] },
+        Vertex::new([-0.5, -0.5, -0.5], [ 0.0, -1.0,  0.0], [0.0, 1.0]),
+        Vertex::new([ 0.5, -0.5, -0.5], [ 0.0, -1.0,  0.0], [1.0, 1.0]),
+        Vertex::new([ 0.5, -0.5,  0.5], [ 0.0, -1.0,  0.0], [1.0, 0.0]),
+        Vertex::new([-0.5, -0.5,  0.5], [ 0.0, -1.0,  0.0], [0.0, 0.0]),
         // Right
-        Vertex { pos: [ 0.5, -0.5, -0.5], nrm: [ 1.0,  0.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [ 0.5,  0.5, -0.5], nrm: [ 1.0,  0.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [ 0.5,  0.5,  0.5], nrm: [ 1.0,  0.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [ 0.5, -0.5,  0.5], nrm: [ 1.0,  0.0,  0.0], color: [1.0, 1.0, 1.0] },
+        Vertex::new([ 0.5, -0.5, -0.5], [ 1.0,  0.0,  0.0], [1.0, 1.0]),
+        Vertex::new([ 0.5,  0.5, -0.5], [ 1.0,  0.0,  0.0], [1.0, 0.0]),
+        Vertex::new([ 0.5,  0.5,  0.5], [ 1.0,  0.0,  0.0], [0.0, 0.0]),
+        Vertex::new([ 0.5, -0.5,  0.5], [ 1.0,  0.0,  0.0], [0.0, 1.0]),
         // Left
-        Vertex { pos: [-0.5, -0.5, -0.5], nrm: [-1.0,  0.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [-0.5, -0.5,  0.5], nrm: [-1.0,  0.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [-0.5,  0.5,  0.5], nrm: [-1.0,  0.0,  0.0], color: [1.0, 1.0, 1.0] },
-        Vertex { pos: [-0.5,  0.5, -0.5], nrm: [-1.0,  0.0,  0.0], color: [1.0, 1.0, 1.0] },
+        Vertex::new([-0.5, -0.5, -0.5], [-1.0,  0.0,  0.0], [0.0, 1.0]),
+        Vertex::new([-0.5, -0.5,  0.5], [-1.0,  0.0,  0.0], [1.0, 1.0]),
+        Vertex::new([-0.5,  0.5,  0.5], [-1.0,  0.0,  0.0], [1.0, 0.0]),
+        Vertex::new([-0.5,  0.5, -0.5], [-1.0,  0.0,  0.0], [0.0, 0.0]),
     ];
     let indices = vec![
         0, 1, 2, 0, 2, 3,
@@ -201,11 +1092,7 @@ fn create_sphere(slices: u32, stacks: u32) -> Mesh {
             let y = phi.cos();
             let z = theta.sin() * phi.sin();
 
-            vertices.push(Vertex {
-                pos: [x * 0.5, y * 0.5, z * 0.5],
-                nrm: [x, y, z],
-                color: [1.0, 1.0, 1.0],
-            });
+            vertices.push(Vertex::new([x * 0.5, y * 0.5, z * 0.5], [x, y, z], [u, v]));
         }
     }
 
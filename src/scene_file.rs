@@ -0,0 +1,92 @@
+use std::io::Write;
+use std::path::Path;
+use glam::Mat4;
+use crate::scene::{RayMask, Scene};
+
+/// Scene file written by the editor, loaded over the built-in scene at startup.
+pub const SCENE_FILE: &str = "scene.txt";
+
+/// Writes every object's mesh, material, rest transform and ray visibility as one line of text:
+/// `object <index> mesh <mesh> material <material> transform <16 floats, column-major> rays <kinds>`,
+/// the kinds being `camera`, `shadow` and `reflection` separated by commas, or `none`.
+/// Precipitation particles are left out, the weather controller owns them, and so is the
+/// material preview's studio.
+pub fn save(scene: &Scene, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let mut file = Vec::new();
+    writeln!(file, "# rust-raytracing scene")?;
+    for (index, object) in scene.objects.iter().enumerate() {
+        if scene.precipitation.contains(&index) || scene.studio.contains(&index) {
+            continue;
+        }
+        write!(file, "object {} mesh {} material {} transform", index, object.mesh_index, object.material_index)?;
+        for value in scene.rest_transform(index).to_cols_array() {
+            write!(file, " {}", value)?;
+        }
+        writeln!(file, " rays {}", object.rays)?;
+    }
+    write_atomically(path, &file)
+}
+
+/// Replaces `path` with `contents` through a synced temporary file and a rename, so a crash
+/// or driver reset during the write leaves either the old or the new file, never a torn one.
+pub fn write_atomically(path: impl AsRef<Path>, contents: &[u8]) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let mut file = std::fs::File::create(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    std::fs::rename(&temp_path, path)
+}
+
+/// Applies a file written by `save` to the scene. Objects are matched by index and must
+/// still use the same mesh, so a file saved from a different version of the scene is
+/// rejected rather than scrambling it. Files from before ray visibility leave it as it is.
+pub fn load(scene: &mut Scene, path: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)?;
+
+    // Parse everything first so a bad line leaves the scene untouched
+    let mut edits = Vec::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", line_number + 1, message);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let has_rays = tokens.len() == 25 && tokens[23] == "rays";
+        if (tokens.len() != 23 && !has_rays) || tokens[0] != "object" || tokens[2] != "mesh" || tokens[4] != "material" || tokens[6] != "transform" {
+            return Err(error("expected `object <i> mesh <m> material <m> transform <16 floats> [rays <kinds>]`").into());
+        }
+        let index: usize = tokens[1].parse()?;
+        let mesh_index: usize = tokens[3].parse()?;
+        let material_index: usize = tokens[5].parse()?;
+        let mut values = [0.0f32; 16];
+        for (value, token) in values.iter_mut().zip(&tokens[7..23]) {
+            *value = token.parse()?;
+        }
+        let rays = if has_rays {
+            Some(RayMask::parse(tokens[24]).ok_or_else(|| error("expected ray kinds `camera`, `shadow`, `reflection` or `none`"))?)
+        } else {
+            None
+        };
+
+        if index >= scene.objects.len() || scene.objects[index].mesh_index != mesh_index {
+            return Err(error("object does not match the scene").into());
+        }
+        if material_index >= scene.materials.len() {
+            return Err(error("material out of range").into());
+        }
+        edits.push((index, material_index, Mat4::from_cols_array(&values), rays));
+    }
+
+    for (index, material_index, transform, rays) in edits {
+        scene.objects[index].material_index = material_index;
+        if let Some(rays) = rays {
+            scene.objects[index].rays = rays;
+        }
+        let rest = scene.rest_transform(index);
+        scene.edit_transform(index, |m| transform * rest.inverse() * m);
+    }
+    Ok(())
+}
@@ -0,0 +1,20 @@
+#version 460
+#extension GL_EXT_nonuniform_qualifier : enable
+
+// Hybrid rendering G-buffer pass: stores which triangle covers the pixel. raygen shades it
+// from there instead of tracing the camera ray. Alpha-tested surfaces are cut out like the
+// any-hit shader does when tracing.
+
+layout(binding = 6, set = 0) uniform sampler2D textures[];
+
+layout(location = 0) flat in uvec2 visibility;
+layout(location = 1) flat in vec2 alpha;
+layout(location = 2) in vec2 uv;
+layout(location = 0) out uvec2 outVisibility;
+
+void main() {
+    if (alpha.x >= 0.0 && texture(textures[nonuniformEXT(int(alpha.x))], uv).r < alpha.y) {
+        discard;
+    }
+    outVisibility = visibility;
+}
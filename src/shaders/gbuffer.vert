@@ -0,0 +1,34 @@
+#version 460
+#extension GL_EXT_scalar_block_layout : enable
+#extension GL_EXT_shader_explicit_arithmetic_types_int64 : require
+#extension GL_EXT_buffer_reference2 : require
+
+// Hybrid rendering G-buffer pass: rasterizes the visible instances, pulling their vertices
+// from the same buffers the ray tracing shaders read. The draw's instance index is the TLAS
+// instance, so one draw covers a run of consecutive TLAS instances sharing a mesh.
+
+#include "include/scene.glsl"
+
+layout(push_constant) uniform Params {
+    mat4 viewProj; // Jittered like this frame's camera rays
+} params;
+
+// x: TLAS instance + 1 (0 where nothing was drawn), y: triangle
+layout(location = 0) flat out uvec2 visibility;
+// x: alpha mask texture index (-1: opaque), y: cutoff, from the material
+layout(location = 1) flat out vec2 alpha;
+layout(location = 2) out vec2 uv;
+
+void main() {
+    InstanceData instance = instanceData[gl_InstanceIndex];
+    SceneDesc desc = sceneDesc[instance.meshIndex];
+    uint index = meshIndex(desc, uint(gl_VertexIndex));
+    Vertex v = Vertices(desc.vertexAddress).v[index];
+
+    InstanceTransform transform = transforms[gl_InstanceIndex];
+    vec3 position = vec4(v.pos[0], v.pos[1], v.pos[2], 1.0) * mat3x4(transform.rows[0], transform.rows[1], transform.rows[2]);
+    gl_Position = params.viewProj * vec4(position, 1.0);
+    visibility = uvec2(gl_InstanceIndex + 1, gl_VertexIndex / 3);
+    alpha = materials[instance.materialIndex].alpha.xy;
+    uv = vec2(v.uv[0], v.uv[1]);
+}
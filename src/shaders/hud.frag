@@ -0,0 +1,41 @@
+#version 460
+#extension GL_EXT_scalar_block_layout : enable
+#extension GL_EXT_shader_explicit_arithmetic_types_int64 : require
+#extension GL_EXT_buffer_reference2 : require
+
+#include "include/display.glsl"
+
+// HUD text: the glyph's bitmap pixels in the instance color over a translucent black cell, so
+// the text stays readable on bright parts of the image. Colors are sRGB, encoded for the
+// swapchain's output.
+
+// 16 rows of 8 pixels per glyph, four rows to a word, top row in the low byte and the leftmost
+// pixel in each row's high bit
+layout(buffer_reference, scalar) readonly buffer Font { uint words[]; };
+
+layout(push_constant) uniform Params {
+    uint64_t glyphsAddress;
+    uint64_t fontAddress;
+    vec2 screenSize;
+    vec2 glyphSize;
+    float backgroundAlpha;
+    uint transfer; // OUTPUT_* of include/display.glsl
+    float paperWhite; // Nits, for HDR output
+} params;
+
+layout(location = 0) in vec2 cellPos;
+layout(location = 1) flat in uint glyph;
+layout(location = 2) flat in vec4 color;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    uint column = min(uint(cellPos.x * 8.0), 7u);
+    uint row = min(uint(cellPos.y * 16.0), 15u);
+    uint word = Font(params.fontAddress).words[glyph * 4u + row / 4u];
+    uint bits = (word >> ((row % 4u) * 8u)) & 0xffu;
+    vec4 cellColor = (bits & (0x80u >> column)) != 0u ? color : vec4(0.0, 0.0, 0.0, params.backgroundAlpha);
+    if (params.transfer != OUTPUT_SDR) {
+        cellColor.rgb = encodeOutput(srgbToLinear(cellColor.rgb), params.transfer, params.paperWhite);
+    }
+    outColor = cellColor;
+}
@@ -0,0 +1,40 @@
+#version 460
+#extension GL_EXT_scalar_block_layout : enable
+#extension GL_EXT_shader_explicit_arithmetic_types_int64 : require
+#extension GL_EXT_buffer_reference2 : require
+
+// HUD text: one quad (two triangles, no vertex buffer) per glyph instance, in window pixels.
+
+struct GlyphInstance {
+    vec2 position; // Top left corner
+    uint glyph;
+    uint color; // RGBA8, red in the low byte
+};
+
+layout(buffer_reference, scalar) readonly buffer Glyphs { GlyphInstance g[]; };
+
+layout(push_constant) uniform Params {
+    uint64_t glyphsAddress;
+    uint64_t fontAddress;
+    vec2 screenSize;
+    vec2 glyphSize; // Cell size in pixels, after the UI scale
+    float backgroundAlpha;
+    uint transfer; // OUTPUT_* of include/display.glsl
+    float paperWhite; // Nits, for HDR output
+} params;
+
+layout(location = 0) out vec2 cellPos; // 0..1 across the cell
+layout(location = 1) flat out uint glyph;
+layout(location = 2) flat out vec4 color;
+
+const vec2 CORNERS[6] = vec2[](vec2(0, 0), vec2(1, 0), vec2(0, 1), vec2(1, 0), vec2(1, 1), vec2(0, 1));
+
+void main() {
+    GlyphInstance instance = Glyphs(params.glyphsAddress).g[gl_InstanceIndex];
+    vec2 corner = CORNERS[gl_VertexIndex];
+    vec2 pixel = instance.position + corner * params.glyphSize;
+    gl_Position = vec4(pixel / params.screenSize * 2.0 - 1.0, 0.0, 1.0);
+    cellPos = corner;
+    glyph = instance.glyph;
+    color = unpackUnorm4x8(instance.color);
+}
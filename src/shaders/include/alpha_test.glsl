@@ -0,0 +1,29 @@
+// Alpha testing of a ray's candidate hits, for the any-hit shaders. The including shader
+// includes scene.glsl and declares the `textures` array (binding 6).
+#ifndef ALPHA_TEST_GLSL
+#define ALPHA_TEST_GLSL
+
+// Whether the candidate hit at barycentrics `attribs` falls in a cut-out part of its material's
+// alpha mask, below the cutoff. Materials without a mask are never cut out.
+bool alphaCutOut(vec2 attribs) {
+    InstanceData instance = instanceData[gl_InstanceID];
+    SceneDesc desc = sceneDesc[instance.meshIndex];
+    Material mat = materials[instance.materialIndex];
+    int alphaMask = int(mat.alpha.x);
+    if (alphaMask < 0) {
+        return false;
+    }
+
+    Vertices vertices = Vertices(desc.vertexAddress);
+    uvec3 ind = triangleIndices(desc, uint(gl_PrimitiveID));
+    Vertex v0 = vertices.v[ind.x];
+    Vertex v1 = vertices.v[ind.y];
+    Vertex v2 = vertices.v[ind.z];
+    const vec3 barycentrics = vec3(1.0 - attribs.x - attribs.y, attribs.x, attribs.y);
+    vec2 uv = vec2(v0.uv[0], v0.uv[1]) * barycentrics.x + vec2(v1.uv[0], v1.uv[1]) * barycentrics.y + vec2(v2.uv[0], v2.uv[1]) * barycentrics.z;
+
+    // No derivatives here: the full resolution mask keeps thin blades from fading with distance
+    return textureLod(textures[nonuniformEXT(alphaMask)], uv, 0.0).r < mat.alpha.y;
+}
+
+#endif
@@ -0,0 +1,14 @@
+// AOVs and the teaching probe, written for the surface the camera sees by whichever shader
+// shades it: the closest hit shader, or raygen for a surface from the hybrid G-buffer
+#ifndef AOV_GLSL
+#define AOV_GLSL
+
+layout(binding = 18, set = 0, rgba16f) uniform writeonly image2D aovNormal; // World space shading normal
+layout(binding = 19, set = 0, rgba16f) uniform writeonly image2D aovAlbedo;
+layout(binding = 20, set = 0, r32ui) uniform writeonly uimage2D aovInstance; // TLAS instance, 0xffffffff on a miss
+// Teaching mode: every shading term of the probe pixel, TEACHING_TERMS per frame slot
+// (albedo, shadow, reflection, refraction, final); w is 1 once the surface was shaded
+layout(binding = 21, set = 0) buffer TeachingProbe { vec4 probeTerms[]; };
+const int TEACHING_TERMS = 5;
+
+#endif
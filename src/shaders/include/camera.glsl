@@ -0,0 +1,48 @@
+// Camera UBO and per-frame push constants, shared by the ray tracing shaders
+#ifndef CAMERA_GLSL
+#define CAMERA_GLSL
+
+layout(binding = 2, set = 0) uniform CameraProperties {
+    mat4 viewInverse;
+    mat4 projInverse;
+    vec4 lightPos; // xyz: position, w: radius
+    vec4 lightColor; // rgb: color times intensity
+    vec4 photonParams; // x: caustics, y: cell size, z: grid cells, w: caustic targets
+    vec4 outputParams; // x: light path channel, y: anaglyph, z: eye separation, w: convergence distance
+    vec4 skyParams; // x: sky occlusion, y: cell size, z: cache cells, w: max samples per cell
+    vec4 lensParams; // x: aperture radius (0: pinhole), y: focus distance, z: focus probe slot
+    vec4 distortionParams; // x: k1, y: k2 (radial), z: p1, w: p2 (tangential)
+    vec4 gizmoParams; // xyz: editor gizmo origin, w: size (0: hidden)
+    vec4 gizmoState; // x: mode (0: translate, 1: rotate, 2: scale), y: active axis, z: UI scale, w: high contrast
+    vec4 weatherParams; // x: overcast, y: wetness, z: snow cover
+    mat4 prevViewProj; // Previous frame's view-projection, for motion vectors
+    vec4 teachingParams; // xy: probe pixel, z: teaching mode on, w: probe slot
+    vec4 tileParams; // xy: size of the frame traced in tiles (the render size when untiled), zw: origin of the tile, pixels
+    vec4 viewportParams; // x: first storage image column of this view (split screen), pixels
+    vec4 projectionParams; // x: projection (PROJECTION_*), y: eye separation (ODS), half height (orthographic) or field of view (fisheye)
+    vec4 timeParams; // x: scene time, seconds, y: animation frame (frames the scene time advanced on)
+    vec4 studioParams; // x: material preview on (the miss shader draws the studio)
+} cam;
+
+// Camera projections, projectionParams.x. The thin lens camera is a perspective one with an aperture.
+const float PROJECTION_PERSPECTIVE = 0.0;
+const float PROJECTION_EQUIRECTANGULAR = 1.0;
+const float PROJECTION_ODS = 2.0; // Stereo equirectangular, left eye on top
+const float PROJECTION_ORTHOGRAPHIC = 3.0;
+const float PROJECTION_FISHEYE = 4.0;
+
+// Storage image pixel of this launch: with split screen each view is traced into its part of the images
+ivec2 imagePixel() {
+    return ivec2(gl_LaunchIDEXT.xy) + ivec2(cam.viewportParams.xy);
+}
+
+// Small data that changes every frame, pushed with each frame's commands rather than uploaded
+layout(push_constant) uniform FrameParams {
+    vec4 settings; // x: soft_shadows, y: reflections, z: refraction, w: sss
+    vec4 exposureParams; // x: accumulating (jitters the samples), y: frames accumulated before this one, z: camera rays per pixel, w: exposure
+    uvec4 seedParams; // x: frame seed, y: run seed (--seed), z: frame number in the random sequence
+    vec4 jitterParams; // xy: offset of a lone camera ray from the pixel center (temporal upscaling), pixels; z: hybrid rendering
+    uvec4 counterParams; // xy: address of this frame's ray counter (ray_count.glsl), 0 if rays aren't counted; z: candidate hits per camera ray the traversal heatmap shows red, 0 without the heatmap
+} frame;
+
+#endif
@@ -0,0 +1,45 @@
+// Color encodings (see color.rs for the conventions): the display images hold sRGB encoded
+// color, everything lit and traced is linear. For the swapchain's output
+// (display::DisplayOutput), 1.0 in linear is the display image's white, shown at the paper
+// white in HDR output.
+
+const uint OUTPUT_SDR = 0u; // sRGB encoded, clamped to 0..1
+const uint OUTPUT_SCRGB = 1u; // Linear, sRGB primaries, 1.0 at 80 nits
+const uint OUTPUT_HDR10 = 2u; // PQ, Rec. 2020 primaries
+
+// sRGB encoded color (the display images, the HUD's colors) to linear
+vec3 srgbToLinear(vec3 c) {
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), greaterThan(c, vec3(0.04045)));
+}
+
+// Linear color to sRGB encoded, clamped to 0..1 (the display images)
+vec3 linearToSrgb(vec3 c) {
+    c = clamp(c, 0.0, 1.0);
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, greaterThan(c, vec3(0.0031308)));
+}
+
+// SMPTE ST 2084 inverse EOTF, luminance as a fraction of 10000 nits
+vec3 pqEncode(vec3 y) {
+    const float m1 = 0.1593017578125;
+    const float m2 = 78.84375;
+    const float c1 = 0.8359375;
+    const float c2 = 18.8515625;
+    const float c3 = 18.6875;
+    vec3 p = pow(clamp(y, 0.0, 1.0), vec3(m1));
+    return pow((c1 + c2 * p) / (1.0 + c3 * p), vec3(m2));
+}
+
+vec3 encodeOutput(vec3 color, uint transfer, float paperWhite) {
+    if (transfer == OUTPUT_SCRGB) {
+        return color * (paperWhite / 80.0);
+    }
+    if (transfer == OUTPUT_HDR10) {
+        // Rec. 709 to Rec. 2020 primaries (columns)
+        const mat3 toRec2020 = mat3(
+            0.6274, 0.0691, 0.0164,
+            0.3293, 0.9195, 0.0880,
+            0.0433, 0.0114, 0.8956);
+        return pqEncode(toRec2020 * max(color, vec3(0.0)) * (paperWhite / 10000.0));
+    }
+    return linearToSrgb(color);
+}
@@ -0,0 +1,16 @@
+// Payload of the auxiliary rays (focus probe, photons, picking): the surface hit, unshaded
+#ifndef HIT_INFO_GLSL
+#define HIT_INFO_GLSL
+
+struct HitInfo {
+    vec3 position;
+    float hitT; // Negative on miss
+    vec3 normal;
+    uint materialIndex;
+    vec4 color;
+    vec4 params;
+    int instanceIndex; // TLAS instance: the scene objects, then the instance batches
+    int primitiveIndex; // Triangle within the object's mesh
+};
+
+#endif
@@ -0,0 +1,20 @@
+// Payload of the shading rays, traced by raygen and filled in by the closest hit and miss shaders
+#ifndef PAYLOAD_GLSL
+#define PAYLOAD_GLSL
+
+struct RayPayload {
+    vec3 color;
+    uint depth;
+    uint seed;
+    uint flags;
+    float coneWidth; // Ray cone footprint at the ray origin, for texture LOD
+    float coneSpread; // Ray cone spread angle
+    float hitT; // Distance to the surface this ray hit, negative on miss
+    vec3 prevPosition; // Where the hit point was in the previous frame, for motion vectors
+    float wavelength; // nm, the one wavelength this path carries since dispersive glass split it, 0 for all
+    uint candidateHits; // Triangle intersections the camera ray's traversal found, counted for the traversal heatmap
+};
+
+const uint PATH_FLAG_CAMERA = 1; // Set on rays traced directly from the camera
+
+#endif
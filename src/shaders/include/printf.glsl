@@ -0,0 +1,24 @@
+// Shader printf for debugging: `PRINTF(format, values...)` takes debugPrintfEXT's format
+// (%d, %u, %f, %v3f and so on), and with `--shader-printf` the renderer logs what it prints.
+// Without it PRINTF compiles to nothing. Guard it with `printfPixel()` to print for one pixel
+// rather than millions. The including shader enables GL_EXT_debug_printf.
+#ifndef PRINTF_GLSL
+#define PRINTF_GLSL
+
+// Set by the renderer with --shader-printf
+#ifndef SHADER_PRINTF
+#define SHADER_PRINTF 0
+#endif
+
+#if SHADER_PRINTF
+#define PRINTF(...) debugPrintfEXT(__VA_ARGS__)
+#else
+#define PRINTF(...)
+#endif
+
+// Whether this invocation is the center of the launch, the screen center for camera rays
+bool printfPixel() {
+    return gl_LaunchIDEXT.xy == gl_LaunchSizeEXT.xy / 2u;
+}
+
+#endif
@@ -0,0 +1,32 @@
+// Ray counter: every ray traced is counted into this frame's slot of a buffer the renderer reads
+// back after the frame, for the rays per second on the HUD and in the metrics.
+// The including shader includes camera.glsl and enables GL_EXT_buffer_reference2,
+// GL_EXT_buffer_reference_uvec2 and GL_KHR_shader_subgroup_arithmetic.
+#ifndef RAY_COUNT_GLSL
+#define RAY_COUNT_GLSL
+
+// Set by the renderer where the ray tracing stages have subgroup arithmetic
+#ifndef SUBGROUP_RAY_COUNT
+#define SUBGROUP_RAY_COUNT 0
+#endif
+
+layout(buffer_reference, std430, buffer_reference_align = 4) buffer RayCounter { uint rays; };
+
+// Counts a ray about to be traced. Where it can, a subgroup sums its rays first, so the counter
+// takes one atomic per subgroup rather than one per ray.
+void countRay() {
+    if (frame.counterParams.x == 0u && frame.counterParams.y == 0u) {
+        return;
+    }
+    RayCounter counter = RayCounter(frame.counterParams.xy);
+#if SUBGROUP_RAY_COUNT
+    uint rays = subgroupAdd(1u);
+    if (subgroupElect()) {
+        atomicAdd(counter.rays, rays);
+    }
+#else
+    atomicAdd(counter.rays, 1u);
+#endif
+}
+
+#endif
@@ -0,0 +1,10 @@
+// TLAS instance mask bits: each ray kind only hits the instances whose mask has its bit, so an
+// object can be left out of one kind of ray (RayMask in scene.rs). Hidden instances have no bits.
+#ifndef RAY_MASKS_GLSL
+#define RAY_MASKS_GLSL
+
+const uint RAY_MASK_CAMERA = 0x01; // Camera rays, the autofocus probe and picking
+const uint RAY_MASK_SHADOW = 0x02; // Shadow and sky visibility rays
+const uint RAY_MASK_REFLECTION = 0x04; // Reflection and refraction rays and caustic photons
+
+#endif
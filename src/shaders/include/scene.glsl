@@ -0,0 +1,72 @@
+// Scene geometry and per-instance data, read by the hit shaders, the hybrid renderer's raygen
+// and G-buffer pass. The including shader enables GL_EXT_scalar_block_layout,
+// GL_EXT_shader_explicit_arithmetic_types_int64 and GL_EXT_buffer_reference2.
+#ifndef SCENE_GLSL
+#define SCENE_GLSL
+
+struct SceneDesc {
+    uint64_t vertexAddress;
+    uint64_t indexAddress;
+    uint indexSize; // Bytes per index: 2 for meshes of up to 65535 vertices, 4 otherwise
+};
+
+layout(binding = 3, set = 0) readonly buffer SceneDesc_ { SceneDesc sceneDesc[]; };
+struct InstanceData {
+    uint materialIndex;
+    uint meshIndex; // Entry in sceneDesc[]
+    uint flags;
+    vec4 color; // Tint, or the replacement color with INSTANCE_COLOR_OVERRIDE
+};
+
+const uint INSTANCE_COLOR_OVERRIDE = 1; // color replaces the material color instead of tinting it
+
+layout(binding = 14, set = 0) readonly buffer InstanceData_ { InstanceData instanceData[]; };
+struct InstanceTransform {
+    vec4 rows[3]; // Row-major 3x4 object-to-world matrix
+};
+layout(binding = 15, set = 0) readonly buffer PrevTransforms { InstanceTransform prevTransforms[]; }; // Previous frame's, for motion vectors
+layout(binding = 23, set = 0) readonly buffer Transforms { InstanceTransform transforms[]; }; // This frame's, as in the TLAS
+
+struct Vertex {
+    float pos[3];
+    float nrm[3];
+    float color[3];
+    float uv[2];
+    float tangent[4]; // xyz: tangent, w: bitangent sign
+};
+
+struct Material {
+    vec4 color;
+    vec4 params; // x: type (5: water, 6: terrain), y: roughness, z: ior, w: emission strength (rock material for terrain)
+    vec4 textures; // x: normal map index (-1 for none), y: normal strength, z: uv scale, w: flicker (emissive)
+    vec4 alpha; // x: alpha mask texture index (-1 for opaque), y: cutoff
+    vec4 sss; // xyz: mean free path per color channel (subsurface scattering), meters
+    vec4 film; // x: thin film thickness, nm (0 for none), y: film ior, z: thickness variation, nm
+    vec4 dispersion; // x: Abbe number (0 for none), y: wavelengths sampled (3 when 0)
+    vec4 lobes; // x: clearcoat weight, y: clearcoat roughness, z: anisotropy (0..1, along the tangent), w: anisotropy rotation, radians
+};
+
+layout(binding = 27, set = 0) readonly buffer Materials { Material materials[]; }; // This frame's, indexed by InstanceData.materialIndex
+
+layout(buffer_reference, scalar) readonly buffer Vertices { Vertex v[]; };
+layout(buffer_reference, scalar) readonly buffer Indices { uvec3 i[]; };
+layout(buffer_reference, scalar) readonly buffer IndexWords { uint i[]; }; // 32-bit indices, or two 16-bit ones per word
+
+// Entry `i` of the mesh's index buffer
+uint meshIndex(SceneDesc desc, uint i) {
+    IndexWords words = IndexWords(desc.indexAddress);
+    if (desc.indexSize == 4u) {
+        return words.i[i];
+    }
+    return (words.i[i >> 1] >> ((i & 1u) * 16u)) & 0xffffu;
+}
+
+// Vertex indices of the mesh's triangle `primitive`
+uvec3 triangleIndices(SceneDesc desc, uint primitive) {
+    if (desc.indexSize == 4u) {
+        return Indices(desc.indexAddress).i[primitive];
+    }
+    return uvec3(meshIndex(desc, primitive * 3u), meshIndex(desc, primitive * 3u + 1u), meshIndex(desc, primitive * 3u + 2u));
+}
+
+#endif
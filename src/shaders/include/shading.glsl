@@ -0,0 +1,724 @@
+// Surface shading: direct light with shadow rays, sky visibility, caustics, projectors, and the
+// reflection and refraction rays, for a surface hit by a ray. The closest hit shader shades the
+// surfaces rays hit, raygen those the hybrid G-buffer pass rasterized.
+// The including shader declares topLevelAS, includes camera.glsl, payload.glsl, hit_info.glsl,
+// random.glsl and ray_count.glsl, declares the RayPayload `prd` (location 0), the shadow payload
+// `bool isShadowed` (location 1) and the HitInfo `probeHit` (location 2), and enables
+// GL_EXT_nonuniform_qualifier besides the extensions of scene.glsl and ray_count.glsl.
+#ifndef SHADING_GLSL
+#define SHADING_GLSL
+
+#include "scene.glsl"
+#include "aov.glsl"
+#include "ray_masks.glsl"
+#include "volume.glsl"
+
+// Reflection and refraction bounces, set by the renderer from the device's ray recursion depth
+#ifndef MAX_BOUNCE_DEPTH
+#define MAX_BOUNCE_DEPTH 5
+#endif
+
+layout(binding = 4, set = 0) readonly buffer PhotonGrid { uint cells[]; }; // rgb flux + photon count per cell
+layout(binding = 6, set = 0) uniform sampler2D textures[];
+layout(binding = 7, set = 0) buffer SkyCache { uint skyCells[]; }; // visible + total sky samples per cell
+
+struct Projector {
+    mat4 viewProj; // World to projector clip space
+    vec4 position; // w: gobo texture index
+    vec4 color; // Irradiance at 1m
+};
+
+layout(binding = 10, set = 0) readonly buffer Projectors { Projector projectors[]; };
+
+// A ray's hit on a triangle: what the closest hit shader gets from its built-ins
+struct SurfaceHit {
+    vec3 rayOrigin;
+    vec3 rayDirection;
+    float t; // Distance along the ray
+    int instanceId; // TLAS instance
+    int primitiveId; // Triangle within the instance's mesh
+    vec2 barycentrics; // Weights of the triangle's second and third vertex
+    mat4x3 objectToWorld;
+    mat4x3 worldToObject;
+};
+
+// Caustics: irradiance deposited by the photon pass into the hash grid cell containing p
+const float FLUX_SCALE = 65536.0;
+
+vec3 causticIrradiance(vec3 p) {
+    ivec3 c = ivec3(floor(p / cam.photonParams.y));
+    uint h = uint(c.x * 73856093) ^ uint(c.y * 19349663) ^ uint(c.z * 83492791);
+    uint cell = (h % uint(cam.photonParams.z)) * 4;
+    return vec3(cells[cell + 0], cells[cell + 1], cells[cell + 2]) / FLUX_SCALE;
+}
+
+// Texture LOD from ray cones (Akenine-Moller et al., "Texture Level of Detail Strategies for
+// Real-Time Ray Tracing"): texel-to-world area ratio of the triangle plus the cone footprint.
+float textureLodFromCone(SurfaceHit hit, ivec2 texSize, Vertex v0, Vertex v1, Vertex v2, vec2 uv0, vec2 uv1, vec2 uv2, float coneWidth, vec3 normal) {
+    vec3 p0 = vec3(hit.objectToWorld * vec4(v0.pos[0], v0.pos[1], v0.pos[2], 1.0));
+    vec3 p1 = vec3(hit.objectToWorld * vec4(v1.pos[0], v1.pos[1], v1.pos[2], 1.0));
+    vec3 p2 = vec3(hit.objectToWorld * vec4(v2.pos[0], v2.pos[1], v2.pos[2], 1.0));
+    float worldArea = length(cross(p1 - p0, p2 - p0));
+
+    vec2 size = vec2(texSize);
+    vec2 e1 = (uv1 - uv0) * size;
+    vec2 e2 = (uv2 - uv0) * size;
+    float texelArea = abs(e1.x * e2.y - e1.y * e2.x);
+
+    float cosTheta = max(abs(dot(normal, hit.rayDirection)), 1e-4);
+    float lambda = 0.5 * log2(max(texelArea, 1e-8) / max(worldArea, 1e-8));
+    return max(lambda + log2(max(coneWidth, 1e-8) / cosTheta), 0.0);
+}
+
+// Environment occlusion: sky visibility cached per surface patch and refined progressively.
+// Each hit adds at most one sky sample to its patch until the estimate has converged.
+const vec3 SKY_AMBIENT = vec3(0.16, 0.18, 0.22);
+
+vec3 cosineHemisphere(vec3 n, inout uint seed) {
+    float r1 = rnd(seed);
+    float r2 = rnd(seed);
+    float phi = 6.28318530718 * r1;
+    float r = sqrt(r2);
+    vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 t = normalize(cross(up, n));
+    vec3 b = cross(n, t);
+    return normalize(t * (r * cos(phi)) + b * (r * sin(phi)) + n * sqrt(1.0 - r2));
+}
+
+float skyVisibility(vec3 p, vec3 n, inout uint seed) {
+    // Patches are keyed by position and dominant normal axis so floors and walls don't share cells
+    vec3 an = abs(n);
+    int axis = an.x > an.y ? (an.x > an.z ? 0 : 2) : (an.y > an.z ? 1 : 2);
+    int face = axis * 2 + (n[axis] < 0.0 ? 1 : 0);
+    ivec3 c = ivec3(floor(p / cam.skyParams.y));
+    uint h = uint(c.x * 73856093) ^ uint(c.y * 19349663) ^ uint(c.z * 83492791) ^ (uint(face) * 2654435761u);
+    uint cell = (h % uint(cam.skyParams.z)) * 2;
+
+    uint visible = skyCells[cell + 0];
+    uint samples = skyCells[cell + 1];
+    bool converged = samples >= uint(cam.skyParams.w) || (samples >= 16u && (visible == 0u || visible == samples));
+
+    if (!converged) {
+        isShadowed = true;
+        uint rayFlags = gl_RayFlagsTerminateOnFirstHitEXT | gl_RayFlagsSkipClosestHitShaderEXT;
+        countRay();
+        traceRayEXT(topLevelAS, rayFlags, RAY_MASK_SHADOW, 0, 0, 1, p + n * 0.01, 0.01, cosineHemisphere(n, seed), 1000.0, 1);
+        uint hit = isShadowed ? 0u : 1u;
+        visible = atomicAdd(skyCells[cell + 0], hit) + hit;
+        samples = atomicAdd(skyCells[cell + 1], 1u) + 1u;
+    }
+
+    return float(visible) / float(max(samples, 1u));
+}
+
+// Irradiance from the projector lights (gobos), including their shadows
+vec3 projectorIrradiance(vec3 p, vec3 n) {
+    vec3 irradiance = vec3(0.0);
+    for (int i = 0; i < projectors.length(); i++) {
+        Projector projector = projectors[i];
+        if (projector.color.rgb == vec3(0.0)) {
+            continue;
+        }
+
+        vec4 clip = projector.viewProj * vec4(p, 1.0);
+        if (clip.w <= 0.0) {
+            continue;
+        }
+        vec2 ndc = clip.xy / clip.w;
+        if (any(greaterThan(abs(ndc), vec2(1.0)))) {
+            continue;
+        }
+
+        vec3 toLight = projector.position.xyz - p;
+        float dist = length(toLight);
+        vec3 l = toLight / dist;
+        float NdotL = dot(n, l);
+        if (NdotL <= 0.0) {
+            continue;
+        }
+
+        isShadowed = true;
+        uint rayFlags = gl_RayFlagsTerminateOnFirstHitEXT | gl_RayFlagsSkipClosestHitShaderEXT;
+        countRay();
+        traceRayEXT(topLevelAS, rayFlags, RAY_MASK_SHADOW, 0, 0, 1, p, 0.01, l, dist, 1);
+        if (isShadowed) {
+            continue;
+        }
+
+        // Image rows go top to bottom, clip space y points up
+        vec2 uv = vec2(0.5 + 0.5 * ndc.x, 0.5 - 0.5 * ndc.y);
+        vec3 gobo = textureLod(textures[nonuniformEXT(int(projector.position.w))], uv, 0.0).rgb;
+        irradiance += projector.color.rgb * gobo * NdotL / (dist * dist);
+    }
+    return irradiance;
+}
+
+// Subsurface scattering by random walk (Chiang et al., "Practical and Controllable Subsurface
+// Scattering for Production Path Tracing"): the light enters below the surface, scatters
+// isotropically through the medium with the material's mean free path, and is lit where the
+// walk comes back out. Distances are sampled from a randomly picked color channel's extinction,
+// weighted over the channels (one-sample MIS), so red can travel further than blue.
+const uint SSS_MAX_BOUNCES = 32;
+
+// Single-scattering albedo for which the medium's multiple-scattering albedo (its color) comes
+// out as `albedo`, Chiang et al.'s fit
+vec3 sssSingleScatteringAlbedo(vec3 albedo) {
+    vec3 s = 4.09712 + 4.20863 * albedo - sqrt(9.59217 + 41.6808 * albedo + 17.7126 * albedo * albedo);
+    return clamp(1.0 - s * s, 0.0, 0.999);
+}
+
+vec3 uniformSphere(inout uint seed) {
+    float z = 1.0 - 2.0 * rnd(seed);
+    float phi = 6.28318530718 * rnd(seed);
+    float r = sqrt(max(1.0 - z * z, 0.0));
+    return vec3(r * cos(phi), r * sin(phi), z);
+}
+
+// One walk into the surface at p (normal n). `direct` is the point light and `ambient` the sky
+// where it comes out, lit like a Lambert surface there, times the walk's throughput. Walks that
+// neither come out nor die out within SSS_MAX_BOUNCES are lost.
+void subsurfaceWalk(vec3 p, vec3 n, vec3 albedo, vec3 meanFreePath, inout uint seed, out vec3 direct, out vec3 ambient) {
+    direct = vec3(0.0);
+    ambient = vec3(0.0);
+    vec3 sigmaT = 1.0 / max(meanFreePath, vec3(1e-6));
+    vec3 ssAlbedo = sssSingleScatteringAlbedo(albedo);
+    vec3 throughput = vec3(1.0);
+    vec3 pos = p - n * 1e-4;
+    vec3 dir = cosineHemisphere(-n, seed);
+    for (uint i = 0u; i < SSS_MAX_BOUNCES; i++) {
+        int channel = min(int(rnd(seed) * 3.0), 2);
+        float dist = -log(max(1.0 - rnd(seed), 1e-6)) / sigmaT[channel];
+        countRay();
+        traceRayEXT(topLevelAS, gl_RayFlagsNoneEXT, RAY_MASK_REFLECTION, 1, 0, 2, pos, 1e-5, dir, dist, 2);
+        if (probeHit.hitT >= 0.0) {
+            // Out through the surface: the chance of getting this far, over its average pdf
+            vec3 transmittance = exp(-sigmaT * probeHit.hitT);
+            throughput *= transmittance / dot(transmittance, vec3(1.0 / 3.0));
+            vec3 exitNormal = dot(probeHit.normal, dir) > 0.0 ? probeHit.normal : -probeHit.normal;
+            vec3 exitPos = probeHit.position + exitNormal * 1e-4;
+
+            vec3 toLight = cam.lightPos.xyz - exitPos;
+            float distToLight = length(toLight);
+            toLight /= distToLight;
+            float NdotL = dot(exitNormal, toLight);
+            if (NdotL > 0.0) {
+                isShadowed = true;
+                uint rayFlags = gl_RayFlagsTerminateOnFirstHitEXT | gl_RayFlagsSkipClosestHitShaderEXT;
+                countRay();
+                traceRayEXT(topLevelAS, rayFlags, RAY_MASK_SHADOW, 0, 0, 1, exitPos, 0.001, toLight, distToLight, 1);
+                if (!isShadowed) {
+                    direct = throughput * NdotL * cam.lightColor.rgb;
+                }
+            }
+            ambient = throughput * SKY_AMBIENT;
+            return;
+        }
+
+        // Scattered inside: its chance over the average pdf of the distance
+        vec3 transmittance = exp(-sigmaT * dist);
+        throughput *= ssAlbedo * sigmaT * transmittance / dot(sigmaT * transmittance, vec3(1.0 / 3.0));
+        pos += dir * dist;
+        dir = uniformSphere(seed);
+
+        // Russian roulette once the walk has lost most of its energy
+        float survival = min(max(throughput.r, max(throughput.g, throughput.b)), 1.0);
+        if (survival < 0.2) {
+            if (rnd(seed) > survival) {
+                return;
+            }
+            throughput /= survival;
+        }
+    }
+}
+
+// World position the object point now at `worldPos` had in the previous frame
+vec3 previousPosition(SurfaceHit hit, vec3 worldPos) {
+    vec4 objectPos = vec4(hit.worldToObject * vec4(worldPos, 1.0), 1.0);
+    InstanceTransform prev = prevTransforms[hit.instanceId];
+    return vec3(dot(prev.rows[0], objectPos), dot(prev.rows[1], objectPos), dot(prev.rows[2], objectPos));
+}
+
+// Water: slope (dh/dx, dh/dz) of the animated surface height at p (world xz, meters). Wind waves
+// plus rings spreading from drops landing at random spots, one cell of a 1 m grid at a time; rain
+// makes more cells drip. `scale` shrinks the pattern.
+const vec3 WATER_WAVES[3] = vec3[](vec3(0.8, 0.6, 2.1), vec3(-0.45, 0.89, 3.7), vec3(0.28, -0.96, 6.3)); // xy: direction, z: wavenumber (rad/m)
+const float WATER_DROP_PERIOD = 1.6; // Seconds from one drop in a cell to the next
+const float WATER_RING_SPEED = 0.35; // m/s
+
+vec2 waterSlope(vec2 p, float time, float scale) {
+    p *= scale;
+    vec2 slope = vec2(0.0);
+    for (int i = 0; i < 3; i++) {
+        // Deep water dispersion, w = sqrt(g k), slope amplitude falling with the wavenumber
+        float k = WATER_WAVES[i].z;
+        float phase = dot(p, WATER_WAVES[i].xy) * k - time * sqrt(9.81 * k);
+        slope += WATER_WAVES[i].xy * (0.06 / float(i + 1)) * cos(phase);
+    }
+
+    float dripping = 0.15 + 0.85 * cam.weatherParams.y;
+    ivec2 home = ivec2(floor(p));
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            ivec2 cell = home + ivec2(x, y);
+            uint cellHash = tea(uint(cell.x) * 73856093u ^ uint(cell.y) * 19349663u, 7u);
+            uint seed = cellHash;
+            float offset = rnd(seed) * WATER_DROP_PERIOD; // Cells drip out of step
+            float cycle = floor((time + offset) / WATER_DROP_PERIOD);
+            float age = time + offset - cycle * WATER_DROP_PERIOD;
+            seed = tea(cellHash, uint(int(cycle)));
+            if (rnd(seed) > dripping) {
+                continue;
+            }
+            vec2 center = vec2(cell) + vec2(rnd(seed), rnd(seed));
+            vec2 toP = p - center;
+            float r = length(toP);
+            if (r < 1e-4) {
+                continue;
+            }
+            // A ring wave packet travelling outwards and fading as it spreads
+            float d = r - age * WATER_RING_SPEED;
+            float envelope = exp(-d * d * 60.0) * (1.0 - age / WATER_DROP_PERIOD);
+            slope += toP / r * cos(d * 40.0) * envelope * 0.25;
+        }
+    }
+    return slope;
+}
+
+// Thin film interference (oil on water, soap): light reflected off the top and the bottom of a
+// film a few hundred nanometers thick adds up in or out of phase depending on the wavelength,
+// which colors the reflection. Airy summation of the two interfaces' Fresnel amplitudes (s and p
+// polarization averaged) at one wavelength per color channel, multiple reflections included.
+const vec3 FILM_WAVELENGTHS = vec3(650.0, 510.0, 475.0); // nm
+
+// Fresnel amplitude reflection coefficients (s, p) from a medium of ior n1 into one of ior n2
+vec2 fresnelAmplitudes(float cos1, float cos2, float n1, float n2) {
+    return vec2((n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2), (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2));
+}
+
+// Reflectance per color channel of a film (ior filmIor, `thickness` nm) on a base of ior baseIor,
+// seen from air at cosTheta to the normal
+vec3 thinFilmReflectance(float cosTheta, float filmIor, float baseIor, float thickness) {
+    float sin1Sq = 1.0 - cosTheta * cosTheta;
+    float cos2 = sqrt(max(1.0 - sin1Sq / (filmIor * filmIor), 0.0));
+    float cos3Sq = 1.0 - sin1Sq / (baseIor * baseIor);
+    if (cos3Sq <= 0.0) {
+        return vec3(1.0); // Total internal reflection at the base
+    }
+    vec2 r12 = fresnelAmplitudes(cosTheta, cos2, 1.0, filmIor);
+    vec2 r23 = fresnelAmplitudes(cos2, sqrt(cos3Sq), filmIor, baseIor);
+
+    // Phase difference of the path through the film and back
+    vec3 cosDelta = cos(4.0 * 3.14159265 * filmIor * thickness * cos2 / FILM_WAVELENGTHS);
+    vec3 reflectance = vec3(0.0);
+    for (int i = 0; i < 2; i++) {
+        float a = r12[i] * r12[i] + r23[i] * r23[i];
+        float b = 2.0 * r12[i] * r23[i];
+        reflectance += (a + b * cosDelta) / (1.0 + r12[i] * r12[i] * r23[i] * r23[i] + b * cosDelta);
+    }
+    return clamp(reflectance * 0.5, 0.0, 1.0);
+}
+
+// Film thickness at world position p: the material's thickness plus a slowly swirling variation,
+// so an oil slick shows bands of color rather than a single tint
+float filmThickness(Material mat, vec3 p, float time) {
+    vec2 q = p.xz * 2.5;
+    q += 0.4 * vec2(sin(q.y * 1.7 + time * 0.15), cos(q.x * 1.3 - time * 0.11));
+    float swirl = sin(q.x * 1.1 + sin(q.y * 0.9)) * cos(q.y * 1.3 - sin(q.x * 0.7 + time * 0.05));
+    return max(mat.film.x + mat.film.z * 0.5 * swirl, 0.0);
+}
+
+// Dispersion: glass bends short wavelengths more than long ones. A path that refracts through a
+// dispersive material picks one of the material's wavelengths, spread evenly over the visible
+// range, and carries it from then on (prd.wavelength), its color weighted by that wavelength's
+// response, so edges and prisms fringe as the samples accumulate.
+const float VISIBLE_MIN = 400.0; // nm
+const float VISIBLE_RANGE = 300.0;
+
+// The k-th of `count` wavelengths, nm
+float sampledWavelength(int k, int count) {
+    return VISIBLE_MIN + VISIBLE_RANGE * (float(k) + 0.5) / float(count);
+}
+
+// Rough red, green and blue response to a wavelength (Gaussian fits)
+vec3 wavelengthResponse(float wavelength) {
+    vec3 x = (wavelength - vec3(610.0, 545.0, 455.0)) / vec3(45.0, 40.0, 35.0);
+    return exp(-0.5 * x * x);
+}
+
+// Cauchy's equation n = A + B / wavelength^2 fitted to the ior at the helium d line (587.6 nm)
+// and the Abbe number, the ior's spread between the hydrogen F (486.1 nm) and C (656.3 nm) lines
+float dispersedIor(float ior, float abbe, float wavelength) {
+    float b = (ior - 1.0) / (abbe * (1.0 / (486.1 * 486.1) - 1.0 / (656.3 * 656.3)));
+    return ior + b * (1.0 / (wavelength * wavelength) - 1.0 / (587.6 * 587.6));
+}
+
+// Reflection of `incoming` off a GGX microfacet normal sampled from the distribution of visible
+// normals (Heitz, "Sampling the GGX Distribution of Visible Normals"), with roughness alpha.x
+// along the tangent t and alpha.y across it. Directions that would go below the surface fall
+// back to the mirror reflection.
+vec3 sampleGgxReflection(vec3 incoming, vec3 n, vec3 t, vec2 alpha, inout uint seed) {
+    vec3 mirror = reflect(incoming, n);
+    if (max(alpha.x, alpha.y) < 1e-4) {
+        return mirror;
+    }
+    vec3 b = cross(n, t);
+    vec3 v = -vec3(dot(incoming, t), dot(incoming, b), dot(incoming, n));
+
+    // Stretch the view to the unit roughness configuration, sample the projected hemisphere
+    vec3 vh = normalize(vec3(alpha * v.xy, v.z));
+    float lengthSq = dot(vh.xy, vh.xy);
+    vec3 t1 = lengthSq > 0.0 ? vec3(-vh.y, vh.x, 0.0) * inversesqrt(lengthSq) : vec3(1.0, 0.0, 0.0);
+    vec3 t2 = cross(vh, t1);
+    float r = sqrt(rnd(seed));
+    float phi = 6.28318530718 * rnd(seed);
+    float p1 = r * cos(phi);
+    float p2 = r * sin(phi);
+    float s = 0.5 * (1.0 + vh.z);
+    p2 = (1.0 - s) * sqrt(max(1.0 - p1 * p1, 0.0)) + s * p2;
+    vec3 nh = p1 * t1 + p2 * t2 + sqrt(max(1.0 - p1 * p1 - p2 * p2, 0.0)) * vh;
+    vec3 m = normalize(vec3(alpha * nh.xy, max(nh.z, 1e-6)));
+
+    vec3 dir = reflect(incoming, normalize(t * m.x + b * m.y + n * m.z));
+    return dot(dir, n) > 0.0 ? dir : mirror;
+}
+
+// Brightness of a flickering light at the current scene time: holds for a few hundredths of a
+// second at a time and sometimes drops out, by up to `amount`. Each instance flickers on its own.
+float flicker(float amount, int instanceId) {
+    if (amount <= 0.0) {
+        return 1.0;
+    }
+    uint seed = tea(uint(cam.timeParams.x * 25.0), uint(instanceId));
+    float level = rnd(seed);
+    float dim = rnd(seed) < 0.1 ? 1.0 : 0.3 * level;
+    return 1.0 - amount * dim;
+}
+
+// Teaching mode: reports the shading terms when this is the camera ray through the probe pixel
+void writeTeachingProbe(uint flags, vec3 albedo, float shadowTerm, vec3 reflection, vec3 refraction, vec3 result) {
+    if (cam.teachingParams.z == 0.0 || (flags & PATH_FLAG_CAMERA) == 0 || imagePixel() != ivec2(cam.teachingParams.xy)) {
+        return;
+    }
+    int base = int(cam.teachingParams.w) * TEACHING_TERMS;
+    probeTerms[base + 0] = vec4(albedo, 1.0);
+    probeTerms[base + 1] = vec4(vec3(shadowTerm), 1.0);
+    probeTerms[base + 2] = vec4(reflection, 1.0);
+    probeTerms[base + 3] = vec4(refraction, 1.0);
+    probeTerms[base + 4] = vec4(result, 1.0);
+}
+
+void shadeSurface(SurfaceHit hit) {
+    // Get Geometry
+    InstanceData instance = instanceData[hit.instanceId];
+    SceneDesc desc = sceneDesc[instance.meshIndex];
+    Vertices vertices = Vertices(desc.vertexAddress);
+
+    uvec3 ind = triangleIndices(desc, uint(hit.primitiveId));
+    
+    Vertex v0 = vertices.v[ind.x];
+    Vertex v1 = vertices.v[ind.y];
+    Vertex v2 = vertices.v[ind.z];
+
+    const vec3 barycentrics = vec3(1.0 - hit.barycentrics.x - hit.barycentrics.y, hit.barycentrics.x, hit.barycentrics.y);
+
+    vec3 n0 = vec3(v0.nrm[0], v0.nrm[1], v0.nrm[2]);
+    vec3 n1 = vec3(v1.nrm[0], v1.nrm[1], v1.nrm[2]);
+    vec3 n2 = vec3(v2.nrm[0], v2.nrm[1], v2.nrm[2]);
+    vec3 normal = normalize(n0 * barycentrics.x + n1 * barycentrics.y + n2 * barycentrics.z);
+    
+    // Transform normal to world space
+    normal = normalize(vec3(hit.objectToWorld * vec4(normal, 0.0)));
+    vec3 worldPos = hit.rayOrigin + hit.rayDirection * hit.t;
+
+    // Material
+    int matIndex = int(instance.materialIndex);
+    Material mat = materials[matIndex];
+    mat.color.rgb = (instance.flags & INSTANCE_COLOR_OVERRIDE) != 0 ? instance.color.rgb : mat.color.rgb * instance.color.rgb;
+
+    // Alpha-tested cards are thin two-sided surfaces, lit on whichever side the ray hits
+    if (mat.alpha.x >= 0.0 && dot(normal, hit.rayDirection) > 0.0) {
+        normal = -normal;
+    }
+
+    // Ray cone footprint at the hit, carried on to secondary rays
+    float coneWidth = prd.coneWidth + prd.coneSpread * hit.t;
+
+    vec4 t0 = vec4(v0.tangent[0], v0.tangent[1], v0.tangent[2], v0.tangent[3]);
+    vec4 t1 = vec4(v1.tangent[0], v1.tangent[1], v1.tangent[2], v1.tangent[3]);
+    vec4 t2 = vec4(v2.tangent[0], v2.tangent[1], v2.tangent[2], v2.tangent[3]);
+    vec4 tangent = t0 * barycentrics.x + t1 * barycentrics.y + t2 * barycentrics.z;
+
+    // Normal mapping: perturb the shading normal with the material's tangent-space normal map
+    int normalMap = int(mat.textures.x);
+    if (normalMap >= 0) {
+        vec2 uv0 = vec2(v0.uv[0], v0.uv[1]) * mat.textures.z;
+        vec2 uv1 = vec2(v1.uv[0], v1.uv[1]) * mat.textures.z;
+        vec2 uv2 = vec2(v2.uv[0], v2.uv[1]) * mat.textures.z;
+        vec2 uv = uv0 * barycentrics.x + uv1 * barycentrics.y + uv2 * barycentrics.z;
+        float lod = textureLodFromCone(hit, textureSize(textures[nonuniformEXT(normalMap)], 0), v0, v1, v2, uv0, uv1, uv2, coneWidth, normal);
+
+        vec3 T = vec3(hit.objectToWorld * vec4(tangent.xyz, 0.0));
+        T = T - normal * dot(normal, T); // Gram-Schmidt against the interpolated normal
+        if (dot(T, T) > 1e-8) {
+            T = normalize(T);
+            vec3 B = cross(normal, T) * (tangent.w < 0.0 ? -1.0 : 1.0);
+
+            // Only xy is read so two-channel (BC5) normal maps work too, z is reconstructed
+            vec2 txy = textureLod(textures[nonuniformEXT(normalMap)], uv, lod).xy * 2.0 - 1.0;
+            vec3 tn = vec3(txy * mat.textures.y, sqrt(max(1.0 - dot(txy, txy), 0.0)));
+            normal = normalize(mat3(T, B, normal) * tn);
+        }
+    }
+
+    float type = mat.params.x; // 0: Lambert, 1: Metal, 2: Glass, 3: SSS, 4: Emissive, 5: Water, 6: Terrain
+    float roughness = mat.params.y;
+    float ior = mat.params.z;
+
+    // Water ripples: the upward-facing surface is perturbed by the animated height field, with the
+    // normal strength and uv scale as ripple strength and scale
+    if (type == 5.0 && normal.y > 0.5) {
+        vec2 slope = waterSlope(worldPos.xz, cam.timeParams.x, mat.textures.z) * mat.textures.y;
+        normal = normalize(normal - vec3(slope.x, 0.0, slope.y));
+    }
+
+    // Direction of anisotropic roughness: the tangent, turned about the normal by lobes.w, or any
+    // direction across the normal for meshes without tangents
+    vec3 surfaceTangent = vec3(hit.objectToWorld * vec4(tangent.xyz, 0.0));
+    surfaceTangent -= normal * dot(normal, surfaceTangent);
+    if (dot(surfaceTangent, surfaceTangent) < 1e-8) {
+        surfaceTangent = cross(normal, abs(normal.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0));
+    }
+    surfaceTangent = normalize(surfaceTangent);
+    if (mat.lobes.w != 0.0) {
+        surfaceTangent = surfaceTangent * cos(mat.lobes.w) + cross(normal, surfaceTangent) * sin(mat.lobes.w);
+    }
+
+    // Vertex colors tint the material, white for meshes without them
+    vec3 c0 = vec3(v0.color[0], v0.color[1], v0.color[2]);
+    vec3 c1 = vec3(v1.color[0], v1.color[1], v1.color[2]);
+    vec3 c2 = vec3(v2.color[0], v2.color[1], v2.color[2]);
+    vec3 albedo = mat.color.rgb * (c0 * barycentrics.x + c1 * barycentrics.y + c2 * barycentrics.z);
+
+    // Terrain: Lambert, grass giving way to the rock material params.w points at on steep slopes and hilltops
+    if (type == 6.0) {
+        Material rock = materials[int(mat.params.w)];
+        float rockiness = (1.0 - normal.y) + max(worldPos.y - 5.0, 0.0) * 0.08;
+        albedo = mix(albedo, rock.color.rgb, smoothstep(0.2, 0.35, rockiness));
+    }
+
+    // AOVs of the surface the camera sees
+    if ((prd.flags & PATH_FLAG_CAMERA) != 0) {
+        imageStore(aovNormal, imagePixel(), vec4(normal, 0.0));
+        imageStore(aovAlbedo, imagePixel(), vec4(albedo, 1.0));
+        imageStore(aovInstance, imagePixel(), uvec4(hit.instanceId));
+    }
+    // Emissive: light sources aren't shaded, their emission only shows up in the beauty channel
+    if (type == 4.0) {
+        float emission = mat.params.w * flicker(mat.textures.w, hit.instanceId);
+        bool componentChannel = (prd.flags & PATH_FLAG_CAMERA) != 0 && cam.outputParams.x > 0.0;
+        prd.color = componentChannel ? (cam.outputParams.x == 6.0 ? albedo : vec3(0.0)) : albedo * emission;
+        writeTeachingProbe(prd.flags, albedo, 0.0, vec3(0.0), vec3(0.0), albedo * emission);
+        prd.hitT = hit.t;
+        prd.prevPosition = previousPosition(hit, worldPos);
+        return;
+    }
+
+    vec3 lightDir = normalize(cam.lightPos.xyz - worldPos);
+    float distToLight = length(cam.lightPos.xyz - worldPos);
+
+    // Soft Shadow (jitter light pos)
+    if (frame.settings.x > 0.0) {
+        float r1 = rnd(prd.seed);
+        float r2 = rnd(prd.seed);
+        vec3 offset = vec3(r1 - 0.5, r2 - 0.5, (r1+r2) - 1.0) * 2.0 * cam.lightPos.w; // Simple jitter within the light's radius
+        lightDir = normalize((cam.lightPos.xyz + offset) - worldPos);
+    }
+
+    // Shadow Ray
+    isShadowed = true;
+    uint rayFlags = gl_RayFlagsTerminateOnFirstHitEXT | gl_RayFlagsSkipClosestHitShaderEXT;
+    countRay();
+    traceRayEXT(topLevelAS, rayFlags, RAY_MASK_SHADOW, 0, 0, 1, worldPos, 0.01, lightDir, distToLight, 1);
+    float shadowTerm = isShadowed ? 0.0 : max(dot(normal, lightDir), 0.0); // Light visibility times the cosine, for the teaching mode
+
+    // Light path components, kept separate so they can be output as individual channels
+    vec3 directDiffuse = vec3(0.0);
+    vec3 indirectDiffuse = vec3(0.0);
+    vec3 caustics = vec3(0.0);
+    vec3 specular = vec3(0.0);
+    vec3 transmission = vec3(0.0);
+    float diffuseWeight = 1.0;
+
+    if (!isShadowed) {
+        float NdotL = max(dot(normal, lightDir), 0.0);
+        directDiffuse = albedo * NdotL * cam.lightColor.rgb;
+    }
+
+    // Ambient: cached sky light, or a flat term in shadow when the cache is off
+    if (cam.skyParams.x > 0.0) {
+        indirectDiffuse = albedo * SKY_AMBIENT * skyVisibility(worldPos, normal, prd.seed);
+    } else if (isShadowed) {
+        indirectDiffuse = albedo * 0.1;
+    }
+
+    // Caustics (photon map gather)
+    if ((type == 0.0 || type == 6.0) && cam.photonParams.x > 0.0) {
+        caustics = albedo * causticIrradiance(worldPos);
+    }
+
+    // Secondary rays always return the full result, only the camera ray is split into channels
+    uint pathFlags = prd.flags;
+    prd.flags = 0;
+
+    // Reflection / Refraction (Simplified)
+    // Secondary rays keep the spread and start from this hit's footprint (planar surfaces)
+    prd.coneWidth = coneWidth;
+    if (prd.depth < MAX_BOUNCE_DEPTH) {
+        if (type == 1.0 && frame.settings.y > 0.0) { // Metal
+             // Glossy reflection, stretched along the tangent by the material's anisotropy
+             float aspect = sqrt(1.0 - 0.9 * mat.lobes.z);
+             vec2 alpha = vec2(roughness * roughness) * vec2(1.0 / aspect, aspect);
+             vec3 refDir = sampleGgxReflection(hit.rayDirection, normal, surfaceTangent, alpha, prd.seed);
+             prd.depth++;
+             countRay();
+             traceRayEXT(topLevelAS, gl_RayFlagsNoneEXT, RAY_MASK_REFLECTION, 0, 0, 0, worldPos, 0.01, refDir, 1000.0, 0);
+             diffuseWeight = roughness;
+             specular = prd.color * (1.0 - roughness);
+        }
+        else if (type == 2.0 && frame.settings.z > 0.0) { // Glass
+             // Dispersion only while accumulating (long exposures, photos), one wavelength per path
+             // being far too noisy for single frames
+             float incomingWavelength = prd.wavelength;
+             vec3 spectralWeight = vec3(1.0);
+             if (mat.dispersion.x > 0.0 && frame.exposureParams.x > 0.0) {
+                 if (prd.wavelength == 0.0) {
+                     int count = mat.dispersion.y > 0.0 ? int(mat.dispersion.y) : 3;
+                     int k = min(int(rnd(prd.seed) * float(count)), count - 1);
+                     vec3 total = vec3(0.0);
+                     for (int i = 0; i < count; i++) {
+                         total += wavelengthResponse(sampledWavelength(i, count));
+                     }
+                     prd.wavelength = sampledWavelength(k, count);
+                     spectralWeight = wavelengthResponse(prd.wavelength) * float(count) / total;
+                 }
+                 ior = dispersedIor(ior, mat.dispersion.x, prd.wavelength);
+             }
+             float eta = 1.0 / ior;
+             if (dot(hit.rayDirection, normal) > 0) {
+                 normal = -normal;
+                 eta = ior;
+             }
+             vec3 refDir = refract(hit.rayDirection, normal, eta);
+             diffuseWeight = 0.1;
+             if (length(refDir) > 0.0) {
+                 prd.depth++;
+                 countRay();
+                 traceRayEXT(topLevelAS, gl_RayFlagsNoneEXT, RAY_MASK_REFLECTION, 0, 0, 0, worldPos, 0.01, refDir, 1000.0, 0);
+                 transmission = prd.color * 0.9 * spectralWeight;
+             } else {
+                 // TIR -> Reflect
+                 vec3 rDir = reflect(hit.rayDirection, normal);
+                 prd.depth++;
+                 countRay();
+                 traceRayEXT(topLevelAS, gl_RayFlagsNoneEXT, RAY_MASK_REFLECTION, 0, 0, 0, worldPos, 0.01, rDir, 1000.0, 0);
+                 specular = prd.color * 0.9 * spectralWeight;
+             }
+             prd.wavelength = incomingWavelength;
+        }
+        else if (type == 5.0) { // Water: reflection and refraction weighted by Fresnel (Schlick)
+             float eta = 1.0 / ior;
+             bool fromAbove = dot(hit.rayDirection, normal) <= 0;
+             if (!fromAbove) {
+                 normal = -normal;
+                 eta = ior;
+             }
+             float cosTheta = max(-dot(hit.rayDirection, normal), 0.0);
+             float f0 = (ior - 1.0) / (ior + 1.0);
+             f0 *= f0;
+             vec3 fresnel = vec3(f0 + (1.0 - f0) * pow(1.0 - cosTheta, 5.0));
+             // A film floating on top colors what's reflected, and what gets through, seen from above
+             if (mat.film.x > 0.0 && fromAbove) {
+                 fresnel = thinFilmReflectance(cosTheta, mat.film.y, ior, filmThickness(mat, worldPos, cam.timeParams.x));
+             }
+             vec3 refDir = refract(hit.rayDirection, normal, eta);
+             if (length(refDir) == 0.0) {
+                 fresnel = vec3(1.0); // TIR
+             }
+             float fresnelAverage = dot(fresnel, vec3(1.0 / 3.0));
+             if (frame.settings.y > 0.0) {
+                 prd.depth++;
+                 countRay();
+                 traceRayEXT(topLevelAS, gl_RayFlagsNoneEXT, RAY_MASK_REFLECTION, 0, 0, 0, worldPos, 0.01, reflect(hit.rayDirection, normal), 1000.0, 0);
+                 specular = prd.color * fresnel;
+             }
+             if (frame.settings.z > 0.0 && fresnelAverage < 1.0) {
+                 prd.depth++;
+                 prd.coneWidth = coneWidth; // The reflection ray left its own footprint
+                 countRay();
+                 traceRayEXT(topLevelAS, gl_RayFlagsNoneEXT, RAY_MASK_REFLECTION, 0, 0, 0, worldPos, 0.01, refDir, 1000.0, 0);
+                 transmission = prd.color * albedo * (1.0 - fresnel);
+                 diffuseWeight = 0.0;
+             } else {
+                 // Without refraction the lit surface stands in for what's under the water
+                 diffuseWeight = 1.0 - fresnelAverage;
+             }
+        }
+    }
+    
+    // Subsurface scattering: the diffuse light comes from a random walk through the skin instead,
+    // the material color being the albedo it averages out to
+    if (type == 3.0 && frame.settings.w > 0.0) {
+        subsurfaceWalk(worldPos, normal, albedo, mat.sss.xyz, prd.seed, directDiffuse, indirectDiffuse);
+    }
+
+    // Clouds block most of the sun, the projectors are unaffected
+    directDiffuse *= 1.0 - 0.7 * cam.weatherParams.x;
+    // So does smoke between the surface and the light
+    directDiffuse *= volumeTransmittance(worldPos, lightDir, distToLight);
+
+    // Projector lights (reuses the shadow payload, so this comes after the main light's shadow is used)
+    directDiffuse += albedo * projectorIrradiance(worldPos, normal);
+
+    directDiffuse *= diffuseWeight;
+    indirectDiffuse *= diffuseWeight;
+    caustics *= diffuseWeight;
+
+    // Clearcoat: a clear varnish over opaque materials reflects by Fresnel with its own roughness,
+    // and the base gets the light that passes through it
+    if (mat.lobes.x > 0.0 && type != 2.0 && type != 5.0 && frame.settings.y > 0.0 && prd.depth < MAX_BOUNCE_DEPTH) {
+        float cosTheta = max(-dot(hit.rayDirection, normal), 0.0);
+        float coatFresnel = mat.lobes.x * (0.04 + 0.96 * pow(1.0 - cosTheta, 5.0));
+        vec3 coatDir = sampleGgxReflection(hit.rayDirection, normal, surfaceTangent, vec2(mat.lobes.y * mat.lobes.y), prd.seed);
+        prd.depth++;
+        prd.coneWidth = coneWidth;
+        countRay();
+        traceRayEXT(topLevelAS, gl_RayFlagsNoneEXT, RAY_MASK_REFLECTION, 0, 0, 0, worldPos, 0.01, coatDir, 1000.0, 0);
+        directDiffuse *= 1.0 - coatFresnel;
+        indirectDiffuse *= 1.0 - coatFresnel;
+        caustics *= 1.0 - coatFresnel;
+        specular = specular * (1.0 - coatFresnel) + prd.color * coatFresnel;
+    }
+
+    vec3 beauty = directDiffuse + indirectDiffuse + caustics + specular + transmission;
+
+    writeTeachingProbe(pathFlags, albedo, shadowTerm, specular, transmission, beauty);
+
+    // Light path channel: 0 beauty, 1 direct diffuse, 2 indirect diffuse, 3 indirect specular, 4 transmission, 5 caustics,
+    // and for the teaching mode 6 albedo, 7 shadow term
+    int channel = (pathFlags & PATH_FLAG_CAMERA) != 0 ? int(cam.outputParams.x) : 0;
+    switch (channel) {
+        case 1: prd.color = directDiffuse; break;
+        case 2: prd.color = indirectDiffuse; break;
+        case 3: prd.color = specular; break;
+        case 4: prd.color = transmission; break;
+        case 5: prd.color = caustics; break;
+        case 6: prd.color = albedo; break;
+        case 7: prd.color = vec3(shadowTerm); break;
+        default: prd.color = beauty; break;
+    }
+    // Set last, secondary rays traced above reuse the payload
+    prd.hitT = hit.t;
+    prd.prevPosition = previousPosition(hit, worldPos);
+}
+
+#endif
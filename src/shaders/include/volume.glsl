@@ -0,0 +1,120 @@
+// Heterogeneous participating medium (smoke, cloud) loaded from a NanoVDB grid, densified into
+// a buffer of voxels over an axis-aligned box. The closest hit and miss shaders ray march it
+// along the segment their ray travelled, and shading.glsl dims the light it shadows.
+// The including shader includes camera.glsl and random.glsl.
+#ifndef VOLUME_GLSL
+#define VOLUME_GLSL
+
+layout(binding = 24, set = 0) readonly buffer Volume {
+    vec4 boundsMin; // xyz: world space corner, w: extinction of density 1, 1/m
+    vec4 boundsMax; // xyz: world space corner, w: largest density in the grid
+    vec4 albedo; // rgb: single scattering albedo, w: phase function anisotropy (Henyey-Greenstein g)
+    uvec4 dims; // xyz: voxels per axis, w: 1 when a volume is loaded
+    float density[]; // x fastest, then y, then z
+} volume;
+
+const uint VOLUME_STEPS = 64; // Ray marching steps through the whole box, at most
+const uint VOLUME_SHADOW_STEPS = 12; // Towards the light
+
+bool volumeLoaded() {
+    return volume.dims.w != 0u;
+}
+
+float volumeVoxel(ivec3 v) {
+    ivec3 dims = ivec3(volume.dims.xyz);
+    v = clamp(v, ivec3(0), dims - 1);
+    return volume.density[(v.z * dims.y + v.y) * dims.x + v.x];
+}
+
+// Trilinearly filtered density at world position `p`, 0 outside the box
+float volumeDensity(vec3 p) {
+    vec3 uvw = (p - volume.boundsMin.xyz) / (volume.boundsMax.xyz - volume.boundsMin.xyz);
+    if (any(lessThan(uvw, vec3(0.0))) || any(greaterThan(uvw, vec3(1.0)))) {
+        return 0.0;
+    }
+    vec3 x = uvw * vec3(volume.dims.xyz) - 0.5;
+    ivec3 i = ivec3(floor(x));
+    vec3 f = x - vec3(i);
+    float d00 = mix(volumeVoxel(i), volumeVoxel(i + ivec3(1, 0, 0)), f.x);
+    float d10 = mix(volumeVoxel(i + ivec3(0, 1, 0)), volumeVoxel(i + ivec3(1, 1, 0)), f.x);
+    float d01 = mix(volumeVoxel(i + ivec3(0, 0, 1)), volumeVoxel(i + ivec3(1, 0, 1)), f.x);
+    float d11 = mix(volumeVoxel(i + ivec3(0, 1, 1)), volumeVoxel(i + ivec3(1, 1, 1)), f.x);
+    return mix(mix(d00, d10, f.y), mix(d01, d11, f.y), f.z);
+}
+
+// Where the ray (origin, direction) is inside the box before tMax, false if it never is
+bool volumeSegment(vec3 origin, vec3 direction, float tMax, out float t0, out float t1) {
+    vec3 invDir = 1.0 / direction;
+    vec3 ta = (volume.boundsMin.xyz - origin) * invDir;
+    vec3 tb = (volume.boundsMax.xyz - origin) * invDir;
+    vec3 tNear = min(ta, tb);
+    vec3 tFar = max(ta, tb);
+    t0 = max(max(tNear.x, tNear.y), max(tNear.z, 0.0));
+    t1 = min(min(tFar.x, tFar.y), min(tFar.z, tMax));
+    return t0 < t1;
+}
+
+// Fraction of the light that makes it through the medium from `p` along `direction` over `dist`
+float volumeTransmittance(vec3 p, vec3 direction, float dist) {
+    float t0, t1;
+    if (!volumeLoaded() || !volumeSegment(p, direction, dist, t0, t1)) {
+        return 1.0;
+    }
+    float dt = (t1 - t0) / float(VOLUME_SHADOW_STEPS);
+    float opticalDepth = 0.0;
+    for (uint i = 0u; i < VOLUME_SHADOW_STEPS; i++) {
+        opticalDepth += volumeDensity(p + direction * (t0 + (float(i) + 0.5) * dt));
+    }
+    return exp(-opticalDepth * volume.boundsMin.w * dt);
+}
+
+// Henyey-Greenstein phase function times 4 pi, so an isotropic medium scatters the light's
+// color like a white Lambert surface facing it does
+float volumePhase(float cosTheta, float g) {
+    float denom = 1.0 + g * g - 2.0 * g * cosTheta;
+    return (1.0 - g * g) / (denom * sqrt(denom));
+}
+
+// Radiance arriving at `origin` from along `direction` when `background` arrives from tMax: the
+// background attenuated by the medium in between plus the light it scatters towards the origin,
+// single scattering from the point light (self-shadowed by the medium) and the sky.
+// The march starts at a random offset within the first step, so banding turns into noise that
+// accumulation averages out.
+vec3 volumeAlongRay(vec3 origin, vec3 direction, float tMax, vec3 background, inout uint seed) {
+    float t0, t1;
+    if (!volumeLoaded() || !volumeSegment(origin, direction, tMax, t0, t1)) {
+        return background;
+    }
+    float boxDiagonal = length(volume.boundsMax.xyz - volume.boundsMin.xyz);
+    float dt = boxDiagonal / float(VOLUME_STEPS);
+    float sigma = volume.boundsMin.w;
+    float overcast = 1.0 - 0.7 * cam.weatherParams.x;
+    vec3 sky = mix(vec3(0.75, 0.85, 1.0), vec3(0.55, 0.58, 0.62), cam.weatherParams.x) * 0.3;
+
+    float transmittance = 1.0;
+    vec3 inscattered = vec3(0.0);
+    for (float t = t0 + rnd(seed) * dt; t < t1; t += dt) {
+        vec3 p = origin + direction * t;
+        float density = volumeDensity(p);
+        if (density <= 0.0) {
+            continue;
+        }
+        vec3 toLight = cam.lightPos.xyz - p;
+        float distToLight = length(toLight);
+        toLight /= distToLight;
+        float lightT = volumeTransmittance(p, toLight, distToLight);
+        vec3 light = cam.lightColor.rgb * overcast * lightT * volumePhase(dot(direction, toLight), volume.albedo.w) + sky;
+
+        // Scattering over the step, integrated against the transmittance falling along it
+        float stepT = exp(-density * sigma * min(dt, t1 - t));
+        inscattered += transmittance * (1.0 - stepT) * volume.albedo.rgb * light;
+        transmittance *= stepT;
+        if (transmittance < 0.01) {
+            transmittance = 0.0;
+            break;
+        }
+    }
+    return background * transmittance + inscattered;
+}
+
+#endif
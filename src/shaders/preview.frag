@@ -0,0 +1,28 @@
+#version 460
+
+// Raster preview: flat Lambert shading from the scene light plus a constant ambient term,
+// lighting both sides of each triangle like the traced shading does. Shaded in linear and
+// sRGB encoded for the storage image, like raygen writes it.
+
+#include "include/display.glsl"
+
+layout(push_constant) uniform Params {
+    mat4 viewProj;
+    vec4 lightPos;
+    vec4 cameraPos;
+} params;
+
+layout(location = 0) in vec3 worldPos;
+layout(location = 1) in vec3 albedo;
+layout(location = 0) out vec4 outColor;
+
+const float AMBIENT = 0.2;
+
+void main() {
+    vec3 normal = normalize(cross(dFdx(worldPos), dFdy(worldPos)));
+    if (dot(normal, params.cameraPos.xyz - worldPos) < 0.0) {
+        normal = -normal;
+    }
+    float diffuse = max(dot(normal, normalize(params.lightPos.xyz - worldPos)), 0.0);
+    outColor = vec4(linearToSrgb(albedo * (AMBIENT + (1.0 - AMBIENT) * diffuse)), 1.0);
+}
@@ -0,0 +1,32 @@
+#version 460
+#extension GL_EXT_scalar_block_layout : enable
+#extension GL_EXT_shader_explicit_arithmetic_types_int64 : require
+#extension GL_EXT_buffer_reference2 : require
+
+// Raster preview, for GPUs without ray tracing pipelines: draws the instances like the hybrid
+// G-buffer pass and passes on their material color, tinted by the vertex colors, for flat shading.
+
+#include "include/scene.glsl"
+
+layout(push_constant) uniform Params {
+    mat4 viewProj;
+    vec4 lightPos; // xyz: position
+    vec4 cameraPos; // xyz: position
+} params;
+
+layout(location = 0) out vec3 worldPos;
+layout(location = 1) out vec3 albedo;
+
+void main() {
+    InstanceData instance = instanceData[gl_InstanceIndex];
+    SceneDesc desc = sceneDesc[instance.meshIndex];
+    uint index = meshIndex(desc, uint(gl_VertexIndex));
+    Vertex v = Vertices(desc.vertexAddress).v[index];
+    Material mat = materials[instance.materialIndex];
+
+    InstanceTransform transform = transforms[gl_InstanceIndex];
+    worldPos = vec4(v.pos[0], v.pos[1], v.pos[2], 1.0) * mat3x4(transform.rows[0], transform.rows[1], transform.rows[2]);
+    gl_Position = params.viewProj * vec4(worldPos, 1.0);
+    albedo = (instance.flags & INSTANCE_COLOR_OVERRIDE) != 0 ? instance.color.rgb : mat.color.rgb * instance.color.rgb;
+    albedo *= vec3(v.color[0], v.color[1], v.color[2]);
+}
@@ -0,0 +1,77 @@
+#version 460
+
+#include "include/display.glsl"
+
+// Resolve pass: scales the shown image bilinearly over the viewport, which keeps its aspect
+// ratio in the swapchain image, optionally sharpens it, and encodes it for the swapchain's
+// output. It reads one of the display images (storage, post or upscale, sRGB encoded) or, for
+// HDR output, the HDR image raygen writes before clamping (with bloom added). Filtering and
+// sharpening work in linear.
+
+layout(binding = 0, set = 0, rgba16f) uniform readonly image2D hdrImage;
+layout(binding = 1, set = 0, rgba8) uniform readonly image2D storageImage;
+layout(binding = 2, set = 0, rgba8) uniform readonly image2D postImage;
+layout(binding = 3, set = 0, rgba8) uniform readonly image2D upscaleImage;
+
+const uint SOURCE_HDR = 0u;
+const uint SOURCE_STORAGE = 1u;
+const uint SOURCE_POST = 2u;
+const uint SOURCE_UPSCALE = 3u;
+
+layout(push_constant) uniform Params {
+    uvec2 sourceSize; // Pixels of the source that hold the image
+    uint source; // SOURCE_*
+    uint transfer; // OUTPUT_*
+    float paperWhite; // Nits
+    float sharpness; // 0 (off) to 1
+} params;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 outColor;
+
+// The source at a pixel, linear
+vec3 load(ivec2 pixel) {
+    pixel = clamp(pixel, ivec2(0), ivec2(params.sourceSize) - 1);
+    if (params.source == SOURCE_STORAGE) {
+        return srgbToLinear(imageLoad(storageImage, pixel).rgb);
+    }
+    if (params.source == SOURCE_POST) {
+        return srgbToLinear(imageLoad(postImage, pixel).rgb);
+    }
+    if (params.source == SOURCE_UPSCALE) {
+        return srgbToLinear(imageLoad(upscaleImage, pixel).rgb);
+    }
+    return imageLoad(hdrImage, pixel).rgb;
+}
+
+// The source between pixel centers, f in pixels from the first one
+vec3 bilinear(vec2 f) {
+    ivec2 i = ivec2(floor(f));
+    vec2 w = f - vec2(i);
+    return mix(mix(load(i), load(i + ivec2(1, 0)), w.x),
+               mix(load(i + ivec2(0, 1)), load(i + ivec2(1, 1)), w.x), w.y);
+}
+
+void main() {
+    vec2 f = uv * vec2(params.sourceSize) - 0.5;
+    vec3 color = bilinear(f);
+
+    if (params.sharpness > 0.0) {
+        // Contrast adaptive sharpening: push the pixel away from its cross of neighbors a
+        // source pixel away, less where they already differ a lot, within their range
+        vec3 n = bilinear(f + vec2(0.0, -1.0));
+        vec3 s = bilinear(f + vec2(0.0, 1.0));
+        vec3 e = bilinear(f + vec2(1.0, 0.0));
+        vec3 w = bilinear(f + vec2(-1.0, 0.0));
+        vec3 lo = min(color, min(min(n, s), min(e, w)));
+        vec3 hi = max(color, max(max(n, s), max(e, w)));
+        // Room to the nearer of black and white relative to the brightest neighbor: near 0 at
+        // hard edges, and none above white in HDR
+        vec3 headroom = clamp(min(lo, 1.0 - min(hi, 1.0)) / max(hi, vec3(1e-4)), 0.0, 1.0);
+        vec3 amount = sqrt(headroom) * params.sharpness;
+        vec3 blur = (n + s + e + w) * 0.25;
+        color = clamp(color + amount * (color - blur), lo, hi);
+    }
+
+    outColor = vec4(encodeOutput(color, params.transfer, params.paperWhite), 1.0);
+}
@@ -0,0 +1,11 @@
+#version 460
+
+// Resolve pass: one triangle covering the viewport, the letterboxed part of the swapchain
+// image, no vertex buffer.
+
+layout(location = 0) out vec2 uv; // 0..1 across the viewport
+
+void main() {
+    uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
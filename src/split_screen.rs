@@ -0,0 +1,44 @@
+use glam::{Mat4, Vec4};
+use crate::camera::Camera;
+use crate::args;
+
+/// Split screen from `--split-screen` (toggled with 7): two views side by side, each with its
+/// own camera, feature toggles (1-4) and light path channel, e.g. to compare two feature sets
+/// of the same scene. Off by default.
+pub fn split_screen_from_args() -> bool {
+    args::flag("--split-screen")
+}
+
+/// The per-view state of the view that isn't controlled. The controlled view's lives in the
+/// renderer, the two are swapped when control switches over (8).
+pub struct ViewState {
+    pub camera: Camera,
+    pub settings: Vec4,
+    pub light_path_channel: usize,
+    pub prev_view_proj: Option<Mat4>,
+}
+
+pub struct SplitScreen {
+    pub other: ViewState,
+    pub right_active: bool, // Whether the controlled view is the right one
+}
+
+impl SplitScreen {
+    /// Starts with both views showing what the renderer shows now, the left one controlled.
+    pub fn new(other: ViewState) -> Self {
+        SplitScreen { other, right_active: false }
+    }
+
+    /// First column and width of the controlled view and the other one in an image `width`
+    /// pixels wide. The left view gets the odd column.
+    pub fn viewports(&self, width: u32) -> [(u32, u32); 2] {
+        let left = (0, width.div_ceil(2));
+        let right = (left.1, width - left.1);
+        if self.right_active { [right, left] } else { [left, right] }
+    }
+
+    /// Whether window column `x` of a window `width` pixels wide is in the controlled view.
+    pub fn in_active_view(&self, x: f32, width: f32) -> bool {
+        (x >= width * 0.5) == self.right_active
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+/// Size of the scene as built on the CPU, from `Scene::stats`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SceneStats {
+    pub meshes: usize,
+    pub vertices: usize, // Of all meshes, each counted once however many instances it has
+    pub triangles: usize,
+    pub objects: usize,
+    pub instances: usize, // TLAS instances: the objects and every batch's instances
+    pub instanced_triangles: usize, // Over all TLAS instances, at full detail
+    pub materials: usize,
+    pub textures: usize,
+    pub geometry_bytes: u64, // Vertices and 32-bit indices of all meshes
+    pub texture_bytes: u64, // Texel data including mip chains
+}
+
+/// GPU side of the scene, from `Renderer::stats`. Sizes are of the device memory allocated
+/// for each, which may round up what was asked for.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub scene: SceneStats,
+    pub blas_count: usize, // Built BLASes: resident meshes, and streamed ones while loaded
+    pub blas_bytes: u64,
+    pub tlas_bytes: u64,
+    pub vertex_buffer_bytes: u64, // Resident meshes' vertices
+    pub index_buffer_bytes: u64, // Resident meshes' indices
+    pub geometry_bytes: u64, // All geometry memory: the above, streamed meshes, instance data and transforms
+    pub texture_bytes: u64, // Material textures
+    pub total_bytes: u64, // Everything the renderer allocated, render targets included
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+impl fmt::Display for SceneStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} meshes ({} vertices, {} triangles, {}), {} objects and {} TLAS instances ({} triangles), {} materials, {} textures ({})",
+            self.meshes, self.vertices, self.triangles, megabytes(self.geometry_bytes),
+            self.objects, self.instances, self.instanced_triangles,
+            self.materials, self.textures, megabytes(self.texture_bytes))
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} BLASes ({}), TLAS {}, vertex buffer {}, index buffer {}, geometry {}, textures {}, {} allocated in total",
+            self.blas_count, megabytes(self.blas_bytes), megabytes(self.tlas_bytes),
+            megabytes(self.vertex_buffer_bytes), megabytes(self.index_buffer_bytes), megabytes(self.geometry_bytes),
+            megabytes(self.texture_bytes), megabytes(self.total_bytes))
+    }
+}
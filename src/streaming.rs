@@ -0,0 +1,161 @@
+use glam::{Mat4, Vec3};
+use crate::scene::Scene;
+use crate::args;
+
+/// Distance from the camera to a chunk's bounds within which its mesh is loaded, unless
+/// `--stream-distance` gives another one.
+const DEFAULT_STREAM_DISTANCE: f32 = 80.0;
+/// Chunks are unloaded this much farther out than they're loaded, so one at the edge doesn't
+/// load and unload every frame.
+const UNLOAD_FACTOR: f32 = 1.25;
+/// Meshes loaded per frame, nearest first, spreading the BLAS builds over frames.
+const MAX_LOADS_PER_FRAME: usize = 4;
+
+/// Geometry streaming distance, from `--streaming` (optionally with `--stream-distance <m>`):
+/// the BLAS and geometry buffers of static meshes are only kept around the camera, so scenes
+/// larger than VRAM can be explored. Off by default, every mesh is uploaded at startup.
+pub fn streaming_from_args() -> Option<f32> {
+    if !args::flag("--streaming") {
+        return None;
+    }
+    let distance = args::number::<f32>("--stream-distance")
+        .filter(|&distance| distance > 0.0)
+        .unwrap_or(DEFAULT_STREAM_DISTANCE);
+    Some(distance)
+}
+
+/// What streaming does with one mesh. A mesh is a chunk, bounded by all its instances.
+#[derive(Clone, Copy, Debug)]
+enum Chunk {
+    Resident, // Skinned, animated, simulated, precipitation or particles: always loaded
+    Unused, // No instances, never loaded
+    Streamed { min: Vec3, max: Vec3 }, // World bounds of its instances
+}
+
+/// Meshes to load and unload this frame.
+#[derive(Default)]
+pub struct StreamingChanges {
+    pub load: Vec<usize>,
+    pub unload: Vec<usize>,
+}
+
+/// Which meshes are loaded, by camera distance to their chunk bounds. The renderer does the
+/// uploads and reports back with `set_loaded` and `load_failed`.
+pub struct Streaming {
+    distance: f32,
+    chunks: Vec<Chunk>,
+    loaded: Vec<bool>,
+    failed: Vec<bool>, // Not retried until the camera leaves the chunk
+}
+
+impl Streaming {
+    pub fn new(scene: &Scene, distance: f32) -> Self {
+        let chunks = chunks(scene);
+        let loaded = chunks.iter().map(|chunk| matches!(chunk, Chunk::Resident)).collect();
+        let streamed = chunks.iter().filter(|chunk| matches!(chunk, Chunk::Streamed { .. })).count();
+        log::info!("Geometry streaming: {} of {} meshes streamed within {} m", streamed, chunks.len(), distance);
+        Streaming { distance, failed: vec![false; chunks.len()], chunks, loaded }
+    }
+
+    /// Recomputes the chunk bounds, after editor moves. Meshes don't change between resident
+    /// and streamed.
+    pub fn refresh(&mut self, scene: &Scene) {
+        for (chunk, fresh) in self.chunks.iter_mut().zip(chunks(scene)) {
+            if !matches!(chunk, Chunk::Resident) {
+                *chunk = if matches!(fresh, Chunk::Resident) { Chunk::Unused } else { fresh };
+            }
+        }
+    }
+
+    /// Whether `mesh_index` is left out of the startup upload.
+    pub fn is_streamed(&self, mesh_index: usize) -> bool {
+        !matches!(self.chunks[mesh_index], Chunk::Resident)
+    }
+
+    pub fn is_loaded(&self, mesh_index: usize) -> bool {
+        self.loaded[mesh_index]
+    }
+
+    pub fn set_loaded(&mut self, mesh_index: usize, loaded: bool) {
+        self.loaded[mesh_index] = loaded;
+    }
+
+    pub fn load_failed(&mut self, mesh_index: usize) {
+        self.failed[mesh_index] = true;
+    }
+
+    /// Chunks that came within the streaming distance of `camera` (nearest first, at most
+    /// `MAX_LOADS_PER_FRAME`) and loaded ones that left the unload distance.
+    pub fn update(&mut self, camera: Vec3) -> StreamingChanges {
+        let mut changes = StreamingChanges::default();
+        let mut candidates = Vec::new();
+        for (mesh_index, chunk) in self.chunks.iter().enumerate() {
+            let distance = match *chunk {
+                Chunk::Resident => continue,
+                Chunk::Unused => f32::INFINITY,
+                Chunk::Streamed { min, max } => camera.clamp(min, max).distance(camera),
+            };
+            if distance > self.distance * UNLOAD_FACTOR {
+                self.failed[mesh_index] = false;
+                if self.loaded[mesh_index] {
+                    changes.unload.push(mesh_index);
+                }
+            } else if distance <= self.distance && !self.loaded[mesh_index] && !self.failed[mesh_index] {
+                candidates.push((distance, mesh_index));
+            }
+        }
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        changes.load = candidates.into_iter().take(MAX_LOADS_PER_FRAME).map(|(_, mesh_index)| mesh_index).collect();
+        changes
+    }
+
+    /// Loaded meshes out of the streamed ones.
+    pub fn loaded_count(&self) -> (usize, usize) {
+        let streamed = (0..self.chunks.len()).filter(|&i| self.is_streamed(i));
+        let (loaded, total) = streamed.fold((0, 0), |(loaded, total), i| (loaded + self.loaded[i] as usize, total + 1));
+        (loaded, total)
+    }
+}
+
+/// The chunk of every mesh in `scene`.
+fn chunks(scene: &Scene) -> Vec<Chunk> {
+    let mut chunks = vec![Chunk::Unused; scene.meshes.len()];
+    let mut include = |mesh_index: usize, transform: Mat4| {
+        let (min, max) = scene.meshes[mesh_index].transformed_bounds(transform);
+        chunks[mesh_index] = match chunks[mesh_index] {
+            Chunk::Resident => Chunk::Resident,
+            Chunk::Unused => Chunk::Streamed { min, max },
+            Chunk::Streamed { min: chunk_min, max: chunk_max } => Chunk::Streamed { min: chunk_min.min(min), max: chunk_max.max(max) },
+        };
+    };
+    for obj in &scene.objects {
+        include(obj.mesh_index, obj.transform);
+    }
+    for batch in &scene.instance_batches {
+        for inst in &batch.instances {
+            include(batch.mesh_index, inst.transform);
+        }
+    }
+
+    // Meshes of moving objects stay loaded, their bounds would change every frame
+    let moving = scene.animations.iter().map(|animation| animation.object_index)
+        .chain(scene.rigid_bodies.iter().map(|body| body.object_index))
+        .chain(scene.precipitation.clone())
+        .chain(scene.particles.clone());
+    for object_index in moving {
+        chunks[scene.objects[object_index].mesh_index] = Chunk::Resident;
+    }
+    if let Some(batch) = scene.grass {
+        chunks[scene.instance_batches[batch].mesh_index] = Chunk::Resident;
+    }
+    for skin in &scene.skins {
+        chunks[skin.mesh_index] = Chunk::Resident;
+    }
+    // Levels of detail come and go with their full mesh, any instance may switch to them
+    for lod in &scene.lods {
+        for level in &lod.levels {
+            chunks[level.mesh_index] = chunks[lod.mesh_index];
+        }
+    }
+    chunks
+}
@@ -0,0 +1,161 @@
+use glam::{Vec2, Vec4};
+
+/// Steps of the teaching mode, in the order a closest hit shader builds up a pixel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stage {
+    Albedo,
+    Shadow,
+    Reflection,
+    Refraction,
+    Final,
+}
+
+/// Shading terms the probe pixel reports, in the order of the probe buffer.
+pub const PROBE_TERMS: usize = 5;
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Albedo => "Primary hit albedo",
+            Stage::Shadow => "Shadow term",
+            Stage::Reflection => "Reflection term",
+            Stage::Refraction => "Refraction term",
+            Stage::Final => "Final color",
+        }
+    }
+
+    /// What the stage shows, for the log.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            Stage::Albedo => "The camera ray hits a surface and looks up its base color. Nothing is lit yet.",
+            Stage::Shadow => "A shadow ray is traced towards the light: black where it is blocked, otherwise the cosine of the angle between the normal and the light direction.",
+            Stage::Reflection => "Metals (and glass at total internal reflection) trace a mirrored ray and add what it sees, weighted by how smooth the surface is.",
+            Stage::Refraction => "Glass bends the ray by its index of refraction, traces it through and adds what it sees behind.",
+            Stage::Final => "Direct and ambient light times the albedo, plus the reflection, refraction and caustics, is the color on screen.",
+        }
+    }
+
+    /// Light path channel the closest hit shader outputs for the stage (`output_params.x`).
+    pub fn channel(self) -> f32 {
+        match self {
+            Stage::Albedo => 6.0,
+            Stage::Shadow => 7.0,
+            Stage::Reflection => 3.0,
+            Stage::Refraction => 4.0,
+            Stage::Final => 0.0,
+        }
+    }
+
+    /// Entry of the stage in the probe buffer.
+    fn probe_term(self) -> usize {
+        self as usize
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Stage::Albedo => Stage::Shadow,
+            Stage::Shadow => Stage::Reflection,
+            Stage::Reflection => Stage::Refraction,
+            Stage::Refraction => Stage::Final,
+            Stage::Final => Stage::Albedo,
+        }
+    }
+}
+
+/// Teaching mode: freezes the scene and steps through the terms that make up each pixel,
+/// showing one term over the whole image and reporting the values of every term at a probe
+/// pixel that the mouse moves instead of the camera.
+pub struct Teaching {
+    pub active: bool,
+    pub stage: Stage,
+    pub probe: Vec2, // Physical pixels
+    pub terms: Option<[Vec4; PROBE_TERMS]>, // Last values read back for the probe pixel, none on the sky
+    traced_probes: [Option<Vec2>; 2], // Probe pixel each frame in flight last traced, none before it did in teaching mode
+    log_pending: bool, // The stage is logged once the terms at the current probe pixel are read back
+    was_paused: bool, // Animation state to restore when leaving
+}
+
+impl Teaching {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            stage: Stage::Albedo,
+            probe: Vec2::ZERO,
+            terms: None,
+            traced_probes: [None; 2],
+            log_pending: false,
+            was_paused: false,
+        }
+    }
+
+    /// Enters teaching mode with the probe at `center`, or leaves it. Returns whether the
+    /// animation should be paused from now on.
+    pub fn toggle(&mut self, center: Vec2, animation_paused: bool) -> bool {
+        self.active = !self.active;
+        if self.active {
+            self.was_paused = animation_paused;
+            self.stage = Stage::Albedo;
+            self.probe = center;
+            self.terms = None;
+            self.traced_probes = [None; 2];
+            self.log_pending = true;
+            true
+        } else {
+            self.was_paused
+        }
+    }
+
+    /// Moves the probe by a mouse motion, keeping it inside `extent`. The terms are logged
+    /// again once they're read back for the pixel it lands on.
+    pub fn move_probe(&mut self, dx: f64, dy: f64, extent: Vec2) {
+        let probe = (self.probe + Vec2::new(dx as f32, dy as f32)).clamp(Vec2::ZERO, extent - 1.0);
+        if probe.floor() != self.probe.floor() {
+            self.log_pending = true;
+        }
+        self.probe = probe;
+    }
+
+    /// Steps to the next stage and logs it, as soon as the terms at the probe pixel are known.
+    pub fn next_stage(&mut self) {
+        self.stage = self.stage.next();
+        if !self.log_pending {
+            self.log_stage();
+        }
+    }
+
+    /// Takes the terms frame slot `slot` read back from its last trace, which only count if it
+    /// traced the current probe pixel, and logs the stage if that's what it waits for.
+    pub fn read_back(&mut self, slot: usize, terms: [Vec4; PROBE_TERMS]) {
+        if self.traced_probes[slot] != Some(self.probe.floor()) {
+            return;
+        }
+        // w is set by the closest hit shader, it stays 0 when the probe ray misses
+        self.terms = (terms[0].w > 0.0).then_some(terms);
+        if self.log_pending {
+            self.log_pending = false;
+            self.log_stage();
+        }
+    }
+
+    /// Records that frame slot `slot` traces the probe pixel where it is now.
+    pub fn traced(&mut self, slot: usize) {
+        self.traced_probes[slot] = Some(self.probe.floor());
+    }
+
+    /// Logs the current stage and the probe pixel's value for it.
+    fn log_stage(&self) {
+        log::info!("Teaching {}/{}: {}", self.stage as usize + 1, PROBE_TERMS, self.stage.name());
+        log::info!("  {}", self.stage.explanation());
+        match &self.terms {
+            Some(terms) => {
+                let value = terms[self.stage.probe_term()];
+                if self.stage == Stage::Shadow {
+                    log::info!("  At pixel ({:.0}, {:.0}): {:.3}", self.probe.x, self.probe.y, value.x);
+                } else {
+                    log::info!("  At pixel ({:.0}, {:.0}): ({:.3}, {:.3}, {:.3})", self.probe.x, self.probe.y, value.x, value.y, value.z);
+                }
+            }
+            None => log::info!("  At pixel ({:.0}, {:.0}): sky, no surface hit", self.probe.x, self.probe.y),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use glam::Vec3;
+use crate::rng;
+use crate::scene::{Mesh, Vertex};
+use crate::args;
+
+/// Side of the terrain square unless `--terrain-size` gives another one, meters.
+const DEFAULT_SIZE: f32 = 130.0;
+/// Spacing of the heightmap samples, meters.
+const CELL_SIZE: f32 = 1.0;
+/// Height of the tallest hills, meters.
+const HILL_HEIGHT: f32 = 9.0;
+/// Width of the largest hills, meters.
+const FEATURE_SIZE: f32 = 24.0;
+const OCTAVES: u32 = 5;
+/// Half extent of the flat square left around the street, meters.
+const FLAT_HALF_EXTENT: f32 = 14.0;
+/// Distance over which the hills rise from the flat square, meters.
+const RAMP: f32 = 12.0;
+/// Texture coordinates repeat every this many meters.
+const UV_REPEAT: f32 = 4.0;
+
+/// Procedural terrain: a heightmap of fractal value noise around the origin, flat in the
+/// middle where the street is, triangulated into one grid mesh.
+pub struct Terrain {
+    pub size: f32, // Side of the square, meters
+    pub seed: u32,
+}
+
+impl Terrain {
+    /// Terrain from `--terrain-size <m>` and `--terrain-seed <n>`; the seed defaults to the
+    /// run's `--seed`, so other seeds give other hills.
+    pub fn from_args() -> Self {
+        let size = args::number::<f32>("--terrain-size")
+            .filter(|&size| size > 2.0 * FLAT_HALF_EXTENT)
+            .unwrap_or(DEFAULT_SIZE);
+        let seed = args::number("--terrain-seed").unwrap_or_else(rng::seed_from_args);
+        Terrain { size, seed }
+    }
+
+    /// Height of the terrain above its flat middle at world `x`, `z`.
+    pub fn height(&self, x: f32, z: f32) -> f32 {
+        let (mut sum, mut total, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, 1.0 / FEATURE_SIZE);
+        for octave in 0..OCTAVES {
+            sum += amplitude * self.value_noise(x * frequency, z * frequency, octave);
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        // Hills rise smoothly out of the flat square
+        let t = ((x.abs().max(z.abs()) - FLAT_HALF_EXTENT) / RAMP).clamp(0.0, 1.0);
+        sum / total * HILL_HEIGHT * t * t * (3.0 - 2.0 * t)
+    }
+
+    /// Grid mesh of the heightmap centered on the origin, with normals from the height
+    /// differences between neighboring samples.
+    pub fn mesh(&self) -> Mesh {
+        let cells = (self.size / CELL_SIZE).ceil() as u32;
+        let step = self.size / cells as f32;
+        let half = self.size * 0.5;
+
+        let mut vertices = Vec::with_capacity(((cells + 1) * (cells + 1)) as usize);
+        for i in 0..=cells {
+            for j in 0..=cells {
+                let (x, z) = (j as f32 * step - half, i as f32 * step - half);
+                let normal = Vec3::new(
+                    self.height(x - step, z) - self.height(x + step, z),
+                    2.0 * step,
+                    self.height(x, z - step) - self.height(x, z + step),
+                ).normalize();
+                vertices.push(Vertex::new([x, self.height(x, z), z], normal.into(), [x / UV_REPEAT, z / UV_REPEAT]));
+            }
+        }
+
+        let mut indices = Vec::with_capacity((cells * cells * 6) as usize);
+        for i in 0..cells {
+            for j in 0..cells {
+                let a = i * (cells + 1) + j;
+                let b = a + cells + 1;
+                indices.extend_from_slice(&[a, b, a + 1, b, b + 1, a + 1]);
+            }
+        }
+        Mesh { vertices, indices }
+    }
+
+    /// Smoothly interpolated random values at the integer lattice points, in [0, 1].
+    fn value_noise(&self, x: f32, z: f32, octave: u32) -> f32 {
+        let (x0, z0) = (x.floor(), z.floor());
+        let fade = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (tx, tz) = (fade(x - x0), fade(z - z0));
+        let lattice = |dx: i32, dz: i32| {
+            let (ix, iz) = (x0 as i32 + dx, z0 as i32 + dz);
+            let cell = (ix as u32).wrapping_mul(73_856_093) ^ (iz as u32).wrapping_mul(19_349_663);
+            rng::tea(self.seed.wrapping_add(octave), cell) as f32 / u32::MAX as f32
+        };
+        let top = lattice(0, 0) + (lattice(1, 0) - lattice(0, 0)) * tx;
+        let bottom = lattice(0, 1) + (lattice(1, 1) - lattice(0, 1)) * tx;
+        top + (bottom - top) * tz
+    }
+}
@@ -0,0 +1,324 @@
+use ash::vk;
+use std::path::Path;
+
+const KTX2_IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const KTX2_HEADER_SIZE: usize = 80;
+const KTX2_LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+/// CPU-side texture with its complete mip chain, ready to be uploaded by the renderer.
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub format: vk::Format,
+    pub levels: Vec<Vec<u8>>, // Mip chain, level 0 first
+}
+
+impl TextureData {
+    /// Wraps RGBA8 pixels and box-filters the mip chain down to 1x1.
+    /// Normal maps are renormalized at every level so they stay unit length.
+    pub fn from_rgba8(width: u32, height: u32, pixels: Vec<u8>, normal_map: bool) -> Self {
+        let mut levels = vec![pixels];
+        let (mut w, mut h) = (width, height);
+
+        while w > 1 || h > 1 {
+            let (nw, nh) = ((w / 2).max(1), (h / 2).max(1));
+            let src = levels.last().unwrap();
+            let mut dst = vec![0u8; (nw * nh * 4) as usize];
+
+            for y in 0..nh {
+                for x in 0..nw {
+                    let mut sum = [0.0f32; 4];
+                    for (sx, sy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let px = (x * 2 + sx).min(w - 1);
+                        let py = (y * 2 + sy).min(h - 1);
+                        let i = ((py * w + px) * 4) as usize;
+                        for c in 0..4 {
+                            sum[c] += src[i + c] as f32 / 255.0 * 0.25;
+                        }
+                    }
+
+                    if normal_map {
+                        let n = glam::Vec3::new(sum[0] * 2.0 - 1.0, sum[1] * 2.0 - 1.0, sum[2] * 2.0 - 1.0)
+                            .normalize_or(glam::Vec3::Z);
+                        sum[0] = n.x * 0.5 + 0.5;
+                        sum[1] = n.y * 0.5 + 0.5;
+                        sum[2] = n.z * 0.5 + 0.5;
+                    }
+
+                    let o = ((y * nw + x) * 4) as usize;
+                    for c in 0..4 {
+                        dst[o + c] = (sum[c] * 255.0).round() as u8;
+                    }
+                }
+            }
+
+            levels.push(dst);
+            w = nw;
+            h = nh;
+        }
+
+        Self { width, height, format: vk::Format::R8G8B8A8_UNORM, levels }
+    }
+
+    /// 1x1 "straight up" normal map, used when a scene has no textures at all.
+    pub fn flat_normal_map() -> Self {
+        Self::from_rgba8(1, 1, vec![128, 128, 255, 255], true)
+    }
+
+    /// Parses a KTX2 container holding a single 2D image with its mip chain.
+    /// The level data is kept as stored (e.g. BC7/BC5 blocks) so it can be copied straight into a `VkImage`.
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        if bytes.len() < KTX2_HEADER_SIZE || bytes[..12] != KTX2_IDENTIFIER {
+            return Err("not a KTX2 file".into());
+        }
+        let read_u32 = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let read_u64 = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+        let format = vk::Format::from_raw(read_u32(12) as i32);
+        let width = read_u32(20);
+        let height = read_u32(24);
+        let depth = read_u32(28);
+        let layer_count = read_u32(32);
+        let face_count = read_u32(36);
+        let level_count = read_u32(40).max(1) as usize;
+        let supercompression = read_u32(44);
+
+        match supercompression {
+            0 => {}
+            1 => return Err("BasisLZ supercompression is not supported, transcode to BC7/BC5 first (e.g. `ktx transcode --target bc7`)".into()),
+            2 => return Err("Zstandard supercompression is not supported".into()),
+            other => return Err(format!("unknown supercompression scheme {}", other).into()),
+        }
+        if format == vk::Format::UNDEFINED {
+            return Err("KTX2 file has no Vulkan format (Basis Universal data is not supported)".into());
+        }
+        if depth > 1 || layer_count > 1 || face_count != 1 {
+            return Err("only single 2D images are supported (no arrays, cubemaps or 3D textures)".into());
+        }
+        if block_size(format).is_none() {
+            return Err(format!("unsupported KTX2 format {:?}", format).into());
+        }
+        if width == 0 || height == 0 {
+            return Err("KTX2 image has no size".into());
+        }
+        // A full mip chain of the base size ends at 1x1, which a 32-bit size reaches within 32 levels
+        let max_levels = (32 - width.max(height).leading_zeros()) as usize;
+        if level_count > 32 || level_count > max_levels {
+            return Err(format!("{} mip levels, but a {}x{} image has at most {}", level_count, width, height, max_levels).into());
+        }
+
+        let level_index_end = level_count.checked_mul(KTX2_LEVEL_INDEX_ENTRY_SIZE).and_then(|size| size.checked_add(KTX2_HEADER_SIZE));
+        if level_index_end.is_none_or(|end| bytes.len() < end) {
+            return Err("truncated KTX2 level index".into());
+        }
+
+        let mut levels = Vec::with_capacity(level_count);
+        for level in 0..level_count {
+            let entry = KTX2_HEADER_SIZE + level * KTX2_LEVEL_INDEX_ENTRY_SIZE;
+            let offset = read_u64(entry) as usize;
+            let length = read_u64(entry + 8) as usize;
+
+            let (w, h) = match (width.checked_shr(level as u32), height.checked_shr(level as u32)) {
+                (Some(w), Some(h)) => (w.max(1), h.max(1)),
+                _ => return Err(format!("mip level {} is beyond the mip chain", level).into()),
+            };
+            let expected = level_size(format, w, h).ok_or("KTX2 mip level is too large")?;
+            if length != expected {
+                return Err(format!("mip level {} is {} bytes, expected {}", level, length, expected).into());
+            }
+            let end = offset.checked_add(length).ok_or("KTX2 mip level out of bounds")?;
+            let data = bytes.get(offset..end).ok_or("KTX2 mip level out of bounds")?;
+            levels.push(data.to_vec());
+        }
+
+        Ok(Self { width, height, format, levels })
+    }
+
+    /// Whether the texel data is stored in BCn blocks (needs the `textureCompressionBC` device feature).
+    pub fn is_block_compressed(&self) -> bool {
+        block_size(self.format).is_some_and(|(dim, _)| dim == 4)
+    }
+}
+
+/// Loads a KTX2 texture if `path` exists, otherwise builds the fallback.
+/// A broken file is reported and replaced by the fallback rather than aborting scene setup.
+pub fn load_ktx2_or(path: impl AsRef<Path>, fallback: impl FnOnce() -> TextureData) -> TextureData {
+    let path = path.as_ref();
+    if !path.exists() {
+        return fallback();
+    }
+    match std::fs::read(path).map_err(|e| e.into()).and_then(|bytes| TextureData::from_ktx2(&bytes)) {
+        Ok(texture) => {
+            log::info!("Loaded {} ({}x{}, {:?}, {} mips)", path.display(), texture.width, texture.height, texture.format, texture.levels.len());
+            texture
+        }
+        Err(e) => {
+            log::warn!("Failed to load {}: {}, using procedural texture", path.display(), e);
+            fallback()
+        }
+    }
+}
+
+/// (block dimension in texels, bytes per block) for the formats the renderer can upload.
+fn block_size(format: vk::Format) -> Option<(u32, usize)> {
+    match format {
+        vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB => Some((1, 4)),
+        vk::Format::BC1_RGBA_UNORM_BLOCK | vk::Format::BC1_RGBA_SRGB_BLOCK | vk::Format::BC4_UNORM_BLOCK => Some((4, 8)),
+        vk::Format::BC3_UNORM_BLOCK | vk::Format::BC3_SRGB_BLOCK | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK | vk::Format::BC7_SRGB_BLOCK => Some((4, 16)),
+        _ => None,
+    }
+}
+
+fn level_size(format: vk::Format, width: u32, height: u32) -> Option<usize> {
+    let (dim, bytes) = block_size(format)?;
+    (width.div_ceil(dim) as usize).checked_mul(height.div_ceil(dim) as usize)?.checked_mul(bytes)
+}
+
+/// Builds a tangent-space normal map from a tileable height function over (u, v) in [0, 1).
+pub fn normal_map_from_height(size: u32, strength: f32, height: impl Fn(f32, f32) -> f32) -> TextureData {
+    let heights: Vec<f32> = (0..size * size)
+        .map(|i| height((i % size) as f32 / size as f32, (i / size) as f32 / size as f32))
+        .collect();
+    let sample = |x: i64, y: i64| {
+        let s = size as i64;
+        heights[(y.rem_euclid(s) * s + x.rem_euclid(s)) as usize]
+    };
+
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size as i64 {
+        for x in 0..size as i64 {
+            // Central differences; +y in the image is +v, which is the bitangent direction
+            let dx = (sample(x + 1, y) - sample(x - 1, y)) * 0.5 * size as f32;
+            let dy = (sample(x, y + 1) - sample(x, y - 1)) * 0.5 * size as f32;
+            let n = glam::Vec3::new(-dx * strength, -dy * strength, 1.0).normalize();
+            pixels.extend_from_slice(&[
+                ((n.x * 0.5 + 0.5) * 255.0).round() as u8,
+                ((n.y * 0.5 + 0.5) * 255.0).round() as u8,
+                ((n.z * 0.5 + 0.5) * 255.0).round() as u8,
+                255,
+            ]);
+        }
+    }
+
+    TextureData::from_rgba8(size, size, pixels, true)
+}
+
+/// Running bond brick wall: raised bricks separated by recessed mortar joints.
+pub fn brick_normal_map(size: u32) -> TextureData {
+    const ROWS: f32 = 8.0;
+    const BRICKS_PER_ROW: f32 = 4.0;
+    const MORTAR: f32 = 0.06; // Joint half-width, in brick heights
+
+    normal_map_from_height(size, 0.02, |u, v| {
+        let row = (v * ROWS).floor();
+        let offset = if row as i32 % 2 == 0 { 0.0 } else { 0.5 };
+        let bu = (u * BRICKS_PER_ROW + offset).fract();
+        let bv = (v * ROWS).fract();
+
+        // Distance to the nearest joint, measured in brick heights on both axes
+        let edge = (bu.min(1.0 - bu) * ROWS / BRICKS_PER_ROW).min(bv.min(1.0 - bv));
+        let brick = smoothstep(MORTAR * 0.5, MORTAR * 1.5, edge);
+        brick + value_noise(u * 64.0, v * 64.0, 64) * 0.15
+    })
+}
+
+/// Vertical, slightly wavy bark grooves.
+pub fn bark_normal_map(size: u32) -> TextureData {
+    normal_map_from_height(size, 0.03, |u, v| {
+        let warp = value_noise(u * 8.0, v * 4.0, 8) * 2.0;
+        let grooves = 0.5 + 0.5 * (u * std::f32::consts::TAU * 12.0 + warp).sin();
+        grooves + value_noise(u * 32.0, v * 32.0, 32) * 0.3
+    })
+}
+
+/// Fine, random aggregate bumps for the road surface.
+pub fn asphalt_normal_map(size: u32) -> TextureData {
+    normal_map_from_height(size, 0.01, |u, v| {
+        value_noise(u * 64.0, v * 64.0, 64) * 0.6
+            + value_noise(u * 128.0, v * 128.0, 128) * 0.4
+    })
+}
+
+/// Gobo of a four-pane window: bright panes inside a dark frame and cross-shaped mullions,
+/// for patterned "light through a window" projections.
+pub fn window_gobo(size: u32) -> TextureData {
+    const FRAME: f32 = 0.08;
+    const MULLION: f32 = 0.025; // Half-width
+
+    gobo_from_fn(size, |u, v| {
+        let frame = smoothstep(FRAME - 0.01, FRAME + 0.01, u.min(1.0 - u).min(v.min(1.0 - v)));
+        let mullion = smoothstep(MULLION - 0.01, MULLION + 0.01, (u - 0.5).abs().min((v - 0.5).abs()));
+        frame * mullion
+    })
+}
+
+/// Foliage "breakup" gobo as used in stage lighting: dappled light inside a soft circular beam.
+pub fn breakup_gobo(size: u32) -> TextureData {
+    gobo_from_fn(size, |u, v| {
+        let r = ((u - 0.5) * (u - 0.5) + (v - 0.5) * (v - 0.5)).sqrt() * 2.0;
+        let beam = 1.0 - smoothstep(0.85, 1.0, r);
+        let leaves = value_noise(u * 6.0, v * 6.0, 6) * 0.65 + value_noise(u * 16.0, v * 16.0, 16) * 0.35;
+        beam * smoothstep(0.45, 0.55, leaves)
+    })
+}
+
+/// Alpha mask of a grass card (in the red channel): a row of tapering, bent blades of
+/// different heights growing from the bottom edge, for alpha-tested foliage.
+pub fn grass_card(size: u32) -> TextureData {
+    const BLADES: i32 = 14;
+    const WIDTH: f32 = 0.035; // Half-width at the base
+
+    gobo_from_fn(size, |u, v| {
+        let up = 1.0 - v; // Height above the bottom edge
+        let coverage = (0..BLADES).map(|i| {
+            let height = 0.55 + 0.45 * hash2(i, 7);
+            if up > height {
+                return 0.0;
+            }
+            let t = up / height;
+            let center = (i as f32 + 0.5 + (hash2(i, 11) - 0.5) * 0.8) / BLADES as f32 + (hash2(i, 13) - 0.5) * 0.3 * t * t;
+            let half_width = WIDTH * (1.0 - t);
+            1.0 - smoothstep(half_width - 0.004, half_width + 0.004, (u - center).abs())
+        }).fold(0.0, f32::max);
+        coverage * smoothstep(0.0, 0.02, u.min(1.0 - u)) // Blades that lean out are cut at the card's sides
+    })
+}
+
+/// Grayscale projector cookie from a transmission function over (u, v) in [0, 1].
+fn gobo_from_fn(size: u32, transmission: impl Fn(f32, f32) -> f32) -> TextureData {
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let t = transmission((x as f32 + 0.5) / size as f32, (y as f32 + 0.5) / size as f32);
+            let value = (t.clamp(0.0, 1.0) * 255.0).round() as u8;
+            pixels.extend_from_slice(&[value, value, value, 255]);
+        }
+    }
+    TextureData::from_rgba8(size, size, pixels, false)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Lattice hash in [0, 1).
+pub(crate) fn hash2(x: i32, y: i32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343) ^ (y as u32).wrapping_mul(0xd816_3841);
+    h = (h ^ (h >> 13)).wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    (h & 0x00ff_ffff) as f32 / 16_777_216.0
+}
+
+/// Smoothly interpolated value noise that tiles every `period` lattice cells.
+fn value_noise(x: f32, y: f32, period: i32) -> f32 {
+    let (xi, yi) = (x.floor() as i32, y.floor() as i32);
+    let (fx, fy) = (x - x.floor(), y - y.floor());
+    let (sx, sy) = (fx * fx * (3.0 - 2.0 * fx), fy * fy * (3.0 - 2.0 * fy));
+    let corner = |dx: i32, dy: i32| hash2((xi + dx).rem_euclid(period), (yi + dy).rem_euclid(period));
+
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * sx;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * sx;
+    top + (bottom - top) * sy
+}
@@ -0,0 +1,145 @@
+use ash::vk;
+use glam::Vec2;
+use std::time::{Duration, Instant};
+use crate::args;
+
+/// Smallest render scale `--render-scale` accepts.
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+
+// Dynamic resolution
+const MIN_DYNAMIC_SCALE: f32 = 0.5;
+const DYNAMIC_INTERVAL: Duration = Duration::from_millis(500); // Between scale adjustments
+const DYNAMIC_HEADROOM: f32 = 0.9; // Share of the frame budget the GPU time is steered to
+const DYNAMIC_SMOOTHING: f32 = 0.1; // Weight of a new GPU frame time in the running average
+const DYNAMIC_MIN_CHANGE: f32 = 0.02; // Smaller corrections are skipped, the image would only shimmer
+
+/// Fraction of the window size that is ray traced, from `--render-scale <scale>`, given as a
+/// fraction (`0.5`) or a percentage (`50`, `67`, `75`). The traced image is upscaled to the
+/// window by the upscale pass. Without it the whole window is traced.
+pub fn render_scale_from_args() -> f32 {
+    let scale = match args::value("--render-scale") {
+        Some(value) => value.trim_end_matches('%').parse::<f32>().unwrap_or_else(|_| {
+            log::warn!("Ignoring --render-scale {}, expected a fraction or a percentage", value);
+            1.0
+        }),
+        None => 1.0,
+    };
+    let scale = if scale > 1.0 { scale / 100.0 } else { scale };
+    scale.clamp(MIN_RENDER_SCALE, 1.0)
+}
+
+/// How the traced image is upscaled to the window.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Upscaler {
+    Bilinear, // Stretches each frame on its own
+    Temporal, // Jitters the camera rays and gathers the frames' samples into a window-size history
+}
+
+impl Upscaler {
+    /// From `--upscaler bilinear|temporal`, temporal by default.
+    pub fn from_args() -> Self {
+        match args::value("--upscaler") {
+            Some("bilinear") => Upscaler::Bilinear,
+            Some("temporal") | None => Upscaler::Temporal,
+            Some(upscaler) => {
+                log::warn!("Unknown --upscaler {}, expected temporal or bilinear", upscaler);
+                Upscaler::Temporal
+            }
+        }
+    }
+}
+
+/// Number of jitter offsets the temporal upscaler cycles through: enough that every window
+/// pixel gets a nearby sample, about 8 per window pixel.
+pub fn jitter_phases(render_width: u32, window_width: u32) -> u32 {
+    let ratio = window_width as f32 / render_width as f32;
+    (8.0 * ratio * ratio).ceil() as u32
+}
+
+/// Offset of the camera rays from the pixel centers in `frame`, in pixels within
+/// [-0.5, 0.5): a Halton (2, 3) sequence, which covers the pixel evenly in any number of
+/// consecutive frames.
+pub fn jitter(frame: u32, phases: u32) -> Vec2 {
+    let index = frame % phases + 1; // The sequence starts at the corner for 0
+    Vec2::new(halton(index, 2), halton(index, 3)) - 0.5
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let (mut result, mut fraction) = (0.0, 1.0);
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// Adjusts the render scale to hold a target frame rate (`--target-fps <fps>`). GPU frame
+/// times are averaged, and twice a second the scale moves so the traced pixel count, which the
+/// GPU time roughly follows, fits the frame budget with some headroom. The scale stays between
+/// 50% and the `--render-scale` the images were created at, so the images are never recreated,
+/// only a smaller part of them is traced.
+pub struct DynamicResolution {
+    pub target_fps: f32,
+    pub scale: f32,
+    max_scale: f32,
+    gpu_ms: Option<f32>, // Running average
+    last_change: Instant,
+}
+
+impl DynamicResolution {
+    pub fn from_args(max_scale: f32) -> Option<Self> {
+        let target_fps = args::number::<f32>("--target-fps")
+            .filter(|&fps| fps > 0.0)?;
+        Some(Self {
+            target_fps,
+            scale: max_scale,
+            max_scale,
+            gpu_ms: None,
+            last_change: Instant::now(),
+        })
+    }
+
+    /// Adds the GPU time of a frame. Returns the new scale when it is time to change it.
+    pub fn add_frame(&mut self, gpu_ms: f32) -> Option<f32> {
+        let average = match self.gpu_ms {
+            Some(average) => average + (gpu_ms - average) * DYNAMIC_SMOOTHING,
+            None => gpu_ms,
+        };
+        self.gpu_ms = Some(average);
+        if self.last_change.elapsed() < DYNAMIC_INTERVAL || average <= 0.0 {
+            return None;
+        }
+        self.last_change = Instant::now();
+
+        let budget_ms = 1000.0 / self.target_fps * DYNAMIC_HEADROOM;
+        let scale = (self.scale * (budget_ms / average).sqrt()).clamp(MIN_DYNAMIC_SCALE.min(self.max_scale), self.max_scale);
+        if (scale - self.scale).abs() < DYNAMIC_MIN_CHANGE {
+            return None;
+        }
+        // The average measured the old scale, start over
+        self.scale = scale;
+        self.gpu_ms = None;
+        Some(scale)
+    }
+}
+
+/// Size of the traced image for a window of `extent` at `scale`, at least one pixel.
+pub fn scaled_extent(extent: vk::Extent2D, scale: f32) -> vk::Extent2D {
+    vk::Extent2D {
+        width: ((extent.width as f32 * scale).round() as u32).max(1),
+        height: ((extent.height as f32 * scale).round() as u32).max(1),
+    }
+}
+
+/// `extent` scaled down, keeping its aspect ratio, until it fits in `max`; as it is if it does.
+pub fn fit_extent(extent: vk::Extent2D, max: vk::Extent2D) -> vk::Extent2D {
+    let scale = (max.width as f32 / extent.width as f32).min(max.height as f32 / extent.height as f32);
+    if scale >= 1.0 {
+        return extent;
+    }
+    vk::Extent2D {
+        width: ((extent.width as f32 * scale).round() as u32).clamp(1, max.width),
+        height: ((extent.height as f32 * scale).round() as u32).clamp(1, max.height),
+    }
+}
@@ -0,0 +1,205 @@
+use glam::{IVec3, Vec3};
+use std::path::Path;
+use crate::args;
+
+/// Densified grids are reduced to at most this many voxels per axis.
+const MAX_RESOLUTION: i32 = 256;
+/// Grids spanning more index space than this on an axis are refused, which keeps the blocks
+/// averaged into one voxel at most 256^3.
+const MAX_EXTENT: i32 = MAX_RESOLUTION * 256;
+/// Where the bottom center of the volume's box is placed: over the open lot next to the puddle.
+const ANCHOR: Vec3 = Vec3::new(4.0, 0.0, 7.5);
+/// Extinction of density 1 per meter unless `--volume-density` gives another one.
+const DEFAULT_EXTINCTION: f32 = 4.0;
+const ALBEDO: [f32; 3] = [0.9, 0.9, 0.9]; // Gray smoke
+const ANISOTROPY: f32 = 0.3; // Mostly forward scattering
+
+// NanoVDB file and grid layout (NanoVDB 32.x), byte offsets
+const FILE_MAGIC: u64 = 0x304244566f6e614e; // "NanoVDB0"
+const GRID_MAGIC: u64 = 0x314244566f6e614e; // "NanoVDB1"
+const FILE_HEADER_SIZE: usize = 16;
+const FILE_METADATA_SIZE: usize = 176;
+const GRID_TYPE_FLOAT: u32 = 1;
+const MAP_VEC_D: usize = 528; // Grid to world translation, 3 doubles
+const VOXEL_SIZE: usize = 608; // 3 doubles
+const TREE: usize = 672;
+const LEAF_SIZE: usize = 2144; // Of a float grid: bbox, masks, statistics and 8^3 values
+const LEAF_VALUES: usize = 96;
+const LOWER_SIZE: usize = 1088 + 4096 * 8; // Masks and statistics, then 16^3 child/tile table entries
+
+/// Heterogeneous participating medium (smoke, cloud) from `--volume <file.nvdb>`: the first
+/// float grid of a NanoVDB file, densified into a box of voxels that the hit and miss shaders
+/// ray march. The grid's world units are meters scaled by `--volume-scale` (1 by default), and
+/// the box is moved to stand on the open lot next to the puddle.
+pub struct Volume {
+    pub min: Vec3, // World space box
+    pub max: Vec3,
+    pub dims: [u32; 3],
+    pub density: Vec<f32>, // x fastest, then y, then z
+    pub max_density: f32,
+    pub extinction: f32, // Of density 1, 1/m
+}
+
+impl Volume {
+    /// The volume the command line asks for, if any. A file that can't be loaded is logged and
+    /// left out rather than stopping the renderer.
+    pub fn from_args() -> Option<Self> {
+        let path = args::value("--volume")?;
+        let scale = args::number::<f32>("--volume-scale").filter(|&scale| scale > 0.0).unwrap_or(1.0);
+        let extinction = args::number::<f32>("--volume-density").filter(|&density| density >= 0.0).unwrap_or(DEFAULT_EXTINCTION);
+        match load_nvdb(path, scale, extinction) {
+            Ok(volume) => {
+                log::info!("Loaded volume {} ({}x{}x{} voxels, {:.1}x{:.1}x{:.1} m)", path,
+                    volume.dims[0], volume.dims[1], volume.dims[2],
+                    volume.max.x - volume.min.x, volume.max.y - volume.min.y, volume.max.z - volume.min.z);
+                Some(volume)
+            }
+            Err(e) => {
+                log::error!("Failed to load volume {}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Contents of the shaders' volume buffer (see volume.glsl): the header followed by the
+    /// voxels. Without a volume only the header is there, marking it absent.
+    pub fn gpu_data(volume: Option<&Volume>) -> Vec<u32> {
+        let Some(volume) = volume else {
+            return vec![0; 16];
+        };
+        let header = [
+            volume.min.x, volume.min.y, volume.min.z, volume.extinction,
+            volume.max.x, volume.max.y, volume.max.z, volume.max_density,
+            ALBEDO[0], ALBEDO[1], ALBEDO[2], ANISOTROPY,
+        ];
+        header.iter().map(|value| value.to_bits())
+            .chain([volume.dims[0], volume.dims[1], volume.dims[2], 1])
+            .chain(volume.density.iter().map(|value| value.to_bits()))
+            .collect()
+    }
+}
+
+/// Reads the first grid of an uncompressed NanoVDB file, which must hold float values (a fog
+/// volume's densities), and resamples its active voxels into a dense box, averaging
+/// blocks of voxels when the grid is larger than `MAX_RESOLUTION` on any axis. Leaves and the
+/// tiles of the lower internal nodes are read; values in the tiles of the upper nodes and the
+/// root (regions of 128^3 voxels or more) are left out.
+fn load_nvdb(path: impl AsRef<Path>, scale: f32, extinction: f32) -> Result<Volume, Box<dyn std::error::Error>> {
+    let bytes = std::fs::read(path)?;
+    let u32_at = |offset: usize| -> Result<u32, String> {
+        bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap())).ok_or_else(|| "file is truncated".to_string())
+    };
+    let u64_at = |offset: usize| -> Result<u64, String> {
+        bytes.get(offset..offset + 8).map(|b| u64::from_le_bytes(b.try_into().unwrap())).ok_or_else(|| "file is truncated".to_string())
+    };
+
+    if u64_at(0)? != FILE_MAGIC {
+        return Err("not a NanoVDB file".into());
+    }
+    let grid_count = u32_at(12)? & 0xffff;
+    let codec = u32_at(12)? >> 16;
+    if grid_count == 0 {
+        return Err("file has no grids".into());
+    }
+    if codec != 0 {
+        return Err("compressed NanoVDB files are not supported, save it with --codec none".into());
+    }
+    // First grid's metadata, its name, then the grid itself
+    let metadata = FILE_HEADER_SIZE;
+    let grid_size = u64_at(metadata)? as usize;
+    let grid_type = u32_at(metadata + 32)?;
+    let name_size = u32_at(metadata + 136)? as usize;
+    if grid_type != GRID_TYPE_FLOAT {
+        return Err(format!("grid type {} is not supported, only float grids are", grid_type).into());
+    }
+    let grid_start = metadata + FILE_METADATA_SIZE + name_size;
+    let grid = grid_start.checked_add(grid_size).and_then(|grid_end| bytes.get(grid_start..grid_end)).ok_or("file is truncated")?;
+    let u32_in = |offset: usize| u32::from_le_bytes(grid[offset..offset + 4].try_into().unwrap());
+    let i32_in = |offset: usize| i32::from_le_bytes(grid[offset..offset + 4].try_into().unwrap());
+    let u64_in = |offset: usize| u64::from_le_bytes(grid[offset..offset + 8].try_into().unwrap());
+    let f32_in = |offset: usize| f32::from_le_bytes(grid[offset..offset + 4].try_into().unwrap());
+    let f64_in = |offset: usize| f64::from_le_bytes(grid[offset..offset + 8].try_into().unwrap());
+    let coord_in = |offset: usize| IVec3::new(i32_in(offset), i32_in(offset + 4), i32_in(offset + 8));
+    if grid.len() < TREE + 64 || u64_in(0) != GRID_MAGIC {
+        return Err("grid header is invalid".into());
+    }
+
+    // Nodes of each level are stored contiguously, at offsets from the tree data. Offset and
+    // count of a level's nodes of `size` bytes, if they lie inside the grid.
+    let nodes = |level: usize, size: usize| -> Option<(usize, usize)> {
+        let offset = TREE.checked_add(usize::try_from(u64_in(TREE + level * 8)).ok()?)?;
+        let count = u32_in(TREE + 32 + level * 4) as usize;
+        (offset.checked_add(count.checked_mul(size)?)? <= grid.len()).then_some((offset, count))
+    };
+    let (Some(leaves), Some(lowers)) = (nodes(0, LEAF_SIZE), nodes(1, LOWER_SIZE)) else {
+        return Err("grid nodes lie outside the grid".into());
+    };
+
+    // Blocks of constant value: the lower nodes' active tiles (8^3 voxels each)
+    let mut tiles = Vec::new();
+    for node in (0..lowers.1).map(|i| lowers.0 + i * LOWER_SIZE) {
+        let origin = coord_in(node) & !127;
+        for n in 0..4096 {
+            let (word, bit) = (n / 64, n % 64);
+            let active = u64_in(node + 32 + word * 8) >> bit & 1 == 1;
+            let child = u64_in(node + 32 + 512 + word * 8) >> bit & 1 == 1;
+            if active && !child {
+                let local = IVec3::new((n >> 8) as i32, ((n >> 4) & 15) as i32, (n & 15) as i32) * 8;
+                tiles.push((origin + local, f32_in(node + 1088 + n * 8)));
+            }
+        }
+    }
+
+    // Index space bounds of the leaves and tiles, the last voxel included
+    let leaf_origin = |i: usize| coord_in(leaves.0 + i * LEAF_SIZE) & !7;
+    let blocks = (0..leaves.1).map(leaf_origin).chain(tiles.iter().map(|&(origin, _)| origin));
+    let (lo, hi) = blocks.fold((IVec3::MAX, IVec3::MIN), |(lo, hi), origin| (lo.min(origin), hi.max(origin + 7)));
+    if lo.cmpgt(hi).any() {
+        return Err("grid has no active voxels".into());
+    }
+    let extent = [0, 1, 2].map(|axis| hi[axis].checked_sub(lo[axis]).and_then(|span| span.checked_add(1)).filter(|&extent| extent <= MAX_EXTENT));
+    let [Some(x), Some(y), Some(z)] = extent else {
+        return Err(format!("grid spans more than {} voxels on an axis", MAX_EXTENT).into());
+    };
+
+    // Resample into the dense box, averaging factor^3 voxels into one
+    let extent = IVec3::new(x, y, z);
+    let factor = (extent.max_element() + MAX_RESOLUTION - 1) / MAX_RESOLUTION;
+    let dims = (extent + factor - 1) / factor;
+    let voxels = (dims.x as usize).checked_mul(dims.y as usize).and_then(|area| area.checked_mul(dims.z as usize)).ok_or("grid is too large")?;
+    let mut density = vec![0.0f32; voxels];
+    let weight = 1.0 / (factor * factor * factor) as f32;
+    let mut add = |voxel: IVec3, value: f32| {
+        let v = (voxel - lo) / factor;
+        density[((v.z * dims.y + v.y) * dims.x + v.x) as usize] += value.max(0.0) * weight;
+    };
+    for i in 0..leaves.1 {
+        let (leaf, origin) = (leaves.0 + i * LEAF_SIZE, leaf_origin(i));
+        for n in 0..512 {
+            let local = IVec3::new((n >> 6) as i32, ((n >> 3) & 7) as i32, (n & 7) as i32);
+            add(origin + local, f32_in(leaf + LEAF_VALUES + n * 4));
+        }
+    }
+    for &(origin, value) in &tiles {
+        for n in 0..512 {
+            add(origin + IVec3::new(n >> 6, (n >> 3) & 7, n & 7), value);
+        }
+    }
+    let max_density = density.iter().copied().fold(0.0, f32::max);
+
+    // Index to world space: the grid's voxel size and translation, in meters times the scale,
+    // then moved so the box stands on the anchor
+    let voxel_size = Vec3::new(f64_in(VOXEL_SIZE) as f32, f64_in(VOXEL_SIZE + 8) as f32, f64_in(VOXEL_SIZE + 16) as f32) * scale;
+    let translation = Vec3::new(f64_in(MAP_VEC_D) as f32, f64_in(MAP_VEC_D + 8) as f32, f64_in(MAP_VEC_D + 16) as f32) * scale;
+    let (min, max) = (lo.as_vec3() * voxel_size + translation, (lo.as_vec3() + (dims * factor).as_vec3()) * voxel_size + translation);
+    let offset = ANCHOR - Vec3::new((min.x + max.x) * 0.5, min.y, (min.z + max.z) * 0.5);
+
+    Ok(Volume {
+        min: min + offset,
+        max: max + offset,
+        dims: [dims.x as u32, dims.y as u32, dims.z as u32],
+        density,
+        max_density,
+        extinction,
+    })
+}
@@ -1,7 +1,217 @@
 use ash::{vk, Entry, Instance, Device};
 use ash::khr::{surface, swapchain, acceleration_structure, ray_tracing_pipeline};
+use ash::nv::device_diagnostic_checkpoints;
+use ash::ext::debug_utils;
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+use crate::capabilities::Capabilities;
+use crate::memory::MemoryTracker;
+use crate::args;
+
+/// Device extensions ray tracing needs. Without them the renderer falls back to the raster preview.
+/// SPIR-V 1.4, float controls and buffer device addresses are core in Vulkan 1.2.
+pub const RAY_TRACING_EXTENSIONS: [&std::ffi::CStr; 3] = [
+    vk::KHR_ACCELERATION_STRUCTURE_NAME,
+    vk::KHR_RAY_TRACING_PIPELINE_NAME,
+    vk::KHR_DEFERRED_HOST_OPERATIONS_NAME,
+];
+
+/// Environment variable overriding the GPU choice like `--gpu`.
+const GPU_ENV_VAR: &str = "RAYTRACING_GPU";
+
+/// The Khronos validation layer, which implements debugPrintfEXT for `--shader-printf`.
+const VALIDATION_LAYER_NAME: &CStr = c"VK_LAYER_KHRONOS_validation";
+
+/// Whether `--gpu-checkpoints` asks for VK_NV_device_diagnostic_checkpoints, so a GPU hang
+/// reports the last commands the device got through.
+pub fn checkpoints_from_args() -> bool {
+    args::flag("--gpu-checkpoints")
+}
+
+/// Whether `--gpu-robustness` asks for VK_EXT_robustness2: buffer and image accesses out of
+/// their descriptor's range read zeros and drop writes, and null descriptors read as zeros,
+/// where they would otherwise be undefined and may hang the GPU. Meant for chasing such bugs,
+/// it costs some performance. Loads through buffer device addresses aren't bounds checked.
+pub fn robustness_from_args() -> bool {
+    args::flag("--gpu-robustness")
+}
+
+/// Whether `--shader-printf` asks for the shaders' `PRINTF` output (`include/printf.glsl`).
+/// The validation layer runs debugPrintfEXT and a debug messenger logs what it prints, along
+/// with the layer's own errors and warnings. Needs the Vulkan SDK's validation layer.
+pub fn shader_printf_from_args() -> bool {
+    args::flag("--shader-printf")
+}
+
+/// Why the validation layer can't run the shaders' debugPrintfEXT, if it can't: it must be
+/// installed, take its settings through VK_EXT_layer_settings, and print to a debug messenger.
+fn shader_printf_unsupported(entry: &Entry, instance_extensions: &[vk::ExtensionProperties]) -> Option<&'static str> {
+    let layers = unsafe { entry.enumerate_instance_layer_properties() }.unwrap_or_default();
+    if !layers.iter().any(|layer| unsafe { CStr::from_ptr(layer.layer_name.as_ptr()) } == VALIDATION_LAYER_NAME) {
+        return Some("the validation layer isn't installed (it comes with the Vulkan SDK)");
+    }
+    let layer_extensions = unsafe { entry.enumerate_instance_extension_properties(Some(VALIDATION_LAYER_NAME)) }.unwrap_or_default();
+    let has_extension = |extensions: &[vk::ExtensionProperties], name: &CStr| extensions.iter()
+        .any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == name);
+    if !has_extension(&layer_extensions, vk::EXT_LAYER_SETTINGS_NAME) {
+        return Some("the validation layer lacks VK_EXT_layer_settings (it needs Vulkan SDK 1.3.272 or newer)");
+    }
+    if !has_extension(instance_extensions, vk::EXT_DEBUG_UTILS_NAME) && !has_extension(&layer_extensions, vk::EXT_DEBUG_UTILS_NAME) {
+        return Some("VK_EXT_debug_utils isn't available");
+    }
+    None
+}
+
+/// Logs what the validation layer reports: the shaders' debugPrintfEXT output at info level,
+/// the layer's errors and warnings as such, anything else at debug level.
+unsafe extern "system" fn log_validation_message(
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    _types: vk::DebugUtilsMessageTypeFlagsEXT,
+    data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    _user_data: *mut std::ffi::c_void,
+) -> vk::Bool32 {
+    let text = |ptr: *const std::ffi::c_char| if ptr.is_null() { Default::default() } else { CStr::from_ptr(ptr).to_string_lossy() };
+    let (id, message) = (text((*data).p_message_id_name), text((*data).p_message));
+    if id.contains("DEBUG-PRINTF") {
+        log::info!("Shader: {}", message);
+    } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        log::error!("Validation: {}", message);
+    } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+        log::warn!("Validation: {}", message);
+    } else {
+        log::debug!("Validation: {}", message);
+    }
+    vk::FALSE
+}
+
+/// A physical device forced by the user instead of the automatic scoring.
+#[derive(Clone, Debug)]
+pub enum GpuChoice {
+    Index(usize), // As listed by --list-gpus
+    Name(String), // Case-insensitive part of the device name
+}
+
+impl GpuChoice {
+    /// From `--gpu <index|name>`, else the `RAYTRACING_GPU` environment variable.
+    pub fn from_args() -> Option<Self> {
+        let value = args::value("--gpu")
+            .map(str::to_string)
+            .or_else(|| std::env::var(GPU_ENV_VAR).ok())?;
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        Some(match value.parse() {
+            Ok(index) => GpuChoice::Index(index),
+            Err(_) => GpuChoice::Name(value.to_string()),
+        })
+    }
+
+    /// Index of the chosen device in `pdevices`, the first one whose name matches for a name.
+    fn find(&self, instance: &Instance, pdevices: &[vk::PhysicalDevice]) -> Option<usize> {
+        match self {
+            GpuChoice::Index(index) => (*index < pdevices.len()).then_some(*index),
+            GpuChoice::Name(name) => {
+                let name = name.to_lowercase();
+                pdevices.iter().position(|&pdevice| gpu_name(instance, pdevice).to_lowercase().contains(&name))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for GpuChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GpuChoice::Index(index) => write!(f, "{}", index),
+            GpuChoice::Name(name) => write!(f, "\"{}\"", name),
+        }
+    }
+}
+
+/// Whether the loader has VK_KHR_portability_enumeration. Without enabling it (and asking for
+/// portability drivers) the loader hides drivers that only implement a subset of Vulkan over
+/// another API, like MoltenVK on macOS, and instance creation fails with INCOMPATIBLE_DRIVER.
+fn has_portability_enumeration(instance_extensions: &[vk::ExtensionProperties]) -> bool {
+    instance_extensions.iter()
+        .any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == vk::KHR_PORTABILITY_ENUMERATION_NAME)
+}
+
+/// Logs the Vulkan devices `--gpu` can pick from with their capability reports, for
+/// `--list-gpus`. Needs no window.
+pub fn list_gpus() -> Result<(), Box<dyn std::error::Error>> {
+    let entry = unsafe { Entry::load()? };
+    let app_info = vk::ApplicationInfo {
+        api_version: vk::API_VERSION_1_2,
+        ..Default::default()
+    };
+    let instance_extensions = unsafe { entry.enumerate_instance_extension_properties(None) }.unwrap_or_default();
+    let portability = has_portability_enumeration(&instance_extensions);
+    let extension_names = if portability { vec![vk::KHR_PORTABILITY_ENUMERATION_NAME.as_ptr()] } else { Vec::new() };
+    let instance = unsafe { entry.create_instance(&vk::InstanceCreateInfo {
+        p_application_info: &app_info,
+        flags: if portability { vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR } else { vk::InstanceCreateFlags::empty() },
+        enabled_extension_count: extension_names.len() as u32,
+        pp_enabled_extension_names: extension_names.as_ptr(),
+        ..Default::default()
+    }, None)? };
+    let pdevices = unsafe { instance.enumerate_physical_devices()? };
+    log::info!("Found {} physical device(s), pick one with --gpu <index|name> or {}=<index|name>", pdevices.len(), GPU_ENV_VAR);
+    for (idx, pdevice) in pdevices.iter().enumerate() {
+        log_gpu(&instance, idx, *pdevice);
+        let extensions = unsafe { instance.enumerate_device_extension_properties(*pdevice) }.unwrap_or_default();
+        Capabilities::query(&instance, *pdevice, &extensions).log();
+    }
+    unsafe { instance.destroy_instance(None) };
+    Ok(())
+}
+
+fn gpu_name(instance: &Instance, pdevice: vk::PhysicalDevice) -> String {
+    let props = unsafe { instance.get_physical_device_properties(pdevice) };
+    unsafe { std::ffi::CStr::from_ptr(props.device_name.as_ptr()) }.to_string_lossy().into_owned()
+}
+
+/// Logs a device's name, type, VRAM and ray tracing support.
+fn log_gpu(instance: &Instance, idx: usize, pdevice: vk::PhysicalDevice) {
+    unsafe {
+        let props = instance.get_physical_device_properties(pdevice);
+        let mem_props = instance.get_physical_device_memory_properties(pdevice);
+        let device_name = gpu_name(instance, pdevice);
+
+        let device_type = match props.device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => "Discrete GPU",
+            vk::PhysicalDeviceType::INTEGRATED_GPU => "Integrated GPU",
+            vk::PhysicalDeviceType::VIRTUAL_GPU => "Virtual GPU",
+            vk::PhysicalDeviceType::CPU => "CPU",
+            _ => "Other",
+        };
+
+        // Calculate total VRAM
+        let mut total_vram: u64 = 0;
+        for i in 0..mem_props.memory_heap_count {
+            let heap = mem_props.memory_heaps[i as usize];
+            if heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL) {
+                total_vram += heap.size;
+            }
+        }
+
+        log::info!("  Device {}: {} ({}) - VRAM: {} MB",
+            idx, device_name, device_type, total_vram / (1024 * 1024));
+
+        // Check raytracing support
+        let available_exts = instance.enumerate_device_extension_properties(pdevice)
+            .unwrap_or_default();
+        let has_rt = available_exts.iter().any(|ext| {
+            let name = std::ffi::CStr::from_ptr(ext.extension_name.as_ptr());
+            name == vk::KHR_RAY_TRACING_PIPELINE_NAME
+        });
+        let has_as = available_exts.iter().any(|ext| {
+            let name = std::ffi::CStr::from_ptr(ext.extension_name.as_ptr());
+            name == vk::KHR_ACCELERATION_STRUCTURE_NAME
+        });
+
+        log::info!("    Ray Tracing: {}, Acceleration Structure: {}", has_rt, has_as);
+    }
+}
 
 pub struct VulkanContext {
     pub entry: Entry,
@@ -12,6 +222,20 @@ pub struct VulkanContext {
     pub device: Device,
     pub queue_family_index: u32,
     pub queue: vk::Queue,
+    // Usually the same as queue_family_index and queue, a separate family on some setups
+    pub present_queue_family_index: u32,
+    pub present_queue: vk::Queue,
+    pub texture_compression_bc: bool,
+    pub ray_tracing: bool, // False on GPUs without ray tracing pipelines, which get the raster preview
+    pub memory_budget: bool, // VK_EXT_memory_budget is enabled
+    pub swapchain_colorspace: bool, // VK_EXT_swapchain_colorspace is enabled, surfaces may offer HDR color spaces
+    pub vulkan13: bool, // Instance and device are Vulkan 1.3: dynamic rendering, synchronization2 and maintenance4 are enabled
+    pub memory: MemoryTracker, // The renderer's living allocations
+    pub checkpoints: Option<device_diagnostic_checkpoints::Device>, // With `--gpu-checkpoints` on drivers that have them
+    pub robustness: bool, // VK_EXT_robustness2 is enabled, with `--gpu-robustness` on drivers that have it
+    pub capabilities: Capabilities, // The selected GPU's, settings beyond them are turned down at startup
+    pub shader_printf: bool, // With `--shader-printf` where the validation layer can run debugPrintfEXT
+    debug_messenger: Option<(debug_utils::Instance, vk::DebugUtilsMessengerEXT)>, // Logs the validation layer's messages, with shader_printf
     
     // Extensions
     pub swapchain_loader: swapchain::Device,
@@ -23,7 +247,10 @@ impl VulkanContext {
     pub fn new(window: &winit::window::Window) -> Result<Self, Box<dyn std::error::Error>> {
         let entry = unsafe { Entry::load()? };
         
-        // Instance
+        // Instance: Vulkan 1.3 where the loader has it, else 1.2, which has everything the
+        // renderer needs and takes the render pass paths instead of the 1.3 features
+        let instance_version = unsafe { entry.try_enumerate_instance_version()? }.unwrap_or(vk::API_VERSION_1_0);
+        let api_version = if instance_version >= vk::API_VERSION_1_3 { vk::API_VERSION_1_3 } else { vk::API_VERSION_1_2 };
         let app_name = CString::new("Rust Raytracing").unwrap();
         let engine_name = CString::new("No Engine").unwrap();
         let app_info = vk::ApplicationInfo {
@@ -31,7 +258,7 @@ impl VulkanContext {
             application_version: 0,
             p_engine_name: engine_name.as_ptr(),
             engine_version: 0,
-            api_version: vk::API_VERSION_1_2,
+            api_version,
             ..Default::default()
         };
 
@@ -39,16 +266,102 @@ impl VulkanContext {
         let window_handle = window.window_handle()?.as_raw();
 
         let mut extension_names = ash_window::enumerate_required_extensions(display_handle)?.to_vec();
-        extension_names.push(vk::EXT_DEBUG_UTILS_NAME.as_ptr());
+        let instance_extensions = unsafe { entry.enumerate_instance_extension_properties(None) }.unwrap_or_default();
+        let has_instance_extension = |name: &CStr| instance_extensions.iter()
+            .any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == name);
+        if has_instance_extension(vk::EXT_DEBUG_UTILS_NAME) {
+            extension_names.push(vk::EXT_DEBUG_UTILS_NAME.as_ptr());
+        }
+        // Extended color spaces are optional, without them HDR output falls back to SDR
+        let swapchain_colorspace = has_instance_extension(vk::EXT_SWAPCHAIN_COLORSPACE_NAME);
+        if swapchain_colorspace {
+            extension_names.push(vk::EXT_SWAPCHAIN_COLORSPACE_NAME.as_ptr());
+        }
+        // Portability drivers (MoltenVK) are listed too, they run the raster preview
+        let portability = has_portability_enumeration(&instance_extensions);
+        if portability {
+            extension_names.push(vk::KHR_PORTABILITY_ENUMERATION_NAME.as_ptr());
+        }
+        // Shader printf: the validation layer, set up to run debugPrintfEXT and nothing else,
+        // since core validation would slow every call down and bury the prints in the log
+        let shader_printf = shader_printf_from_args() && match shader_printf_unsupported(&entry, &instance_extensions) {
+            Some(reason) => {
+                log::warn!("--shader-printf: {}, ignoring", reason);
+                false
+            }
+            None => true,
+        };
+        let mut layer_names = Vec::new();
+        if shader_printf {
+            layer_names.push(VALIDATION_LAYER_NAME.as_ptr());
+            extension_names.push(vk::EXT_LAYER_SETTINGS_NAME.as_ptr());
+            if !has_instance_extension(vk::EXT_DEBUG_UTILS_NAME) {
+                extension_names.push(vk::EXT_DEBUG_UTILS_NAME.as_ptr());
+            }
+        }
+        let printf_enables = [c"VK_VALIDATION_FEATURE_ENABLE_DEBUG_PRINTF_EXT".as_ptr()];
+        let disabled = vk::FALSE;
+        let layer_settings = [
+            vk::LayerSettingEXT {
+                p_layer_name: VALIDATION_LAYER_NAME.as_ptr(),
+                p_setting_name: c"enables".as_ptr(),
+                ty: vk::LayerSettingTypeEXT::STRING,
+                value_count: printf_enables.len() as u32,
+                p_values: printf_enables.as_ptr().cast(),
+                ..Default::default()
+            },
+            vk::LayerSettingEXT {
+                p_layer_name: VALIDATION_LAYER_NAME.as_ptr(),
+                p_setting_name: c"validate_core".as_ptr(),
+                ty: vk::LayerSettingTypeEXT::BOOL32,
+                value_count: 1,
+                p_values: (&disabled as *const vk::Bool32).cast(),
+                ..Default::default()
+            },
+            // Prints go to the debug messenger, and so to the log and the HUD
+            vk::LayerSettingEXT {
+                p_layer_name: VALIDATION_LAYER_NAME.as_ptr(),
+                p_setting_name: c"printf_to_stdout".as_ptr(),
+                ty: vk::LayerSettingTypeEXT::BOOL32,
+                value_count: 1,
+                p_values: (&disabled as *const vk::Bool32).cast(),
+                ..Default::default()
+            },
+        ];
+        let layer_settings_info = vk::LayerSettingsCreateInfoEXT {
+            setting_count: layer_settings.len() as u32,
+            p_settings: layer_settings.as_ptr(),
+            ..Default::default()
+        };
 
         let create_info = vk::InstanceCreateInfo {
+            p_next: if shader_printf { &layer_settings_info as *const _ as *const _ } else { std::ptr::null() },
+            flags: if portability { vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR } else { vk::InstanceCreateFlags::empty() },
             p_application_info: &app_info,
+            enabled_layer_count: layer_names.len() as u32,
+            pp_enabled_layer_names: layer_names.as_ptr(),
             enabled_extension_count: extension_names.len() as u32,
             pp_enabled_extension_names: extension_names.as_ptr(),
             ..Default::default()
         };
 
         let instance = unsafe { entry.create_instance(&create_info, None)? };
+        let debug_messenger = if shader_printf {
+            let loader = debug_utils::Instance::new(&entry, &instance);
+            let messenger_info = vk::DebugUtilsMessengerCreateInfoEXT {
+                message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+                message_type: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+                pfn_user_callback: Some(log_validation_message),
+                ..Default::default()
+            };
+            let messenger = unsafe { loader.create_debug_utils_messenger(&messenger_info, None)? };
+            log::info!("Shader printf enabled, PRINTF output is logged");
+            Some((loader, messenger))
+        } else {
+            None
+        };
 
         // Surface
         let surface_loader = surface::Instance::new(&entry, &instance);
@@ -61,97 +374,54 @@ impl VulkanContext {
 
         // Log all available devices
         for (idx, pdevice) in pdevices.iter().enumerate() {
-            unsafe {
-                let props = instance.get_physical_device_properties(*pdevice);
-                let mem_props = instance.get_physical_device_memory_properties(*pdevice);
-                let device_name = std::ffi::CStr::from_ptr(props.device_name.as_ptr())
-                    .to_string_lossy();
-
-                let device_type = match props.device_type {
-                    vk::PhysicalDeviceType::DISCRETE_GPU => "Discrete GPU",
-                    vk::PhysicalDeviceType::INTEGRATED_GPU => "Integrated GPU",
-                    vk::PhysicalDeviceType::VIRTUAL_GPU => "Virtual GPU",
-                    vk::PhysicalDeviceType::CPU => "CPU",
-                    _ => "Other",
-                };
-
-                // Calculate total VRAM
-                let mut total_vram: u64 = 0;
-                for i in 0..mem_props.memory_heap_count {
-                    let heap = mem_props.memory_heaps[i as usize];
-                    if heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL) {
-                        total_vram += heap.size;
-                    }
-                }
-
-                log::info!("  Device {}: {} ({}) - VRAM: {} MB",
-                    idx, device_name, device_type, total_vram / (1024 * 1024));
-
-                // Check raytracing support
-                let available_exts = instance.enumerate_device_extension_properties(*pdevice)
-                    .unwrap_or_default();
-                let has_rt = available_exts.iter().any(|ext| {
-                    let name = std::ffi::CStr::from_ptr(ext.extension_name.as_ptr());
-                    name == vk::KHR_RAY_TRACING_PIPELINE_NAME
-                });
-                let has_as = available_exts.iter().any(|ext| {
-                    let name = std::ffi::CStr::from_ptr(ext.extension_name.as_ptr());
-                    name == vk::KHR_ACCELERATION_STRUCTURE_NAME
-                });
-
-                log::info!("    Ray Tracing: {}, Acceleration Structure: {}", has_rt, has_as);
-            }
+            log_gpu(&instance, idx, *pdevice);
         }
 
         // Score and select best device
-        let mut scored_devices: Vec<(vk::PhysicalDevice, u32, u32)> = Vec::new();
+        let mut scored_devices: Vec<(vk::PhysicalDevice, (u32, u32), u32)> = Vec::new();
 
         for pdevice in pdevices.iter() {
             unsafe {
                 let props = instance.get_physical_device_properties(*pdevice);
                 let queue_families = instance.get_physical_device_queue_family_properties(*pdevice);
 
-                // Find suitable queue family
-                let q_index = queue_families.iter().enumerate().find_map(|(i, q)| {
-                    let supports_graphics = q.queue_flags.contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE);
-                    let supports_present = surface_loader
-                        .get_physical_device_surface_support(*pdevice, i as u32, surface)
-                        .unwrap_or(false);
-
-                    if supports_graphics && supports_present {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                });
+                // Find suitable queue families: graphics and compute, presenting from the same
+                // family if one can, else from any family that can
+                let supports_present = |i: u32| surface_loader
+                    .get_physical_device_surface_support(*pdevice, i, surface)
+                    .unwrap_or(false);
+                let graphics_families: Vec<u32> = queue_families.iter().enumerate()
+                    .filter(|(_, q)| q.queue_flags.contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE))
+                    .map(|(i, _)| i as u32)
+                    .collect();
+                let q_indices = graphics_families.iter().copied().find(|&i| supports_present(i)).map(|i| (i, i))
+                    .or_else(|| {
+                        let present = (0..queue_families.len() as u32).find(|&i| supports_present(i))?;
+                        graphics_families.first().map(|&graphics| (graphics, present))
+                    });
 
-                if let Some(queue_idx) = q_index {
+                if let Some(queue_indices) = q_indices {
                     // Check required extensions
                     let available_exts = instance.enumerate_device_extension_properties(*pdevice)
                         .unwrap_or_default();
 
-                    let required_exts = [
-                        vk::KHR_SWAPCHAIN_NAME,
-                        vk::KHR_ACCELERATION_STRUCTURE_NAME,
-                        vk::KHR_RAY_TRACING_PIPELINE_NAME,
-                        vk::KHR_DEFERRED_HOST_OPERATIONS_NAME,
-                        vk::KHR_BUFFER_DEVICE_ADDRESS_NAME,
-                    ];
-
-                    let has_all_exts = required_exts.iter().all(|required| {
-                        available_exts.iter().any(|ext| {
-                            let name = std::ffi::CStr::from_ptr(ext.extension_name.as_ptr());
-                            name == *required
-                        })
+                    let has_ext = |required: &std::ffi::CStr| available_exts.iter().any(|ext| {
+                        std::ffi::CStr::from_ptr(ext.extension_name.as_ptr()) == required
                     });
+                    let suitable = props.api_version >= vk::API_VERSION_1_2 && has_ext(vk::KHR_SWAPCHAIN_NAME);
+                    let has_rt_exts = RAY_TRACING_EXTENSIONS.into_iter().all(has_ext);
 
-                    if has_all_exts {
-                        // Score: discrete GPU = 1000, integrated = 500, other = 100
+                    if suitable {
+                        // Score: discrete GPU = 1000, integrated = 500, other = 100, and any GPU
+                        // that can ray trace beats those that only run the raster preview
                         let mut score = match props.device_type {
                             vk::PhysicalDeviceType::DISCRETE_GPU => 1000,
                             vk::PhysicalDeviceType::INTEGRATED_GPU => 500,
                             _ => 100,
                         };
+                        if has_rt_exts {
+                            score += 100_000;
+                        }
 
                         // Prefer devices with more VRAM
                         let mem_props = instance.get_physical_device_memory_properties(*pdevice);
@@ -162,51 +432,133 @@ impl VulkanContext {
                             }
                         }
 
-                        scored_devices.push((*pdevice, queue_idx, score));
+                        scored_devices.push((*pdevice, queue_indices, score));
                     }
                 }
             }
         }
 
         if scored_devices.is_empty() {
-            return Err("No suitable GPU found with required Vulkan ray tracing extensions. \
-                       Required: VK_KHR_ray_tracing_pipeline, VK_KHR_acceleration_structure. \
-                       Please ensure your GPU supports hardware ray tracing and drivers are up to date.".into());
+            return Err("No suitable GPU found. Required: Vulkan 1.2, a graphics queue, a queue that can present \
+                       and VK_KHR_swapchain. \
+                       Please ensure your drivers are up to date.".into());
         }
 
         // Sort by score (highest first)
         scored_devices.sort_by(|a, b| b.2.cmp(&a.2));
 
-        let (physical_device, queue_family_index) = (scored_devices[0].0, scored_devices[0].1);
-
-        unsafe {
-            let props = instance.get_physical_device_properties(physical_device);
-            let device_name = std::ffi::CStr::from_ptr(props.device_name.as_ptr())
-                .to_string_lossy();
-            log::info!("Selected GPU: {} (score: {})", device_name, scored_devices[0].2);
+        // --gpu or RAYTRACING_GPU overrides the scoring, hybrid laptops may score the wrong GPU highest
+        let selected = match GpuChoice::from_args() {
+            Some(choice) => {
+                let index = choice.find(&instance, &pdevices)
+                    .ok_or_else(|| format!("No GPU {} found, --list-gpus shows the available ones", choice))?;
+                scored_devices.iter().find(|scored| scored.0 == pdevices[index])
+                    .ok_or_else(|| format!("GPU {} ({}) lacks Vulkan 1.2, a graphics queue, a queue that can present or VK_KHR_swapchain",
+                        index, gpu_name(&instance, pdevices[index])))?
+            }
+            None => &scored_devices[0],
+        };
+        let (physical_device, (queue_family_index, present_queue_family_index)) = (selected.0, selected.1);
+
+        log::info!("Selected GPU: {} (score: {})", gpu_name(&instance, physical_device), selected.2);
+        let available_extensions = unsafe { instance.enumerate_device_extension_properties(physical_device) }.unwrap_or_default();
+        let capabilities = Capabilities::query(&instance, physical_device, &available_extensions);
+        capabilities.log();
+        let ray_tracing = match capabilities.ray_tracing_unsupported() {
+            Some(reason) => {
+                log::warn!("The selected GPU can't ray trace: {}, falling back to a flat shaded raster preview", reason);
+                false
+            }
+            None => true,
+        };
+        // Dynamic rendering, synchronization2 and maintenance4 are core features every 1.3 device has
+        let vulkan13 = api_version >= vk::API_VERSION_1_3 && capabilities.api_version >= vk::API_VERSION_1_3;
+        if !vulkan13 {
+            log::info!("Vulkan 1.3 isn't available, drawing to the swapchain with a render pass");
         }
 
         // Device
         let queue_priorities = [1.0];
-        let queue_info = vk::DeviceQueueCreateInfo {
+        let mut queue_families = vec![queue_family_index];
+        if present_queue_family_index != queue_family_index {
+            log::info!("Presenting from queue family {}, rendering on queue family {}", present_queue_family_index, queue_family_index);
+            queue_families.push(present_queue_family_index);
+        }
+        let queue_infos: Vec<vk::DeviceQueueCreateInfo> = queue_families.iter().map(|&queue_family_index| vk::DeviceQueueCreateInfo {
             queue_family_index,
             queue_count: 1,
             p_queue_priorities: queue_priorities.as_ptr(),
             ..Default::default()
+        }).collect();
+
+        let mut device_extension_names = vec![vk::KHR_SWAPCHAIN_NAME.as_ptr()];
+        if ray_tracing {
+            device_extension_names.extend(RAY_TRACING_EXTENSIONS.map(|name| name.as_ptr()));
+        }
+        let has_extension = |name: &CStr| available_extensions.iter()
+            .any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == name);
+        // Memory budgets are optional, without them allocations aren't checked up front
+        let memory_budget = has_extension(vk::EXT_MEMORY_BUDGET_NAME);
+        if memory_budget {
+            device_extension_names.push(vk::EXT_MEMORY_BUDGET_NAME.as_ptr());
+        }
+        // A portability driver's device must have its subset enabled, the features it lacks
+        // (triangle fans, events and the like) are ones the renderer doesn't use
+        if capabilities.portability_subset {
+            device_extension_names.push(crate::capabilities::PORTABILITY_SUBSET_NAME.as_ptr());
+            log::info!("The selected GPU implements Vulkan as a portability subset (e.g. MoltenVK)");
+        }
+        let checkpoints = checkpoints_from_args() && has_extension(vk::NV_DEVICE_DIAGNOSTIC_CHECKPOINTS_NAME);
+        if checkpoints {
+            device_extension_names.push(vk::NV_DEVICE_DIAGNOSTIC_CHECKPOINTS_NAME.as_ptr());
+            log::info!("GPU diagnostic checkpoints enabled");
+        } else if checkpoints_from_args() {
+            log::warn!("--gpu-checkpoints: the driver doesn't support VK_NV_device_diagnostic_checkpoints, ignoring");
+        }
+        let mut robustness2 = vk::PhysicalDeviceRobustness2FeaturesEXT::default();
+        let robustness = robustness_from_args() && has_extension(vk::EXT_ROBUSTNESS2_NAME) && {
+            let mut supported = vk::PhysicalDeviceFeatures2 { p_next: &mut robustness2 as *mut _ as *mut _, ..Default::default() };
+            unsafe { instance.get_physical_device_features2(physical_device, &mut supported) };
+            supported.features.robust_buffer_access == vk::TRUE && robustness2.robust_buffer_access2 == vk::TRUE
         };
+        if robustness {
+            device_extension_names.push(vk::EXT_ROBUSTNESS2_NAME.as_ptr());
+            log::info!("GPU robustness enabled: robust buffer access 2, robust image access 2: {}, null descriptors: {}",
+                robustness2.robust_image_access2 == vk::TRUE, robustness2.null_descriptor == vk::TRUE);
+        } else if robustness_from_args() {
+            log::warn!("--gpu-robustness: the driver doesn't support VK_EXT_robustness2's robust buffer access, ignoring");
+        }
+        // Enables what the device supports of it
+        robustness2.p_next = std::ptr::null_mut();
+        // debugPrintfEXT compiles to a non-semantic instruction set, core in Vulkan 1.3
+        if shader_printf && !vulkan13 && has_extension(vk::KHR_SHADER_NON_SEMANTIC_INFO_NAME) {
+            device_extension_names.push(vk::KHR_SHADER_NON_SEMANTIC_INFO_NAME.as_ptr());
+        }
 
-        let device_extension_names = [
-            vk::KHR_SWAPCHAIN_NAME.as_ptr(),
-            vk::KHR_ACCELERATION_STRUCTURE_NAME.as_ptr(),
-            vk::KHR_RAY_TRACING_PIPELINE_NAME.as_ptr(),
-            vk::KHR_DEFERRED_HOST_OPERATIONS_NAME.as_ptr(),
-            vk::KHR_SPIRV_1_4_NAME.as_ptr(),
-            vk::KHR_SHADER_FLOAT_CONTROLS_NAME.as_ptr(),
-            vk::KHR_BUFFER_DEVICE_ADDRESS_NAME.as_ptr(),
-        ];
+        // BCn textures are optional, KTX2 files using them are skipped if unsupported
+        let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+        let texture_compression_bc = supported_features.texture_compression_bc == vk::TRUE;
+        let features = vk::PhysicalDeviceFeatures {
+            texture_compression_bc: supported_features.texture_compression_bc,
+            // Robust buffer access 2 needs the original robust buffer access too
+            robust_buffer_access: robustness as vk::Bool32,
+            ..Default::default()
+        };
 
         let mut features12 = vk::PhysicalDeviceVulkan12Features {
             buffer_device_address: vk::TRUE,
+            // Texture array indexed per material in the closest hit shader
+            runtime_descriptor_array: ray_tracing as vk::Bool32,
+            shader_sampled_image_array_non_uniform_indexing: ray_tracing as vk::Bool32,
+            ..Default::default()
+        };
+
+        let mut features13 = vk::PhysicalDeviceVulkan13Features {
+            // The swapchain pass renders without a render pass or framebuffers, and moves the
+            // swapchain image between layouts with vkCmdPipelineBarrier2
+            dynamic_rendering: vk::TRUE,
+            synchronization2: vk::TRUE,
+            maintenance4: vk::TRUE,
             ..Default::default()
         };
         
@@ -220,25 +572,38 @@ impl VulkanContext {
             ..Default::default()
         };
 
-        // Chain features
+        // Chain features: 1.2, then 1.3's, then ray tracing's
         as_features.p_next = &mut rt_features as *mut _ as *mut _;
-        features12.p_next = &mut as_features as *mut _ as *mut _;
+        let ray_tracing_features: *mut std::ffi::c_void = if ray_tracing { &mut as_features as *mut _ as *mut _ } else { std::ptr::null_mut() };
+        if vulkan13 {
+            features13.p_next = ray_tracing_features;
+            features12.p_next = &mut features13 as *mut _ as *mut _;
+        } else {
+            features12.p_next = ray_tracing_features;
+        }
+        if robustness {
+            robustness2.p_next = features12.p_next;
+            features12.p_next = &mut robustness2 as *mut _ as *mut _;
+        }
 
         let device_create_info = vk::DeviceCreateInfo {
-            queue_create_info_count: 1,
-            p_queue_create_infos: &queue_info,
+            queue_create_info_count: queue_infos.len() as u32,
+            p_queue_create_infos: queue_infos.as_ptr(),
             enabled_extension_count: device_extension_names.len() as u32,
             pp_enabled_extension_names: device_extension_names.as_ptr(),
+            p_enabled_features: &features,
             p_next: &mut features12 as *mut _ as *mut _,
             ..Default::default()
         };
 
         let device = unsafe { instance.create_device(physical_device, &device_create_info, None)? };
         let queue = unsafe { device.get_device_queue(queue_family_index, 0) };
+        let present_queue = unsafe { device.get_device_queue(present_queue_family_index, 0) };
 
         let swapchain_loader = swapchain::Device::new(&instance, &device);
         let as_loader = acceleration_structure::Device::new(&instance, &device);
         let rt_pipeline_loader = ray_tracing_pipeline::Device::new(&instance, &device);
+        let checkpoints = checkpoints.then(|| device_diagnostic_checkpoints::Device::new(&instance, &device));
 
         Ok(Self {
             entry,
@@ -249,6 +614,19 @@ impl VulkanContext {
             device,
             queue_family_index,
             queue,
+            present_queue_family_index,
+            present_queue,
+            texture_compression_bc,
+            ray_tracing,
+            memory_budget,
+            swapchain_colorspace,
+            vulkan13,
+            memory: MemoryTracker::default(),
+            checkpoints,
+            robustness,
+            capabilities,
+            shader_printf,
+            debug_messenger,
             swapchain_loader,
             as_loader,
             rt_pipeline_loader,
@@ -256,11 +634,92 @@ impl VulkanContext {
     }
 }
 
+/// A memory heap's size and, with VK_EXT_memory_budget, how much of it this process may use
+/// and is using.
+#[derive(Clone, Copy, Debug)]
+pub struct HeapBudget {
+    pub size: u64,
+    pub device_local: bool,
+    pub budget: Option<u64>,
+    pub usage: Option<u64>,
+}
+
+impl VulkanContext {
+    /// Every memory heap's current budget and usage.
+    pub fn heap_budgets(&self) -> Vec<HeapBudget> {
+        let mut budget_props = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let mut props = vk::PhysicalDeviceMemoryProperties2::default();
+        if self.memory_budget {
+            props.p_next = &mut budget_props as *mut _ as *mut _;
+        }
+        unsafe { self.instance.get_physical_device_memory_properties2(self.physical_device, &mut props) };
+        let heaps = &props.memory_properties.memory_heaps[..props.memory_properties.memory_heap_count as usize];
+        heaps.iter().enumerate().map(|(i, heap)| HeapBudget {
+            size: heap.size,
+            device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+            budget: self.memory_budget.then_some(budget_props.heap_budget[i]),
+            usage: self.memory_budget.then_some(budget_props.heap_usage[i]),
+        }).collect()
+    }
+
+    /// Bytes that can still be allocated from the device local heaps within their budgets, if
+    /// the driver reports budgets.
+    pub fn device_local_headroom(&self) -> Option<u64> {
+        self.heap_budgets().iter()
+            .filter(|heap| heap.device_local)
+            .map(|heap| Some(heap.budget?.saturating_sub(heap.usage?)))
+            .sum()
+    }
+
+    /// Logs every heap's usage against its budget.
+    pub fn log_heap_budgets(&self) {
+        const MB: u64 = 1024 * 1024;
+        for (i, heap) in self.heap_budgets().iter().enumerate() {
+            let kind = if heap.device_local { "device local" } else { "host" };
+            match (heap.budget, heap.usage) {
+                (Some(budget), Some(usage)) => log::info!("  Heap {} ({}): {} MB used of a {} MB budget ({} MB heap)",
+                    i, kind, usage / MB, budget / MB, heap.size / MB),
+                _ => log::info!("  Heap {} ({}): {} MB, no budget reported", i, kind, heap.size / MB),
+            }
+        }
+    }
+
+    /// Records a diagnostic checkpoint named `marker` into `cmd`, if checkpoints are enabled.
+    pub fn checkpoint(&self, cmd: vk::CommandBuffer, marker: &'static CStr) {
+        if let Some(loader) = &self.checkpoints {
+            unsafe { loader.cmd_set_checkpoint(cmd, marker.as_ptr().cast()) };
+        }
+    }
+
+    /// Logs the last checkpoints the graphics queue reached, per pipeline stage. Meant for after
+    /// a DEVICE_LOST: the command that hung is between the last checkpoint that reached the top
+    /// of the pipe and the last one that reached the bottom.
+    pub fn log_checkpoints(&self) {
+        let Some(loader) = &self.checkpoints else {
+            log::error!("Run with --gpu-checkpoints to see where the GPU stopped");
+            return;
+        };
+        let mut data = unsafe { vec![vk::CheckpointDataNV::default(); loader.get_queue_checkpoint_data_len(self.queue)] };
+        unsafe { loader.get_queue_checkpoint_data(self.queue, &mut data) };
+        if data.is_empty() {
+            log::error!("The GPU reported no checkpoints");
+        }
+        for checkpoint in &data {
+            // Markers are only ever set from `checkpoint`, so they point to static C strings
+            let marker = unsafe { CStr::from_ptr(checkpoint.p_checkpoint_marker.cast()) };
+            log::error!("  Last checkpoint at {:?}: {}", checkpoint.stage, marker.to_string_lossy());
+        }
+    }
+}
+
 impl Drop for VulkanContext {
     fn drop(&mut self) {
         unsafe {
             self.device.destroy_device(None);
             self.surface_loader.destroy_surface(self.surface, None);
+            if let Some((loader, messenger)) = &self.debug_messenger {
+                loader.destroy_debug_utils_messenger(*messenger, None);
+            }
             self.instance.destroy_instance(None);
         }
     }
@@ -0,0 +1,198 @@
+use glam::{Mat4, Quat, Vec3};
+use crate::scene::{Material, Scene};
+
+// Scene content the weather acts on
+const PUDDLE_OBJECT: usize = 1;
+const ASPHALT_MATERIAL: usize = 8;
+const RAIN_MATERIAL: usize = 13;
+const SNOW_MATERIAL: usize = 14;
+
+// Particles fall in a box around the camera and respawn at its top
+const PRECIPITATION_RADIUS: f32 = 12.0; // Half extent in x and z (meters)
+const PRECIPITATION_HEIGHT: f32 = 10.0;
+const RAIN_VELOCITY: Vec3 = Vec3::new(0.8, -9.0, 0.3); // m/s, slanted by a light wind
+const SNOW_FALL_SPEED: f32 = 1.0; // m/s
+const SNOW_SWAY: f32 = 0.5; // Meters of side to side drift
+
+// Rates per second at which the scene follows the weather
+const OVERCAST_RATE: f32 = 0.3;
+const WETTING_RATE: f32 = 0.08;
+const DRYING_RATE: f32 = 0.02;
+const SNOW_COVER_RATE: f32 = 0.04;
+const THAW_RATE: f32 = 0.01;
+const MAX_PUDDLE_GROWTH: f32 = 1.8; // Puddle scale factor when fully wet
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WeatherKind {
+    Clear,
+    Rain,
+    Snow,
+}
+
+#[derive(Clone, Copy)]
+struct Particle {
+    position: Vec3,
+    phase: f32, // Snow sway phase, radians
+}
+
+/// Weather controller: moves the scene's precipitation particles and gradually wets the
+/// asphalt and grows the puddle (rain) or covers the asphalt in snow, while the sky
+/// turns overcast and the sun dims. Asphalt and puddle are restored as things dry and thaw.
+pub struct Weather {
+    pub kind: WeatherKind,
+    pub overcast: f32, // 0..1, dims the sun and grays the sky
+    pub wetness: f32, // 0..1
+    pub snow_cover: f32, // 0..1
+    particles: Vec<Particle>,
+    dry_asphalt: Material,
+    puddle_growth: f32, // Scale factor last applied to the puddle
+    rng: u32,
+}
+
+impl Weather {
+    /// Weather for `scene`, with precipitation scattered by `seed`.
+    pub fn new(scene: &Scene, seed: u32) -> Self {
+        let mut weather = Self {
+            kind: WeatherKind::Clear,
+            overcast: 0.0,
+            wetness: 0.0,
+            snow_cover: 0.0,
+            particles: Vec::with_capacity(scene.precipitation.len()),
+            dry_asphalt: scene.materials[ASPHALT_MATERIAL],
+            puddle_growth: 1.0,
+            rng: crate::rng::tea(seed, 0x9e37_79b9).max(1), // Xorshift state must not be 0
+        };
+        for _ in scene.precipitation.clone() {
+            let particle = Particle {
+                position: weather.random_position(Vec3::ZERO, 0.0, PRECIPITATION_HEIGHT),
+                phase: weather.random() * std::f32::consts::TAU,
+            };
+            weather.particles.push(particle);
+        }
+        weather
+    }
+
+    pub fn cycle(&mut self, scene: &mut Scene) {
+        let next = match self.kind {
+            WeatherKind::Clear => WeatherKind::Rain,
+            WeatherKind::Rain => WeatherKind::Snow,
+            WeatherKind::Snow => WeatherKind::Clear,
+        };
+        self.set(next, scene);
+    }
+
+    /// Switches to `kind`; the scene then changes over gradually in `update`.
+    pub fn set(&mut self, kind: WeatherKind, scene: &mut Scene) {
+        self.kind = kind;
+        let (visible, material_index) = match self.kind {
+            WeatherKind::Clear => (false, RAIN_MATERIAL),
+            WeatherKind::Rain => (true, RAIN_MATERIAL),
+            WeatherKind::Snow => (true, SNOW_MATERIAL),
+        };
+        for object in &mut scene.objects[scene.precipitation.clone()] {
+            object.visible = visible;
+            object.material_index = material_index;
+        }
+    }
+
+    /// Whether anything is changing, i.e. `update` needs to run and the TLAS and materials be refreshed.
+    pub fn active(&self) -> bool {
+        self.kind != WeatherKind::Clear || self.wetness > 0.0 || self.snow_cover > 0.0 || self.overcast > 0.0
+    }
+
+    /// Takes the dry asphalt from the scene's materials again, after the material file changed it.
+    pub fn materials_reloaded(&mut self, scene: &Scene) {
+        self.dry_asphalt = scene.materials[ASPHALT_MATERIAL];
+    }
+
+    /// Advances the weather by `dt` seconds, with the particles following the camera.
+    pub fn update(&mut self, dt: f32, camera_position: Vec3, scene: &mut Scene) {
+        let approach = |value: f32, target: f32, rate: f32| {
+            if value < target { (value + rate * dt).min(target) } else { (value - rate * dt).max(target) }
+        };
+        let (overcast, wet, snow) = match self.kind {
+            WeatherKind::Clear => (0.0, 0.0, 0.0),
+            WeatherKind::Rain => (1.0, 1.0, 0.0),
+            WeatherKind::Snow => (0.8, 0.0, 1.0),
+        };
+        self.overcast = approach(self.overcast, overcast, OVERCAST_RATE);
+        self.wetness = approach(self.wetness, wet, if wet > self.wetness { WETTING_RATE } else { DRYING_RATE });
+        // Rain washes the snow away faster than it thaws
+        let thaw = if self.kind == WeatherKind::Rain { WETTING_RATE } else { THAW_RATE };
+        self.snow_cover = approach(self.snow_cover, snow, if snow > self.snow_cover { SNOW_COVER_RATE } else { thaw });
+
+        // Wet asphalt darkens and turns glossy, snow whitens it
+        let mut asphalt = self.dry_asphalt;
+        if self.wetness > 0.0 {
+            asphalt.params[0] = 1.0; // Metal type: reflection weighted by 1 - roughness
+            asphalt.params[1] = 1.0 - 0.6 * self.wetness;
+            for channel in &mut asphalt.color[..3] {
+                *channel *= 1.0 - 0.4 * self.wetness;
+            }
+        }
+        for (channel, dry) in asphalt.color[..3].iter_mut().zip(self.dry_asphalt.color) {
+            *channel += (0.9 - dry) * self.snow_cover;
+        }
+        asphalt.textures[1] *= 1.0 - self.snow_cover; // Snow fills in the surface detail
+        scene.materials[ASPHALT_MATERIAL] = asphalt;
+
+        // Grown on top of the puddle's current placement, which editor moves may have changed:
+        // the growth applied last time comes off its transform before the new one goes on
+        let growth = 1.0 + (MAX_PUDDLE_GROWTH - 1.0) * self.wetness;
+        let puddle = &mut scene.objects[PUDDLE_OBJECT].transform;
+        let rest = *puddle * Mat4::from_scale(Vec3::new(1.0 / self.puddle_growth, 1.0, 1.0 / self.puddle_growth));
+        *puddle = rest * Mat4::from_scale(Vec3::new(growth, 1.0, growth));
+        self.puddle_growth = growth;
+
+        if self.kind == WeatherKind::Clear {
+            return;
+        }
+        for i in 0..self.particles.len() {
+            let mut particle = self.particles[i];
+            let velocity = match self.kind {
+                WeatherKind::Snow => Vec3::new(particle.phase.cos() * SNOW_SWAY, -SNOW_FALL_SPEED, particle.phase.sin() * SNOW_SWAY),
+                _ => RAIN_VELOCITY,
+            };
+            particle.position += velocity * dt;
+            particle.phase += dt;
+
+            // Respawn at the top when landed or left behind by the camera
+            let offset = particle.position - camera_position;
+            if particle.position.y < 0.0 || offset.x.abs() > PRECIPITATION_RADIUS || offset.z.abs() > PRECIPITATION_RADIUS {
+                particle.position = self.random_position(camera_position, PRECIPITATION_HEIGHT * 0.8, PRECIPITATION_HEIGHT);
+            }
+
+            let transform = match self.kind {
+                // Streak stretched along the fall direction
+                WeatherKind::Rain => Mat4::from_scale_rotation_translation(
+                    Vec3::new(0.01, 0.3, 0.01),
+                    Quat::from_rotation_arc(Vec3::Y, -RAIN_VELOCITY.normalize()),
+                    particle.position,
+                ),
+                _ => Mat4::from_scale_rotation_translation(
+                    Vec3::splat(0.04),
+                    Quat::from_rotation_y(particle.phase) * Quat::from_rotation_x(particle.phase * 0.7),
+                    particle.position,
+                ),
+            };
+            scene.objects[scene.precipitation.start + i].transform = transform;
+            self.particles[i] = particle;
+        }
+    }
+
+    fn random_position(&mut self, center: Vec3, min_height: f32, max_height: f32) -> Vec3 {
+        Vec3::new(
+            center.x + (self.random() * 2.0 - 1.0) * PRECIPITATION_RADIUS,
+            min_height + self.random() * (max_height - min_height),
+            center.z + (self.random() * 2.0 - 1.0) * PRECIPITATION_RADIUS,
+        )
+    }
+
+    // Xorshift, uniform in [0, 1)
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng >> 8) as f32 / (1u32 << 24) as f32
+    }
+}